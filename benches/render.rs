@@ -0,0 +1,258 @@
+// Rendering benchmarks. Run with `cargo bench`, optionally followed by `--`
+// and a name filter (`cargo bench -- intersect`).
+//
+// This is a plain `harness = false` binary: every case is timed with
+// `Instant`, and the best of a few rounds is reported, so numbers from before
+// and after a change can be compared directly.
+
+use lux::*;
+use std::hint::black_box;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// Size of the benchmark image
+const WIDTH: u32 = 160;
+const HEIGHT: u32 = 120;
+
+// Time `f` over `iters` calls per round and print the best time per call
+fn bench(filter: &Option<String>, name: &str, iters: u32, mut f: impl FnMut()) -> Option<Duration> {
+    const ROUNDS: u32 = 5;
+
+    if let Some(filter) = filter {
+        if !name.contains(filter.as_str()) {
+            return None;
+        }
+    }
+
+    // Warm up
+    f();
+
+    let mut best = Duration::MAX;
+    for _ in 0..ROUNDS {
+        let start = Instant::now();
+        for _ in 0..iters {
+            f();
+        }
+        best = best.min(start.elapsed() / iters);
+    }
+
+    println!("{:<28} {:>12.3?} / iter", name, best);
+    Some(best)
+}
+
+// Fixed 5 x 4 grid of shiny spheres and one light
+fn small_scene(settings: RenderSettings) -> GlobalSettings {
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, settings, Camera::default());
+
+    globals.add_material(
+        "shiny",
+        Material {
+            color: Vector3D::new(0.6, 0.6, 0.6),
+            specular: 0.3,
+            diffusive: 0.4,
+            reflective: 0.5,
+            ..Default::default()
+        },
+    );
+
+    for j in 0..4 {
+        for i in 0..5 {
+            let pos = Vector3D::new(-1.5 + i as f64 * 0.75, 1.0 - j as f64 * 0.66, 2.0);
+            add_sphere(&pos, 0.3, "shiny", &mut globals).unwrap();
+        }
+    }
+
+    add_light(Vector3D::ZERO, Vector3D::ONE, 2.0, &mut globals).unwrap();
+    globals
+}
+
+// Render the small scene and report primary rays per second
+fn bench_render(filter: &Option<String>, name: &str, settings: RenderSettings) {
+    let mut globals = small_scene(settings);
+
+    if let Some(time) = bench(filter, name, 1, || render_scene(&mut globals).unwrap()) {
+        let rays = (WIDTH * HEIGHT * settings.samples.max(1)) as f64;
+        println!("{:<28} {:>12.0} rays/s", "", rays / time.as_secs_f64());
+    }
+}
+
+// Render the demo scene at 2048 x 1536 on 1 to 8 threads and report how
+// much faster each is than one thread
+fn bench_threads(filter: &Option<String>) {
+    let mut single = None;
+    for threads in 1..=8 {
+        let settings = RenderSettings {
+            threads,
+            ..RenderSettings::default()
+        };
+        let mut globals = GlobalSettings::new(2048, 1536, settings, Camera::default());
+        demo::build_scene(&mut globals);
+
+        let name = format!("threads/demo_2048x1536/{}", threads);
+        if let Some(time) = bench(filter, &name, 1, || render_scene(&mut globals).unwrap()) {
+            let single = *single.get_or_insert(time);
+            println!("{:<28} {:>12.2}x", "", single.as_secs_f64() / time.as_secs_f64());
+        }
+    }
+}
+
+// Render the demo scene again after changing a material's color, from
+// scratch and with the geometry cache, and report how much faster the
+// cache makes it
+fn bench_geometry_cache(filter: &Option<String>) {
+    let mut cold = None;
+    for geometry_cache in [false, true] {
+        let settings = RenderSettings {
+            geometry_cache,
+            ..RenderSettings::default()
+        };
+        let mut globals = GlobalSettings::new(1920, 1080, settings, Camera::default());
+        demo::build_scene(&mut globals);
+
+        let mut flip = false;
+        let name = format!("geometry_cache/recolor/{}", if geometry_cache { "cached" } else { "cold" });
+        let time = bench(filter, &name, 1, || {
+            flip = !flip;
+            globals.materials.get_mut("red").unwrap().color.y = if flip { 0.5 } else { 0.0 };
+            render_scene(&mut globals).unwrap();
+        });
+        if let Some(time) = time {
+            let cold = *cold.get_or_insert(time);
+            println!("{:<28} {:>12.2}x", "", cold.as_secs_f64() / time.as_secs_f64());
+        }
+    }
+}
+
+// Fill the small scene up to the primitive limit with instances of one
+// shared sphere, and report the memory each of them takes up
+fn bench_instances(filter: &Option<String>, name: &str) {
+    let geometry: Arc<dyn Primitive> = Arc::new(PrimSphere {
+        position: Vector3D::ZERO,
+        velocity: Vector3D::ZERO,
+        radius: 0.1,
+        m: MaterialRef::Named("shiny".to_string()),
+    });
+    let count = MAXPRIMCOUNT as usize - small_scene(RenderSettings::default()).primitives().len();
+
+    let build = || {
+        let mut globals = small_scene(RenderSettings::default());
+        for i in 0..count {
+            let offset = Vector3D::new((i % 8) as f64 * 0.4 - 1.4, (i / 8) as f64 * 0.4 - 1.0, 4.0);
+            add_instance(&geometry, Mat4::translate(offset), None, &mut globals).unwrap();
+        }
+        globals
+    };
+
+    if bench(filter, name, 100, || drop(build())).is_some() {
+        let before = small_scene(RenderSettings::default()).stats().memory;
+        let added = build().stats().memory - before;
+        println!("{:<28} {:>12} bytes / instance", "", added / count);
+    }
+}
+
+// A million triangles, as in a big scanned mesh, shared by eight render
+// workers
+const MESH_TRIANGLES: usize = 1_000_000;
+const WORKERS: usize = 8;
+
+// Fill a scene up to the primitive limit with small quads, the nearest
+// thing to a triangle mesh there is (a quad being two triangles), and
+// report what a mesh of `MESH_TRIANGLES` takes up before rendering starts:
+// once in the `Scene` all the workers share, and with a copy per worker
+fn bench_scene_sharing(filter: &Option<String>, name: &str) {
+    let empty = || GlobalSettings::new(1, 1, RenderSettings::default(), Camera::default());
+    let build = || {
+        let mut globals = empty();
+        globals.materials = small_scene(RenderSettings::default()).materials.clone();
+        for i in 0..MAXPRIMCOUNT {
+            let origin = Vector3D::new((i % 32) as f64 * 0.1 - 1.6, (i / 32) as f64 * 0.1 - 1.6, 4.0);
+            add_quad(origin, Vector3D::new(0.1, 0.0, 0.0), Vector3D::new(0.0, 0.1, 0.0), "shiny", &mut globals).unwrap();
+        }
+        globals
+    };
+
+    if bench(filter, name, 10, || drop(build())).is_some() {
+        let per_quad = (build().stats().memory - empty().stats().memory) / MAXPRIMCOUNT as usize;
+        let mesh = (per_quad * MESH_TRIANGLES / 2) as f64 / (1024.0 * 1024.0);
+        println!("{:<28} {:>12.1} MB shared", "", mesh);
+        println!("{:<28} {:>12.1} MB copied for {} workers", "", mesh * WORKERS as f64, WORKERS);
+    }
+}
+
+fn main() {
+    // Cargo passes `--bench`; anything else is a name filter
+    let filter = std::env::args().skip(1).find(|a| !a.starts_with("--"));
+
+    bench_render(&filter, "render/whitted", RenderSettings::default());
+
+    bench_render(
+        &filter,
+        "render/pathtrace_4spp",
+        RenderSettings {
+            mode: RenderMode::PathTrace,
+            samples: 4,
+            ..RenderSettings::default()
+        },
+    );
+
+    bench_threads(&filter);
+    bench_geometry_cache(&filter);
+
+    let sphere = PrimSphere {
+        position: Vector3D::new(0.0, 0.0, 2.0),
+        velocity: Vector3D::ZERO,
+        radius: 0.5,
+        m: MaterialRef::Named("shiny".to_string()),
+    };
+    let hit = Ray {
+        origin: Vector3D::new(0.0, 0.0, -5.0),
+        direction: Vector3D::UNIT_Z,
+        time: 0.0,
+    };
+    let miss = Ray {
+        origin: Vector3D::new(0.0, 0.0, -5.0),
+        direction: Vector3D::UNIT_Y,
+        time: 0.0,
+    };
+
+    bench(&filter, "sphere/intersect_hit", 1_000_000, || {
+        let mut dist = 0.0;
+        black_box(black_box(&sphere).intersect(black_box(hit), 1e-4, &mut dist));
+    });
+
+    bench(&filter, "sphere/intersect_miss", 1_000_000, || {
+        let mut dist = 0.0;
+        black_box(black_box(&sphere).intersect(black_box(miss), 1e-4, &mut dist));
+    });
+
+    let instance = Instance::new(Arc::new(sphere.clone()), Mat4::scale(Vector3D::new(1.0, 2.0, 1.0))).unwrap();
+
+    bench(&filter, "instance/intersect_hit", 1_000_000, || {
+        let mut dist = 0.0;
+        black_box(black_box(&instance).intersect(black_box(hit), 1e-4, &mut dist));
+    });
+
+    bench_instances(&filter, "instance/fill_scene");
+    bench_scene_sharing(&filter, "scene_sharing/mesh_1m");
+
+    let a = Vector3D::new(1.0, 2.0, 3.0);
+    let b = Vector3D::new(-0.5, 0.25, 4.0);
+
+    bench(&filter, "vector/dot", 1_000_000, || {
+        black_box(black_box(a).v3d_dot_mul(black_box(b)));
+    });
+
+    bench(&filter, "vector/cross", 1_000_000, || {
+        black_box(Vector3D::cross(black_box(a), black_box(b)));
+    });
+
+    bench(&filter, "vector/norm", 1_000_000, || {
+        let mut v = black_box(a);
+        v.v3d_norm();
+        black_box(v);
+    });
+
+    bench(&filter, "vector/reflect", 1_000_000, || {
+        black_box(Vector3D::reflect(black_box(a), black_box(b)));
+    });
+}
@@ -0,0 +1,4 @@
+// The C library of lux. Everything in it comes from lux::ffi; this crate
+// only exists to build it as a shared library, include/lux.h declares it.
+
+pub use lux::ffi::*;
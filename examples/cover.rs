@@ -0,0 +1,29 @@
+// Render the random cover scene (`demo::random_cover`) to cover.png. Run
+// with `cargo run --release --example cover [SEED]`; the same seed always
+// gives the same picture.
+
+use lux::*;
+
+fn main() {
+    let seed = match std::env::args().nth(1) {
+        Some(arg) => arg.parse().unwrap_or_else(|_| {
+            eprintln!("usage: cover [SEED]");
+            std::process::exit(1);
+        }),
+        None => 0,
+    };
+
+    let settings = RenderSettings {
+        samples: 4,
+        ..Default::default()
+    };
+    let mut globals = GlobalSettings::new(640, 360, settings, Camera::default());
+    demo::random_cover(seed, &mut globals);
+    println!("{} primitives", globals.primitives().len());
+
+    if let Err(e) = render_scene(&mut globals).and_then(|()| save_image(&globals.img, "cover.png")) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+    println!("Wrote cover.png");
+}
@@ -0,0 +1,86 @@
+/* Renders the demo scene through the C interface and compares it with the
+ * golden image the Rust tests use, then shows an error coming back.
+ *
+ *   cargo build --release -p lux-capi
+ *   cc -Iinclude examples/ffi_demo.c -Ltarget/release -llux_capi -o target/ffi_demo
+ *   LD_LIBRARY_PATH=target/release target/ffi_demo
+ *
+ * Run it from the top of the repository, or pass the path of
+ * tests/golden/whitted.ppm. Writes the render to ffi_demo.ppm.
+ */
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "lux.h"
+
+#define WIDTH 160
+#define HEIGHT 120
+
+/* Same tolerance as tests/golden.rs */
+#define CHANNEL_TOLERANCE 2
+#define MAX_DIFFERENT_PIXELS (WIDTH * HEIGHT / 200)
+
+/* Reads a binary PPM of WIDTH x HEIGHT into rgb, 0 if that worked */
+static int read_ppm(const char *path, unsigned char *rgb) {
+    FILE *f = fopen(path, "rb");
+    int width, height, max;
+    int ok;
+
+    if (!f) {
+        return -1;
+    }
+    ok = fscanf(f, "P6 %d %d %d", &width, &height, &max) == 3 && fgetc(f) != EOF && width == WIDTH &&
+         height == HEIGHT && max == 255 && fread(rgb, 3, WIDTH * HEIGHT, f) == WIDTH * HEIGHT;
+    fclose(f);
+    return ok ? 0 : -1;
+}
+
+int main(int argc, char **argv) {
+    const char *golden_path = argc > 1 ? argv[1] : "tests/golden/whitted.ppm";
+    static unsigned char pixels[WIDTH * HEIGHT * 4];
+    static unsigned char golden[WIDTH * HEIGHT * 3];
+    LuxScene *scene = lux_scene_new(WIDTH, HEIGHT);
+    FILE *out;
+    int different = 0;
+    int i, c;
+
+    if (!scene || lux_scene_add_builtin(scene, "grid") != 0 || lux_render(scene, pixels, sizeof pixels) != 0) {
+        fprintf(stderr, "rendering failed: %s\n", scene ? lux_last_error(scene) : "no scene");
+        return 1;
+    }
+
+    out = fopen("ffi_demo.ppm", "wb");
+    if (out) {
+        fprintf(out, "P6\n%d %d\n255\n", WIDTH, HEIGHT);
+        for (i = 0; i < WIDTH * HEIGHT; i++) {
+            fwrite(&pixels[i * 4], 1, 3, out);
+        }
+        fclose(out);
+    }
+
+    if (read_ppm(golden_path, golden) != 0) {
+        fprintf(stderr, "can't read %s\n", golden_path);
+        return 1;
+    }
+    for (i = 0; i < WIDTH * HEIGHT; i++) {
+        for (c = 0; c < 3; c++) {
+            if (abs(pixels[i * 4 + c] - golden[i * 3 + c]) > CHANNEL_TOLERANCE) {
+                different++;
+                break;
+            }
+        }
+    }
+    printf("%d of %d pixels differ from %s\n", different, WIDTH * HEIGHT, golden_path);
+
+    /* Errors come back as -1 and a message */
+    if (lux_add_sphere(scene, (LuxVector){0.0, 0.0, 3.0}, 1.0, "no such material") != -1 || !lux_last_error(scene)) {
+        fprintf(stderr, "expected an error for an unknown material\n");
+        return 1;
+    }
+    printf("error as expected: %s\n", lux_last_error(scene));
+
+    lux_scene_free(scene);
+    return different > MAX_DIFFERENT_PIXELS;
+}
@@ -0,0 +1,81 @@
+/* C interface of lux, see src/ffi.rs. Generated by cbindgen. */
+
+#ifndef LUX_H
+#define LUX_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef struct LuxScene LuxScene;
+
+typedef struct LuxVector {
+  double x;
+  double y;
+  double z;
+} LuxVector;
+
+typedef struct LuxMaterial {
+  LuxVector color;
+  double specular;
+  double diffusive;
+  double reflective;
+  double roughness;
+  double refractive;
+  double ior;
+  LuxVector emission;
+  double wrap;
+  double dispersion;
+  double translucency;
+} LuxMaterial;
+
+typedef struct LuxCamera {
+  LuxVector position;
+  LuxVector look_at;
+  LuxVector up;
+  double fov;
+} LuxCamera;
+
+typedef struct LuxRenderSettings {
+  bool path_trace;
+  uint32_t samples;
+  uint32_t max_depth;
+  uint64_t seed;
+  bool shadows;
+  bool denoise;
+  double exposure;
+} LuxRenderSettings;
+
+LuxScene *lux_scene_new(uint32_t width, uint32_t height);
+
+void lux_scene_free(LuxScene *scene);
+
+const char *lux_last_error(const LuxScene *scene);
+
+int32_t lux_scene_add_builtin(LuxScene *scene, const char *name);
+
+int32_t lux_scene_load(LuxScene *scene, const char *path);
+
+int32_t lux_add_material(LuxScene *scene, const char *name, LuxMaterial material);
+
+int32_t lux_add_sphere(LuxScene *scene, LuxVector center, double radius, const char *material);
+
+int32_t lux_add_light(LuxScene *scene, LuxVector position, LuxVector color, double intensity);
+
+LuxCamera lux_camera_default(void);
+
+int32_t lux_set_camera(LuxScene *scene, LuxCamera camera);
+
+LuxRenderSettings lux_render_settings_default(void);
+
+int32_t lux_set_render_settings(LuxScene *scene, LuxRenderSettings settings);
+
+int32_t lux_render(LuxScene *scene, uint8_t *buffer, size_t len);
+
+int32_t lux_render_alloc(LuxScene *scene, uint8_t **buffer, size_t *len);
+
+void lux_buffer_free(uint8_t *buffer, size_t len);
+
+#endif /* LUX_H */
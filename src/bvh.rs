@@ -0,0 +1,281 @@
+// Bounding volume hierarchy over the scene's primitives. Every node holds a
+// box around everything below it, so a ray only gets tested against the
+// primitives whose boxes it actually passes through.
+//
+// The nodes sit in one array in depth first order: an inner node's first
+// child comes right after it, and the node remembers where the second one
+// is. Leaves point at a run of `order`, the primitive indices sorted so that
+// every leaf's primitives are next to each other.
+
+use crate::{Ray, Vector3D};
+
+// How `Bvh::new` decides where to split a node
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum BvhBuild {
+    // Halve the primitives along the axis their centers are spread out the
+    // most. Quick to build and fine for evenly spread scenes.
+    #[default]
+    Median,
+    // Try every split along every axis and keep the one the surface area
+    // heuristic expects to be cheapest to trace. Slower to build, but much
+    // better for clustered scenes.
+    Sah,
+}
+
+// Most primitives in a leaf of the median split
+const LEAF_SIZE: usize = 2;
+
+// Most primitives the SAH puts in a leaf, even if it thinks splitting them
+// up wouldn't pay off
+const MAX_LEAF_SIZE: usize = 8;
+
+// Cost of visiting a node, relative to testing one primitive
+const TRAVERSAL_COST: f64 = 0.25;
+
+// Slack for the box test, so rounding can't make a ray miss the box of a
+// primitive it hits (Pharr et al., "Physically Based Rendering", 3.9.2)
+const BOX_SLACK: f64 = 1.0 + 3.0 * f64::EPSILON;
+
+struct Node {
+    min: Vector3D,
+    max: Vector3D,
+    // Leaves: `count` primitives starting at `start` in `order`. Inner
+    // nodes: `count` is 0, `start` is the second child and `axis` the axis
+    // the children were split along.
+    start: usize,
+    count: usize,
+    axis: usize,
+}
+
+pub struct Bvh {
+    nodes: Vec<Node>,
+    order: Vec<usize>,
+}
+
+impl Bvh {
+    // Hierarchy over primitives with these bounds (see `Primitive::bounds`).
+    // The primitives are referred to by their index in `bounds`.
+    pub fn new(bounds: &[(Vector3D, Vector3D)], build: BvhBuild) -> Self {
+        let mut bvh = Self {
+            nodes: Vec::new(),
+            order: (0..bounds.len()).collect(),
+        };
+
+        if !bounds.is_empty() {
+            bvh.build(bounds, 0, bounds.len(), build);
+        }
+        bvh
+    }
+
+    // Number of primitives in the hierarchy
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    // Closest primitive hit by `ray`, as its index, the distance to it and
+    // whatever else `test` found out. `test` intersects the primitive with
+    // the given index and returns the distance to the hit, if there is one.
+    // Of several hits at the same distance the one with the lowest index
+    // wins, like in a plain loop over all primitives.
+    //
+    // Also returns how many nodes were visited.
+    pub fn closest<H>(&self, ray: &Ray, mut test: impl FnMut(usize) -> Option<(f64, H)>) -> (Option<(usize, f64, H)>, u32) {
+        let mut best: Option<(usize, f64, H)> = None;
+        let mut visits = 0;
+
+        if self.nodes.is_empty() {
+            return (best, visits);
+        }
+
+        let inv = ray.direction.map(|d| 1.0 / d);
+        let mut stack = vec![0];
+
+        while let Some(i) = stack.pop() {
+            let node = &self.nodes[i];
+            let limit = best.as_ref().map_or(f64::INFINITY, |b| b.1);
+
+            visits += 1;
+            if !node.hit_by(ray, inv, limit) {
+                continue;
+            }
+
+            if node.count > 0 {
+                for &p in &self.order[node.start..node.start + node.count] {
+                    if let Some((dist, found)) = test(p) {
+                        let closer = match &best {
+                            Some((q, d, _)) => dist < *d || (dist == *d && p < *q),
+                            None => true,
+                        };
+                        if closer {
+                            best = Some((p, dist, found));
+                        }
+                    }
+                }
+                continue;
+            }
+
+            // The child on the side the ray comes from first, so the hits
+            // found there can rule out the other one
+            let (near, far) = match ray.direction[node.axis] < 0.0 {
+                true => (node.start, i + 1),
+                false => (i + 1, node.start),
+            };
+            stack.push(far);
+            stack.push(near);
+        }
+
+        (best, visits)
+    }
+
+    // Build the subtree over `order[start..end]`, returning its index
+    fn build(&mut self, bounds: &[(Vector3D, Vector3D)], start: usize, end: usize, build: BvhBuild) -> usize {
+        let (mut min, mut max) = bounds[self.order[start]];
+        for &p in &self.order[start..end] {
+            min = min.min(bounds[p].0);
+            max = max.max(bounds[p].1);
+        }
+
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            min,
+            max,
+            start,
+            count: end - start,
+            axis: 0,
+        });
+
+        let split = match build {
+            BvhBuild::Median => self.median_split(bounds, start, end),
+            BvhBuild::Sah => self.sah_split(bounds, start, end, area(min, max)),
+        };
+
+        if let Some((axis, mid)) = split {
+            self.build(bounds, start, mid, build);
+            let second = self.build(bounds, mid, end, build);
+
+            let node = &mut self.nodes[index];
+            node.start = second;
+            node.count = 0;
+            node.axis = axis;
+        }
+        index
+    }
+
+    // Split at the middle primitive along the axis the centers are spread
+    // out the most. Returns the axis and where the second half starts.
+    fn median_split(&mut self, bounds: &[(Vector3D, Vector3D)], start: usize, end: usize) -> Option<(usize, usize)> {
+        if end - start <= LEAF_SIZE {
+            return None;
+        }
+
+        let items = &mut self.order[start..end];
+        let (mut min, mut max) = (center(bounds[items[0]]), center(bounds[items[0]]));
+        for &p in items.iter() {
+            min = min.min(center(bounds[p]));
+            max = max.max(center(bounds[p]));
+        }
+        max.v3d_sub(min);
+        let axis = (0..3).max_by(|&a, &b| max[a].total_cmp(&max[b])).unwrap();
+
+        let mid = items.len() / 2;
+        items.select_nth_unstable_by(mid, |&a, &b| center(bounds[a])[axis].total_cmp(&center(bounds[b])[axis]));
+        Some((axis, start + mid))
+    }
+
+    // Split where the surface area heuristic expects the cheapest traversal:
+    // a ray that hits the node hits a child with a chance proportional to
+    // the child's surface area, and then has to test all its primitives.
+    // Returns None when a leaf is cheaper.
+    fn sah_split(&mut self, bounds: &[(Vector3D, Vector3D)], start: usize, end: usize, parent_area: f64) -> Option<(usize, usize)> {
+        let n = end - start;
+        if n == 1 {
+            return None;
+        }
+        // Everything in one spot, so no split can separate anything
+        if parent_area <= 0.0 {
+            return match n > MAX_LEAF_SIZE {
+                true => self.median_split(bounds, start, end),
+                false => None,
+            };
+        }
+
+        let by_center = |axis: usize| {
+            move |a: &usize, b: &usize| center(bounds[*a])[axis].total_cmp(&center(bounds[*b])[axis])
+        };
+
+        // Best (cost, axis, primitives on the left)
+        let mut best = (f64::INFINITY, 0, 0);
+        let mut items = self.order[start..end].to_vec();
+        for axis in 0..3 {
+            items.sort_by(by_center(axis));
+
+            // Area of the box around the last `n - k` primitives, for every k
+            let mut right_area = vec![0.0; n];
+            let (mut min, mut max) = bounds[items[n - 1]];
+            for k in (1..n).rev() {
+                min = min.min(bounds[items[k]].0);
+                max = max.max(bounds[items[k]].1);
+                right_area[k] = area(min, max);
+            }
+
+            let (mut min, mut max) = bounds[items[0]];
+            for k in 1..n {
+                min = min.min(bounds[items[k - 1]].0);
+                max = max.max(bounds[items[k - 1]].1);
+
+                let cost = TRAVERSAL_COST
+                    + (area(min, max) * k as f64 + right_area[k] * (n - k) as f64) / parent_area;
+                if cost < best.0 {
+                    best = (cost, axis, k);
+                }
+            }
+        }
+
+        let (cost, axis, k) = best;
+        if cost >= n as f64 && n <= MAX_LEAF_SIZE {
+            return None;
+        }
+
+        self.order[start..end].sort_by(by_center(axis));
+        Some((axis, start + k))
+    }
+}
+
+impl Node {
+    // Whether `ray` passes through the box closer than `limit` (slab test).
+    // `inv` is 1 over the ray's direction.
+    fn hit_by(&self, ray: &Ray, inv: Vector3D, limit: f64) -> bool {
+        let (mut near, mut far) = (0.0_f64, limit);
+
+        for axis in 0..3 {
+            let a = (self.min[axis] - ray.origin[axis]) * inv[axis];
+            let b = (self.max[axis] - ray.origin[axis]) * inv[axis];
+            near = near.max(a.min(b));
+            far = far.min(a.max(b));
+        }
+
+        near <= far * BOX_SLACK
+    }
+}
+
+fn center((min, max): (Vector3D, Vector3D)) -> Vector3D {
+    let mut c = min;
+    c.v3d_add(max);
+    c.v3d_mul_scalar(0.5);
+    c
+}
+
+// Surface area of a box
+fn area(min: Vector3D, max: Vector3D) -> f64 {
+    let mut d = max;
+    d.v3d_sub(min);
+    2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+}
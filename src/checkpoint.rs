@@ -0,0 +1,197 @@
+// Checkpoints of a render in progress: what every pixel has taken so far,
+// saved to a file now and then so a long render that gets killed can pick
+// up where it was instead of starting over (see `GlobalSettings::checkpoint`).
+//
+// A checkpoint keeps the running sums every pixel averages at the end, not
+// the averages, along with the pixel's random number generator. The samples
+// taken after resuming are then the ones the render would have taken
+// anyway, and the image comes out the same as rendering them all in one go.
+// The file is a little endian dump of that behind a header with a key of
+// the settings and scene, so a render only picks up its own checkpoints.
+// Raising the samples is fine, that's what resuming is for. With the
+// stratified sampler the cells depend on the total, though: the samples
+// taken before were spread for the old one, so a render resumed with more
+// samples is only about the same as one in one go (resumed with as many,
+// it's the same).
+
+use crate::{GlobalSettings, LuxError, PixelState, Rng, Sampling, Vector3D};
+
+// Start of every checkpoint file
+const MAGIC: &[u8; 8] = b"LUXCKPT1";
+
+// What a checkpoint holds: the samples per pixel the render had got to, and
+// the pixels it rendered in order
+pub(crate) struct Saved {
+    pub(crate) done: u32,
+    pub(crate) pixels: Vec<PixelState>,
+}
+
+// Hash of the image size, the seed, the settings that decide how the
+// samples are taken and how many primitives and lights there are. Changes
+// to the materials or where things are go unnoticed.
+fn key(sampling: &Sampling, globals: &GlobalSettings) -> u64 {
+    let s = &globals.settings;
+    let description = format!(
+        "{:?} {} {:?} {:?} {:?} {:?} {} {:?} {} {} {:?} {:?} {} {} {} {:?} {:?} {} {} {}",
+        globals.img.dimensions(),
+        s.seed,
+        s.mode,
+        s.shading,
+        s.sampler,
+        s.filter,
+        sampling.filter_radius,
+        s.dispersion,
+        s.max_depth,
+        s.light_sampling,
+        s.clamp,
+        s.reject_outliers,
+        s.adaptive,
+        s.min_samples,
+        s.adaptive_threshold,
+        s.region,
+        sampling.camera,
+        globals.primitive_list.len(),
+        globals.light_list.len(),
+        globals.light_aovs.len()
+    );
+
+    // FNV-1a, which unlike the standard library's hasher stays the same
+    // from one build to the next
+    description.bytes().fold(0xcbf2_9ce4_8422_2325, |h, b| (h ^ b as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+// Save `pixels`, rendered up to `done` samples each, as the checkpoint at
+// `path`. It's written next to it first and then moved over it, so a render
+// killed while saving keeps the last one.
+pub(crate) fn save<'a>(
+    path: &str,
+    sampling: &Sampling,
+    globals: &GlobalSettings,
+    done: u32,
+    pixels: impl Iterator<Item = &'a PixelState>,
+) -> Result<(), LuxError> {
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&key(sampling, globals).to_le_bytes());
+    out.extend_from_slice(&done.to_le_bytes());
+
+    let floats = |out: &mut Vec<u8>, values: &[f64]| values.iter().for_each(|v| out.extend_from_slice(&v.to_le_bytes()));
+    for p in pixels {
+        out.extend_from_slice(&(p.pixel as u64).to_le_bytes());
+        out.extend_from_slice(&p.taken.to_le_bytes());
+        out.push(p.converged as u8);
+        out.extend_from_slice(&p.rng.state.to_le_bytes());
+        floats(&mut out, &[p.color.x, p.color.y, p.color.z, p.weights, p.covered, p.mean, p.m2]);
+        out.extend_from_slice(&(p.sums.len() as u32).to_le_bytes());
+        for sum in &p.sums {
+            floats(&mut out, &[sum.x, sum.y, sum.z]);
+        }
+        out.extend_from_slice(&(p.brightest.len() as u32).to_le_bytes());
+        for (l, sample) in &p.brightest {
+            floats(&mut out, &[*l, sample.x, sample.y, sample.z]);
+        }
+    }
+
+    let temporary = format!("{}.tmp", path);
+    std::fs::write(&temporary, out)
+        .and_then(|()| std::fs::rename(&temporary, path))
+        .map_err(|source| LuxError::Io {
+            path: path.to_string(),
+            source,
+        })
+}
+
+// The checkpoint at `path`, `None` if there's no such file. A file that
+// isn't a checkpoint is an error, and so is one of another render.
+pub(crate) fn load(path: &str, sampling: &Sampling, globals: &GlobalSettings) -> Result<Option<Saved>, LuxError> {
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(source) => {
+            return Err(LuxError::Io {
+                path: path.to_string(),
+                source,
+            })
+        }
+    };
+
+    let mut r = Reader { bytes: &bytes, path };
+    if r.array::<8>()? != *MAGIC {
+        return Err(LuxError::Decode(format!("{}: not a lux checkpoint", path)));
+    }
+    if r.u64()? != key(sampling, globals) {
+        return Err(LuxError::InvalidSettings(format!(
+            "{}: checkpoint of another scene, image size or render settings",
+            path
+        )));
+    }
+    let done = r.u32()?;
+
+    let (width, height) = globals.img.dimensions();
+    let mut pixels: Vec<PixelState> = Vec::new();
+    while !r.bytes.is_empty() {
+        let pixel = r.u64()? as usize;
+        if pixel >= (width * height) as usize || pixels.last().is_some_and(|p| p.pixel >= pixel) {
+            return Err(LuxError::Decode(format!("{}: pixel {} out of place", path, pixel)));
+        }
+        let taken = r.u32()?;
+        let converged = r.array::<1>()?[0] != 0;
+        let rng = Rng { state: r.u64()? };
+        let color = r.vector()?;
+        let (weights, covered, mean, m2) = (r.f64()?, r.f64()?, r.f64()?, r.f64()?);
+        let sums = (0..r.u32()?).map(|_| r.vector()).collect::<Result<_, _>>()?;
+        let brightest = (0..r.u32()?).map(|_| Ok((r.f64()?, r.vector()?))).collect::<Result<_, LuxError>>()?;
+
+        pixels.push(PixelState {
+            pixel,
+            x: pixel as u32 % width,
+            y: pixel as u32 / width,
+            rng,
+            color,
+            weights,
+            sums,
+            covered,
+            mean,
+            m2,
+            taken,
+            converged,
+            brightest,
+        });
+    }
+
+    Ok(Some(Saved { done, pixels }))
+}
+
+// Reads the numbers of a checkpoint off the front of `bytes`
+struct Reader<'a> {
+    bytes: &'a [u8],
+    path: &'a str,
+}
+
+impl Reader<'_> {
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], LuxError> {
+        if self.bytes.len() < N {
+            return Err(LuxError::Decode(format!("{}: checkpoint cut short", self.path)));
+        }
+        let mut array = [0; N];
+        array.copy_from_slice(&self.bytes[..N]);
+        self.bytes = &self.bytes[N..];
+        Ok(array)
+    }
+
+    fn u32(&mut self) -> Result<u32, LuxError> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Result<u64, LuxError> {
+        self.array().map(u64::from_le_bytes)
+    }
+
+    fn f64(&mut self) -> Result<f64, LuxError> {
+        self.array().map(f64::from_le_bytes)
+    }
+
+    fn vector(&mut self) -> Result<Vector3D, LuxError> {
+        Ok(Vector3D::new(self.f64()?, self.f64()?, self.f64()?))
+    }
+}
@@ -0,0 +1,275 @@
+// Render configuration files.
+//
+// Settings that aren't part of the scene (resolution, sampling, camera) can be
+// kept in a small TOML file like this one:
+//
+//   [image]
+//   width = 1280
+//   height = 720
+//   bit_depth = 16
+//
+//   [render]
+//   mode = "pathtrace"
+//   samples = 64
+//
+//   [camera]
+//   position = [0.0, 1.0, -6.0]
+//   look_at = [0.0, 0.0, 0.0]
+//   fov = 40.0
+//
+// Only the bits of TOML needed for that are understood (see toml.rs). Missing
+// keys keep their default value. Unknown keys are an error so typos don't go
+// unnoticed.
+
+use crate::toml::{self, boolean, number, vector, whole, Value};
+use crate::{
+    BvhBuild, Camera, CheckpointInterval, Dispersion, Filter, LuxError, Projection, Region, RenderMode, RenderSettings, Sampler, Shading, StereoMode, Vector3D,
+};
+use std::time::Duration;
+
+// Config file picked up from the working directory when none is given
+pub const DEFAULT_CONFIG_FILE: &str = "render.toml";
+
+// Everything needed to render besides the scene itself
+#[derive(Clone, Copy)]
+pub struct RenderConfig {
+    pub width: u32,
+    pub height: u32,
+    // Bits per channel of the saved PNG, 8 or 16
+    pub bit_depth: u8,
+    pub render: RenderSettings,
+    pub camera: Camera,
+}
+
+impl Default for RenderConfig {
+    fn default() -> Self {
+        Self {
+            width: 1920,
+            height: 1080,
+            bit_depth: 8,
+            render: RenderSettings::default(),
+            camera: Camera::default(),
+        }
+    }
+}
+
+impl RenderConfig {
+    // Parse the contents of a config file. Keys not in the file keep their
+    // defaults.
+    pub fn from_toml(text: &str) -> Result<RenderConfig, LuxError> {
+        let mut config = RenderConfig::default();
+        // Turned to once everything else is read, so it doesn't matter if
+        // `position` comes before or after it
+        let mut look_at = None;
+
+        for table in toml::parse(text)? {
+            if table.array {
+                return Err(format!("line {}: unexpected [[{}]]", table.line, table.name).into());
+            }
+
+            for e in &table.entries {
+                if (table.name.as_str(), e.key.as_str()) == ("camera", "look_at") {
+                    let target = vector(&e.key, &e.value).map_err(|err| format!("line {}: {}", e.line, err))?;
+                    look_at = Some((e.line, target));
+                    continue;
+                }
+
+                config
+                    .set(&table.name, &e.key, &e.value)
+                    .map_err(|err| format!("line {}: {}", e.line, err))?;
+            }
+        }
+
+        if let Some((line, target)) = look_at {
+            config.camera.look_at(target, Vector3D::UNIT_Y).map_err(|e| e.at_line(line))?;
+        }
+
+        Ok(config)
+    }
+
+    // Read and parse a config file
+    pub fn load(path: &str) -> Result<RenderConfig, LuxError> {
+        let text = toml::read(path)?;
+        RenderConfig::from_toml(&text).map_err(|e| e.in_file(path))
+    }
+
+    fn set(&mut self, section: &str, key: &str, v: &Value) -> Result<(), String> {
+        let r = &mut self.render;
+        let c = &mut self.camera;
+
+        match (section, key) {
+            ("image", "width") => self.width = whole(key, v)?,
+            ("image", "height") => self.height = whole(key, v)?,
+            ("image", "bit_depth") => {
+                self.bit_depth = match whole(key, v)? {
+                    8 => 8,
+                    16 => 16,
+                    _ => return Err("'bit_depth' expects 8 or 16".to_string()),
+                }
+            }
+
+            ("render", "mode") => {
+                r.mode = match v {
+                    Value::Str(s) if s == "whitted" => RenderMode::Whitted,
+                    Value::Str(s) if s == "pathtrace" => RenderMode::PathTrace,
+                    _ => return Err("'mode' expects \"whitted\" or \"pathtrace\"".to_string()),
+                }
+            }
+            ("render", "shading") => {
+                r.shading = match v {
+                    Value::Str(s) => Shading::from_name(s),
+                    _ => None,
+                }
+                .ok_or_else(|| format!("'shading' expects {}", Shading::NAMES))?
+            }
+            ("render", "samples") => r.samples = whole(key, v)?,
+            ("render", "sampler") => {
+                r.sampler = match v {
+                    Value::Str(s) if s == "random" => Sampler::Random,
+                    Value::Str(s) if s == "stratified" => Sampler::Stratified,
+                    _ => return Err("'sampler' expects \"random\" or \"stratified\"".to_string()),
+                }
+            }
+            ("render", "filter") => {
+                r.filter = match v {
+                    Value::Str(s) if s == "box" => Filter::Box,
+                    Value::Str(s) if s == "tent" => Filter::Tent,
+                    Value::Str(s) if s == "gaussian" => Filter::Gaussian,
+                    _ => return Err("'filter' expects \"box\", \"tent\" or \"gaussian\"".to_string()),
+                }
+            }
+            ("render", "filter_radius") => r.filter_radius = Some(number(key, v)?),
+            ("render", "dispersion") => {
+                r.dispersion = match v {
+                    Value::Str(s) if s == "split" => Dispersion::Split,
+                    Value::Str(s) if s == "spectral" => Dispersion::Spectral,
+                    _ => return Err("'dispersion' expects \"split\" or \"spectral\"".to_string()),
+                }
+            }
+            ("render", "adaptive") => r.adaptive = boolean(key, v)?,
+            ("render", "min_samples") => r.min_samples = whole(key, v)?,
+            ("render", "adaptive_threshold") => r.adaptive_threshold = number(key, v)?,
+            ("render", "max_time") => {
+                r.max_time = match v {
+                    Value::Number(n) => Duration::try_from_secs_f64(*n).ok(),
+                    Value::Str(s) => duration(s),
+                    _ => None,
+                };
+                if r.max_time.is_none() {
+                    return Err("'max_time' expects seconds or a time like \"90s\" or \"5m\"".to_string());
+                }
+            }
+            ("render", "target_noise") => r.target_noise = Some(number(key, v)?),
+            ("render", "max_depth") => r.max_depth = whole(key, v)?,
+            ("render", "light_sampling") => r.light_sampling = boolean(key, v)?,
+            ("render", "clamp") => r.clamp = Some(number(key, v)?),
+            ("render", "reject_outliers") => r.reject_outliers = Some(number(key, v)?),
+            ("render", "seed") => r.seed = whole(key, v)?,
+            ("render", "edges") => r.edges = boolean(key, v)?,
+            ("render", "axes") => r.axes = Some(number(key, v)?),
+            ("render", "toon_bands") => r.toon_bands = Some(whole(key, v)?),
+            ("render", "shadows") => r.shadows = boolean(key, v)?,
+            ("render", "caustics") => r.caustics = boolean(key, v)?,
+            ("render", "photons") => r.photons = whole(key, v)?,
+            ("render", "stats") => r.stats = boolean(key, v)?,
+            ("render", "light_aovs") => r.light_aovs = boolean(key, v)?,
+            ("render", "denoise") => r.denoise = boolean(key, v)?,
+            ("render", "denoise_strength") => r.denoise_strength = number(key, v)?,
+            ("render", "denoise_radius") => r.denoise_radius = whole(key, v)?,
+            ("render", "dither") => r.dither = boolean(key, v)?,
+            ("render", "exposure") => r.exposure = number(key, v)?,
+            ("render", "bvh") => {
+                r.bvh = match v {
+                    Value::Str(s) if s == "median" => BvhBuild::Median,
+                    Value::Str(s) if s == "sah" => BvhBuild::Sah,
+                    _ => return Err("'bvh' expects \"median\" or \"sah\"".to_string()),
+                }
+            }
+            ("render", "ray_epsilon") => r.ray_epsilon = number(key, v)?,
+            ("render", "stereo") => {
+                r.stereo = match v {
+                    Value::Str(s) if s == "off" => None,
+                    Value::Str(s) if s == "anaglyph" => Some(StereoMode::Anaglyph),
+                    Value::Str(s) if s == "side-by-side" => Some(StereoMode::SideBySide),
+                    _ => return Err("'stereo' expects \"off\", \"anaglyph\" or \"side-by-side\"".to_string()),
+                }
+            }
+            ("render", "eye_separation") => r.eye_separation = number(key, v)?,
+            ("render", "convergence") => r.convergence = number(key, v)?,
+            ("render", "conserve_energy") => r.conserve_energy = boolean(key, v)?,
+            ("render", "cull_back_faces") => r.cull_back_faces = boolean(key, v)?,
+            ("render", "threads") => r.threads = whole(key, v)?,
+            ("render", "geometry_cache") => r.geometry_cache = boolean(key, v)?,
+            ("render", "checkpoint_every") => {
+                r.checkpoint_every = match v {
+                    Value::Number(n) => Duration::try_from_secs_f64(*n).ok().map(CheckpointInterval::Time),
+                    Value::Str(s) => checkpoint_interval(s),
+                    _ => None,
+                }
+                .ok_or_else(|| "'checkpoint_every' expects seconds, a time like \"5m\" or samples per pixel like \"16spp\"".to_string())?
+            }
+            ("render", "region") => {
+                r.region = match v {
+                    Value::Array(a) if a.len() == 4 && a.iter().all(|n| *n >= 0.0 && n.fract() == 0.0) => Some(Region {
+                        x: a[0] as u32,
+                        y: a[1] as u32,
+                        width: a[2] as u32,
+                        height: a[3] as u32,
+                    }),
+                    _ => return Err("'region' expects [x, y, width, height] in whole pixels".to_string()),
+                }
+            }
+
+            ("camera", "position") => c.position = vector(key, v)?,
+            ("camera", "velocity") => c.velocity = vector(key, v)?,
+            ("camera", "shutter_time") => c.shutter_time = number(key, v)?,
+            ("camera", "fov") => c.fov = number(key, v)?,
+            ("camera", "roll") => c.roll = number(key, v)?,
+            ("camera", "projection") => {
+                c.projection = match v {
+                    Value::Str(s) if s == "perspective" => Projection::Perspective,
+                    Value::Str(s) if s == "equirectangular" => Projection::Equirectangular,
+                    Value::Str(s) if s == "fisheye" => Projection::Fisheye { fov_degrees: 180.0 },
+                    _ => {
+                        return Err(
+                            "'projection' expects \"perspective\", \"equirectangular\" or \"fisheye\"".to_string(),
+                        )
+                    }
+                }
+            }
+            // Implies the fisheye projection
+            ("camera", "fisheye_fov") => c.projection = Projection::Fisheye { fov_degrees: number(key, v)? },
+
+            ("", _) => return Err(format!("'{}' must be inside a section", key)),
+            _ => return Err(format!("unknown setting '{}' in [{}]", key, section)),
+        }
+
+        Ok(())
+    }
+}
+
+// Length of time like "90s", "1.5m" or "250ms". A plain number is seconds.
+pub fn duration(text: &str) -> Option<Duration> {
+    let text = text.trim();
+    let (number, unit) = match text.find(|c: char| c.is_ascii_alphabetic()) {
+        Some(i) => text.split_at(i),
+        None => (text, "s"),
+    };
+    let scale = match unit {
+        "ms" => 0.001,
+        "s" => 1.0,
+        "m" => 60.0,
+        "h" => 3600.0,
+        _ => return None,
+    };
+    let seconds = number.trim().parse::<f64>().ok()? * scale;
+    Duration::try_from_secs_f64(seconds).ok()
+}
+
+// Time between checkpoints like `duration`, or samples per pixel like "16spp"
+pub fn checkpoint_interval(text: &str) -> Option<CheckpointInterval> {
+    match text.trim().strip_suffix("spp") {
+        Some(samples) => samples.trim().parse().ok().map(CheckpointInterval::Samples),
+        None => duration(text).map(CheckpointInterval::Time),
+    }
+}
@@ -0,0 +1,326 @@
+// The built-in scenes. The demo scene (`grid`) is a grid of small spheres
+// spelling out a pattern, lit by a single light; `main` renders it unless
+// told otherwise. The others show off one feature each. The golden image
+// tests compare against renders of all of them.
+
+use crate::*;
+
+// Names of the built-in scenes, for messages
+pub const NAMES: &str = "grid, cornell, glass, mirror-hall, floor, cover or fog";
+
+// The function building the built-in scene called `name`, if there is one
+pub fn find(name: &str) -> Option<fn(&mut GlobalSettings)> {
+    match name {
+        "grid" => Some(build_scene),
+        "cornell" => Some(cornell),
+        "glass" => Some(glass),
+        "mirror-hall" => Some(mirror_hall),
+        "floor" => Some(floor),
+        "cover" => Some(cover),
+        "fog" => Some(fog),
+        _ => None,
+    }
+}
+
+// Material with only a diffuse part
+fn matte(r: f64, g: f64, b: f64) -> Material {
+    Material {
+        color: Vector3D::new(r, g, b),
+        diffusive: 0.8,
+        ..Default::default()
+    }
+}
+
+fn v(x: f64, y: f64, z: f64) -> Vector3D {
+    Vector3D::new(x, y, z)
+}
+
+// Add the demo scene's materials, spheres and light to `globals`
+pub fn build_scene(globals: &mut GlobalSettings) {
+    // Set three materials here. Spheres refer to them by name, so tweaking
+    // one of these changes every sphere that uses it.
+    let mirror = Material {
+        color: Vector3D::new(0.6, 0.6, 0.6),
+        specular: 0.3,
+        diffusive: 0.2,
+        reflective: 0.8,
+        ..Default::default()
+    };
+
+    let green = Material {
+        color: Vector3D::new(0.1, 1.0, 0.1),
+        specular: 0.1,
+        diffusive: 0.3,
+        reflective: 0.4,
+        ..Default::default()
+    };
+
+    let red = Material {
+        color: Vector3D::new(1.0, 0.1, 0.1),
+        specular: 0.1,
+        diffusive: 0.3,
+        reflective: 0.4,
+        ..Default::default()
+    };
+
+    globals.add_material("mirror", mirror);
+    globals.add_material("green", green);
+    globals.add_material("red", red);
+
+    // Position of spheres in the image. Edit this to alter the sphere pattern.
+    // The default is a 9 x 6 pattern, but any rectangular map works.
+    let sphere_pos_map = [
+        "g..g..r..",
+        "g..g.....",
+        "gggg..r..",
+        "g..g..r..",
+        "g..g..r..",
+        "......r..",
+    ];
+
+    let materials = HashMap::from([('g', "green".into()), ('r', "red".into()), ('.', "mirror".into())]);
+
+    // Colored spheres stand in a row in front, the mirrors behind them wave
+    // back and forth
+    let grid = SphereGrid {
+        origin: Vector3D::new(-2.0, 1.25, 2.0),
+        spacing: 0.5,
+        radius: 0.25,
+        depth: |c, i, j| match c {
+            '.' => ((i + j) as f64).sin() * 0.8,
+            _ => -0.5,
+        },
+    };
+    add_sphere_grid(&sphere_pos_map, &materials, &grid, globals).unwrap();
+
+    // Add a single light source  
+    let lightpos: Vector3D = Vector3D::ZERO;
+    let lightcolor: Vector3D = Vector3D::ONE;
+    add_light(lightpos, lightcolor, 2.0, globals).unwrap();
+}
+
+// Cornell box: a room open towards the camera with a red wall on the left, a
+// green one on the right, a matte and a mirror sphere on the floor and a
+// square light in the ceiling. The light is an emissive quad, so the box is
+// only lit with --pathtrace.
+pub fn cornell(globals: &mut GlobalSettings) {
+    let mut mirror = matte(0.9, 0.9, 0.9);
+    mirror.diffusive = 0.0;
+    mirror.reflective = 0.9;
+
+    let mut light = matte(1.0, 1.0, 1.0);
+    light.diffusive = 0.0;
+    light.emission = v(12.0, 12.0, 12.0);
+
+    // The walls are only there from inside, so a camera can look in
+    // through any of them
+    let wall = |r, g, b| Material {
+        sidedness: Sidedness::FrontOnly,
+        ..matte(r, g, b)
+    };
+
+    globals.add_material("white", matte(0.75, 0.75, 0.75));
+    globals.add_material("white wall", wall(0.75, 0.75, 0.75));
+    globals.add_material("red", wall(0.75, 0.15, 0.15));
+    globals.add_material("green", wall(0.15, 0.75, 0.15));
+    globals.add_material("mirror", mirror);
+    globals.add_material("light", light);
+
+    // Floor, ceiling, back, left and right, all facing into the box
+    let walls = [
+        (v(-1.0, -1.0, -1.5), v(0.0, 0.0, 2.0), v(2.0, 0.0, 0.0), "white wall"),
+        (v(-1.0, 1.0, -1.5), v(2.0, 0.0, 0.0), v(0.0, 0.0, 2.0), "white wall"),
+        (v(-1.0, -1.0, 0.5), v(0.0, 2.0, 0.0), v(2.0, 0.0, 0.0), "white wall"),
+        (v(-1.0, -1.0, -1.5), v(0.0, 2.0, 0.0), v(0.0, 0.0, 2.0), "red"),
+        (v(1.0, -1.0, -1.5), v(0.0, 0.0, 2.0), v(0.0, 2.0, 0.0), "green"),
+    ];
+    for (origin, edge_u, edge_v, m) in walls {
+        add_quad(origin, edge_u, edge_v, m, globals).unwrap();
+    }
+
+    add_sphere(&v(-0.45, -0.65, -0.2), 0.35, "white", globals).unwrap();
+    add_sphere(&v(0.45, -0.65, -0.7), 0.35, "mirror", globals).unwrap();
+
+    // Just below the ceiling, so it doesn't fight with it
+    add_quad(v(-0.3, 0.999, -0.8), v(0.6, 0.0, 0.0), v(0.0, 0.0, 0.6), "light", globals).unwrap();
+}
+
+// A glass ball close up, with colored balls behind it showing through upside
+// down
+pub fn glass(globals: &mut GlobalSettings) {
+    let mut glass = matte(1.0, 1.0, 1.0);
+    glass.diffusive = 0.0;
+    glass.specular = 0.2;
+    glass.reflective = 0.05;
+    glass.refractive = 0.9;
+
+    globals.add_material("glass", glass);
+    globals.add_material("floor", matte(0.6, 0.6, 0.6));
+    globals.add_material("red", matte(0.9, 0.2, 0.2));
+    globals.add_material("blue", matte(0.2, 0.3, 0.9));
+
+    add_quad(v(-6.0, -1.0, -2.0), v(0.0, 0.0, 12.0), v(12.0, 0.0, 0.0), "floor", globals).unwrap();
+    add_sphere(&v(0.0, -0.1, 1.0), 0.9, "glass", globals).unwrap();
+    add_sphere(&v(-0.8, -0.5, 4.0), 0.5, "red", globals).unwrap();
+    add_sphere(&v(0.9, 0.0, 5.0), 0.7, "blue", globals).unwrap();
+
+    add_light(v(-2.0, 4.0, -1.0), v(1.0, 1.0, 1.0), 1.2, globals).unwrap();
+}
+
+// Two mirrors facing each other across a hall, with a red ball between them
+// that repeats into the distance until the bounces run out
+pub fn mirror_hall(globals: &mut GlobalSettings) {
+    let mut mirror = matte(0.9, 0.95, 0.9);
+    mirror.diffusive = 0.05;
+    mirror.reflective = 0.9;
+
+    globals.add_material("mirror", mirror);
+    globals.add_material("floor", matte(0.5, 0.5, 0.45));
+    globals.add_material("red", matte(0.9, 0.15, 0.1));
+
+    add_quad(v(-1.2, -1.0, -6.0), v(0.0, 2.5, 0.0), v(0.0, 0.0, 14.0), "mirror", globals).unwrap();
+    add_quad(v(1.2, -1.0, -6.0), v(0.0, 0.0, 14.0), v(0.0, 2.5, 0.0), "mirror", globals).unwrap();
+    add_quad(v(-1.2, -1.0, -6.0), v(0.0, 0.0, 14.0), v(2.4, 0.0, 0.0), "floor", globals).unwrap();
+    add_sphere(&v(-0.3, -0.6, 2.5), 0.4, "red", globals).unwrap();
+
+    add_light(v(0.0, 1.2, 0.0), v(1.0, 1.0, 1.0), 1.5, globals).unwrap();
+}
+
+// Side of a floor tile, and tiles along each side of the floor
+const TILE: f64 = 1.5;
+const TILES: i32 = 7;
+
+// Balls floating over a checkered floor, lit from high above so each one
+// casts its shadow about straight down. The floor is made of quads, one per
+// tile. The scene is there to show the shadows, so it turns them on.
+pub fn floor(globals: &mut GlobalSettings) {
+    globals.settings.shadows = true;
+
+    let mut shiny = matte(0.7, 0.7, 0.75);
+    shiny.diffusive = 0.3;
+    shiny.specular = 0.3;
+    shiny.reflective = 0.6;
+
+    globals.add_material("light tile", matte(0.85, 0.85, 0.8));
+    globals.add_material("dark tile", matte(0.15, 0.15, 0.2));
+    globals.add_material("red", matte(0.9, 0.2, 0.15));
+    globals.add_material("blue", matte(0.2, 0.35, 0.9));
+    globals.add_material("shiny", shiny);
+
+    let start = v(-TILE * TILES as f64 / 2.0, -1.0, -2.0);
+    for i in 0..TILES {
+        for j in 0..TILES {
+            let origin = v(start.x + i as f64 * TILE, start.y, start.z + j as f64 * TILE);
+            let m = match (i + j) % 2 {
+                0 => "light tile",
+                _ => "dark tile",
+            };
+            add_quad(origin, v(0.0, 0.0, TILE), v(TILE, 0.0, 0.0), m, globals).unwrap();
+        }
+    }
+
+    for (center, radius, m) in FLOOR_BALLS {
+        add_sphere(&v(center.0, center.1, center.2), radius, m, globals).unwrap();
+    }
+
+    add_light(v(0.0, 6.0, 3.5), v(1.0, 1.0, 1.0), 1.0, globals).unwrap();
+}
+
+// Center, radius and material of the balls over the floor
+pub const FLOOR_BALLS: [((f64, f64, f64), f64, &str); 3] = [
+    ((-1.2, 0.0, 3.5), 0.5, "red"),
+    ((0.8, -0.2, 2.8), 0.45, "shiny"),
+    ((1.6, 0.3, 5.0), 0.6, "blue"),
+];
+
+// Small balls on the cover field: rows along z and columns along x, one
+// ball per cell, nudged around inside it
+const COVER_ROWS: i32 = 20;
+const COVER_COLUMNS: i32 = 15;
+const COVER_BALL: f64 = 0.2;
+
+// The three big balls in the middle of the cover field: matte, glass and
+// metal
+pub const COVER_BIG_BALLS: [(f64, f64, f64); 3] = [(-2.4, 0.0, 5.0), (0.0, 0.0, 5.0), (2.4, 0.0, 5.0)];
+
+// The "Ray Tracing in One Weekend" cover: hundreds of small balls of random
+// matte, metal and glass materials scattered over a ground plane, around
+// three big ones. The same `seed` always gives the same scene. Meant as a
+// stress test for the BVH with a bit of every material.
+pub fn random_cover(seed: u64, globals: &mut GlobalSettings) {
+    globals.settings.shadows = true;
+
+    let mut glass = matte(1.0, 1.0, 1.0);
+    glass.diffusive = 0.0;
+    glass.specular = 0.05;
+    glass.reflective = 0.05;
+    glass.refractive = 0.9;
+
+    let metal = |color: Vector3D, roughness: f64| {
+        let mut m = matte(color.x, color.y, color.z);
+        m.diffusive = 0.1;
+        m.specular = 0.1;
+        m.reflective = 0.8;
+        m.roughness = roughness;
+        m
+    };
+
+    globals.add_material("ground", matte(0.5, 0.5, 0.5));
+    globals.add_material("glass", glass);
+    add_quad(v(-50.0, -1.0, -5.0), v(0.0, 0.0, 100.0), v(100.0, 0.0, 0.0), "ground", globals).unwrap();
+
+    let [left, middle, right] = COVER_BIG_BALLS.map(|(x, y, z)| v(x, y, z));
+    add_sphere(&left, 1.0, matte(0.4, 0.2, 0.1), globals).unwrap();
+    add_sphere(&middle, 1.0, "glass", globals).unwrap();
+    add_sphere(&right, 1.0, metal(v(0.7, 0.6, 0.5), 0.0), globals).unwrap();
+
+    let mut rng = Rng::new(seed);
+    for row in 0..COVER_ROWS {
+        for column in 0..COVER_COLUMNS {
+            let x = (column - COVER_COLUMNS / 2) as f64 + 0.9 * rng.next_f64();
+            let z = row as f64 + 0.9 * rng.next_f64();
+            let center = v(x, -1.0 + COVER_BALL, z);
+            let choice = rng.next_f64();
+
+            // Keep clear of the big balls
+            let near_big = COVER_BIG_BALLS.iter().any(|&(bx, _, bz)| (x - bx).hypot(z - bz) < 1.2);
+            if near_big {
+                continue;
+            }
+
+            let mut random_color = || v(rng.next_f64(), rng.next_f64(), rng.next_f64());
+            if choice < 0.8 {
+                let mut color = random_color();
+                color.v3d_mul_v3d(random_color());
+                add_sphere(&center, COVER_BALL, matte(color.x, color.y, color.z), globals).unwrap();
+            } else if choice < 0.95 {
+                let color = random_color().map(|c| 0.5 + c * 0.5);
+                add_sphere(&center, COVER_BALL, metal(color, rng.next_f64() * 0.5), globals).unwrap();
+            } else {
+                add_sphere(&center, COVER_BALL, "glass", globals).unwrap();
+            }
+        }
+    }
+
+    add_light(v(-4.0, 8.0, -2.0), v(1.0, 1.0, 1.0), 1.2, globals).unwrap();
+}
+
+// The cover scene for the seed of the render settings, so `--seed` picks a
+// different one
+pub fn cover(globals: &mut GlobalSettings) {
+    random_cover(globals.settings.seed, globals);
+}
+
+// The demo scene in dark fog, with the light shafts on. The light is moved
+// behind the spheres, so it shines through the gaps between them towards
+// the camera.
+pub fn fog(globals: &mut GlobalSettings) {
+    build_scene(globals);
+    globals.light_list[0].position = v(0.0, 0.0, 6.0);
+    globals.light_list[0].intensity = 6.0;
+    globals.fog = Fog {
+        density: 0.15,
+        color: v(0.02, 0.02, 0.03),
+        shafts: 32,
+    };
+}
@@ -0,0 +1,237 @@
+// Image based lighting. An equirectangular HDR image (like the .hdr and
+// .exr files sky probes come as) wrapped around the scene: rays that miss
+// everything see it, and it lights the surfaces.
+//
+// The image's columns go once around the vertical axis and its rows from
+// straight up to straight down, laid out so an `--equirect` render from
+// the default camera gives back the image itself: the middle of the image
+// is +z, its left and right edges -z, its top +y.
+//
+// The path tracer samples it like a light, picking bright pixels more
+// often than dark ones. `trace` only uses it as colored ambient light,
+// the light a surface facing some way gets from all of it (without any
+// shadows), worked out once as spherical harmonics.
+
+use std::f64::consts::PI;
+use std::fs::File;
+use std::io::BufReader;
+
+use image::codecs::hdr::HdrDecoder;
+use image::Rgb;
+
+use crate::{LuxError, Rng, Vector3D};
+
+// Rows the irradiance is summed over at least, and twice as many columns
+const MIN_CELLS: usize = 64;
+
+pub struct EnvMap {
+    width: usize,
+    height: usize,
+    // Radiance of every pixel, row by row from the top
+    pixels: Vec<Vector3D>,
+    // Running sums for picking a pixel in proportion to its luminance
+    // times the solid angle it covers: of whole rows, then along every row
+    rows: Vec<f64>,
+    columns: Vec<f64>,
+    // Cosine of the angle from straight up of the top edge of every row,
+    // and of the bottom edge of the last one
+    edges: Vec<f64>,
+    // Irradiance as 9 spherical harmonics coefficients, already weighted
+    // for the cosine
+    irradiance: [Vector3D; 9],
+}
+
+impl EnvMap {
+    // Environment map from the image at `path`, in any format the image
+    // crate reads. HDR formats keep their full range.
+    pub fn load(path: &str) -> Result<Self, LuxError> {
+        let to_vector = |p: &Rgb<f32>| Vector3D::new(p[0] as f64, p[1] as f64, p[2] as f64);
+
+        // `image::open` squeezes Radiance files into 8 bits, so those are
+        // decoded by hand
+        if path.to_ascii_lowercase().ends_with(".hdr") {
+            let file = File::open(path).map_err(|source| LuxError::Io {
+                path: path.to_string(),
+                source,
+            })?;
+            let decoder = HdrDecoder::new(BufReader::new(file)).map_err(|e| LuxError::from_image(path, e))?;
+            let meta = decoder.metadata();
+            let pixels = decoder.read_image_hdr().map_err(|e| LuxError::from_image(path, e))?;
+            return Self::from_pixels(meta.width, meta.height, pixels.iter().map(to_vector).collect());
+        }
+
+        let img = image::open(path).map_err(|e| LuxError::from_image(path, e))?.to_rgb32f();
+        Self::from_pixels(img.width(), img.height(), img.pixels().map(to_vector).collect())
+    }
+
+    // Environment map from the radiance of `width` x `height` pixels, row
+    // by row from the top
+    pub fn from_pixels(width: u32, height: u32, pixels: Vec<Vector3D>) -> Result<Self, LuxError> {
+        let (width, height) = (width as usize, height as usize);
+        if width == 0 || height == 0 || pixels.len() != width * height {
+            return Err(LuxError::Scene(format!(
+                "an environment map of {}x{} needs {} pixels, not {}",
+                width,
+                height,
+                width * height,
+                pixels.len()
+            )));
+        }
+        if pixels.iter().any(|p| p.min_component() < 0.0 || !p.max_component().is_finite()) {
+            return Err(LuxError::Scene("environment maps can't have negative or infinite pixels".to_string()));
+        }
+
+        let edges: Vec<f64> = (0..=height).map(|y| (y as f64 / height as f64 * PI).cos()).collect();
+
+        let mut rows = Vec::with_capacity(height);
+        let mut columns = Vec::with_capacity(width * height);
+        let mut total = 0.0;
+        for y in 0..height {
+            let solid_angle = 2.0 * PI / width as f64 * (edges[y] - edges[y + 1]);
+            let mut sum = 0.0;
+            for p in &pixels[y * width..(y + 1) * width] {
+                sum += p.luminance() * solid_angle;
+                columns.push(sum);
+            }
+            total += sum;
+            rows.push(total);
+        }
+
+        let mut map = Self {
+            width,
+            height,
+            pixels,
+            rows,
+            columns,
+            edges,
+            irradiance: [Vector3D::ZERO; 9],
+        };
+        map.irradiance = map.project_irradiance();
+        Ok(map)
+    }
+
+    // The same color all around
+    pub fn solid(color: Vector3D) -> Self {
+        Self::from_pixels(1, 1, vec![color]).unwrap()
+    }
+
+    // Light coming from the direction `dir` (a unit vector)
+    pub fn radiance(&self, dir: Vector3D) -> Vector3D {
+        let (x, y) = self.pixel_of(dir);
+        self.pixels[y * self.width + x]
+    }
+
+    // Random direction towards the map, picked in proportion to how much
+    // light comes from there, and its pdf over solid angle. `None` for a
+    // black map.
+    pub fn sample(&self, rng: &mut Rng) -> Option<(Vector3D, f64)> {
+        let total = *self.rows.last()?;
+        if total <= 0.0 {
+            return None;
+        }
+
+        let y = pick(&self.rows, rng.next_f64() * total);
+        let row = &self.columns[y * self.width..(y + 1) * self.width];
+        let row_total = row[self.width - 1];
+        let x = pick(row, rng.next_f64() * row_total);
+
+        // Anywhere in the pixel, every bit of solid angle alike
+        let u = (x as f64 + rng.next_f64()) / self.width as f64;
+        let t = rng.next_f64();
+        let cos_theta = self.edges[y] * (1.0 - t) + self.edges[y + 1] * t;
+        let dir = direction(u, cos_theta);
+
+        let pdf = self.pdf(dir);
+        (pdf > 0.0).then_some((dir, pdf))
+    }
+
+    // Pdf of `sample` picking `dir`, over solid angle: the luminance there
+    // over the map's total
+    pub fn pdf(&self, dir: Vector3D) -> f64 {
+        match self.rows.last() {
+            Some(&total) if total > 0.0 => self.radiance(dir).luminance() / total,
+            _ => 0.0,
+        }
+    }
+
+    // Light a white diffuse surface facing `n` reflects when the whole map
+    // shines on it: the irradiance over pi. A map of one color gives that
+    // color back.
+    pub fn diffuse(&self, n: Vector3D) -> Vector3D {
+        let basis = sh_basis(n);
+        let mut c = Vector3D::ZERO;
+        for (coefficient, b) in self.irradiance.iter().zip(basis) {
+            let mut term = *coefficient;
+            term.v3d_mul_scalar(b);
+            c.v3d_add(term);
+        }
+        c.v3d_mul_scalar(1.0 / PI);
+        c.max(Vector3D::ZERO)
+    }
+
+    fn pixel_of(&self, dir: Vector3D) -> (usize, usize) {
+        let u = dir.x.atan2(dir.z) / (2.0 * PI) + 0.5;
+        let v = 0.5 - dir.y.clamp(-1.0, 1.0).asin() / PI;
+        let x = ((u * self.width as f64) as usize).min(self.width - 1);
+        let y = ((v * self.height as f64) as usize).min(self.height - 1);
+        (x, y)
+    }
+
+    // Irradiance coefficients (Ramamoorthi and Hanrahan): the map's
+    // radiance projected onto the first 9 spherical harmonics, times how
+    // much the cosine keeps of every band. Small maps are split into finer
+    // cells for that, so a map of a few pixels is still summed up right.
+    fn project_irradiance(&self) -> [Vector3D; 9] {
+        const BANDS: [f64; 9] = [PI, 2.0 * PI / 3.0, 2.0 * PI / 3.0, 2.0 * PI / 3.0, PI / 4.0, PI / 4.0, PI / 4.0, PI / 4.0, PI / 4.0];
+
+        let columns = self.width.max(MIN_CELLS * 2);
+        let rows = self.height.max(MIN_CELLS);
+        let mut coefficients = [Vector3D::ZERO; 9];
+        for y in 0..rows {
+            let (top, bottom) = ((y as f64 / rows as f64 * PI).cos(), ((y + 1) as f64 / rows as f64 * PI).cos());
+            let solid_angle = 2.0 * PI / columns as f64 * (top - bottom);
+            for x in 0..columns {
+                let dir = direction((x as f64 + 0.5) / columns as f64, (top + bottom) / 2.0);
+                let radiance = self.radiance(dir);
+                for (c, b) in coefficients.iter_mut().zip(sh_basis(dir)) {
+                    let mut term = radiance;
+                    term.v3d_mul_scalar(b * solid_angle);
+                    c.v3d_add(term);
+                }
+            }
+        }
+
+        for (c, band) in coefficients.iter_mut().zip(BANDS) {
+            c.v3d_mul_scalar(band);
+        }
+        coefficients
+    }
+}
+
+// Direction across `u` of the map's width (0 to 1), at `cos_theta`, the
+// cosine of the angle from straight up
+fn direction(u: f64, cos_theta: f64) -> Vector3D {
+    let (sin_lon, cos_lon) = ((u - 0.5) * 2.0 * PI).sin_cos();
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    Vector3D::new(sin_theta * sin_lon, cos_theta, sin_theta * cos_lon)
+}
+
+// First entry of the running sums `sums` that's above `target`
+fn pick(sums: &[f64], target: f64) -> usize {
+    sums.partition_point(|&s| s <= target).min(sums.len() - 1)
+}
+
+// The first 9 real spherical harmonics at `d`
+fn sh_basis(d: Vector3D) -> [f64; 9] {
+    [
+        0.282_095,
+        0.488_603 * d.y,
+        0.488_603 * d.z,
+        0.488_603 * d.x,
+        1.092_548 * d.x * d.y,
+        1.092_548 * d.y * d.z,
+        0.315_392 * (3.0 * d.z * d.z - 1.0),
+        1.092_548 * d.x * d.z,
+        0.546_274 * (d.x * d.x - d.y * d.y),
+    ]
+}
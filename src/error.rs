@@ -0,0 +1,122 @@
+// The one error type of the crate. Everything that can fail in the public
+// API (loading scenes and configs, adding things to a scene, rendering,
+// saving images) returns a `LuxError`, so callers only have one type to
+// match on.
+
+use std::fmt;
+
+#[derive(Debug)]
+pub enum LuxError {
+    // A file couldn't be read or written
+    Io { path: String, source: std::io::Error },
+    // A file was read but its contents are garbled, e.g. a scene file that
+    // isn't UTF-8 text
+    Decode(String),
+    // A scene or config file isn't what lux expects. The message says where
+    // ("scene.toml: line 3: ...").
+    Parse(String),
+    // A file format lux can't read or write
+    UnsupportedFormat(String),
+    // The scene is full: it holds `max` primitives or lights at most
+    SceneCapacity { kind: &'static str, max: u32 },
+    // Something added to the scene doesn't make sense, like a material name
+    // that isn't in the library
+    Scene(String),
+    // The render settings can't work, like a region outside the image
+    InvalidSettings(String),
+}
+
+impl LuxError {
+    // Same error with "line N: " in front of the message
+    pub(crate) fn at_line(self, line: usize) -> Self {
+        self.prefixed(format!("line {}: ", line))
+    }
+
+    // Same error with "path: " in front of the message
+    pub(crate) fn in_file(self, path: &str) -> Self {
+        self.prefixed(format!("{}: ", path))
+    }
+
+    // Only the errors that are just a message get the prefix, the others
+    // already say what they're about
+    fn prefixed(self, prefix: String) -> Self {
+        match self {
+            LuxError::Decode(m) => LuxError::Decode(prefix + &m),
+            LuxError::Parse(m) => LuxError::Parse(prefix + &m),
+            LuxError::UnsupportedFormat(m) => LuxError::UnsupportedFormat(prefix + &m),
+            LuxError::Scene(m) => LuxError::Scene(prefix + &m),
+            LuxError::InvalidSettings(m) => LuxError::InvalidSettings(prefix + &m),
+            e => e,
+        }
+    }
+
+    // Error from reading or writing the image file `path`
+    pub(crate) fn from_image(path: &str, e: image::ImageError) -> Self {
+        match e {
+            image::ImageError::IoError(source) => LuxError::Io {
+                path: path.to_string(),
+                source,
+            },
+            image::ImageError::Unsupported(e) => LuxError::UnsupportedFormat(format!("{}: {}", path, e)),
+            image::ImageError::Decoding(e) => LuxError::Decode(format!("{}: {}", path, e)),
+            e => LuxError::Io {
+                path: path.to_string(),
+                source: std::io::Error::other(e),
+            },
+        }
+    }
+
+    // Error from writing the PNG `path` with the png crate
+    pub(crate) fn from_png_encoding(path: &str, e: png::EncodingError) -> Self {
+        match e {
+            png::EncodingError::IoError(source) => LuxError::Io {
+                path: path.to_string(),
+                source,
+            },
+            e => LuxError::UnsupportedFormat(format!("{}: {}", path, e)),
+        }
+    }
+
+    // Error from reading the PNG `path` with the png crate
+    pub(crate) fn from_png_decoding(path: &str, e: png::DecodingError) -> Self {
+        match e {
+            png::DecodingError::IoError(source) => LuxError::Io {
+                path: path.to_string(),
+                source,
+            },
+            e => LuxError::Decode(format!("{}: {}", path, e)),
+        }
+    }
+}
+
+impl fmt::Display for LuxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LuxError::Io { path, source } => write!(f, "{}: {}", path, source),
+            LuxError::Decode(m)
+            | LuxError::Parse(m)
+            | LuxError::UnsupportedFormat(m)
+            | LuxError::Scene(m)
+            | LuxError::InvalidSettings(m) => write!(f, "{}", m),
+            LuxError::SceneCapacity { kind, max } => {
+                write!(f, "too many {}s, a scene holds {} at most", kind, max)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LuxError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LuxError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+// The file parsers build their messages as strings
+impl From<String> for LuxError {
+    fn from(message: String) -> Self {
+        LuxError::Parse(message)
+    }
+}
@@ -0,0 +1,396 @@
+// C interface, for driving the renderer from C, C++ or Python (ctypes)
+// without going through scene files. Built with the `ffi` feature, and as a
+// shared library by the lux-capi crate in capi/; the declarations are in
+// include/lux.h and examples/ffi_demo.c shows them in use.
+//
+// A scene is an opaque `LuxScene` handle from `lux_scene_new`, freed with
+// `lux_scene_free`. Everything crossing over is a plain `#[repr(C)]` struct
+// or a number. Functions that can fail return 0 when they worked and -1
+// when they didn't, and `lux_last_error` then says why. Nothing panics
+// across the boundary: a panic inside is caught and turned into an error.
+//
+// Every pointer passed in must be null or valid for what it points to:
+// scenes from `lux_scene_new` that weren't freed yet, NUL-terminated UTF-8
+// strings, buffers at least as long as the length given with them. Null
+// scenes and strings are refused with -1.
+
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+use crate::{add_light, add_sphere, demo, render_scene, scene, Camera, GlobalSettings, LuxError, Material, RenderMode, RenderSettings, Vector3D};
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LuxVector {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl From<LuxVector> for Vector3D {
+    fn from(v: LuxVector) -> Self {
+        Vector3D::new(v.x, v.y, v.z)
+    }
+}
+
+impl From<Vector3D> for LuxVector {
+    fn from(v: Vector3D) -> Self {
+        LuxVector { x: v.x, y: v.y, z: v.z }
+    }
+}
+
+// The parts of `Material` that are plain numbers. Materials made from it
+// are two-sided, without bumps or cutouts.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LuxMaterial {
+    pub color: LuxVector,
+    pub specular: f64,
+    pub diffusive: f64,
+    pub reflective: f64,
+    pub roughness: f64,
+    pub refractive: f64,
+    pub ior: f64,
+    pub emission: LuxVector,
+    pub wrap: f64,
+    pub dispersion: f64,
+    pub translucency: f64,
+}
+
+impl From<LuxMaterial> for Material {
+    fn from(m: LuxMaterial) -> Self {
+        Material {
+            color: m.color.into(),
+            specular: m.specular,
+            diffusive: m.diffusive,
+            reflective: m.reflective,
+            roughness: m.roughness,
+            refractive: m.refractive,
+            ior: m.ior,
+            emission: m.emission.into(),
+            wrap: m.wrap,
+            dispersion: m.dispersion,
+            translucency: m.translucency,
+            ..Default::default()
+        }
+    }
+}
+
+// Camera at `position` looking at `look_at`, with `up` pointing to the top
+// of the image and a vertical field of view of `fov` degrees
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LuxCamera {
+    pub position: LuxVector,
+    pub look_at: LuxVector,
+    pub up: LuxVector,
+    pub fov: f64,
+}
+
+// The render settings worth changing from outside; the rest keep their
+// defaults
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LuxRenderSettings {
+    // Path trace instead of using the Whitted tracer
+    pub path_trace: bool,
+    pub samples: u32,
+    pub max_depth: u32,
+    pub seed: u64,
+    pub shadows: bool,
+    pub denoise: bool,
+    pub exposure: f64,
+}
+
+pub struct LuxScene {
+    globals: GlobalSettings,
+    // What went wrong last, for `lux_last_error`
+    error: Option<CString>,
+}
+
+// Run `f` on the scene behind `scene`, keeping its error (or panic) for
+// `lux_last_error`. 0 if it worked, -1 if not.
+unsafe fn with_scene(scene: *mut LuxScene, f: impl FnOnce(&mut GlobalSettings) -> Result<(), LuxError>) -> i32 {
+    let Some(scene) = scene.as_mut() else {
+        return -1;
+    };
+
+    let result = catch_unwind(AssertUnwindSafe(|| f(&mut scene.globals)));
+    let message = match result {
+        Ok(Ok(())) => {
+            scene.error = None;
+            return 0;
+        }
+        Ok(Err(e)) => e.to_string(),
+        Err(panic) => match panic.downcast_ref::<&str>() {
+            Some(s) => format!("panic: {}", s),
+            None => match panic.downcast_ref::<String>() {
+                Some(s) => format!("panic: {}", s),
+                None => "panic".to_string(),
+            },
+        },
+    };
+    // Messages don't have NULs in them, but if one did it would end there
+    let message = message.split('\0').next().unwrap_or_default().to_string();
+    scene.error = CString::new(message).ok();
+    -1
+}
+
+// The string `s` points to
+unsafe fn text<'a>(s: *const c_char, what: &str) -> Result<&'a str, LuxError> {
+    if s.is_null() {
+        return Err(LuxError::InvalidSettings(format!("no {} given", what)));
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|_| LuxError::Decode(format!("the {} isn't UTF-8", what)))
+}
+
+// New empty scene rendering `width` x `height` pixels, with the default
+// camera and render settings. Null if the size is 0.
+#[no_mangle]
+pub extern "C" fn lux_scene_new(width: u32, height: u32) -> *mut LuxScene {
+    if width == 0 || height == 0 {
+        return ptr::null_mut();
+    }
+
+    catch_unwind(|| {
+        Box::into_raw(Box::new(LuxScene {
+            globals: GlobalSettings::new(width, height, RenderSettings::default(), Camera::default()),
+            error: None,
+        }))
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// Free a scene from `lux_scene_new`.
+///
+/// # Safety
+///
+/// `scene` must be null or a scene from `lux_scene_new` that wasn't freed
+/// yet. It can't be used after this.
+#[no_mangle]
+pub unsafe extern "C" fn lux_scene_free(scene: *mut LuxScene) {
+    if !scene.is_null() {
+        drop(Box::from_raw(scene));
+    }
+}
+
+/// Message of the last error on `scene`, null if its last call worked. It
+/// stays valid until the next call with the scene.
+///
+/// # Safety
+///
+/// `scene` must be null or a scene from `lux_scene_new` that wasn't freed
+/// yet.
+#[no_mangle]
+pub unsafe extern "C" fn lux_last_error(scene: *const LuxScene) -> *const c_char {
+    match scene.as_ref().and_then(|s| s.error.as_ref()) {
+        Some(message) => message.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// Add one of the built-in scenes to `scene`, by the name `--scene` knows it
+/// by ("grid" is the demo scene)
+///
+/// # Safety
+///
+/// `scene` must be null or a scene from `lux_scene_new` that wasn't freed
+/// yet, and `name` null or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn lux_scene_add_builtin(scene: *mut LuxScene, name: *const c_char) -> i32 {
+    with_scene(scene, |globals| {
+        let name = text(name, "scene name")?;
+        let build = demo::find(name).ok_or_else(|| LuxError::Scene(format!("no built-in scene '{}'", name)))?;
+        build(globals);
+        Ok(())
+    })
+}
+
+/// Load the scene file at `path` into `scene`
+///
+/// # Safety
+///
+/// `scene` must be null or a scene from `lux_scene_new` that wasn't freed
+/// yet, and `path` null or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn lux_scene_load(scene: *mut LuxScene, path: *const c_char) -> i32 {
+    with_scene(scene, |globals| scene::load(text(path, "path")?, globals))
+}
+
+/// Add `material` to the library of `scene` as `name`, replacing any
+/// material of that name
+///
+/// # Safety
+///
+/// `scene` must be null or a scene from `lux_scene_new` that wasn't freed
+/// yet, and `name` null or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn lux_add_material(scene: *mut LuxScene, name: *const c_char, material: LuxMaterial) -> i32 {
+    with_scene(scene, |globals| {
+        globals.add_material(text(name, "material name")?, material.into());
+        Ok(())
+    })
+}
+
+/// Add a sphere of the library material `material`
+///
+/// # Safety
+///
+/// `scene` must be null or a scene from `lux_scene_new` that wasn't freed
+/// yet, and `material` null or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn lux_add_sphere(scene: *mut LuxScene, center: LuxVector, radius: f64, material: *const c_char) -> i32 {
+    with_scene(scene, |globals| add_sphere(&center.into(), radius, text(material, "material name")?, globals))
+}
+
+/// Add a point light
+///
+/// # Safety
+///
+/// `scene` must be null or a scene from `lux_scene_new` that wasn't freed
+/// yet.
+#[no_mangle]
+pub unsafe extern "C" fn lux_add_light(scene: *mut LuxScene, position: LuxVector, color: LuxVector, intensity: f64) -> i32 {
+    with_scene(scene, |globals| add_light(position.into(), color.into(), intensity, globals))
+}
+
+// Camera of a new scene
+#[no_mangle]
+pub extern "C" fn lux_camera_default() -> LuxCamera {
+    let camera = Camera::default();
+    let mut ahead = camera.forward();
+    ahead.v3d_add(camera.position);
+    LuxCamera {
+        position: camera.position.into(),
+        look_at: ahead.into(),
+        up: Vector3D::UNIT_Y.into(),
+        fov: camera.fov,
+    }
+}
+
+/// Move the camera of `scene`
+///
+/// # Safety
+///
+/// `scene` must be null or a scene from `lux_scene_new` that wasn't freed
+/// yet.
+#[no_mangle]
+pub unsafe extern "C" fn lux_set_camera(scene: *mut LuxScene, camera: LuxCamera) -> i32 {
+    with_scene(scene, |globals| {
+        let mut c = globals.camera;
+        c.position = camera.position.into();
+        c.fov = camera.fov;
+        c.look_at(camera.look_at.into(), camera.up.into())?;
+        globals.camera = c;
+        Ok(())
+    })
+}
+
+// Render settings of a new scene
+#[no_mangle]
+pub extern "C" fn lux_render_settings_default() -> LuxRenderSettings {
+    let settings = RenderSettings::default();
+    LuxRenderSettings {
+        path_trace: settings.mode == RenderMode::PathTrace,
+        samples: settings.samples,
+        max_depth: settings.max_depth,
+        seed: settings.seed,
+        shadows: settings.shadows,
+        denoise: settings.denoise,
+        exposure: settings.exposure,
+    }
+}
+
+/// Change the render settings of `scene`
+///
+/// # Safety
+///
+/// `scene` must be null or a scene from `lux_scene_new` that wasn't freed
+/// yet.
+#[no_mangle]
+pub unsafe extern "C" fn lux_set_render_settings(scene: *mut LuxScene, settings: LuxRenderSettings) -> i32 {
+    with_scene(scene, |globals| {
+        let s = &mut globals.settings;
+        s.mode = match settings.path_trace {
+            true => RenderMode::PathTrace,
+            false => RenderMode::Whitted,
+        };
+        s.samples = settings.samples;
+        s.max_depth = settings.max_depth;
+        s.seed = settings.seed;
+        s.shadows = settings.shadows;
+        s.denoise = settings.denoise;
+        s.exposure = settings.exposure;
+        Ok(())
+    })
+}
+
+/// Render `scene` into `buffer`, which holds `len` bytes: RGBA with 8 bits
+/// per channel, row by row from the top, so width * height * 4 of them
+///
+/// # Safety
+///
+/// `scene` must be null or a scene from `lux_scene_new` that wasn't freed
+/// yet, and `buffer` null or writable for `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn lux_render(scene: *mut LuxScene, buffer: *mut u8, len: usize) -> i32 {
+    with_scene(scene, |globals| {
+        let needed = globals.img.as_raw().len();
+        if buffer.is_null() || len < needed {
+            return Err(LuxError::InvalidSettings(format!("the image needs a buffer of {} bytes", needed)));
+        }
+
+        render_scene(globals)?;
+        ptr::copy_nonoverlapping(globals.img.as_raw().as_ptr(), buffer, needed);
+        Ok(())
+    })
+}
+
+/// Same as `lux_render`, into a buffer of its own that goes to `buffer`
+/// with its length in `len`. Free it with `lux_buffer_free`. If the render
+/// fails, or `buffer` or `len` is null, it returns -1 and `buffer` gets
+/// null.
+///
+/// # Safety
+///
+/// `scene` must be null or a scene from `lux_scene_new` that wasn't freed
+/// yet, and `buffer` and `len` null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn lux_render_alloc(scene: *mut LuxScene, buffer: *mut *mut u8, len: *mut usize) -> i32 {
+    if let Some(buffer) = buffer.as_mut() {
+        *buffer = ptr::null_mut();
+    }
+
+    let mut pixels = Vec::new();
+    let result = with_scene(scene, |globals| {
+        if buffer.is_null() || len.is_null() {
+            return Err(LuxError::InvalidSettings("no place given for the buffer and its length".to_string()));
+        }
+        render_scene(globals)?;
+        pixels = globals.img.as_raw().clone();
+        Ok(())
+    });
+    if result != 0 {
+        return result;
+    }
+
+    *len = pixels.len();
+    *buffer = Box::into_raw(pixels.into_boxed_slice()) as *mut u8;
+    0
+}
+
+/// Free a buffer from `lux_render_alloc`, with the length it gave
+///
+/// # Safety
+///
+/// `buffer` must be null or a buffer from `lux_render_alloc` that wasn't
+/// freed yet, and `len` the length it came with.
+#[no_mangle]
+pub unsafe extern "C" fn lux_buffer_free(buffer: *mut u8, len: usize) {
+    if !buffer.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(buffer, len)));
+    }
+}
@@ -0,0 +1,117 @@
+// Static kd-tree for finding the points closest to a position. Built once
+// from a list of points with some data attached (photons, for the caustics
+// pass) and only queried after that.
+//
+// The tree is kept balanced in a single array: every range of the array is a
+// subtree whose root is the middle element, with the points on one side of
+// its splitting plane before it and the rest after it. That needs no child
+// pointers, just the splitting axis of every node.
+
+use crate::Vector3D;
+
+pub struct KdTree<T> {
+    nodes: Vec<(Vector3D, T)>,
+    // Splitting axis (0, 1 or 2) of the node at the same index
+    axes: Vec<u8>,
+}
+
+// Points found by `KdTree::nearest`, as (squared distance, index into nodes)
+type Found = Vec<(f64, usize)>;
+
+impl<T> KdTree<T> {
+    pub fn new(mut points: Vec<(Vector3D, T)>) -> Self {
+        let mut axes = vec![0; points.len()];
+        build(&mut points, &mut axes);
+
+        Self { nodes: points, axes }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    // Up to `k` points no further than `max_dist` from `pos`, nearest first,
+    // with their distance to `pos`
+    pub fn nearest(&self, pos: Vector3D, k: usize, max_dist: f64) -> Vec<(f64, &Vector3D, &T)> {
+        let mut found = Vec::with_capacity(k + 1);
+        if k > 0 {
+            self.search(0, self.nodes.len(), pos, k, max_dist * max_dist, &mut found);
+        }
+
+        found
+            .into_iter()
+            .map(|(d2, i)| {
+                let (p, item) = &self.nodes[i];
+                (d2.sqrt(), p, item)
+            })
+            .collect()
+    }
+
+    fn search(&self, start: usize, end: usize, pos: Vector3D, k: usize, max_d2: f64, found: &mut Found) {
+        if start >= end {
+            return;
+        }
+
+        let mid = start + (end - start) / 2;
+        let (p, _) = &self.nodes[mid];
+        let axis = self.axes[mid] as usize;
+
+        let mut d = *p;
+        d.v3d_sub(pos);
+        let d2 = d.v3d_dot_mul(d);
+
+        // Only the points seen so far matter once `k` are found
+        let limit = |found: &Found| match found.len() < k {
+            true => max_d2,
+            false => found[k - 1].0,
+        };
+
+        if d2 <= limit(found) {
+            let at = found.partition_point(|&(f, _)| f <= d2);
+            found.insert(at, (d2, mid));
+            found.truncate(k);
+        }
+
+        // The side `pos` is on first, the other one only if the splitting
+        // plane is close enough to hold something nearer
+        let offset = pos[axis] - p[axis];
+        let (near, far) = match offset < 0.0 {
+            true => ((start, mid), (mid + 1, end)),
+            false => ((mid + 1, end), (start, mid)),
+        };
+
+        self.search(near.0, near.1, pos, k, max_d2, found);
+        if offset * offset <= limit(found) {
+            self.search(far.0, far.1, pos, k, max_d2, found);
+        }
+    }
+}
+
+// Arrange `points` into a balanced tree, see the top of the file
+fn build<T>(points: &mut [(Vector3D, T)], axes: &mut [u8]) {
+    if points.is_empty() {
+        return;
+    }
+
+    // Split along the axis the points are spread out the most
+    let (mut min, mut max) = (points[0].0, points[0].0);
+    for (p, _) in points.iter() {
+        min = min.min(*p);
+        max = max.max(*p);
+    }
+    max.v3d_sub(min);
+    let axis = (0..3).max_by(|&a, &b| max[a].total_cmp(&max[b])).unwrap();
+
+    let mid = points.len() / 2;
+    points.select_nth_unstable_by(mid, |a, b| a.0[axis].total_cmp(&b.0[axis]));
+    axes[mid] = axis as u8;
+
+    let (left, rest) = points.split_at_mut(mid);
+    let (left_axes, rest_axes) = axes.split_at_mut(mid);
+    build(left, left_axes);
+    build(&mut rest[1..], &mut rest_axes[1..]);
+}
@@ -0,0 +1,5056 @@
+// A very simple ray tracer by Bourbon
+// Assume BSD-style license
+//
+// The images in the 'renders' folder are results of experiments with the
+// camera's position (`camerapos` variable in `Render` function).
+//
+// ----------------------------------------------------------------------------
+//
+// PRIM is for a primitive (Sphere or Quad here)
+
+use image::{ImageBuffer, ImageFormat, Rgb, Rgb32FImage, Rgba, RgbaImage};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+#[macro_use]
+pub mod log;
+pub mod bvh;
+pub mod checkpoint;
+pub mod config;
+pub mod demo;
+pub mod envmap;
+pub mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod kdtree;
+pub mod math;
+pub mod media;
+pub mod metadata;
+pub mod scene;
+pub mod sdf;
+pub mod stats;
+mod toml;
+
+pub use bvh::BvhBuild;
+pub use config::RenderConfig;
+pub use envmap::EnvMap;
+pub use error::LuxError;
+pub use math::{Mat4, Quaternion, Vector3D};
+pub use media::Fog;
+pub use sdf::PrimSdf;
+use bvh::Bvh;
+use kdtree::KdTree;
+use math::{cosine_weighted_hemisphere, dir_around, orthonormal_basis, sample_cone, sample_cone_cos, solve_quartic};
+use stats::{PassStats, RayCounters};
+
+// Global constants
+pub const MAXPRIMCOUNT: u32 = 1024;
+pub const MAXLIGHTCOUNT: u32 = 10;
+
+// Color of rays that don't hit anything, unless the scene has its own
+// environment (see `Scene::environment`)
+pub const BACKGROUND: Vector3D = Vector3D::new(0.02, 0.1, 0.17);
+
+// Default ambient light, see `Scene::ambient`
+pub const AMBIENT: Vector3D = Vector3D::new(0.02, 0.02, 0.02);
+
+// Material properties and color
+#[derive(Clone, Copy)]
+pub struct Material {
+    pub specular: f64,
+    pub diffusive: f64,
+    pub reflective: f64,
+    // How blurry the reflection is, from 0 (a perfect mirror) to 1. Only the
+    // path tracer looks at this; `trace` always reflects like a mirror.
+    pub roughness: f64,
+    // How much light passes through the surface, bent by the index of
+    // refraction `ior`. Only `trace` looks at these.
+    pub refractive: f64,
+    pub ior: f64,
+    pub color: Vector3D,
+    // Light given off by the surface. Only the path tracer looks at this.
+    pub emission: Vector3D,
+    // Relief pattern that tilts the shading normal (see `bump_normal`)
+    pub bump: Option<Bump>,
+    // Holes in the surface that rays go through (see `Cutout`)
+    pub cutout: Option<Cutout>,
+    // Whether the back of the surface is there too
+    pub sidedness: Sidedness,
+    // Translucency for wax or skin: how far the diffuse light wraps around
+    // past the side facing the light, softening the terminator. The
+    // diffuse term becomes max(0, (cos + wrap) / (1 + wrap)); 0 is plain
+    // Lambert. Only `trace` looks at this.
+    pub wrap: f64,
+    // How much more blue light is bent than red (the index of refraction
+    // of blue minus that of red, `ior` being green's), splitting white
+    // light into colors. Crown glass is about 0.01, flint glass 0.03. See
+    // `Dispersion` for how `trace` handles it; caustics ignore it.
+    pub dispersion: f64,
+    // Diffuse light let through thin things like lampshades or paper:
+    // lights behind the surface add |cos| * translucency of their diffuse
+    // light to its front. 0 lets nothing through. Only `trace` looks at
+    // this.
+    pub translucency: f64,
+    // How many mirror reflections and refractions deep `trace` follows
+    // rays leaving this material, instead of `RenderSettings::max_depth`.
+    // Glass can go deep while mirrors stop early. A ray stopped by these
+    // sees the environment rather than black.
+    pub max_reflection_depth: Option<u32>,
+    pub max_refraction_depth: Option<u32>,
+}
+
+// White, but black until some part of it is turned up: no light leaves it.
+// Scene files start from this too.
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            specular: 0.0,
+            diffusive: 0.0,
+            reflective: 0.0,
+            roughness: 0.0,
+            refractive: 0.0,
+            ior: 1.5,
+            color: Vector3D::ONE,
+            emission: Vector3D::ZERO,
+            bump: None,
+            cutout: None,
+            sidedness: Sidedness::TwoSided,
+            wrap: 0.0,
+            dispersion: 0.0,
+            translucency: 0.0,
+            max_reflection_depth: None,
+            max_refraction_depth: None,
+        }
+    }
+}
+
+impl Material {
+    // Index of refraction of color channel `c` (0 red, 1 green, 2 blue)
+    pub fn channel_ior(&self, c: usize) -> f64 {
+        self.ior + self.dispersion * (c as f64 - 1.0) / 2.0
+    }
+}
+
+// What camera rays that don't hit anything see
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum CameraBackground {
+    // The environment, like every other ray
+    #[default]
+    Environment,
+    Color(Vector3D),
+    // Nothing: the pixel is transparent, for compositing the render over
+    // something else. Only the rays that hit something count towards its
+    // color, see `GlobalSettings::coverage`.
+    Transparent,
+}
+
+// Which kinds of rays can see a primitive, for staging tricks like an
+// invisible object that still casts a shadow, or a blocker that only keeps
+// light away
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VisibilityFlags {
+    pub camera: bool,
+    pub shadow: bool,
+    // Reflected and refracted rays, and the path tracer's bounces
+    pub reflection: bool,
+}
+
+impl Default for VisibilityFlags {
+    fn default() -> Self {
+        Self {
+            camera: true,
+            shadow: true,
+            reflection: true,
+        }
+    }
+}
+
+impl VisibilityFlags {
+    pub fn sees(&self, kind: RayKind) -> bool {
+        match kind {
+            RayKind::Camera => self.camera,
+            RayKind::Shadow => self.shadow,
+            RayKind::Reflection => self.reflection,
+        }
+    }
+}
+
+// What a ray is traced for, which decides the primitives it can see
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RayKind {
+    Camera,
+    // Testing whether a light can be seen
+    Shadow,
+    // Everything else: reflections, refractions, bounces and photons
+    Reflection,
+}
+
+impl RayKind {
+    // Kind of a ray `depth` bounces from the camera
+    fn at_depth(depth: u32) -> Self {
+        match depth {
+            0 => RayKind::Camera,
+            _ => RayKind::Reflection,
+        }
+    }
+}
+
+// Which sides of a surface rays can hit. The front is the side the
+// primitive's normal points to.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Sidedness {
+    // Both, and the back is shaded like the front, with the normal turned
+    // towards the ray
+    #[default]
+    TwoSided,
+    // Only the front. Rays from outside go through the back as if it
+    // weren't there, in every tracer and for shadows too, so a wall can
+    // close off a room from inside without blocking a camera outside it.
+    // Rays from inside a closed primitive still hit it.
+    FrontOnly,
+}
+
+// Procedural relief for bump mapping. Patterns repeat `scale` times over the
+// primitive's texture coordinates; `depth` sets how steep they look.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Bump {
+    // Rows of bricks with sunken mortar lines in between
+    Bricks { scale: f64, depth: f64 },
+    // Smooth egg crate bumps
+    Waves { scale: f64, depth: f64 },
+}
+
+// Width of the mortar between bricks, as a fraction of a brick
+const MORTAR: f64 = 0.08;
+
+impl Bump {
+    // Height of the pattern at (s, t), in pattern cells, between 0 and 1
+    fn height(&self, s: f64, t: f64) -> f64 {
+        match self {
+            Bump::Bricks { .. } => {
+                // Every other row is shifted by half a brick
+                let row = t.floor();
+                let fx = (s + 0.5 * row.rem_euclid(2.0)).rem_euclid(1.0);
+                let fy = t - row;
+
+                let edge = |f: f64| smoothstep(0.0, MORTAR, f) * smoothstep(0.0, MORTAR, 1.0 - f);
+                edge(fx) * edge(fy)
+            }
+            Bump::Waves { .. } => {
+                let tau = 2.0 * std::f64::consts::PI;
+                0.5 + 0.5 * (tau * s).sin() * (tau * t).sin()
+            }
+        }
+    }
+
+    fn scale_depth(&self) -> (f64, f64) {
+        match *self {
+            Bump::Bricks { scale, depth } | Bump::Waves { scale, depth } => (scale, depth),
+        }
+    }
+}
+
+// Procedural alpha mask for leaves, fences and the like. Where the alpha is
+// below `threshold` the surface isn't there, and rays (shadow rays too) go
+// on as if it had a hole. Patterns repeat `scale` times over the primitive's
+// texture coordinates; primitives without them have no holes.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Cutout {
+    // Checkerboard of opaque (alpha 1) and see-through (0) squares
+    Checker { scale: f64, threshold: f64 },
+    // Round holes: the alpha goes from 0 in the middle of every cell to 1
+    // half a cell away from it
+    Dots { scale: f64, threshold: f64 },
+}
+
+// Most holes a ray goes through in one primitive before it counts as a
+// miss, so a surface that's all holes can't keep it going forever
+const MAX_PUNCH_THROUGHS: u32 = 32;
+
+impl Cutout {
+    fn alpha(&self, s: f64, t: f64) -> f64 {
+        match self {
+            Cutout::Checker { .. } => match (s.floor() + t.floor()).rem_euclid(2.0) == 0.0 {
+                true => 1.0,
+                false => 0.0,
+            },
+            Cutout::Dots { .. } => {
+                let (ds, dt) = (s.rem_euclid(1.0) - 0.5, t.rem_euclid(1.0) - 0.5);
+                (2.0 * (ds * ds + dt * dt).sqrt()).min(1.0)
+            }
+        }
+    }
+
+    // Whether the surface point with texture coordinates (u, v) is a hole
+    pub fn is_hole(&self, u: f64, v: f64) -> bool {
+        let (scale, threshold) = match *self {
+            Cutout::Checker { scale, threshold } | Cutout::Dots { scale, threshold } => (scale, threshold),
+        };
+        self.alpha(u * scale, v * scale) < threshold
+    }
+}
+
+fn smoothstep(edge0: f64, edge1: f64, x: f64) -> f64 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+// The shading normal `n` at `pos` on `prim`, tilted by the slope of the
+// material's bump pattern. `wo` points back along the incoming ray; the
+// result never turns away from it, so bumps can't make a surface seen from
+// the front shade like its back. Primitives without texture coordinates
+// keep `n`.
+pub fn bump_normal(prim: &dyn Primitive, m: &Material, pos: Vector3D, n: Vector3D, wo: Vector3D, time: f64) -> Vector3D {
+    let bump = match m.bump {
+        Some(bump) => bump,
+        None => return n,
+    };
+
+    let ((u, v), (tu, tv)) = match (prim.uv(pos, time), prim.tangents(pos, time)) {
+        (Some(uv), Some(t)) => (uv, t),
+        _ => return n,
+    };
+
+    // Slope of the pattern by central differences
+    const STEP: f64 = 1e-3;
+    let (scale, depth) = bump.scale_depth();
+    let (s, t) = (u * scale, v * scale);
+    let ds = (bump.height(s + STEP, t) - bump.height(s - STEP, t)) / (2.0 * STEP);
+    let dt = (bump.height(s, t + STEP) - bump.height(s, t - STEP)) / (2.0 * STEP);
+
+    let mut tilted = n;
+    let mut along_u = tu;
+    along_u.v3d_mul_scalar(-depth * ds);
+    tilted.v3d_add(along_u);
+    let mut along_v = tv;
+    along_v.v3d_mul_scalar(-depth * dt);
+    tilted.v3d_add(along_v);
+    tilted.v3d_norm();
+
+    // Pull it back just over the horizon if it turned away from the viewer
+    const MIN_COS: f64 = 1e-3;
+    let cos = tilted.v3d_dot_mul(wo);
+    if cos < MIN_COS {
+        let mut fix = wo;
+        fix.v3d_mul_scalar(MIN_COS - cos);
+        tilted.v3d_add(fix);
+        tilted.v3d_norm();
+    }
+
+    tilted
+}
+
+impl Material {
+    // How much of the incoming light the diffuse, glossy, mirror and
+    // refracted parts send on together. Above 1 the surface gives off more
+    // light than it receives.
+    pub fn reflectance(&self) -> f64 {
+        self.diffusive + self.specular + self.reflective + self.refractive
+    }
+
+    // Copy that can't gain energy: the color is clamped to [0, 1] and the
+    // diffuse, glossy, mirror and refracted parts are scaled down to add up
+    // to 1 at most. Emission is left alone.
+    pub fn conserved(&self) -> Material {
+        let mut m = *self;
+        m.color = m.color.clamp(Vector3D::ZERO, Vector3D::ONE);
+
+        let total = m.reflectance();
+        if total > 1.0 {
+            m.diffusive /= total;
+            m.specular /= total;
+            m.reflective /= total;
+            m.refractive /= total;
+        }
+        m
+    }
+}
+
+// Handle of a material in the registry of `GlobalSettings`, from
+// `register_material`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MaterialId(usize);
+
+impl std::fmt::Display for MaterialId {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+// How a primitive refers to its material: by name from the material library
+// in `GlobalSettings`, by handle from its material registry, or with a
+// material of its own.
+#[derive(Clone)]
+pub enum MaterialRef {
+    Named(String),
+    Id(MaterialId),
+    Inline(Material),
+}
+
+impl From<&str> for MaterialRef {
+    fn from(name: &str) -> Self {
+        MaterialRef::Named(name.to_string())
+    }
+}
+
+impl From<MaterialId> for MaterialRef {
+    fn from(id: MaterialId) -> Self {
+        MaterialRef::Id(id)
+    }
+}
+
+impl From<Material> for MaterialRef {
+    fn from(m: Material) -> Self {
+        MaterialRef::Inline(m)
+    }
+}
+
+impl From<&Material> for MaterialRef {
+    fn from(m: &Material) -> Self {
+        MaterialRef::Inline(*m)
+    }
+}
+
+// Anything that can be put in the scene and hit by a ray. The render
+// threads share the scene, so primitives must be safe to share.
+pub trait Primitive: Send + Sync {
+    // Distance along `ray` to the nearest hit goes to `dist`. Hits closer
+    // than `t_min` don't count. Returns 0 on a miss, 1 on a hit and -1 on a
+    // hit from inside the primitive.
+    fn intersect(&self, ray: Ray, t_min: f64, dist: &mut f64) -> i32;
+
+    // Surface normal at `pos`, which must be on the surface at `time`
+    fn normal(&self, pos: Vector3D, time: f64) -> Vector3D;
+
+    fn material_ref(&self) -> &MaterialRef;
+
+    // Short name of the kind of primitive, like "sphere"
+    fn kind(&self) -> &'static str;
+
+    // Corners of an axis aligned box around the primitive, smallest first
+    fn bounds(&self) -> (Vector3D, Vector3D);
+
+    // For emissive primitives: a random direction from `from` towards the
+    // primitive at `time` and its pdf (per solid angle). `None` if the
+    // primitive can't be seen from `from`.
+    fn sample_direction(&self, from: Vector3D, time: f64, rng: &mut Rng) -> Option<(Vector3D, f64)>;
+
+    // Pdf that `sample_direction` picks the direction from `from` to the
+    // surface point `hit`
+    fn direction_pdf(&self, from: Vector3D, hit: Vector3D, time: f64) -> f64;
+
+    // Texture coordinates of the surface point `pos`, each in [0, 1]. `None`
+    // for primitives that have none.
+    fn uv(&self, _pos: Vector3D, _time: f64) -> Option<(f64, f64)> {
+        None
+    }
+
+    // Unit directions along the surface in which u and v of `uv` grow at
+    // `pos`, for bump mapping. `None` where they aren't defined.
+    fn tangents(&self, _pos: Vector3D, _time: f64) -> Option<(Vector3D, Vector3D)> {
+        None
+    }
+
+    // Feed everything about the shape and place of the primitive (not its
+    // material) to `state`, to tell whether it changed since the last
+    // render. `false` for primitives that can't, which never count as
+    // unchanged.
+    fn hash_geometry(&self, _state: &mut dyn Hasher) -> bool {
+        false
+    }
+
+    // Whether the primitive has an inside for rays to be in, like a sphere,
+    // rather than being a bare surface like a quad. Only closed primitives
+    // are media that refracted rays go into and come out of again.
+    fn is_closed(&self) -> bool {
+        true
+    }
+}
+
+// Feed `values` to `state` bit for bit, for `Primitive::hash_geometry`
+fn hash_floats(state: &mut dyn Hasher, values: impl IntoIterator<Item = f64>) {
+    for v in values {
+        state.write_u64(v.to_bits());
+    }
+}
+
+// Sphere. A sphere with a velocity moves while the shutter is open and
+// gets motion blurred.
+#[derive(Clone)]
+pub struct PrimSphere {
+    // Center at time 0
+    pub position: Vector3D,
+    // Distance the center moves per unit of time
+    pub velocity: Vector3D,
+    pub radius: f64,
+    pub m: MaterialRef,
+}
+
+// Parallelogram spanned by two edges from one corner. Good for floors, walls
+// and, with an emissive material, rectangular area lights.
+#[derive(Clone)]
+pub struct PrimQuad {
+    pub origin: Vector3D,
+    pub edge_u: Vector3D,
+    pub edge_v: Vector3D,
+    pub m: MaterialRef,
+}
+
+// Ring around `center`: a circle of radius `major_radius` in the plane
+// perpendicular to `axis` (a unit vector), thickened into a tube of radius
+// `minor_radius`
+#[derive(Clone)]
+pub struct PrimTorus {
+    pub center: Vector3D,
+    pub axis: Vector3D,
+    pub major_radius: f64,
+    pub minor_radius: f64,
+    pub m: MaterialRef,
+}
+
+// Another primitive moved, rotated or scaled by a transform. Rays are taken
+// into the primitive's own space for the intersection and normals are
+// brought back out, so the wrapped primitive doesn't know it's been moved.
+//
+// The wrapped primitive is shared, so any number of instances can place
+// copies of the same geometry around the scene without copying it. Each one
+// can also give its copy a different material.
+pub struct Instance {
+    pub object: Arc<dyn Primitive>,
+    // Replaces the object's material when set
+    pub material: Option<MaterialRef>,
+    to_world: Mat4,
+    to_object: Mat4,
+    // Whether the transform keeps angles (no uneven scaling or shearing), so
+    // light sampling can be left to the wrapped primitive
+    keeps_angles: bool,
+}
+
+// Light ray
+#[derive(Copy, Clone)]
+pub struct Ray {
+    pub direction: Vector3D,
+    pub origin: Vector3D,
+    // When in the shutter interval the ray was sent. Rays spawned off a hit
+    // keep the time of the ray that hit.
+    pub time: f64,
+}
+
+impl Ray {
+    // Where a ray leaving the surface point `point` in `direction` starts:
+    // `eps` off the surface along its geometric `normal`, on the side the
+    // ray leaves to, so it can't hit the surface it starts on. Which way
+    // `normal` faces doesn't matter. Pushing along the normal rather than
+    // along the ray keeps rays that leave at a grazing angle just as far
+    // from the surface as any other.
+    pub fn offset_ray_origin(point: Vector3D, normal: Vector3D, direction: Vector3D, eps: f64) -> Vector3D {
+        let mut origin = normal;
+        if direction.v3d_dot_mul(normal) < 0.0 {
+            origin.v3d_mul_scalar(-eps);
+        } else {
+            origin.v3d_mul_scalar(eps);
+        }
+        origin.v3d_add(point);
+        origin
+    }
+}
+
+// What shape of light a `Light` sends out
+#[derive(Copy, Clone)]
+pub enum LightKind {
+    // Same in every direction
+    Point,
+    // Cone around the unit vector `direction`. Full strength inside the
+    // inner angle, fading out smoothly up to the outer angle (both measured
+    // from the axis, in degrees) and dark beyond it.
+    Spot {
+        direction: Vector3D,
+        inner_angle: f64,
+        outer_angle: f64,
+    },
+}
+
+#[derive(Clone)]
+// Light source. `color` is the hue (components in [0, 1]) and `intensity`
+// scales how bright the light is. With `RenderSettings::light_aovs` every
+// `group` of lights gets an image of its own.
+pub struct Light {
+    pub position: Vector3D,
+    pub color: Vector3D,
+    pub intensity: f64,
+    pub kind: LightKind,
+    pub group: String,
+}
+
+// Group of the lights that weren't put in one
+pub const DEFAULT_LIGHT_GROUP: &str = "default";
+
+impl Light {
+    // Light with the color of a black body at `kelvin` degrees, placed at the
+    // origin. Uses Tanner Helland's curve fit, good from 1000K to 40000K.
+    pub fn from_temperature(kelvin: f64, intensity: f64) -> Self {
+        let t = kelvin.clamp(1000.0, 40000.0) / 100.0;
+
+        let r = if t <= 66.0 {
+            255.0
+        } else {
+            329.698727446 * (t - 60.0).powf(-0.1332047592)
+        };
+
+        let g = if t <= 66.0 {
+            99.4708025861 * t.ln() - 161.1195681661
+        } else {
+            288.1221695283 * (t - 60.0).powf(-0.0755148492)
+        };
+
+        let b = if t >= 66.0 {
+            255.0
+        } else if t <= 19.0 {
+            0.0
+        } else {
+            138.5177312231 * (t - 10.0).ln() - 305.0447927307
+        };
+
+        Self {
+            position: Vector3D::ZERO,
+            color: Vector3D::new(
+                r.clamp(0.0, 255.0) / 255.0,
+                g.clamp(0.0, 255.0) / 255.0,
+                b.clamp(0.0, 255.0) / 255.0,
+            ),
+            intensity,
+            kind: LightKind::Point,
+            group: DEFAULT_LIGHT_GROUP.to_string(),
+        }
+    }
+
+    // Color times intensity, i.e. the light that actually arrives
+    pub fn radiance(&self) -> Vector3D {
+        let mut c = self.color;
+        c.v3d_mul_scalar(self.intensity);
+        c
+    }
+
+    // How much of the light reaches `pos`, from 0 to 1. Only spot lights
+    // are ever less than 1.
+    pub fn falloff(&self, pos: Vector3D) -> f64 {
+        match self.kind {
+            LightKind::Point => 1.0,
+            LightKind::Spot { direction, inner_angle, outer_angle } => {
+                let mut to_pos = pos;
+                to_pos.v3d_sub(self.position);
+                to_pos.v3d_norm();
+
+                let cos_angle = to_pos.v3d_dot_mul(direction);
+                let cos_inner = inner_angle.to_radians().cos();
+                let cos_outer = outer_angle.to_radians().cos();
+
+                if cos_angle >= cos_inner {
+                    1.0
+                } else if cos_angle <= cos_outer {
+                    0.0
+                } else {
+                    // Smoothstep between the two cones
+                    let t = (cos_angle - cos_outer) / (cos_inner - cos_outer);
+                    t * t * (3.0 - 2.0 * t)
+                }
+            }
+        }
+    }
+
+    // Light arriving at `pos`, falloff included
+    pub fn radiance_at(&self, pos: Vector3D) -> Vector3D {
+        let mut c = self.radiance();
+        c.v3d_mul_scalar(self.falloff(pos));
+        c
+    }
+}
+
+// Vertical field of view (degrees) of the default camera. The view window
+// used to be hardcoded to -2..2 by 1.5..-1.5 at distance 5, so this keeps 4:3
+// images looking exactly like they used to.
+pub const DEFAULT_FOV: f64 = 33.398_488_467_987_24;
+
+// How the camera maps the image onto directions
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    // Regular pinhole camera with a flat view window
+    Perspective,
+    // Full 360 x 180 degree panorama, longitude across and latitude down the
+    // image. Meant for 2:1 images.
+    Equirectangular,
+    // Equidistant fisheye: the angle from the view direction grows linearly
+    // with the distance from the image center. The image circle fits the
+    // shorter side of the image, pixels outside of it stay black.
+    Fisheye { fov_degrees: f64 },
+}
+
+// Camera looking down +z, unless its `orientation` turns it. For the
+// perspective projection the view window is derived from the vertical field
+// of view and the image's aspect ratio, so spheres stay round at any
+// resolution. The camera can move while the shutter is open, which blurs the
+// image when several samples per pixel are taken.
+#[derive(Clone, Copy, Debug)]
+pub struct Camera {
+    // Where the camera is when the shutter opens
+    pub position: Vector3D,
+    // Distance the camera moves per unit of time
+    pub velocity: Vector3D,
+    // How long the shutter stays open. 0 means no motion blur.
+    pub shutter_time: f64,
+    // Vertical field of view in degrees
+    pub fov: f64,
+    // Which way the camera faces: the rotation turning +z to the view
+    // direction and +y to the top of the image (see `look_at`)
+    pub orientation: Quaternion,
+    // Counter-clockwise tilt of the camera around its view direction, in
+    // degrees
+    pub roll: f64,
+    pub projection: Projection,
+    // Sideways shift of the perspective view window, in view window units.
+    // Stereo pairs use it to converge the two eyes.
+    pub shift: f64,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            position: Vector3D::new(0.0, 0.0, -5.0),
+            velocity: Vector3D::ZERO,
+            shutter_time: 0.0,
+            fov: DEFAULT_FOV,
+            orientation: Quaternion::IDENTITY,
+            roll: 0.0,
+            projection: Projection::Perspective,
+            shift: 0.0,
+        }
+    }
+}
+
+impl Camera {
+    // How far the camera has moved from `position` at `time`
+    pub fn offset(&self, time: f64) -> Vector3D {
+        let mut offset = self.velocity;
+        offset.v3d_mul_scalar(time);
+        offset
+    }
+
+    // Half the width and height of the view window, for an image of
+    // `width` x `height` pixels. For the perspective projection that's the
+    // window at distance 1, for the others it's measured in radians.
+    pub fn window(&self, width: u32, height: u32) -> (f64, f64) {
+        let aspect = width as f64 / height as f64;
+
+        match self.projection {
+            Projection::Perspective => {
+                let half_height = (self.fov.to_radians() * 0.5).tan();
+                (half_height * aspect, half_height)
+            }
+            Projection::Equirectangular => (std::f64::consts::PI, std::f64::consts::FRAC_PI_2),
+            Projection::Fisheye { fov_degrees } => {
+                let half = fov_degrees.to_radians() * 0.5;
+                if aspect >= 1.0 {
+                    (half * aspect, half)
+                } else {
+                    (half, half / aspect)
+                }
+            }
+        }
+    }
+
+    // Right and up vectors of the camera, tilted by `roll`
+    pub fn basis(&self) -> (Vector3D, Vector3D) {
+        let (sin, cos) = self.roll.to_radians().sin_cos();
+
+        let right = Vector3D::new(cos, sin, 0.0);
+        let up = Vector3D::new(-sin, cos, 0.0);
+        (self.orientation.rotate(right), self.orientation.rotate(up))
+    }
+
+    // Direction the camera looks in
+    pub fn forward(&self) -> Vector3D {
+        self.orientation.rotate(Vector3D::UNIT_Z)
+    }
+
+    // Turn the camera towards `target`, keeping the top of the image as
+    // close to `up` as it can (see `Quaternion::look_at` for looking
+    // straight down or up). Fails if the target is the camera's own
+    // position or `up` is zero.
+    pub fn look_at(&mut self, target: Vector3D, up: Vector3D) -> Result<(), LuxError> {
+        let mut forward = target;
+        forward.v3d_sub(self.position);
+
+        self.orientation = Quaternion::look_at(forward, up).ok_or_else(|| {
+            LuxError::InvalidSettings("the camera can't look at its own position or without an up direction".to_string())
+        })?;
+        Ok(())
+    }
+
+    // The orientation as a view direction and an up vector, both unit
+    // length and without `roll`. `look_at(position + forward, up)` turns
+    // the camera back to it.
+    pub fn view(&self) -> (Vector3D, Vector3D) {
+        (self.forward(), self.orientation.rotate(Vector3D::UNIT_Y))
+    }
+
+    // Camera `t` of the way from `self` to the keyframe `next`, for
+    // animations. Positions and numbers are blended linearly and the
+    // orientation with `Quaternion::slerp`, so a turning camera turns at a
+    // steady speed. The projection is `self`'s.
+    pub fn interpolate(&self, next: &Camera, t: f64) -> Camera {
+        let blend = |a: f64, b: f64| a + (b - a) * t;
+        let blend_vec = |a: Vector3D, b: Vector3D| a.zip(b, blend);
+
+        Camera {
+            position: blend_vec(self.position, next.position),
+            velocity: blend_vec(self.velocity, next.velocity),
+            shutter_time: blend(self.shutter_time, next.shutter_time),
+            fov: blend(self.fov, next.fov),
+            orientation: Quaternion::slerp(self.orientation, next.orientation, t),
+            roll: blend(self.roll, next.roll),
+            projection: self.projection,
+            shift: blend(self.shift, next.shift),
+        }
+    }
+
+    // Point of the view window at (px, py) in the pixel coordinates of a
+    // `width` x `height` image. (x + 0.5, y + 0.5) is the center of pixel
+    // (x, y).
+    pub fn window_point(&self, px: f64, py: f64, width: u32, height: u32) -> (f64, f64) {
+        let (half_width, half_height) = self.window(width, height);
+
+        let sx = (px / width as f64 * 2.0 - 1.0) * half_width;
+        let sy = (1.0 - py / height as f64 * 2.0) * half_height;
+        (sx, sy)
+    }
+
+    // Ray through (px, py) in pixel coordinates, see `window_point`
+    pub fn pixel_ray(&self, px: f64, py: f64, width: u32, height: u32, time: f64) -> Option<Ray> {
+        let (sx, sy) = self.window_point(px, py, width, height);
+        self.ray(sx, sy, time)
+    }
+
+    // Ray through the point (sx, sy) of the view window at `time`. `None`
+    // for points the projection doesn't cover.
+    pub fn ray(&self, sx: f64, sy: f64, time: f64) -> Option<Ray> {
+        let mut origin = self.position;
+        origin.v3d_add(self.offset(time));
+
+        let (right, up) = self.basis();
+        let forward = self.forward();
+
+        // Weights of right, up and forward
+        let (a, b, c) = match self.projection {
+            Projection::Perspective => (sx + self.shift, sy, 1.0),
+            Projection::Equirectangular => {
+                // sx is the longitude, sy the latitude
+                let (sin_lon, cos_lon) = sx.sin_cos();
+                let (sin_lat, cos_lat) = sy.sin_cos();
+                (cos_lat * sin_lon, sin_lat, cos_lat * cos_lon)
+            }
+            Projection::Fisheye { fov_degrees } => {
+                // The distance from the center is the angle from `forward`
+                let theta = (sx * sx + sy * sy).sqrt();
+                if theta > fov_degrees.to_radians() * 0.5 {
+                    return None;
+                }
+
+                let phi = sy.atan2(sx);
+                let sin_theta = theta.sin();
+                (sin_theta * phi.cos(), sin_theta * phi.sin(), theta.cos())
+            }
+        };
+
+        let mut direction = right;
+        direction.v3d_mul_scalar(a);
+
+        let mut v = up;
+        v.v3d_mul_scalar(b);
+        direction.v3d_add(v);
+
+        let mut v = forward;
+        v.v3d_mul_scalar(c);
+        direction.v3d_add(v);
+        direction.v3d_norm();
+
+        Some(Ray { origin, direction, time })
+    }
+
+    // Where `point` shows up in a `width` x `height` image, in the pixel
+    // coordinates of `window_point`, as seen when the shutter opens. The
+    // inverse of `pixel_ray`. `None` for points behind a perspective camera
+    // or outside a fisheye's image circle.
+    pub fn project(&self, point: Vector3D, width: u32, height: u32) -> Option<(f64, f64)> {
+        let mut d = point;
+        d.v3d_sub(self.position);
+
+        let (right, up) = self.basis();
+        let (a, b, c) = (d.v3d_dot_mul(right), d.v3d_dot_mul(up), d.v3d_dot_mul(self.forward()));
+
+        let (sx, sy) = match self.projection {
+            Projection::Perspective => {
+                if c <= 0.0 {
+                    return None;
+                }
+                (a / c - self.shift, b / c)
+            }
+            Projection::Equirectangular => {
+                let horizontal = (a * a + c * c).sqrt();
+                (a.atan2(c), b.atan2(horizontal))
+            }
+            Projection::Fisheye { fov_degrees } => {
+                let theta = (a * a + b * b).sqrt().atan2(c);
+                if theta > fov_degrees.to_radians() * 0.5 {
+                    return None;
+                }
+
+                let phi = b.atan2(a);
+                (theta * phi.cos(), theta * phi.sin())
+            }
+        };
+
+        let (half_width, half_height) = self.window(width, height);
+        let px = (sx / half_width + 1.0) * 0.5 * width as f64;
+        let py = (1.0 - sy / half_height) * 0.5 * height as f64;
+        Some((px, py))
+    }
+}
+
+// How `render_stereo` puts the two eyes together
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StereoMode {
+    // Red/cyan glasses: red from the left eye, green and blue from the right
+    Anaglyph,
+    // Left and right eye next to each other in a double width image
+    SideBySide,
+}
+
+// Which tracer `render` uses for each pixel
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RenderMode {
+    // Classic recursive tracer (`trace`)
+    Whitted,
+    // Monte Carlo path tracer (`pathtrace`)
+    PathTrace,
+}
+
+// What `render` shows for each pixel. Everything but `Full` is a debug view
+// of the first hit that skips the lights, and shows misses in magenta so
+// holes stand out.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shading {
+    // The tracer picked by `RenderMode`
+    Full,
+    // Normal xyz as rgb, mapped from [-1, 1] to [0, 1]
+    Normals,
+    // Grayscale, white up close and black at the far end of the scene
+    Depth,
+    // Material color, unlit
+    Albedo,
+    // Texture coordinates as red and green with grid lines. Gray for
+    // primitives without any.
+    Uv,
+}
+
+impl Shading {
+    // For error messages
+    pub const NAMES: &'static str = "full, normals, depth, albedo or uv";
+
+    // Parse a mode name as used on the command line and in config files
+    pub fn from_name(name: &str) -> Option<Shading> {
+        match name {
+            "full" => Some(Shading::Full),
+            "normals" => Some(Shading::Normals),
+            "depth" => Some(Shading::Depth),
+            "albedo" => Some(Shading::Albedo),
+            "uv" => Some(Shading::Uv),
+            _ => None,
+        }
+    }
+}
+
+// How `trace` follows light through dispersive glass (see
+// `Material::dispersion`), where every color channel goes its own way
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Dispersion {
+    // Trace a refracted ray per channel and keep one channel of each. Clean
+    // fringes even with one sample, three times the rays behind the glass.
+    Split,
+    // Every sample picks one channel at random and only follows that one
+    // through the glass. No extra rays, but the fringes need many samples
+    // to stop being noisy.
+    Spectral,
+}
+
+// Color channels a ray in `trace` carries, for dispersion
+#[derive(Clone, Copy, PartialEq)]
+enum Wavelength {
+    // All of them
+    All,
+    // All of them until the first dispersive surface, then only this one
+    // (`Dispersion::Spectral`)
+    Picked(usize),
+    // Only this one, bent by its own index of refraction
+    Only(usize),
+}
+
+// Where in the pixel the jittered samples go
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sampler {
+    // Anywhere, independently of each other
+    Random,
+    // One per cell of a grid over the pixel, jittered inside the cell, so
+    // they can't clump together. There are as many cells as samples: rows
+    // of N (N x N for a square number), with the leftover cells sharing a
+    // last, lower row.
+    Stratified,
+}
+
+impl Sampler {
+    // Offset inside the pixel, in [0, 1) on both axes, of sample `index` out
+    // of `count`
+    pub fn offset(&self, index: u32, count: u32, rng: &mut Rng) -> (f64, f64) {
+        let jx = rng.next_f64();
+        let jy = rng.next_f64();
+
+        match self {
+            Sampler::Random => (jx, jy),
+            Sampler::Stratified => {
+                let count = count.max(1);
+                let n = (count as f64).sqrt().ceil() as u32;
+                let cell = index % count;
+                let (row, column) = (cell / n, cell % n);
+
+                // Every row is as high as its share of the cells, so all
+                // cells have the same area and every part of the pixel gets
+                // as many samples
+                let cells = n.min(count - row * n) as f64;
+                let (count, n) = (count as f64, n as f64);
+                ((column as f64 + jx) / cells, (row as f64 * n + jy * cells) / count)
+            }
+        }
+    }
+}
+
+// How much a sample counts towards a pixel, by how far from the pixel's
+// center it was taken. With several samples per pixel they're spread over
+// the whole reach of the filter, which can go past the pixel's edge into
+// its neighbors, and the pixel is their weighted average.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Filter {
+    // Every sample counts the same
+    #[default]
+    Box,
+    // Weight falls off linearly to 0 at the radius
+    Tent,
+    // Bell curve with a standard deviation of a third of the radius, shifted
+    // down to reach 0 at the radius
+    Gaussian,
+}
+
+impl Filter {
+    // Radius in pixels the filter reaches unless told otherwise. The box
+    // covers exactly its pixel.
+    pub fn default_radius(&self) -> f64 {
+        match self {
+            Filter::Box => 0.5,
+            Filter::Tent => 1.0,
+            Filter::Gaussian => 1.5,
+        }
+    }
+
+    // Weight of a sample (dx, dy) pixels from the pixel's center, for a
+    // filter reaching `radius` pixels along each axis
+    pub fn weight(&self, dx: f64, dy: f64, radius: f64) -> f64 {
+        if dx.abs() > radius || dy.abs() > radius {
+            return 0.0;
+        }
+
+        let along = |d: f64| match self {
+            Filter::Box => 1.0,
+            Filter::Tent => 1.0 - d.abs() / radius,
+            Filter::Gaussian => {
+                let sigma = radius / 3.0;
+                let g = |d: f64| (-d * d / (2.0 * sigma * sigma)).exp();
+                (g(d) - g(radius)).max(0.0)
+            }
+        };
+        along(dx) * along(dy)
+    }
+}
+
+// Rectangle of pixels, with (x, y) its top left corner
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Region {
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        x >= self.x && x - self.x < self.width && y >= self.y && y - self.y < self.height
+    }
+}
+
+// Knobs for a render, filled in from the command line
+#[derive(Clone, Copy, Debug)]
+pub struct RenderSettings {
+    pub mode: RenderMode,
+    pub shading: Shading,
+    pub sampler: Sampler,
+    // Reconstruction filter for the samples of a pixel, and how far it
+    // reaches in pixels (`Filter::default_radius` when not set)
+    pub filter: Filter,
+    pub filter_radius: Option<f64>,
+    // Samples per pixel. More than one jitters the rays inside the pixel
+    // and over the camera's shutter interval.
+    pub samples: u32,
+    // Adaptive sampling: every pixel gets at least `min_samples`, and more
+    // (up to `samples`) only while the error of its brightness is above
+    // `adaptive_threshold`. Flat areas stop early, edges and noise get the
+    // full count.
+    pub adaptive: bool,
+    pub min_samples: u32,
+    pub adaptive_threshold: f64,
+    // Render budgets: stop taking samples once the render has taken
+    // `max_time`, or once the pixels' noise (the standard error of their
+    // brightness, on average) is down to `target_noise`, whichever comes
+    // first. `samples` stays the most a pixel gets.
+    pub max_time: Option<std::time::Duration>,
+    pub target_noise: Option<f64>,
+    // Maximum number of bounces
+    pub max_depth: u32,
+    // Path tracer: sample a light at every bounce (next-event estimation,
+    // see `pathtrace`). Only worth turning off to see how much it helps.
+    pub light_sampling: bool,
+    // Firefly suppression, both off by default and both biased: they take
+    // light away to get rid of the odd blinding pixel. `clamp` is the most
+    // any channel of the light a path tracer sample finds after its first
+    // bounce may be. `reject_outliers` is a percentage: of every pixel's
+    // samples, that share of the brightest ones are toned down to the
+    // brightness of the next one before averaging (winsorizing); the light
+    // AOVs keep the raw samples.
+    pub clamp: Option<f64>,
+    pub reject_outliers: Option<f64>,
+    // Whitted tracer: how to trace dispersive glass
+    pub dispersion: Dispersion,
+    pub seed: u64,
+    // Draw dark lines along silhouettes and creases after rendering
+    pub edges: bool,
+    // Draw the world axes this long from the origin on top of the render:
+    // x red, y green, z blue
+    pub axes: Option<f64>,
+    // Cel shading: `trace` rounds the diffuse light down to this many flat
+    // bands instead of a smooth falloff
+    pub toon_bands: Option<u32>,
+    // Whitted: only let a light shine on a surface when a shadow ray says
+    // nothing is in between. The path tracer always checks.
+    pub shadows: bool,
+    // Shoot `photons` photons from the lights through mirrors and glass
+    // before rendering, so `trace` can show the light they focus (see
+    // `build_caustics`)
+    pub caustics: bool,
+    pub photons: u32,
+    // Count the rays traced by every render pass (see stats.rs)
+    pub stats: bool,
+    // Also keep the light of every light group in a framebuffer of its own
+    // (`GlobalSettings::light_aovs`). Whitted only.
+    pub light_aovs: bool,
+    // Run the edge-aware denoiser over the framebuffer before resolving.
+    // Strength is how different two colors may be and still get blended,
+    // radius is how far (in pixels) the filter reaches.
+    pub denoise: bool,
+    pub denoise_strength: f64,
+    pub denoise_radius: u32,
+    // Ordered dithering when quantizing to 8 bits, so smooth gradients don't
+    // break up into bands
+    pub dither: bool,
+    // How the BVH over the primitives is built (see bvh.rs)
+    pub bvh: BvhBuild,
+    // Brighten (above 0) or darken the image by this many stops when
+    // resolving the framebuffer
+    pub exposure: f64,
+    // How far rays leaving a surface are pushed off it, and the closest hit
+    // any ray accepts. Should grow with the scale of the scene.
+    pub ray_epsilon: f64,
+    // Render a stereo pair instead of a single image (see `render_stereo`).
+    // The eyes sit `eye_separation` apart. With a `convergence` distance
+    // above 0 things at that distance end up in the screen plane, otherwise
+    // the eyes look straight ahead.
+    pub stereo: Option<StereoMode>,
+    pub eye_separation: f64,
+    pub convergence: f64,
+    // Render every material as `Material::conserved`, so none can reflect
+    // more light than falls on it
+    pub conserve_energy: bool,
+    // Skip surfaces seen from behind, as if they weren't there, instead of
+    // shading the side the ray came from. Rays from inside a primitive
+    // still hit it.
+    pub cull_back_faces: bool,
+    // Only render these pixels. The rest of the image stays transparent.
+    // Rays are still set up for the whole image, so the region looks the
+    // same as in a full render.
+    pub region: Option<Region>,
+    // Render threads, 0 for one per core. The image is the same however
+    // many there are.
+    pub threads: u32,
+    // Keep what the camera rays hit, and as long as the geometry, camera
+    // and samples stay the same, shade from that next time instead of
+    // tracing them again. The image is the same either way.
+    pub geometry_cache: bool,
+    // How often a render with `GlobalSettings::checkpoint` saves the
+    // samples taken so far
+    pub checkpoint_every: CheckpointInterval,
+}
+
+// Time or samples per pixel between checkpoints. They're only saved
+// between batches of samples (see `render_budgeted`), so a few samples
+// more may go by.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CheckpointInterval {
+    Time(std::time::Duration),
+    Samples(u32),
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            mode: RenderMode::Whitted,
+            shading: Shading::Full,
+            sampler: Sampler::Random,
+            filter: Filter::Box,
+            filter_radius: None,
+            samples: 1,
+            adaptive: false,
+            min_samples: 4,
+            adaptive_threshold: 0.01,
+            max_time: None,
+            target_noise: None,
+            max_depth: 4,
+            light_sampling: true,
+            clamp: None,
+            reject_outliers: None,
+            dispersion: Dispersion::Split,
+            seed: 0,
+            edges: false,
+            axes: None,
+            toon_bands: None,
+            shadows: false,
+            caustics: false,
+            photons: 200_000,
+            stats: false,
+            light_aovs: false,
+            denoise: false,
+            denoise_strength: 0.5,
+            denoise_radius: 8,
+            dither: false,
+            bvh: BvhBuild::Median,
+            exposure: 0.0,
+            ray_epsilon: 1e-4,
+            stereo: None,
+            eye_separation: 0.06,
+            convergence: 0.0,
+            conserve_energy: false,
+            cull_back_faces: false,
+            region: None,
+            threads: 0,
+            geometry_cache: false,
+            checkpoint_every: CheckpointInterval::Time(std::time::Duration::from_secs(60)),
+        }
+    }
+}
+
+// Tiny xorshift random number generator. Good enough for sampling, and the
+// same seed always gives the same image.
+#[derive(Clone, Copy)]
+pub struct Rng {
+    pub state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // Run the seed through splitmix64 so nearby seeds (pixel indices)
+        // give unrelated streams. xorshift must never be seeded with 0.
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+
+        Self {
+            state: if z == 0 { 1 } else { z },
+        }
+    }
+
+    // Uniform number in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        (self.state >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+// Primitive found by `Scene::pick`
+#[derive(Clone, Copy)]
+pub struct PickResult {
+    // Index into `primitive_list`
+    pub primitive_index: usize,
+    // Distance along the pixel's ray
+    pub distance: f64,
+    pub point: Vector3D,
+    pub normal: Vector3D,
+}
+
+// What gets rendered: the settings, the camera, the primitives and lights
+// with their materials and what surrounds them, and the structures built
+// over them to trace rays faster. Rendering only reads it, so the render
+// threads all share one; the image they render to is in `GlobalSettings`.
+pub struct Scene {
+    pub settings: RenderSettings,
+    pub camera: Camera,
+
+    // Added with `add_primitive`/`add_light` and friends, which check the
+    // capacity. Read them through `primitives()` and `lights()`.
+    pub(crate) primitive_list: Vec<Box<dyn Primitive>>,
+    // Which rays see each primitive, in the same order
+    pub(crate) visibility: Vec<VisibilityFlags>,
+    pub(crate) light_list: Vec<Light>,
+
+    // Named materials that primitives can share. Primitives only keep the
+    // name, so changing a material here changes every primitive using it.
+    pub materials: HashMap<String, Material>,
+    // Materials primitives share by `MaterialId`, the same way, without
+    // looking up a name. Only ever added to, so handles stay valid.
+    registry: Vec<Material>,
+
+    // Light that reaches every surface from everywhere, so the sides facing
+    // away from the lights aren't pitch black. The Whitted tracer adds it to
+    // every hit, times the diffuse color. The path tracer finds the real
+    // indirect light instead.
+    pub ambient: Vector3D,
+
+    // What reflected, refracted and bounced rays that miss everything see,
+    // and what lights the scene from the sky in the path tracer
+    pub environment: Vector3D,
+    // Image seen and lighting the scene all around instead of the plain
+    // `environment` color (see envmap.rs)
+    pub environment_map: Option<EnvMap>,
+    // What camera rays that miss everything see
+    pub camera_background: CameraBackground,
+    // Fog every ray goes through, none by default (see media.rs)
+    pub fog: Fog,
+
+    // Hierarchy over `primitive_list`, built by `render_scene`. Only used
+    // while it still covers every primitive.
+    pub bvh: Option<Bvh>,
+
+    // Primitives entirely behind the camera, found by `render_scene`. Only
+    // used while it still covers every primitive.
+    pub(crate) behind_camera: Option<BehindCamera>,
+
+    // Photons left on diffuse surfaces by the caustics pass
+    pub caustics: Option<KdTree<Photon>>,
+
+    // Whether the rays traced get counted. Every thread keeps its own
+    // counts, which end up in `GlobalSettings::pass_stats`.
+    pub counters: RayCounters,
+}
+
+// A scene and the image it's rendered to: the framebuffers and whatever
+// else a render changes. It reads through to the scene, so `globals.camera`
+// is `globals.scene.camera`.
+pub struct GlobalSettings {
+    pub scene: Scene,
+    pub img: RgbaImage,
+
+    // Linear HDR color of every pixel, row by row. `resolve` turns it into
+    // `img` once all the passes are done.
+    pub framebuffer: Vec<Vector3D>,
+
+    // Distance to, normal at and base color (albedo) of the primary hit of
+    // every pixel, row by row, what denoisers want alongside the render.
+    // Misses have an infinite depth and a zero normal and albedo.
+    pub depth_buffer: Vec<f64>,
+    pub normal_buffer: Vec<Vector3D>,
+    pub albedo_buffer: Vec<Vector3D>,
+    // Samples taken for every pixel
+    pub sample_counts: Vec<u32>,
+    // Share of every pixel's camera rays that hit something, which is the
+    // alpha of the image. Always 1 unless the camera background is
+    // transparent; then the framebuffers hold the color times this.
+    pub coverage: Vec<f64>,
+
+    // With `settings.light_aovs`: the part of `framebuffer` every group in
+    // `light_groups()` contributed, in that order, and last everything that
+    // didn't come from a light. They add up to `framebuffer` (before
+    // denoising and edges).
+    pub light_aovs: Vec<Vec<Vector3D>>,
+
+    // What every render pass traced when `settings.stats` is on. Every
+    // `render_scene` adds its passes.
+    pub pass_stats: Vec<PassStats>,
+
+    // Which budget stopped the last `render_scene` early, if one did
+    pub budget_stop: Option<BudgetStop>,
+
+    // Set this from another thread to stop the render going on, like
+    // `--watch` does when the scene file changes. It's looked at between
+    // passes, and the render stops with `BudgetStop::Cancelled`.
+    pub cancel: Option<Arc<AtomicBool>>,
+
+    // Camera hits of the last render, with `settings.geometry_cache`
+    pub geometry_cache: Option<GeometryCache>,
+
+    // Save the samples taken so far to this file every
+    // `settings.checkpoint_every` and once the render is over, and with
+    // `resume` carry on from what it holds instead of starting over (see
+    // checkpoint.rs)
+    pub checkpoint: Option<String>,
+    pub resume: bool,
+}
+
+impl std::ops::Deref for GlobalSettings {
+    type Target = Scene;
+
+    fn deref(&self) -> &Scene {
+        &self.scene
+    }
+}
+
+impl std::ops::DerefMut for GlobalSettings {
+    fn deref_mut(&mut self) -> &mut Scene {
+        &mut self.scene
+    }
+}
+
+impl Scene {
+    // Empty scene
+    pub fn new(settings: RenderSettings, camera: Camera) -> Self {
+        Self {
+            settings,
+            camera,
+            primitive_list: Vec::new(),
+            visibility: Vec::new(),
+            light_list: Vec::new(),
+            materials: HashMap::new(),
+            registry: Vec::new(),
+            ambient: AMBIENT,
+            environment: BACKGROUND,
+            environment_map: None,
+            fog: Fog::default(),
+            camera_background: CameraBackground::Environment,
+            bvh: None,
+            behind_camera: None,
+            caustics: None,
+            counters: RayCounters::default(),
+        }
+    }
+
+    // What a ray going in `direction` that misses everything sees: the
+    // camera background for camera rays (`refl_depth` 0), the environment
+    // for the rest. A transparent background is black here.
+    pub fn background(&self, direction: Vector3D, refl_depth: u32) -> Vector3D {
+        match (refl_depth, self.camera_background) {
+            (0, CameraBackground::Color(c)) => c,
+            (0, CameraBackground::Transparent) => Vector3D::ZERO,
+            _ => match &self.environment_map {
+                Some(map) => map.radiance(direction),
+                None => self.environment,
+            },
+        }
+    }
+
+    // The scene's primitives, in the order they were added
+    pub fn primitives(&self) -> &[Box<dyn Primitive>] {
+        &self.primitive_list
+    }
+
+    // Which rays see primitive `index`
+    pub fn visibility(&self, index: usize) -> VisibilityFlags {
+        self.visibility[index]
+    }
+
+    // Hide primitive `index` from some kinds of rays. Panics if there's no
+    // such primitive.
+    pub fn set_visibility(&mut self, index: usize, flags: VisibilityFlags) {
+        self.visibility[index] = flags;
+    }
+
+    // The scene's lights, in the order they were added
+    pub fn lights(&self) -> &[Light] {
+        &self.light_list
+    }
+
+    // The lights, to change them in place
+    pub fn lights_mut(&mut self) -> &mut [Light] {
+        &mut self.light_list
+    }
+
+    // Names of the light groups, in the order their first lights were added
+    pub fn light_groups(&self) -> Vec<&str> {
+        let mut groups: Vec<&str> = Vec::new();
+        for light in &self.light_list {
+            if !groups.contains(&light.group.as_str()) {
+                groups.push(&light.group);
+            }
+        }
+        groups
+    }
+
+    // (Re)build the BVH over the primitives the way the settings ask
+    pub fn build_bvh(&mut self) {
+        let bounds: Vec<_> = self.primitive_list.iter().map(|p| p.bounds()).collect();
+        self.bvh = Some(Bvh::new(&bounds, self.settings.bvh));
+    }
+
+    // Find the primitives behind the camera again, see `BehindCamera`
+    fn find_behind_camera(&mut self) {
+        self.behind_camera = BehindCamera::new(&self.camera, &self.primitive_list, self.settings.ray_epsilon);
+    }
+
+    // Add a material to the library, replacing any material with that name.
+    pub fn add_material(&mut self, name: &str, m: Material) {
+        self.materials.insert(name.to_string(), m);
+    }
+
+    // Add a material to the registry. Primitives given the handle share it,
+    // and changing it with `registered_material_mut` changes all of them.
+    pub fn register_material(&mut self, m: Material) -> MaterialId {
+        self.registry.push(m);
+        MaterialId(self.registry.len() - 1)
+    }
+
+    // Registered material, `None` if `id` is from another scene's registry
+    pub fn registered_material(&self, id: MaterialId) -> Option<&Material> {
+        self.registry.get(id.0)
+    }
+
+    pub fn registered_material_mut(&mut self, id: MaterialId) -> Option<&mut Material> {
+        self.registry.get_mut(id.0)
+    }
+
+    // Material a primitive refers to, as it's rendered. Names and handles
+    // are checked when the primitive is added, so the lookup can't fail.
+    pub fn material(&self, m: &MaterialRef) -> Material {
+        let m = match m {
+            MaterialRef::Named(name) => self.materials[name],
+            MaterialRef::Id(id) => self.registry[id.0],
+            MaterialRef::Inline(m) => *m,
+        };
+
+        match self.settings.conserve_energy {
+            true => m.conserved(),
+            false => m,
+        }
+    }
+
+    // Whether pixel (x, y) gets rendered, see `RenderSettings::region`
+    pub fn in_region(&self, x: u32, y: u32) -> bool {
+        self.settings.region.is_none_or(|r| r.contains(x, y))
+    }
+
+    // What the center of pixel (px, py) of a `width` x `height` image sees
+    // through `camera`. That's the same ray `render` uses for the pixel
+    // without supersampling, so an editor can find out what was clicked.
+    pub fn pick(&self, camera: &Camera, px: u32, py: u32, width: u32, height: u32) -> Option<PickResult> {
+        let ray = camera.pixel_ray(px as f64 + 0.5, py as f64 + 0.5, width, height, 0.0)?;
+        let (primitive_index, distance) = closest_hit(&ray, RayKind::Camera, self)?;
+
+        let mut point = ray.direction;
+        point.v3d_mul_scalar(distance);
+        point.v3d_add(ray.origin);
+
+        Some(PickResult {
+            primitive_index,
+            distance,
+            point,
+            normal: self.primitive_list[primitive_index].normal(point, ray.time),
+        })
+    }
+}
+
+impl GlobalSettings {
+    // Empty scene rendering to a `width` x `height` image
+    pub fn new(width: u32, height: u32, settings: RenderSettings, camera: Camera) -> Self {
+        let pixels = (width * height) as usize;
+
+        Self {
+            scene: Scene::new(settings, camera),
+            img: RgbaImage::new(width, height),
+            framebuffer: vec![Vector3D::ZERO; pixels],
+            depth_buffer: vec![f64::INFINITY; pixels],
+            normal_buffer: vec![Vector3D::ZERO; pixels],
+            albedo_buffer: vec![Vector3D::ZERO; pixels],
+            sample_counts: vec![0; pixels],
+            coverage: vec![1.0; pixels],
+            light_aovs: Vec::new(),
+            pass_stats: Vec::new(),
+            budget_stop: None,
+            cancel: None,
+            geometry_cache: None,
+            checkpoint: None,
+            resume: false,
+        }
+    }
+
+    // Render to a `width` x `height` image from now on. The image and the
+    // per-pixel buffers start over if the size changes.
+    pub fn set_size(&mut self, width: u32, height: u32) {
+        if self.img.dimensions() == (width, height) {
+            return;
+        }
+
+        let pixels = (width * height) as usize;
+        self.img = RgbaImage::new(width, height);
+        self.framebuffer = vec![Vector3D::ZERO; pixels];
+        self.depth_buffer = vec![f64::INFINITY; pixels];
+        self.normal_buffer = vec![Vector3D::ZERO; pixels];
+        self.albedo_buffer = vec![Vector3D::ZERO; pixels];
+        self.sample_counts = vec![0; pixels];
+        self.coverage = vec![1.0; pixels];
+        self.light_aovs.clear();
+    }
+
+    // Error out on settings that can't give an image
+    pub fn check_settings(&self) -> Result<(), LuxError> {
+        let (width, height) = self.img.dimensions();
+        let invalid = |m: &str| Err(LuxError::InvalidSettings(m.to_string()));
+
+        if width == 0 || height == 0 {
+            return invalid("the image has no pixels");
+        }
+        if let Some(r) = self.settings.region {
+            if r.width == 0 || r.height == 0 || r.x + r.width > width || r.y + r.height > height {
+                return Err(LuxError::InvalidSettings(format!(
+                    "region {},{},{},{} isn't inside the {}x{} image",
+                    r.x, r.y, r.width, r.height, width, height
+                )));
+            }
+        }
+        if !(self.settings.ray_epsilon >= 0.0 && self.settings.ray_epsilon.is_finite()) {
+            return invalid("the ray epsilon must be 0 or more");
+        }
+        if self.settings.target_noise.is_some_and(|t| !(t >= 0.0 && t.is_finite())) {
+            return invalid("the target noise must be 0 or more");
+        }
+        if self.settings.filter_radius.is_some_and(|r| !(r > 0.0 && r.is_finite())) {
+            return invalid("the filter radius must be above 0");
+        }
+        if self.settings.axes.is_some_and(|l| l.is_nan() || l <= 0.0) {
+            return invalid("the axes must be longer than 0");
+        }
+        if self.settings.clamp.is_some_and(|c| c.is_nan() || c <= 0.0) {
+            return invalid("the clamp must be above 0");
+        }
+        if self.settings.reject_outliers.is_some_and(|p| !(p > 0.0 && p < 100.0)) {
+            return invalid("the share of outliers to reject must be between 0 and 100 percent");
+        }
+        if self.settings.checkpoint_every == CheckpointInterval::Samples(0) {
+            return invalid("checkpoints must be at least one sample apart");
+        }
+        if !(self.fog.density >= 0.0 && self.fog.density.is_finite()) {
+            return invalid("the fog density must be 0 or more");
+        }
+        if self.camera.projection == Projection::Perspective && !(self.camera.fov > 0.0 && self.camera.fov < 180.0) {
+            return invalid("the field of view must be between 0 and 180 degrees");
+        }
+
+        Ok(())
+    }
+}
+
+impl PrimSphere {
+    // Where the center is at `time`
+    pub fn center(&self, time: f64) -> Vector3D {
+        let mut c = self.velocity;
+        c.v3d_mul_scalar(time);
+        c.v3d_add(self.position);
+        c
+    }
+}
+
+// Methods for `Prim` 
+impl Primitive for PrimSphere{
+	fn normal(&self, pos: Vector3D, time: f64) -> Vector3D {
+		let mut ret = pos;
+		ret.v3d_sub(self.center(time));
+		let f = 1.0 / self.radius;
+		ret.v3d_mul_scalar(f);
+		ret.v3d_norm();
+
+		ret
+	}
+
+	fn intersect(&self, ray: Ray, t_min: f64, dist: &mut f64) -> i32 {
+		let mut v_precalc = ray.origin;
+		v_precalc.v3d_sub(self.center(ray.time));
+
+		let det_precalc: f64 = self.radius * self.radius - v_precalc.v3d_dot_mul(v_precalc);
+
+		let b = - v_precalc.v3d_dot_mul(ray.direction);
+		let mut det = b*b + det_precalc;
+
+		let mut retval: i32 = 0;
+
+		if det > 0.0 {
+			det = det.sqrt();
+			let i1 = b - det;
+			let i2 = b + det;
+
+			if i1 > t_min {
+				retval = 1;
+				*dist = i1;
+			} else if i2 > t_min {
+				// Starting inside, so the ray leaves through the far side
+				retval = -1;
+				*dist = i2;
+			}
+		}
+
+		retval
+	}
+
+	fn material_ref(&self) -> &MaterialRef {
+		&self.m
+	}
+
+	fn kind(&self) -> &'static str {
+		"sphere"
+	}
+
+	fn hash_geometry(&self, state: &mut dyn Hasher) -> bool {
+		let (p, v) = (self.position, self.velocity);
+		hash_floats(state, [p.x, p.y, p.z, v.x, v.y, v.z, self.radius]);
+		true
+	}
+
+	// Covers a moving sphere from time 0 to 1, the longest sensible shutter
+	fn bounds(&self) -> (Vector3D, Vector3D) {
+		let r = Vector3D::new(self.radius, self.radius, self.radius);
+		let (start, end) = (self.center(0.0), self.center(1.0));
+
+		let mut min = start.min(end);
+		min.v3d_sub(r);
+		let mut max = start.max(end);
+		max.v3d_add(r);
+		(min, max)
+	}
+
+	// Uniform direction inside the cone the sphere covers
+	fn sample_direction(&self, from: Vector3D, time: f64, rng: &mut Rng) -> Option<(Vector3D, f64)> {
+		let center = self.center(time);
+		let cos_max = sphere_cone_cos(center, self.radius, from)?;
+
+		let mut axis = center;
+		axis.v3d_sub(from);
+		axis.v3d_norm();
+
+		Some((sample_cone_cos(axis, cos_max, rng), cone_pdf(cos_max)))
+	}
+
+	fn direction_pdf(&self, from: Vector3D, _hit: Vector3D, time: f64) -> f64 {
+		sphere_cone_cos(self.center(time), self.radius, from).map_or(0.0, cone_pdf)
+	}
+
+	// Longitude and latitude, with v = 0 at the top
+	fn uv(&self, pos: Vector3D, time: f64) -> Option<(f64, f64)> {
+		let n = self.normal(pos, time);
+		let u = 0.5 + n.z.atan2(n.x) / (2.0 * std::f64::consts::PI);
+		let v = n.y.clamp(-1.0, 1.0).acos() / std::f64::consts::PI;
+		Some((u, v))
+	}
+
+	// East and south, undefined at the poles
+	fn tangents(&self, pos: Vector3D, time: f64) -> Option<(Vector3D, Vector3D)> {
+		let n = self.normal(pos, time);
+		let sin_theta = (n.x * n.x + n.z * n.z).sqrt();
+		if sin_theta < 1e-9 {
+			return None;
+		}
+
+		let east = Vector3D::new(-n.z / sin_theta, 0.0, n.x / sin_theta);
+		let south = Vector3D::new(n.y * n.x / sin_theta, -sin_theta, n.y * n.z / sin_theta);
+		Some((east, south))
+	}
+}
+
+impl PrimQuad {
+    // Unnormalized normal. Its length is the area of the quad.
+    fn cross(&self) -> Vector3D {
+        Vector3D::cross(self.edge_u, self.edge_v)
+    }
+
+    // Coordinates of a point in the quad's plane along the two edges, each
+    // in [0, 1] inside the quad
+    fn edge_coords(&self, pos: Vector3D) -> (f64, f64) {
+        let n = self.cross();
+        let mut rel = pos;
+        rel.v3d_sub(self.origin);
+
+        let nn = n.v3d_dot_mul(n);
+        let a = n.v3d_dot_mul(Vector3D::cross(rel, self.edge_v)) / nn;
+        let b = n.v3d_dot_mul(Vector3D::cross(self.edge_u, rel)) / nn;
+        (a, b)
+    }
+}
+
+impl Primitive for PrimQuad {
+    fn normal(&self, _pos: Vector3D, _time: f64) -> Vector3D {
+        let mut n = self.cross();
+        n.v3d_norm();
+        n
+    }
+
+    fn intersect(&self, ray: Ray, t_min: f64, dist: &mut f64) -> i32 {
+        let n = self.cross();
+        let denom = n.v3d_dot_mul(ray.direction);
+
+        // Parallel to the plane
+        if denom.abs() < 1e-12 {
+            return 0;
+        }
+
+        let mut to_plane = self.origin;
+        to_plane.v3d_sub(ray.origin);
+        let t = n.v3d_dot_mul(to_plane) / denom;
+
+        if t <= t_min {
+            return 0;
+        }
+
+        let mut hit = ray.direction;
+        hit.v3d_mul_scalar(t);
+        hit.v3d_add(ray.origin);
+
+        let (a, b) = self.edge_coords(hit);
+        if !(0.0..=1.0).contains(&a) || !(0.0..=1.0).contains(&b) {
+            return 0;
+        }
+
+        *dist = t;
+        1
+    }
+
+    fn material_ref(&self) -> &MaterialRef {
+        &self.m
+    }
+
+    fn kind(&self) -> &'static str {
+        "quad"
+    }
+
+    fn hash_geometry(&self, state: &mut dyn Hasher) -> bool {
+        let (o, u, v) = (self.origin, self.edge_u, self.edge_v);
+        hash_floats(state, [o.x, o.y, o.z, u.x, u.y, u.z, v.x, v.y, v.z]);
+        true
+    }
+
+    fn is_closed(&self) -> bool {
+        false
+    }
+
+    fn bounds(&self) -> (Vector3D, Vector3D) {
+        let mut u = self.origin;
+        u.v3d_add(self.edge_u);
+        let mut v = self.origin;
+        v.v3d_add(self.edge_v);
+        let mut uv = u;
+        uv.v3d_add(self.edge_v);
+
+        let min = self.origin.min(u).min(v).min(uv);
+        let max = self.origin.max(u).max(v).max(uv);
+        (min, max)
+    }
+
+    // Uniform point on the quad, converted to a solid angle pdf
+    fn sample_direction(&self, from: Vector3D, time: f64, rng: &mut Rng) -> Option<(Vector3D, f64)> {
+        let mut p = self.edge_u;
+        p.v3d_mul_scalar(rng.next_f64());
+        let mut v = self.edge_v;
+        v.v3d_mul_scalar(rng.next_f64());
+        p.v3d_add(v);
+        p.v3d_add(self.origin);
+
+        let pdf = self.direction_pdf(from, p, time);
+        if pdf <= 0.0 {
+            return None;
+        }
+
+        p.v3d_sub(from);
+        p.v3d_norm();
+        Some((p, pdf))
+    }
+
+    fn direction_pdf(&self, from: Vector3D, hit: Vector3D, _time: f64) -> f64 {
+        let mut d = hit;
+        d.v3d_sub(from);
+        let dist2 = d.v3d_dot_mul(d);
+        d.v3d_norm();
+
+        let cross = self.cross();
+        let area = cross.v3d_length();
+        let cos = (cross.v3d_dot_mul(d) / area).abs();
+
+        if cos < 1e-9 {
+            return 0.0;
+        }
+
+        dist2 / (area * cos)
+    }
+
+    fn uv(&self, pos: Vector3D, _time: f64) -> Option<(f64, f64)> {
+        Some(self.edge_coords(pos))
+    }
+
+    fn tangents(&self, _pos: Vector3D, _time: f64) -> Option<(Vector3D, Vector3D)> {
+        let mut u = self.edge_u;
+        u.v3d_norm();
+        let mut v = self.edge_v;
+        v.v3d_norm();
+        Some((u, v))
+    }
+}
+
+impl PrimTorus {
+    // `pos` in the torus' own frame: centered, with the axis along z
+    fn local(&self, pos: Vector3D) -> Vector3D {
+        let (u, v) = orthonormal_basis(self.axis);
+        let mut rel = pos;
+        rel.v3d_sub(self.center);
+        Vector3D::new(rel.v3d_dot_mul(u), rel.v3d_dot_mul(v), rel.v3d_dot_mul(self.axis))
+    }
+
+    // Direction `dir` in the torus' own frame
+    fn local_dir(&self, dir: Vector3D) -> Vector3D {
+        let (u, v) = orthonormal_basis(self.axis);
+        Vector3D::new(dir.v3d_dot_mul(u), dir.v3d_dot_mul(v), dir.v3d_dot_mul(self.axis))
+    }
+
+    // Point on the center circle of the tube closest to local point `p`
+    fn ring_point(&self, p: Vector3D) -> Vector3D {
+        let len = (p.x * p.x + p.y * p.y).sqrt();
+        if len == 0.0 {
+            // On the axis every point of the circle is as close
+            return Vector3D::new(self.major_radius, 0.0, 0.0);
+        }
+        let s = self.major_radius / len;
+        Vector3D::new(p.x * s, p.y * s, 0.0)
+    }
+}
+
+impl Primitive for PrimTorus {
+    // Points on the surface solve
+    //   (|p|^2 + R^2 - r^2)^2 = 4 R^2 (p.x^2 + p.y^2)
+    // which is a quartic in the distance along the ray
+    fn intersect(&self, ray: Ray, t_min: f64, dist: &mut f64) -> i32 {
+        let (big_r, small_r) = (self.major_radius, self.minor_radius);
+        let mut o = self.local(ray.origin);
+        let d = self.local_dir(ray.direction);
+
+        // The quartic loses precision quickly with large coefficients, so a
+        // ray from far away is first moved up to the bounding sphere
+        let bound = big_r + small_r;
+        let mut skipped = 0.0;
+        let oo = o.v3d_dot_mul(o);
+        if oo > bound * bound {
+            let b = o.v3d_dot_mul(d);
+            let disc = b * b - (oo - bound * bound);
+            if disc < 0.0 || -b + disc.sqrt() <= t_min {
+                return 0;
+            }
+
+            skipped = (-b - disc.sqrt()).max(0.0);
+            let mut step = d;
+            step.v3d_mul_scalar(skipped);
+            o.v3d_add(step);
+        }
+
+        let k = o.v3d_dot_mul(o) + big_r * big_r - small_r * small_r;
+        let od = o.v3d_dot_mul(d);
+        let r4 = 4.0 * big_r * big_r;
+
+        let roots = solve_quartic(
+            1.0,
+            4.0 * od,
+            4.0 * od * od + 2.0 * k - r4 * (d.x * d.x + d.y * d.y),
+            4.0 * od * k - 2.0 * r4 * (o.x * d.x + o.y * d.y),
+            k * k - r4 * (o.x * o.x + o.y * o.y),
+        );
+
+        let t = match roots.into_iter().map(|t| t + skipped).find(|&t| t > t_min) {
+            Some(t) => t,
+            None => return 0,
+        };
+        *dist = t;
+
+        // Starting inside the tube?
+        let start = self.local(ray.origin);
+        let mut from_ring = start;
+        from_ring.v3d_sub(self.ring_point(start));
+        if from_ring.v3d_dot_mul(from_ring) < small_r * small_r {
+            -1
+        } else {
+            1
+        }
+    }
+
+    // Away from the nearest point of the tube's center circle
+    fn normal(&self, pos: Vector3D, _time: f64) -> Vector3D {
+        let p = self.local(pos);
+        let mut n = p;
+        n.v3d_sub(self.ring_point(p));
+
+        let (u, v) = orthonormal_basis(self.axis);
+        let mut world = u;
+        world.v3d_mul_scalar(n.x);
+        let mut along_v = v;
+        along_v.v3d_mul_scalar(n.y);
+        world.v3d_add(along_v);
+        let mut along_axis = self.axis;
+        along_axis.v3d_mul_scalar(n.z);
+        world.v3d_add(along_axis);
+
+        world.v3d_norm();
+        world
+    }
+
+    fn material_ref(&self) -> &MaterialRef {
+        &self.m
+    }
+
+    fn kind(&self) -> &'static str {
+        "torus"
+    }
+
+    fn hash_geometry(&self, state: &mut dyn Hasher) -> bool {
+        let (c, a) = (self.center, self.axis);
+        hash_floats(state, [c.x, c.y, c.z, a.x, a.y, a.z, self.major_radius, self.minor_radius]);
+        true
+    }
+
+    // The ring reaches R * sqrt(1 - a^2) along a world axis whose component
+    // of the torus axis is a, and the tube adds r in every direction
+    fn bounds(&self) -> (Vector3D, Vector3D) {
+        let extent = self
+            .axis
+            .map(|a| self.major_radius * (1.0 - a * a).max(0.0).sqrt() + self.minor_radius);
+
+        let mut min = self.center;
+        min.v3d_sub(extent);
+        let mut max = self.center;
+        max.v3d_add(extent);
+        (min, max)
+    }
+
+    // Not sampled directly as a light; the path tracer still finds emissive
+    // tori by chance
+    fn sample_direction(&self, _from: Vector3D, _time: f64, _rng: &mut Rng) -> Option<(Vector3D, f64)> {
+        None
+    }
+
+    fn direction_pdf(&self, _from: Vector3D, _hit: Vector3D, _time: f64) -> f64 {
+        0.0
+    }
+
+    // Angle around the axis and angle around the tube
+    fn uv(&self, pos: Vector3D, _time: f64) -> Option<(f64, f64)> {
+        let p = self.local(pos);
+        let ring = (p.x * p.x + p.y * p.y).sqrt() - self.major_radius;
+
+        let u = 0.5 + p.y.atan2(p.x) / (2.0 * std::f64::consts::PI);
+        let v = 0.5 + p.z.atan2(ring) / (2.0 * std::f64::consts::PI);
+        Some((u, v))
+    }
+}
+
+impl Instance {
+    // `object` transformed by `to_world`. Fails if the transform squashes
+    // space flat and can't be undone.
+    pub fn new(object: Arc<dyn Primitive>, to_world: Mat4) -> Result<Self, LuxError> {
+        let to_object = to_world
+            .inverse()
+            .ok_or_else(|| LuxError::Scene("transform can't be inverted".to_string()))?;
+
+        // The axes must stay at right angles and keep the same length
+        let axes: Vec<Vector3D> = (0..3)
+            .map(|i| Vector3D::new(to_world.m[0][i], to_world.m[1][i], to_world.m[2][i]))
+            .collect();
+        let len = axes[0].v3d_dot_mul(axes[0]);
+        let tolerance = 1e-9 * len;
+        let keeps_angles = (0..3).all(|i| {
+            (axes[i].v3d_dot_mul(axes[i]) - len).abs() < tolerance
+                && axes[i].v3d_dot_mul(axes[(i + 1) % 3]).abs() < tolerance
+        });
+
+        Ok(Self {
+            object,
+            material: None,
+            to_world,
+            to_object,
+            keeps_angles,
+        })
+    }
+
+    // Use `m` instead of the object's own material
+    pub fn with_material(mut self, m: impl Into<MaterialRef>) -> Self {
+        self.material = Some(m.into());
+        self
+    }
+
+    // `ray` in object space, and how much longer its direction got there
+    fn object_ray(&self, ray: Ray) -> (Ray, f64) {
+        let mut direction = self.to_object.transform_vector(ray.direction);
+        let scale = direction.v3d_length();
+        direction.v3d_mul_scalar(1.0 / scale);
+
+        let origin = self.to_object.transform_point(ray.origin);
+        (Ray { origin, direction, time: ray.time }, scale)
+    }
+}
+
+impl Primitive for Instance {
+    fn intersect(&self, ray: Ray, t_min: f64, dist: &mut f64) -> i32 {
+        let (ray, scale) = self.object_ray(ray);
+
+        let mut object_dist = 0.0;
+        let res = self.object.intersect(ray, t_min * scale, &mut object_dist);
+        if res != 0 {
+            *dist = object_dist / scale;
+        }
+        res
+    }
+
+    // Normals go back to world space with the inverse transpose, which keeps
+    // them perpendicular to the surface under uneven scaling
+    fn normal(&self, pos: Vector3D, time: f64) -> Vector3D {
+        let n = self.object.normal(self.to_object.transform_point(pos), time);
+
+        let mut n = self.to_object.transpose().transform_vector(n);
+        n.v3d_norm();
+        n
+    }
+
+    fn material_ref(&self) -> &MaterialRef {
+        self.material.as_ref().unwrap_or_else(|| self.object.material_ref())
+    }
+
+    fn kind(&self) -> &'static str {
+        self.object.kind()
+    }
+
+    fn hash_geometry(&self, state: &mut dyn Hasher) -> bool {
+        hash_floats(state, self.to_world.m.iter().flatten().copied());
+        self.object.hash_geometry(state)
+    }
+
+    fn is_closed(&self) -> bool {
+        self.object.is_closed()
+    }
+
+    // Box around the transformed corners of the object's box
+    fn bounds(&self) -> (Vector3D, Vector3D) {
+        let (lo, hi) = self.object.bounds();
+
+        let mut min = Vector3D::new(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+        let mut max = Vector3D::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY);
+        for corner in 0..8 {
+            let c = Vector3D::new(
+                if corner & 1 == 0 { lo.x } else { hi.x },
+                if corner & 2 == 0 { lo.y } else { hi.y },
+                if corner & 4 == 0 { lo.z } else { hi.z },
+            );
+            let c = self.to_world.transform_point(c);
+            min = min.min(c);
+            max = max.max(c);
+        }
+        (min, max)
+    }
+
+    // Transforms that keep angles also keep solid angles, so the wrapped
+    // primitive's pdfs still hold. Otherwise the light is only found by
+    // chance, which the path tracer's MIS handles fine.
+    fn sample_direction(&self, from: Vector3D, time: f64, rng: &mut Rng) -> Option<(Vector3D, f64)> {
+        if !self.keeps_angles {
+            return None;
+        }
+
+        let (dir, pdf) = self.object.sample_direction(self.to_object.transform_point(from), time, rng)?;
+        let mut dir = self.to_world.transform_vector(dir);
+        dir.v3d_norm();
+        Some((dir, pdf))
+    }
+
+    fn direction_pdf(&self, from: Vector3D, hit: Vector3D, time: f64) -> f64 {
+        if !self.keeps_angles {
+            return 0.0;
+        }
+
+        self.object
+            .direction_pdf(self.to_object.transform_point(from), self.to_object.transform_point(hit), time)
+    }
+
+    fn uv(&self, pos: Vector3D, time: f64) -> Option<(f64, f64)> {
+        self.object.uv(self.to_object.transform_point(pos), time)
+    }
+
+    fn tangents(&self, pos: Vector3D, time: f64) -> Option<(Vector3D, Vector3D)> {
+        let (u, v) = self.object.tangents(self.to_object.transform_point(pos), time)?;
+
+        let mut u = self.to_world.transform_vector(u);
+        u.v3d_norm();
+        let mut v = self.to_world.transform_vector(v);
+        v.v3d_norm();
+        Some((u, v))
+    }
+}
+
+// Add any primitive to the scene. Fails if it refers to a material name
+// that isn't in the library or a handle that isn't in the registry, or if
+// the scene already has `MAXPRIMCOUNT` primitives.
+pub fn add_primitive(p: Box<dyn Primitive>, scene: &mut Scene) -> Result<(), LuxError> {
+    match p.material_ref() {
+        MaterialRef::Named(name) if !scene.materials.contains_key(name) => {
+            return Err(LuxError::Scene(format!("unknown material '{}'", name)));
+        }
+        MaterialRef::Id(id) if scene.registered_material(*id).is_none() => {
+            return Err(LuxError::Scene(format!("unknown material {}", id)));
+        }
+        _ => {}
+    }
+
+    if scene.primitive_list.len() >= MAXPRIMCOUNT as usize {
+        return Err(LuxError::SceneCapacity {
+            kind: "primitive",
+            max: MAXPRIMCOUNT,
+        });
+    }
+
+    scene.primitive_list.push(p);
+    scene.visibility.push(VisibilityFlags::default());
+    Ok(())
+}
+
+// Add a light to the scene, if there's room for it
+fn push_light(l: Light, scene: &mut Scene) -> Result<(), LuxError> {
+    if scene.light_list.len() >= MAXLIGHTCOUNT as usize {
+        return Err(LuxError::SceneCapacity {
+            kind: "light",
+            max: MAXLIGHTCOUNT,
+        });
+    }
+
+    scene.light_list.push(l);
+    Ok(())
+}
+
+// Spawn a sphere at the specified position `pos`. `m` is the name of a
+// material in the library, the `MaterialId` of a registered one or a
+// `Material` of its own. The radius must be above 0: a sphere of radius 0
+// can't be seen, and a negative one would act like its positive twin.
+// Either is an error and nothing gets added.
+pub fn add_sphere(pos: &Vector3D, rad: f64, m: impl Into<MaterialRef>, scene: &mut Scene) -> Result<(), LuxError> {
+    add_moving_sphere(pos, &Vector3D::ZERO, rad, m, scene)
+}
+
+// How `add_sphere_grid` lays out its spheres
+#[derive(Clone, Copy)]
+pub struct SphereGrid {
+    // Center of the sphere for the first character of the first row.
+    // Columns go towards +x and rows towards -y, `spacing` apart.
+    pub origin: Vector3D,
+    pub spacing: f64,
+    pub radius: f64,
+    // How far along z from `origin` a sphere goes, given its character,
+    // column and row
+    pub depth: fn(char, usize, usize) -> f64,
+}
+
+impl Default for SphereGrid {
+    fn default() -> Self {
+        Self {
+            origin: Vector3D::ZERO,
+            spacing: 1.0,
+            radius: 0.5,
+            depth: |_, _, _| 0.0,
+        }
+    }
+}
+
+// Turn text art into spheres: every character of `rows` with an entry in
+// `materials` becomes a sphere with that material, placed as `grid` says.
+// Other characters are left empty.
+pub fn add_sphere_grid(rows: &[&str], materials: &HashMap<char, MaterialRef>, grid: &SphereGrid, scene: &mut Scene) -> Result<(), LuxError> {
+    for (j, row) in rows.iter().enumerate() {
+        for (i, c) in row.chars().enumerate() {
+            let m = match materials.get(&c) {
+                Some(m) => m.clone(),
+                None => continue,
+            };
+
+            let pos = Vector3D::new(
+                grid.origin.x + (i as f64) * grid.spacing,
+                grid.origin.y - (j as f64) * grid.spacing,
+                grid.origin.z + (grid.depth)(c, i, j),
+            );
+            add_sphere(&pos, grid.radius, m, scene)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Spawn a sphere that starts at `pos` and moves by `velocity` per unit of
+// time. It's blurred along its path when the camera has a shutter time.
+pub fn add_moving_sphere(pos: &Vector3D, velocity: &Vector3D, rad: f64, m: impl Into<MaterialRef>, scene: &mut Scene) -> Result<(), LuxError> {
+    if rad <= 0.0 || rad.is_nan() {
+        return Err(LuxError::Scene(format!("a sphere's radius must be above 0, not {}", rad)));
+    }
+
+    let p = PrimSphere {
+        position: Vector3D::new(pos.x, pos.y, pos.z),
+        velocity: *velocity,
+        radius: rad,
+        m: m.into(),
+    };
+
+    add_primitive(Box::new(p), scene)
+}
+
+// Spawn a torus around `center`. `axis` goes through the hole and doesn't
+// need to be normalized.
+pub fn add_torus(center: Vector3D, axis: Vector3D, major_radius: f64, minor_radius: f64, m: impl Into<MaterialRef>, scene: &mut Scene) -> Result<(), LuxError> {
+    let mut axis = axis;
+    axis.v3d_norm();
+
+    let p = PrimTorus {
+        center,
+        axis,
+        major_radius,
+        minor_radius,
+        m: m.into(),
+    };
+
+    add_primitive(Box::new(p), scene)
+}
+
+// Spawn a quad with one corner at `origin` and the edges `edge_u`, `edge_v`.
+// The front side is the one `edge_u` x `edge_v` points to.
+pub fn add_quad(origin: Vector3D, edge_u: Vector3D, edge_v: Vector3D, m: impl Into<MaterialRef>, scene: &mut Scene) -> Result<(), LuxError> {
+    let p = PrimQuad {
+        origin,
+        edge_u,
+        edge_v,
+        m: m.into(),
+    };
+
+    add_primitive(Box::new(p), scene)
+}
+
+// Place a copy of the shared `geometry`, transformed by `to_world`. `m`
+// replaces the geometry's material if given. Fails if the transform can't
+// be inverted or the material isn't in the library.
+pub fn add_instance(geometry: &Arc<dyn Primitive>, to_world: Mat4, m: Option<MaterialRef>, scene: &mut Scene) -> Result<(), LuxError> {
+    let mut p = Instance::new(Arc::clone(geometry), to_world)?;
+    p.material = m;
+
+    add_primitive(Box::new(p), scene)
+}
+
+// Spawn a light source at the specified position `pos`. A scene holds up to
+// `MAXLIGHTCOUNT` of them.
+pub fn add_light(pos: Vector3D, color: Vector3D, intensity: f64, scene: &mut Scene) -> Result<(), LuxError> {
+    let l = Light {
+        position: Vector3D::new(pos.x, pos.y, pos.z),
+        color: Vector3D::new(color.x, color.y, color.z),
+        intensity,
+        kind: LightKind::Point,
+        group: DEFAULT_LIGHT_GROUP.to_string(),
+    };
+
+    push_light(l, scene)
+}
+
+// Spawn a spot light at `pos` shining along `direction`. The cone is fully
+// lit up to `inner_angle` and fades out until `outer_angle` (degrees from the
+// axis).
+pub fn add_spot_light(pos: Vector3D, direction: Vector3D, inner_angle: f64, outer_angle: f64, color: Vector3D, intensity: f64, scene: &mut Scene) -> Result<(), LuxError> {
+    let mut direction = direction;
+    direction.v3d_norm();
+
+    let l = Light {
+        position: pos,
+        color,
+        intensity,
+        kind: LightKind::Spot {
+            direction,
+            inner_angle,
+            outer_angle: outer_angle.max(inner_angle),
+        },
+        group: DEFAULT_LIGHT_GROUP.to_string(),
+    };
+
+    push_light(l, scene)
+}
+
+// Trace the ray
+// Diffuse factor `dot` (0 to 1) rounded down to one of `bands` evenly
+// spaced levels, from unlit to fully lit
+fn toon(dot: f64, bands: u32) -> f64 {
+    if bands < 2 {
+        return 1.0;
+    }
+
+    let steps = (bands - 1) as f64;
+    (dot * bands as f64).floor().min(steps) / steps
+}
+
+// Diffuse light `light` adds to a surface of `color` and material `m`,
+// where `dot` (above 0) says how much of it reaches the surface
+fn diffuse(dot: f64, light: Vector3D, color: Vector3D, m: &Material, scene: &Scene) -> Vector3D {
+    let dot = match scene.settings.toon_bands {
+        Some(bands) => toon(dot, bands),
+        None => dot,
+    };
+
+    //color += ((lightiter)->Color * prim_color) * diff;
+    let mut c = light;
+    c.v3d_mul_v3d(color);
+    c.v3d_mul_scalar(dot * m.diffusive);
+    c
+}
+
+// Light split up by where it came from, for the light AOVs
+struct LightSplit<'a> {
+    // Group of every light, as an index into `sums`
+    group_of: &'a [usize],
+    // Light from each group, and last the light that didn't come from any of
+    // the lights: the background, ambient light and caustics
+    sums: Vec<Vector3D>,
+}
+
+impl LightSplit<'_> {
+    fn other(&self) -> usize {
+        self.sums.len() - 1
+    }
+
+    // Add `color` to the sum at `index`, scaled by `weight`, the share of it
+    // that makes it back to the camera
+    fn add(&mut self, index: usize, color: Vector3D, weight: Vector3D) {
+        let mut c = color;
+        c.v3d_mul_v3d(weight);
+        self.sums[index].v3d_add(c);
+    }
+}
+
+// How deep a ray of `trace` is: the mirror reflections and refractions it
+// took to get there, counted apart for the caps of `Material`, and the
+// closed primitives it went into and didn't come out of yet (innermost
+// last), for the indices of refraction on both sides of the next surface
+#[derive(Clone, Copy, Debug, Default)]
+struct TraceDepth<'a> {
+    reflection: u32,
+    refraction: u32,
+    media: &'a [usize],
+}
+
+impl TraceDepth<'_> {
+    fn total(self) -> u32 {
+        self.reflection + self.refraction
+    }
+}
+
+// What becomes of a reflected or refracted ray `trace` could spawn
+enum Spawn<'a> {
+    // Followed, this deep
+    Trace(TraceDepth<'a>),
+    // Stopped by the cap of the material it leaves, so it sees the
+    // environment
+    Environment,
+    // Stopped by `RenderSettings::max_depth`, so it adds nothing
+    Nothing,
+}
+
+// Whether to follow a ray leaving a material that caps its kind of bounce
+// at `cap`, when `count` of them led there. It would be `next` deep.
+// Without a cap every bounce counts against `RenderSettings::max_depth`.
+fn spawn<'a>(depth: TraceDepth, count: u32, next: TraceDepth<'a>, cap: Option<u32>, scene: &Scene) -> Spawn<'a> {
+    match cap {
+        Some(cap) if count < cap => Spawn::Trace(next),
+        Some(_) => Spawn::Environment,
+        None if depth.total() < scene.settings.max_depth => Spawn::Trace(next),
+        None => Spawn::Nothing,
+    }
+}
+
+pub fn trace(ray: &Ray, refl_depth: u32, scene: &Scene) -> Vector3D{
+    let depth = TraceDepth {
+        reflection: refl_depth,
+        ..Default::default()
+    };
+    trace_split(ray, depth, scene, None, Vector3D::ONE, Wavelength::All)
+}
+
+// `trace` for a ray `depth` deep, also adding what every light group
+// contributes to `split`. Light seen in a reflection or through glass
+// counts for the group of the light that lit the surface it came from.
+fn trace_split(ray: &Ray, depth: TraceDepth, scene: &Scene, split: Option<&mut LightSplit>, weight: Vector3D, wavelength: Wavelength) -> Vector3D{
+    // Make the ray bounce off every sphere 
+    let hit = surface_hit(ray, RayKind::at_depth(depth.total()), scene);
+    shade_split(ray, hit, depth, scene, split, weight, wavelength)
+}
+
+// The rest of `trace_split`, for a ray that's already known to hit `hit`
+fn shade_split(ray: &Ray, hit: SurfaceHit, depth: TraceDepth, scene: &Scene, mut split: Option<&mut LightSplit>, mut weight: Vector3D, wavelength: Wavelength) -> Vector3D{
+    let mut color = Vector3D::ZERO;
+    let refl_depth = depth.total();
+
+    let fog = &scene.fog;
+    let (index, dist, inside) = match hit {
+        Some(hit) => hit,
+        None => {
+            let mut background = environment(ray.direction, refl_depth, scene, split.as_deref_mut(), weight);
+            if refl_depth == 0 {
+                background.v3d_add(fog_shafts(ray, f64::INFINITY, scene, split, weight));
+            }
+            return background;
+        }
+    };
+    let prim = scene.primitive_list[index].as_ref();
+
+    // Everything lit at the hit is dimmed by the fog in front of it, and
+    // the fog's own color makes up the difference
+    let seen_weight = weight;
+    if !fog.is_clear() {
+        weight.v3d_mul_scalar(fog.transmittance(dist));
+        if let Some(s) = split.as_deref_mut() {
+            s.add(s.other(), fog.glow(dist), seen_weight);
+        }
+    }
+
+    let mut pi = ray.direction;
+    pi.v3d_mul_scalar(dist);
+    pi.v3d_add(ray.origin);
+
+    let m = scene.material(prim.material_ref());
+    let prim_color: Vector3D = m.color;
+    let mut n: Vector3D = prim.normal(pi, ray.time);
+
+    // Shade the side the ray came from. A ray that starts inside the
+    // primitive (e.g. one that went into a glass sphere) hits the far side
+    // from within, and a quad can be seen from behind. Either way the normal
+    // has to point back at the ray, like it does for other hits.
+    if n.v3d_dot_mul(ray.direction) > 0.0 {
+        n.v3d_mul_scalar(-1.0);
+    }
+
+    // Secondary rays leave from the actual surface, not the bumped one
+    let face = n;
+    let mut wo = ray.direction;
+    wo.v3d_mul_scalar(-1.0);
+    let n = bump_normal(prim, &m, pi, n, wo, ray.time);
+
+    if m.diffusive > 0.0 {
+        let mut ambient = scene.ambient;
+        if let Some(map) = &scene.environment_map {
+            ambient.v3d_add(map.diffuse(n));
+        }
+        ambient.v3d_mul_v3d(prim_color);
+        ambient.v3d_mul_scalar(m.diffusive);
+        color.v3d_add(ambient);
+        if let Some(s) = split.as_deref_mut() {
+            s.add(s.other(), ambient, weight);
+        }
+    }
+
+    // Every light adds its own diffuse and specular contribution, so several
+    // (colored) lights simply sum up.
+    for (i, light_iter) in scene.light_list.iter().enumerate() {
+
+        let mut l: Vector3D = light_iter.position;
+        l.v3d_sub(pi);
+        let light_dist = l.v3d_length();
+        l.v3d_norm();
+
+        if scene.settings.shadows {
+            scene.counters.shadow();
+            if let Some((_, d)) = closest_hit(&secondary_ray(pi, face, l, ray.time, scene), RayKind::Shadow, scene) {
+                if d < light_dist {
+                    continue;
+                }
+            }
+        }
+
+        // Light on the side the ray came from, and light from behind
+        // coming through. The shadow ray above already left from the side
+        // the light is on, so whatever is behind the surface can block it.
+        if m.diffusive > 0.0 {
+            let cos = l.v3d_dot_mul(n);
+            for dot in [(cos + m.wrap) / (1.0 + m.wrap), -cos * m.translucency] {
+                if dot > 0.0 {
+                    let color_add = diffuse(dot, light_iter.radiance_at(pi), prim_color, &m, scene);
+                    color.v3d_add(color_add);
+                    if let Some(s) = split.as_deref_mut() {
+                        s.add(s.group_of[i], color_add, weight);
+                    }
+                }
+            }
+        }
+
+        if m.specular > 0.0 {
+            // `l` points away from the surface, so its mirror image points
+            // into it and lines up with the view ray for a highlight
+            let r: Vector3D = Vector3D::reflect(l, n);
+
+            let mut dot: f64 = ray.direction.v3d_dot_mul(r);
+            if dot > 0.0 {
+                dot *= dot;
+                dot *= dot;
+                dot *= dot;
+                let spec = dot * m.specular;
+
+                let mut color_add = light_iter.radiance_at(pi);
+                color_add.v3d_mul_scalar(spec);
+                color.v3d_add(color_add);
+                if let Some(s) = split.as_deref_mut() {
+                    s.add(s.group_of[i], color_add, weight);
+                }
+            }
+        }
+    }
+
+    // Light focused onto the surface by mirrors and glass
+    if let Some(map) = &scene.caustics {
+        if m.diffusive > 0.0 {
+            let mut c = caustic_light(map, pi, n);
+            c.v3d_mul_v3d(prim_color);
+            c.v3d_mul_scalar(m.diffusive);
+            color.v3d_add(c);
+            if let Some(s) = split.as_deref_mut() {
+                s.add(s.other(), c, weight);
+            }
+        }
+    }
+
+    // The reflection doesn't depend on the lights, so it's added once
+    // rather than once per light.
+    let refl = m.reflective;
+    let reflected = TraceDepth {
+        reflection: depth.reflection + 1,
+        ..depth
+    };
+    let reflection = match refl > 0.0 {
+        true => spawn(depth, depth.reflection, reflected, m.max_reflection_depth, scene),
+        false => Spawn::Nothing,
+    };
+    if !matches!(reflection, Spawn::Nothing) {
+        let r: Vector3D = Vector3D::reflect(ray.direction, n);
+
+        let mut w = weight;
+        w.v3d_mul_scalar(refl);
+        w.v3d_mul_v3d(prim_color);
+        let mut rcol: Vector3D = match reflection {
+            Spawn::Trace(next) => {
+                scene.counters.secondary(next.total());
+                let tempr = secondary_ray(pi, face, r, ray.time, scene);
+                trace_split(&tempr, next, scene, split.as_deref_mut(), w, wavelength)
+            }
+            _ => environment(r, refl_depth + 1, scene, split.as_deref_mut(), w),
+        };
+
+        rcol.v3d_mul_scalar(refl);
+        rcol.v3d_mul_v3d(prim_color);
+        color.v3d_add(rcol);
+    }
+
+    // Light coming through the surface. `n` faces the ray, so going in or
+    // out only changes the ratio of the indices. Light that can't get out
+    // (total internal reflection) is reflected back in.
+    let refr = m.refractive;
+    let refracted = TraceDepth {
+        refraction: depth.refraction + 1,
+        ..depth
+    };
+    let refraction = match refr > 0.0 {
+        true => spawn(depth, depth.refraction, refracted, m.max_refraction_depth, scene),
+        false => Spawn::Nothing,
+    };
+    if !matches!(refraction, Spawn::Nothing) {
+        // The media on the other side: the ones around the primitive when
+        // coming out of it, and the primitive as well when going in. The
+        // index of refraction there is that of the innermost.
+        let mut beyond = depth.media.to_vec();
+        match inside {
+            true => {
+                if let Some(at) = beyond.iter().rposition(|&i| i == index) {
+                    beyond.remove(at);
+                }
+            }
+            false if prim.is_closed() => beyond.push(index),
+            false => {}
+        }
+
+        let (passes, count) = refraction_passes(&m, wavelength);
+        for &(ior, next, keep) in &passes[..count] {
+            let other = medium_ior(if inside { &beyond } else { depth.media }, next, scene);
+            let eta = if inside { ior / other } else { other / ior };
+            // Light that can't get out stays where it was
+            let (t, media) = match Vector3D::refract(ray.direction, n, eta) {
+                Some(t) => (t, &beyond[..]),
+                None => (Vector3D::reflect(ray.direction, n), depth.media),
+            };
+
+            let mut w = weight;
+            w.v3d_mul_scalar(refr);
+            w.v3d_mul_v3d(prim_color);
+            if let Some((c, scale)) = keep {
+                w = only_channel(w, c, scale);
+            }
+            let mut tcol = match refraction {
+                Spawn::Trace(deeper) => {
+                    scene.counters.secondary(deeper.total());
+                    let deeper = TraceDepth { media, ..deeper };
+                    trace_split(&secondary_ray(pi, face, t, ray.time, scene), deeper, scene, split.as_deref_mut(), w, next)
+                }
+                _ => environment(t, refl_depth + 1, scene, split.as_deref_mut(), w),
+            };
+
+            tcol.v3d_mul_scalar(refr);
+            tcol.v3d_mul_v3d(prim_color);
+            if let Some((c, scale)) = keep {
+                tcol = only_channel(tcol, c, scale);
+            }
+            color.v3d_add(tcol);
+        }
+    }
+
+    let mut color = fog.apply(color, dist);
+    if refl_depth == 0 {
+        color.v3d_add(fog_shafts(ray, dist, scene, split, seen_weight));
+    }
+
+    let ret_vector: Vector3D = Vector3D {
+        x: color.x,
+        y: color.y,
+        z: color.z,
+    };
+
+    ret_vector
+}
+
+// What a ray `refl_depth` deep going off in `direction` sees when it
+// doesn't hit anything, through the fog, added to `split` as well
+fn environment(direction: Vector3D, refl_depth: u32, scene: &Scene, split: Option<&mut LightSplit>, weight: Vector3D) -> Vector3D {
+    let background = scene.fog.apply(scene.background(direction, refl_depth), f64::INFINITY);
+    if let Some(s) = split {
+        s.add(s.other(), background, weight);
+    }
+    background
+}
+
+// Light the fog scatters towards the camera along the first `length` of a
+// camera ray, split up by light group like the rest
+fn fog_shafts(ray: &Ray, length: f64, scene: &Scene, mut split: Option<&mut LightSplit>, weight: Vector3D) -> Vector3D {
+    let mut total = Vector3D::ZERO;
+    scene.fog.in_scattered(ray, length, scene, |light, c| {
+        total.v3d_add(c);
+        if let Some(s) = split.as_deref_mut() {
+            s.add(s.group_of[light], c, weight);
+        }
+    });
+    total
+}
+
+// Index of refraction for light carrying `wavelength` inside the innermost
+// of `media` (primitive indices, see `TraceDepth`), 1 outside all of them
+fn medium_ior(media: &[usize], wavelength: Wavelength, scene: &Scene) -> f64 {
+    let Some(&i) = media.last() else {
+        return 1.0;
+    };
+
+    let m = material_of(scene.primitive_list[i].as_ref(), scene);
+    match wavelength {
+        Wavelength::All => m.ior,
+        Wavelength::Picked(c) | Wavelength::Only(c) => m.channel_ior(c),
+    }
+}
+
+// A refracted ray to trace: its index of refraction, what it carries on
+// with, and which channel of its light to keep and how much of it
+type RefractionPass = (f64, Wavelength, Option<(usize, f64)>);
+
+// Refracted rays to trace through `m` for a ray carrying `wavelength`, and
+// how many of the three there are. Glass without dispersion only needs the
+// one ray.
+fn refraction_passes(m: &Material, wavelength: Wavelength) -> ([RefractionPass; 3], usize) {
+    let one = |ior, next, keep| ([(ior, next, keep); 3], 1);
+
+    if m.dispersion == 0.0 {
+        return one(m.ior, wavelength, None);
+    }
+    match wavelength {
+        Wavelength::All => ([0, 1, 2].map(|c| (m.channel_ior(c), Wavelength::Only(c), Some((c, 1.0)))), 3),
+        // One channel stands in for all three
+        Wavelength::Picked(c) => one(m.channel_ior(c), Wavelength::Only(c), Some((c, 3.0))),
+        Wavelength::Only(c) => one(m.channel_ior(c), wavelength, None),
+    }
+}
+
+// Channel `c` of `v` times `scale`, the others 0
+fn only_channel(v: Vector3D, c: usize, scale: f64) -> Vector3D {
+    let mut out = Vector3D::ZERO;
+    out[c] = v[c] * scale;
+    out
+}
+
+// ----------------------------------------------------------------------------
+//
+// Path tracing
+//
+// `trace` above is a classic Whitted tracer. `pathtrace` follows random
+// bounces instead. At every bounce it picks one light and samples it directly
+// with a shadow ray (next-event estimation). Emissive spheres can also be
+// found by the BSDF-sampled bounce, so both estimates are weighted with the
+// power heuristic (multiple importance sampling). That keeps small lights and
+// sharp glossy lobes from blowing up the variance.
+//
+// With `RenderSettings::light_sampling` off the lights are only found by
+// chance, which is much noisier and never finds point lights; it's there to
+// compare against.
+//
+// Point lights keep the convention of `trace`: no distance falloff, and
+// `radiance()` is the light arriving at a surface facing the light. That way
+// both tracers agree on the demo scene.
+
+// Phong exponent of the glossy lobe. Same as the `dot^8` highlight in `trace`.
+const PHONG_EXPONENT: f64 = 8.0;
+
+// Half-angle (radians) of the reflection cone of a material with roughness 1
+const MAX_ROUGHNESS_ANGLE: f64 = std::f64::consts::FRAC_PI_2;
+
+// Closest primitive a `kind` ray can see along `ray`: its index, the
+// distance to it and whether the ray is inside it
+type SurfaceHit = Option<(usize, f64, bool)>;
+
+fn surface_hit(ray: &Ray, kind: RayKind, scene: &Scene) -> SurfaceHit {
+    first_hit(ray, kind, scene, |p| match intersect_solid(p, ray, scene) {
+        Some((dist, res)) if !culled(p, ray, res, dist, scene) => Some((dist, res == -1)),
+        _ => None,
+    })
+}
+
+// Closest primitive a `kind` ray can see along `ray`, as its index and the
+// distance to it.
+pub fn closest_hit(ray: &Ray, kind: RayKind, scene: &Scene) -> Option<(usize, f64)> {
+    surface_hit(ray, kind, scene).map(|(i, dist, _)| (i, dist))
+}
+
+// Material of `p` as it was given, for looking at the parts that decide
+// where the surface is. Cheaper than `Scene::material`.
+fn material_of<'a>(p: &'a dyn Primitive, scene: &'a Scene) -> &'a Material {
+    match p.material_ref() {
+        MaterialRef::Named(name) => &scene.materials[name],
+        MaterialRef::Id(id) => &scene.registry[id.0],
+        MaterialRef::Inline(m) => m,
+    }
+}
+
+// `p.intersect` for `ray`, as the distance and the result, but skipping hits
+// in the holes of the material's cutout: from there the ray carries on in
+// the same direction.
+fn intersect_solid(p: &dyn Primitive, ray: &Ray, scene: &Scene) -> Option<(f64, i32)> {
+    let mut t_min = scene.settings.ray_epsilon;
+
+    for _ in 0..=MAX_PUNCH_THROUGHS {
+        let mut dist = 0.0;
+        let res = p.intersect(*ray, t_min, &mut dist);
+        if res == 0 {
+            return None;
+        }
+
+        let hole = material_of(p, scene).cutout.is_some_and(|c| {
+            let mut pi = ray.direction;
+            pi.v3d_mul_scalar(dist);
+            pi.v3d_add(ray.origin);
+            p.uv(pi, ray.time).is_some_and(|(u, v)| c.is_hole(u, v))
+        });
+        if !hole {
+            return Some((dist, res));
+        }
+        t_min = dist + scene.settings.ray_epsilon;
+    }
+    None
+}
+
+// Whether `cull_back_faces` or a front only material skips the hit `dist`
+// along `ray` (`res` as returned by `p.intersect`): the ray came from
+// outside but sees the back of the surface
+fn culled(p: &dyn Primitive, ray: &Ray, res: i32, dist: f64, scene: &Scene) -> bool {
+    if res != 1 {
+        return false;
+    }
+    if !scene.settings.cull_back_faces && material_of(p, scene).sidedness == Sidedness::TwoSided {
+        return false;
+    }
+
+    let mut pi = ray.direction;
+    pi.v3d_mul_scalar(dist);
+    pi.v3d_add(ray.origin);
+    p.normal(pi, ray.time).v3d_dot_mul(ray.direction) > 0.0
+}
+
+// Closest primitive hit by `ray`, as its index, the distance to it and what
+// else `test` found out about the hit. `test` intersects `ray` with one
+// primitive; primitives hidden from `kind` rays aren't tested. Goes through the BVH if it's up to date, and through every
+// primitive otherwise; either way the same primitive is found.
+fn first_hit<H>(ray: &Ray, kind: RayKind, scene: &Scene, mut test: impl FnMut(&dyn Primitive) -> Option<(f64, H)>) -> Option<(usize, f64, H)> {
+    let primitives = &scene.primitive_list;
+
+    // Primitives the ray can't reach, if it starts out in front of the
+    // camera and heads away from it like camera rays do
+    let behind = scene
+        .behind_camera
+        .as_ref()
+        .filter(|b| b.behind.len() == primitives.len() && b.ahead_of(ray))
+        .map(|b| b.behind.as_slice());
+    let skipped = |i: usize| behind.is_some_and(|b| b[i]) || !scene.visibility[i].sees(kind);
+
+    if let Some(bvh) = scene.bvh.as_ref().filter(|b| b.len() == primitives.len()) {
+        let (hit, visits) = bvh.closest(ray, |i| {
+            if skipped(i) {
+                return None;
+            }
+            scene.counters.tests(1);
+            test(primitives[i].as_ref())
+        });
+        scene.counters.nodes(visits);
+        return hit;
+    }
+
+    let mut hit: Option<(usize, f64, H)> = None;
+    let mut tests = 0;
+    for (i, p) in primitives.iter().enumerate() {
+        if skipped(i) {
+            continue;
+        }
+        tests += 1;
+        if let Some((dist, found)) = test(p.as_ref()) {
+            if hit.as_ref().is_none_or(|h| dist < h.1) {
+                hit = Some((i, dist, found));
+            }
+        }
+    }
+
+    scene.counters.tests(tests);
+    hit
+}
+
+// Primitives whose bounding sphere is entirely behind the plane the camera
+// is in while the shutter is open. A ray that starts on or in front of
+// that plane and doesn't head backwards, like every camera ray of a
+// perspective camera, can't hit them, so they aren't even tested. Other
+// rays, like reflections, still see them.
+pub(crate) struct BehindCamera {
+    // The plane: points p with p . normal = offset, the camera looking
+    // towards +normal
+    normal: Vector3D,
+    offset: f64,
+    behind: Vec<bool>,
+}
+
+impl BehindCamera {
+    // `None` for projections that see behind the camera. `slack` is how far
+    // a ray may start behind the plane, and how far behind it a primitive
+    // has to be on top of that.
+    fn new(camera: &Camera, primitives: &[Box<dyn Primitive>], slack: f64) -> Option<Self> {
+        if camera.projection != Projection::Perspective {
+            return None;
+        }
+
+        let normal = camera.forward();
+        // The camera may move backwards while the shutter is open
+        let moved = camera.offset(camera.shutter_time).v3d_dot_mul(normal).min(0.0);
+        let offset = camera.position.v3d_dot_mul(normal) + moved - slack;
+
+        let behind = primitives
+            .iter()
+            .map(|p| {
+                let (min, max) = p.bounds();
+                let mut center = min;
+                center.v3d_add(max);
+                center.v3d_mul_scalar(0.5);
+                let mut half = max;
+                half.v3d_sub(center);
+                center.v3d_dot_mul(normal) + half.v3d_length() < offset - slack
+            })
+            .collect();
+        Some(Self { normal, offset, behind })
+    }
+
+    fn ahead_of(&self, ray: &Ray) -> bool {
+        ray.origin.v3d_dot_mul(self.normal) >= self.offset && ray.direction.v3d_dot_mul(self.normal) >= 0.0
+    }
+
+    fn count(&self) -> usize {
+        self.behind.iter().filter(|&&b| b).count()
+    }
+}
+
+// Ray starting slightly off the surface point `pos` with geometric normal
+// `normal` in direction `dir`, at `time`. Every ray spawned at a surface
+// starts like this.
+fn secondary_ray(pos: Vector3D, normal: Vector3D, dir: Vector3D, time: f64, scene: &Scene) -> Ray {
+    Ray {
+        origin: Ray::offset_ray_origin(pos, normal, dir, scene.settings.ray_epsilon),
+        direction: dir,
+        time,
+    }
+}
+
+fn is_emissive(m: &Material) -> bool {
+    m.emission.x > 0.0 || m.emission.y > 0.0 || m.emission.z > 0.0
+}
+
+// Cosine of the half-angle of the cone that sphere `s` covers as seen from
+// `pos`, or `None` if `pos` is inside the sphere.
+fn sphere_cone_cos(center: Vector3D, radius: f64, pos: Vector3D) -> Option<f64> {
+    let mut d = center;
+    d.v3d_sub(pos);
+    let dist2 = d.v3d_dot_mul(d);
+    let r2 = radius * radius;
+
+    if dist2 <= r2 {
+        return None;
+    }
+
+    Some((1.0 - r2 / dist2).sqrt())
+}
+
+// Solid angle pdf of uniformly sampling a cone
+fn cone_pdf(cos_max: f64) -> f64 {
+    1.0 / (2.0 * std::f64::consts::PI * (1.0 - cos_max))
+}
+
+fn power_heuristic(pdf_a: f64, pdf_b: f64) -> f64 {
+    let a = pdf_a * pdf_a;
+    let b = pdf_b * pdf_b;
+
+    if a + b == 0.0 {
+        return 0.0;
+    }
+
+    a / (a + b)
+}
+
+// Chance of picking the diffuse, glossy and mirror lobes of a material
+fn lobe_weights(m: &Material) -> (f64, f64, f64) {
+    let total = m.diffusive + m.specular + m.reflective;
+
+    if total <= 0.0 {
+        return (0.0, 0.0, 0.0);
+    }
+
+    (m.diffusive / total, m.specular / total, m.reflective / total)
+}
+
+// Diffuse + glossy part of the BSDF for light coming from `wi` and leaving
+// towards `wo` (both point away from the surface). Also returns the pdf of
+// sampling `wi` from those two lobes. The mirror lobe is a delta and is
+// handled by the caller.
+fn eval_bsdf(m: &Material, n: Vector3D, wo: Vector3D, wi: Vector3D) -> (Vector3D, f64) {
+    let cos_i = n.v3d_dot_mul(wi);
+
+    if cos_i <= 0.0 {
+        return (Vector3D::ZERO, 0.0);
+    }
+
+    let (pd, ps, _) = lobe_weights(m);
+
+    let mut f = m.color;
+    f.v3d_mul_scalar(m.diffusive * std::f64::consts::FRAC_1_PI);
+    let mut pdf = pd * cos_i * std::f64::consts::FRAC_1_PI;
+
+    if m.specular > 0.0 {
+        let mut incident = wo;
+        incident.v3d_mul_scalar(-1.0);
+        let cos_a = Vector3D::reflect(incident, n).v3d_dot_mul(wi);
+
+        if cos_a > 0.0 {
+            let lobe = cos_a.powf(PHONG_EXPONENT) / (2.0 * std::f64::consts::PI);
+            let spec = m.specular * (PHONG_EXPONENT + 2.0) * lobe;
+
+            f.v3d_add(Vector3D::new(spec, spec, spec));
+            pdf += ps * (PHONG_EXPONENT + 1.0) * lobe;
+        }
+    }
+
+    (f, pdf)
+}
+
+// Next-event estimation. Picks one light (point light or emissive primitive),
+// samples it and returns its weighted contribution at `pos`, where `ray` hit.
+// `n` is the shading normal there and `face` the geometric one.
+#[allow(clippy::too_many_arguments)]
+fn sample_light(
+    ray: &Ray,
+    pos: Vector3D,
+    n: Vector3D,
+    face: Vector3D,
+    m: &Material,
+    emitters: &[usize],
+    scene: &Scene,
+    rng: &mut Rng,
+) -> Vector3D {
+    let mut result = Vector3D::ZERO;
+    let time = ray.time;
+    let mut wo = ray.direction;
+    wo.v3d_mul_scalar(-1.0);
+    let light_count = light_count(emitters, scene);
+
+    if light_count == 0 {
+        return result;
+    }
+
+    let pick = ((rng.next_f64() * light_count as f64) as usize).min(light_count - 1);
+    let pick_pdf = 1.0 / light_count as f64;
+
+    if pick < scene.light_list.len() {
+        // Point lights can't be hit by a bounce, so no MIS here
+        let light = &scene.light_list[pick];
+
+        let mut wi = light.position;
+        wi.v3d_sub(pos);
+        let dist = wi.v3d_length();
+        wi.v3d_norm();
+
+        let (f, _) = eval_bsdf(m, n, wo, wi);
+        let cos_i = n.v3d_dot_mul(wi);
+        if cos_i <= 0.0 {
+            return result;
+        }
+
+        scene.counters.shadow();
+        if let Some((_, d)) = closest_hit(&secondary_ray(pos, face, wi, time, scene), RayKind::Shadow, scene) {
+            if d < dist {
+                return result;
+            }
+        }
+
+        result = light.radiance_at(pos);
+        result.v3d_mul_v3d(f);
+        result.v3d_mul_scalar(std::f64::consts::PI * cos_i / pick_pdf);
+    } else if pick < scene.light_list.len() + emitters.len() {
+        let idx = emitters[pick - scene.light_list.len()];
+        let emitter = &scene.primitive_list[idx];
+
+        let (wi, pdf) = match emitter.sample_direction(pos, time, rng) {
+            Some(sample) => sample,
+            None => return result,
+        };
+
+        let cos_i = n.v3d_dot_mul(wi);
+        if cos_i <= 0.0 {
+            return result;
+        }
+
+        // The sampled direction must reach the light unblocked
+        scene.counters.shadow();
+        match closest_hit(&secondary_ray(pos, face, wi, time, scene), RayKind::Shadow, scene) {
+            Some((hit, _)) if hit == idx => {}
+            _ => return result,
+        }
+
+        let light_pdf = pick_pdf * pdf;
+        let (f, bsdf_pdf) = eval_bsdf(m, n, wo, wi);
+        let weight = power_heuristic(light_pdf, bsdf_pdf);
+
+        result = scene.material(emitter.material_ref()).emission;
+        result.v3d_mul_v3d(f);
+        result.v3d_mul_scalar(cos_i * weight / light_pdf);
+    } else if let Some(map) = &scene.environment_map {
+        let (wi, pdf) = match map.sample(rng) {
+            Some(sample) => sample,
+            None => return result,
+        };
+
+        let cos_i = n.v3d_dot_mul(wi);
+        if cos_i <= 0.0 {
+            return result;
+        }
+
+        // Nothing at all may be in the way
+        scene.counters.shadow();
+        if closest_hit(&secondary_ray(pos, face, wi, time, scene), RayKind::Shadow, scene).is_some() {
+            return result;
+        }
+
+        let light_pdf = pick_pdf * pdf;
+        let (f, bsdf_pdf) = eval_bsdf(m, n, wo, wi);
+        let weight = power_heuristic(light_pdf, bsdf_pdf);
+
+        // Fog hides the map completely, like it does from bounces that miss
+        result = map.radiance(wi);
+        result.v3d_mul_v3d(f);
+        result.v3d_mul_scalar(cos_i * weight * scene.fog.transmittance(f64::INFINITY) / light_pdf);
+    }
+
+    result
+}
+
+// How many lights `sample_light` picks from: the point lights, the
+// emissive primitives `emitters` and the environment map
+fn light_count(emitters: &[usize], scene: &Scene) -> usize {
+    scene.light_list.len() + emitters.len() + scene.environment_map.is_some() as usize
+}
+
+// Follow one random path through the scene and return the light it carries
+// back along `ray`. `emitters` are the indices of emissive primitives.
+pub fn pathtrace(ray: &Ray, emitters: &[usize], scene: &Scene, rng: &mut Rng) -> Vector3D {
+    pathtrace_from(ray, closest_hit(ray, RayKind::Camera, scene), emitters, scene, rng)
+}
+
+// `pathtrace` for a camera ray that's already known to hit `first`
+fn pathtrace_from(ray: &Ray, first: Option<(usize, f64)>, emitters: &[usize], scene: &Scene, rng: &mut Rng) -> Vector3D {
+    let mut color = Vector3D::ZERO;
+    // Light found after the first bounce, when it's to be clamped
+    let mut indirect = Vector3D::ZERO;
+    let clamp = scene.settings.clamp;
+    let mut throughput = Vector3D::ONE;
+    let mut ray = *ray;
+    let light_count = light_count(emitters, scene);
+
+    // Pdf of the bounce that spawned `ray`. `None` for camera and mirror rays,
+    // which light sampling can't produce.
+    let mut last_pdf: Option<f64> = None;
+    let mut last_pos = ray.origin;
+
+    for depth in 0..=scene.settings.max_depth {
+        if depth > 0 {
+            scene.counters.secondary(depth);
+        }
+
+        let hit = match depth {
+            0 => first,
+            _ => closest_hit(&ray, RayKind::at_depth(depth), scene),
+        };
+
+        // Fog on the way: its glow and the light it scatters towards the
+        // camera, then dim whatever comes from behind it
+        let fog = &scene.fog;
+        if !fog.is_clear() {
+            let length = hit.map_or(f64::INFINITY, |(_, dist)| dist);
+            let mut glow = fog.glow(length);
+            if depth == 0 {
+                fog.in_scattered(&ray, length, scene, |_, c| glow.v3d_add(c));
+            }
+            glow.v3d_mul_v3d(throughput);
+            gather(&mut color, &mut indirect, depth, clamp, glow);
+            throughput.v3d_mul_scalar(fog.transmittance(length));
+        }
+
+        let (idx, dist) = match hit {
+            Some(hit) => hit,
+            None => {
+                let mut sky = scene.background(ray.direction, depth);
+
+                // Light sampling could have picked the map there too
+                if let (Some(map), Some(bsdf_pdf)) = (&scene.environment_map, last_pdf.filter(|_| scene.settings.light_sampling)) {
+                    let light_pdf = map.pdf(ray.direction) / light_count as f64;
+                    sky.v3d_mul_scalar(power_heuristic(bsdf_pdf, light_pdf));
+                }
+                sky.v3d_mul_v3d(throughput);
+                gather(&mut color, &mut indirect, depth, clamp, sky);
+                break;
+            }
+        };
+
+        let prim = &scene.primitive_list[idx];
+        let m = &scene.material(prim.material_ref());
+
+        let mut pi = ray.direction;
+        pi.v3d_mul_scalar(dist);
+        pi.v3d_add(ray.origin);
+
+        if is_emissive(m) {
+            let mut weight = 1.0;
+
+            if let Some(bsdf_pdf) = last_pdf.filter(|_| scene.settings.light_sampling) {
+                let light_pdf = prim.direction_pdf(last_pos, pi, ray.time) / light_count as f64;
+                weight = power_heuristic(bsdf_pdf, light_pdf);
+            }
+
+            let mut emitted = m.emission;
+            emitted.v3d_mul_v3d(throughput);
+            emitted.v3d_mul_scalar(weight);
+            gather(&mut color, &mut indirect, depth, clamp, emitted);
+        }
+
+        if depth == scene.settings.max_depth {
+            break;
+        }
+
+        let mut wo = ray.direction;
+        wo.v3d_mul_scalar(-1.0);
+
+        // Shade the side the ray came from
+        let mut n = prim.normal(pi, ray.time);
+        if n.v3d_dot_mul(wo) < 0.0 {
+            n.v3d_mul_scalar(-1.0);
+        }
+        let face = n;
+        let n = bump_normal(prim.as_ref(), m, pi, n, wo, ray.time);
+
+        if scene.settings.light_sampling {
+            let mut direct = sample_light(&ray, pi, n, face, m, emitters, scene, rng);
+            direct.v3d_mul_v3d(throughput);
+            gather(&mut color, &mut indirect, depth, clamp, direct);
+        }
+
+        // Pick a lobe and bounce
+        let (pd, ps, pr) = lobe_weights(m);
+        if pd + ps + pr <= 0.0 {
+            break;
+        }
+
+        let u = rng.next_f64();
+        let wi;
+
+        if u < pr {
+            let mirror = Vector3D::reflect(ray.direction, n);
+
+            // Rough metals reflect into a cone around the mirror direction.
+            // Directions that end up below the surface are absorbed.
+            wi = if m.roughness > 0.0 {
+                sample_cone(mirror, m.roughness.min(1.0) * MAX_ROUGHNESS_ANGLE, rng)
+            } else {
+                mirror
+            };
+
+            if n.v3d_dot_mul(wi) <= 0.0 {
+                break;
+            }
+
+            let mut refl = m.color;
+            refl.v3d_mul_scalar(m.reflective / pr);
+            throughput.v3d_mul_v3d(refl);
+            last_pdf = None;
+        } else {
+            wi = if u < pr + pd {
+                cosine_weighted_hemisphere(n, rng)
+            } else {
+                let phi = 2.0 * std::f64::consts::PI * rng.next_f64();
+                let cos_a = rng.next_f64().powf(1.0 / (PHONG_EXPONENT + 1.0));
+                dir_around(Vector3D::reflect(ray.direction, n), cos_a, phi)
+            };
+
+            let (f, pdf) = eval_bsdf(m, n, wo, wi);
+            if pdf <= 0.0 {
+                break;
+            }
+
+            let mut weight = f;
+            weight.v3d_mul_scalar(n.v3d_dot_mul(wi) / pdf);
+            throughput.v3d_mul_v3d(weight);
+            last_pdf = Some(pdf);
+        }
+
+        last_pos = pi;
+        ray = secondary_ray(pi, face, wi, ray.time, scene);
+    }
+
+    if let Some(max) = clamp {
+        color.v3d_add(clamp_radiance(indirect, max));
+    }
+    color
+}
+
+// Add light `c` that a path found `depth` bounces from the camera to
+// `color`. With a clamp, the light found after the first bounce goes to
+// `indirect` instead, to be clamped once the path is done.
+fn gather(color: &mut Vector3D, indirect: &mut Vector3D, depth: u32, clamp: Option<f64>, c: Vector3D) {
+    match clamp {
+        Some(_) if depth > 0 => indirect.v3d_add(c),
+        _ => color.v3d_add(c),
+    }
+}
+
+// `c` scaled down so no channel is above `max`, keeping its hue
+fn clamp_radiance(c: Vector3D, max: f64) -> Vector3D {
+    let brightest = c.max_component();
+    if brightest <= max {
+        return c;
+    }
+
+    let mut clamped = c;
+    clamped.v3d_mul_scalar(max / brightest);
+    clamped
+}
+
+// ----------------------------------------------------------------------------
+//
+// Caustics
+//
+// Glass and mirrors focus light into bright spots on the surfaces behind
+// them, which `trace` can't find by itself: it only ever looks straight at
+// the lights. So before rendering, photons are shot from every light at
+// every mirror and glass primitive and followed through their reflections
+// and refractions. Where they land on something diffuse they're kept in a
+// kd-tree, and `trace` adds up the photons around each hit point (a density
+// estimate) to get the focused light there.
+
+// Photons gathered per shading point, and how far away they may be
+const CAUSTIC_GATHER: usize = 50;
+const CAUSTIC_RADIUS: f64 = 0.2;
+
+// A photon that landed on a diffuse surface
+#[derive(Clone, Copy)]
+pub struct Photon {
+    // Direction it was travelling in
+    pub direction: Vector3D,
+    pub power: Vector3D,
+}
+
+// Shoot `settings.photons` photons and keep the ones that reach a diffuse
+// surface by way of a mirror or glass. `None` if the scene has nothing
+// refractive, since then there's nothing worth the pass.
+pub fn build_caustics(scene: &Scene) -> Option<KdTree<Photon>> {
+    let materials: Vec<Material> = scene.primitive_list.iter().map(|p| scene.material(p.material_ref())).collect();
+
+    if !materials.iter().any(|m| m.refractive > 0.0) {
+        debug!("No refractive materials, skipping the caustics pass");
+        return None;
+    }
+
+    let targets: Vec<usize> = (0..materials.len())
+        .filter(|&i| materials[i].reflective > 0.0 || materials[i].refractive > 0.0)
+        .collect();
+    let count = (scene.settings.photons as usize / (scene.light_list.len() * targets.len()).max(1)).max(1);
+
+    let mut rng = Rng::new(!scene.settings.seed);
+    let mut photons = Vec::new();
+
+    for light in &scene.light_list {
+        for &target in &targets {
+            // Aim at the sphere around the target's bounds, or everywhere if
+            // the light is inside it
+            let (min, max) = scene.primitive_list[target].bounds();
+            let mut center = min;
+            center.v3d_add(max);
+            center.v3d_mul_scalar(0.5);
+            let mut extent = max;
+            extent.v3d_sub(min);
+
+            let (axis, cos_max) = match sphere_cone_cos(center, extent.v3d_length() * 0.5, light.position) {
+                Some(cos_max) => {
+                    let mut axis = center;
+                    axis.v3d_sub(light.position);
+                    axis.v3d_norm();
+                    (axis, cos_max)
+                }
+                None => (Vector3D::UNIT_Y, -1.0),
+            };
+            let share = 1.0 / (cone_pdf(cos_max) * count as f64);
+
+            for _ in 0..count {
+                let ray = Ray {
+                    origin: light.position,
+                    direction: sample_cone_cos(axis, cos_max, &mut rng),
+                    time: 0.0,
+                };
+                shoot_photon(ray, light, target, share, scene, &mut rng, &mut photons);
+            }
+        }
+    }
+
+    debug!("{} caustic photons stored", photons.len());
+    Some(KdTree::new(photons))
+}
+
+// Follow one photon sent from `light` along `ray`, which covers `share` of
+// the light's sphere of directions. It only counts if the first thing it hits
+// is the primitive `target` it was aimed at, otherwise it belongs to another
+// target (or to the direct light `trace` already handles).
+fn shoot_photon(
+    mut ray: Ray,
+    light: &Light,
+    target: usize,
+    share: f64,
+    scene: &Scene,
+    rng: &mut Rng,
+    photons: &mut Vec<(Vector3D, Photon)>,
+) {
+    let mut power = Vector3D::ZERO;
+
+    for bounce in 0..=scene.settings.max_depth {
+        let (i, dist) = match closest_hit(&ray, RayKind::Reflection, scene) {
+            Some(hit) => hit,
+            None => return,
+        };
+
+        let mut pos = ray.direction;
+        pos.v3d_mul_scalar(dist);
+        pos.v3d_add(ray.origin);
+
+        if bounce == 0 {
+            if i != target {
+                return;
+            }
+
+            // `trace`'s lights don't get dimmer with distance, so the photon
+            // is made as bright as the light is where it first lands
+            power = light.radiance_at(pos);
+            power.v3d_mul_scalar(share * dist * dist);
+        }
+
+        let prim = &scene.primitive_list[i];
+        let m = scene.material(prim.material_ref());
+
+        if bounce > 0 && m.diffusive > 0.0 {
+            photons.push((pos, Photon { direction: ray.direction, power }));
+        }
+
+        // Russian roulette between the mirror and the glass part, and
+        // stopping here
+        let total = m.reflective + m.refractive;
+        let survive = total.min(1.0);
+        if rng.next_f64() >= survive {
+            return;
+        }
+        power.v3d_mul_v3d(m.color);
+        power.v3d_mul_scalar(total / survive);
+
+        let mut n = prim.normal(pos, ray.time);
+        let inside = n.v3d_dot_mul(ray.direction) > 0.0;
+        if inside {
+            n.v3d_mul_scalar(-1.0);
+        }
+
+        let reflected = Vector3D::reflect(ray.direction, n);
+        let dir = match rng.next_f64() * total < m.refractive {
+            true => {
+                let eta = if inside { m.ior } else { 1.0 / m.ior };
+                Vector3D::refract(ray.direction, n, eta).unwrap_or(reflected)
+            }
+            false => reflected,
+        };
+
+        ray = secondary_ray(pos, n, dir, ray.time, scene);
+    }
+}
+
+// Light the caustic photons bring to `pos`, a point with normal `n`. Only
+// photons arriving at the side `n` is on count.
+fn caustic_light(map: &KdTree<Photon>, pos: Vector3D, n: Vector3D) -> Vector3D {
+    let found = map.nearest(pos, CAUSTIC_GATHER, CAUSTIC_RADIUS);
+
+    // Spread over the disc that holds the photons, or the whole gather
+    // radius if there are only a few around
+    let radius = match found.last() {
+        Some(&(d, _, _)) if found.len() == CAUSTIC_GATHER => d,
+        Some(_) => CAUSTIC_RADIUS,
+        None => return Vector3D::ZERO,
+    };
+
+    let mut sum = Vector3D::ZERO;
+    for (_, _, photon) in found {
+        if photon.direction.v3d_dot_mul(n) < 0.0 {
+            sum.v3d_add(photon.power);
+        }
+    }
+
+    sum.v3d_mul_scalar(1.0 / (std::f64::consts::PI * radius * radius));
+    sum
+}
+
+// Color that debug shading gives rays that hit nothing
+const MISS_COLOR: Vector3D = Vector3D::new(1.0, 0.0, 1.0);
+
+// Texture coordinate cells per unit, and how much of a cell the grid lines
+// of `Shading::Uv` take up
+const UV_GRID: f64 = 10.0;
+const UV_LINE: f64 = 0.05;
+
+// Distance from the camera to the far corner of the scene's bounding box,
+// which `Shading::Depth` maps to black
+fn scene_depth(camera: &Camera, scene: &Scene) -> f64 {
+    let (min, max) = match scene.bounds() {
+        Some(bounds) => bounds,
+        None => return 1.0,
+    };
+
+    let mut far: f64 = 0.0;
+    for corner in 0..8 {
+        let mut d = Vector3D::new(
+            if corner & 1 == 0 { min.x } else { max.x },
+            if corner & 2 == 0 { min.y } else { max.y },
+            if corner & 4 == 0 { min.z } else { max.z },
+        );
+        d.v3d_sub(camera.position);
+        far = far.max(d.v3d_length());
+    }
+    far
+}
+
+// Color of `ray`, which hits `hit`, under one of the debug `shading` modes.
+// `far` is the distance shown as black in depth mode.
+fn debug_shade(ray: &Ray, hit: Option<(usize, f64)>, shading: Shading, far: f64, scene: &Scene) -> Vector3D {
+    let (index, dist) = match hit {
+        Some(hit) => hit,
+        None => return MISS_COLOR,
+    };
+
+    let prim = &scene.primitive_list[index];
+    let mut pi = ray.direction;
+    pi.v3d_mul_scalar(dist);
+    pi.v3d_add(ray.origin);
+
+    match shading {
+        Shading::Full => unreachable!("full shading isn't a debug mode"),
+        Shading::Normals => prim.normal(pi, ray.time).map(|c| c * 0.5 + 0.5),
+        Shading::Depth => {
+            let g = (1.0 - dist / far).clamp(0.0, 1.0);
+            Vector3D::new(g, g, g)
+        }
+        Shading::Albedo => scene.material(prim.material_ref()).color,
+        Shading::Uv => match prim.uv(pi, ray.time) {
+            Some((u, v)) => {
+                let line = |t: f64| (t * UV_GRID).fract() < UV_LINE;
+                if line(u) || line(v) {
+                    Vector3D::ZERO
+                } else {
+                    Vector3D::new(u, v, 0.0)
+                }
+            }
+            None => Vector3D::new(0.5, 0.5, 0.5),
+        },
+    }
+}
+
+// What every pixel's samples need to know about the render, worked out once
+// per pass
+struct Sampling {
+    camera: Camera,
+    // Size of the image
+    width: u32,
+    height: u32,
+    emitters: Vec<usize>,
+    samples: u32,
+    min_samples: u32,
+    shading: Shading,
+    far: f64,
+    // Light group of every light, for splitting the light up into the AOVs
+    split_light: bool,
+    group_of: Vec<usize>,
+    // Misses leave the pixel transparent
+    transparent: bool,
+    filter: Filter,
+    filter_radius: f64,
+    // Brightest samples every pixel keeps for rejecting outliers: the ones
+    // to tone down and the one after them. 0 when that's off.
+    keep_brightest: usize,
+    // Camera hits every pixel keeps in the geometry cache: the center's,
+    // then every sample's if where the samples go doesn't depend on the
+    // shading (which can draw from the pixel's random numbers in between)
+    hit_slots: usize,
+}
+
+impl Sampling {
+    fn new(globals: &GlobalSettings) -> Self {
+        let camera = globals.camera;
+        let samples = globals.settings.samples.max(1);
+        let shading = globals.settings.shading;
+        let groups = globals.light_groups();
+
+        Self {
+            camera,
+            width: globals.img.width(),
+            height: globals.img.height(),
+            emitters: emitters(globals),
+            samples,
+            min_samples: match globals.settings.adaptive {
+                true => globals.settings.min_samples.clamp(1, samples),
+                false => samples,
+            },
+            shading,
+            far: if shading == Shading::Depth { scene_depth(&camera, globals) } else { 0.0 },
+            split_light: !globals.light_aovs.is_empty(),
+            group_of: globals.light_list.iter().map(|l| groups.iter().position(|g| *g == l.group).unwrap()).collect(),
+            transparent: globals.camera_background == CameraBackground::Transparent,
+            filter: globals.settings.filter,
+            filter_radius: globals.settings.filter_radius.unwrap_or(globals.settings.filter.default_radius()),
+            keep_brightest: match globals.settings.reject_outliers {
+                Some(percent) => outliers(samples, percent) + 1,
+                None => 0,
+            },
+            hit_slots: match shading != Shading::Full
+                || (globals.settings.mode == RenderMode::Whitted && globals.settings.dispersion == Dispersion::Split)
+            {
+                true if samples > 1 => 1 + samples as usize,
+                _ => 1,
+            },
+        }
+    }
+}
+
+// A pixel partway through its samples
+#[derive(Clone)]
+struct PixelState {
+    pixel: usize,
+    x: u32,
+    y: u32,
+    rng: Rng,
+    // Sum of the samples, each times its filter weight, and of the weights
+    color: Vector3D,
+    weights: f64,
+    // Light of every AOV so far, weighted the same way
+    sums: Vec<Vector3D>,
+    // Weight of the samples whose camera ray didn't leave the pixel
+    // transparent
+    covered: f64,
+    // Running mean and sum of squared deviations of the samples'
+    // brightness (Welford), for the adaptive sampling's stop test
+    mean: f64,
+    m2: f64,
+    taken: u32,
+    // Adaptive sampling decided the pixel needs no more samples
+    converged: bool,
+    // The brightest samples so far with their brightness, brightest first
+    // (see `Sampling::keep_brightest`)
+    brightest: Vec<(f64, Vector3D)>,
+}
+
+impl PixelState {
+    // Start on pixel (x, y) of `tile`, remembering what its center sees for
+    // the post-processing
+    fn new(x: u32, y: u32, sampling: &Sampling, scene: &Scene, tile: &mut Tile) -> Self {
+        let (width, height) = (sampling.width, sampling.height);
+        let pixel = (y * width + x) as usize;
+        let i = pixel - tile.start;
+
+        // What `Scene::pick` would find, but from the geometry
+        // cache if it's there
+        let center = sampling.camera.pixel_ray(x as f64 + 0.5, y as f64 + 0.5, width, height, 0.0);
+        let hit = center.and_then(|ray| Some((ray, cached_hit(&ray, tile.hits(i, sampling).first_mut(), scene)?)));
+        match hit {
+            Some((ray, (index, distance, _))) => {
+                let prim = &scene.primitive_list[index];
+                let mut point = ray.direction;
+                point.v3d_mul_scalar(distance);
+                point.v3d_add(ray.origin);
+                tile.depth[i] = distance;
+                tile.normal[i] = prim.normal(point, ray.time);
+                tile.albedo[i] = scene.material(prim.material_ref()).color;
+            }
+            None => {
+                tile.depth[i] = f64::INFINITY;
+                tile.normal[i] = Vector3D::ZERO;
+                tile.albedo[i] = Vector3D::ZERO;
+            }
+        }
+
+        Self {
+            pixel,
+            x,
+            y,
+            // One generator per pixel, so the image only depends on the seed
+            // and not on the order pixels are rendered in.
+            rng: pixel_rng(scene, pixel),
+            color: Vector3D::ZERO,
+            weights: 0.0,
+            sums: vec![Vector3D::ZERO; tile.aovs.len()],
+            covered: 0.0,
+            mean: 0.0,
+            m2: 0.0,
+            taken: 0,
+            converged: false,
+            brightest: Vec::new(),
+        }
+    }
+
+    // Standard error of the mean brightness so far, if there are enough
+    // samples to tell
+    fn error(&self) -> Option<f64> {
+        let n = self.taken as f64;
+        (self.taken > 1).then(|| (self.m2 / (n * (n - 1.0))).sqrt())
+    }
+
+    // Take samples until there are `until` of them, or adaptive sampling
+    // says the pixel is done. `hits` are the pixel's geometry cache
+    // entries.
+    fn sample(&mut self, until: u32, sampling: &Sampling, scene: &Scene, hits: &mut [Option<SurfaceHit>]) {
+        let (width, height) = (sampling.width, sampling.height);
+        let (px, py) = (self.x as f64, self.y as f64);
+        let camera = &sampling.camera;
+        let samples = sampling.samples;
+
+        let mut split = LightSplit {
+            group_of: &sampling.group_of,
+            sums: std::mem::take(&mut self.sums),
+        };
+        while !self.converged && self.taken < until {
+            if self.taken >= sampling.min_samples
+                && self.error().is_some_and(|e| e <= scene.settings.adaptive_threshold)
+            {
+                self.converged = true;
+                break;
+            }
+
+            // With several samples, jitter over the filter's reach for
+            // anti-aliasing and spread them over the shutter interval
+            // for motion blur. A single sample is the plain pixel ray.
+            let (ray, weight) = if samples > 1 {
+                let (jx, jy) = scene.settings.sampler.offset(self.taken, samples, &mut self.rng);
+                let time = camera.shutter_time * self.rng.next_f64();
+
+                // Offsets from the pixel's corner; the box's are jx and jy
+                let r = sampling.filter_radius;
+                let (ox, oy) = (jx * 2.0 * r + (0.5 - r), jy * 2.0 * r + (0.5 - r));
+                let weight = sampling.filter.weight(ox - 0.5, oy - 0.5, r);
+                (camera.pixel_ray(px + ox, py + oy, width, height, time), weight)
+            } else {
+                (camera.pixel_ray(px + 0.5, py + 0.5, width, height, 0.0), 1.0)
+            };
+
+            // Trace the ray. Outside the projection the pixel stays black,
+            // and with a transparent background so do misses. A single
+            // sample is the center's ray, which the cache has first.
+            if ray.is_some() {
+                scene.counters.primary();
+            }
+            let slot = match samples {
+                1 => hits.first_mut(),
+                _ => hits.get_mut(1 + self.taken as usize),
+            };
+            let hit = ray.and_then(|r| cached_hit(&r, slot, scene));
+            let ray = match sampling.transparent {
+                true => ray.filter(|_| hit.is_some()),
+                false => ray,
+            };
+            if ray.is_some() || !sampling.transparent {
+                self.covered += weight;
+            }
+            let sample = match (ray, scene.settings.mode) {
+                (None, _) => Vector3D::ZERO,
+                (Some(ray), _) if sampling.shading != Shading::Full => {
+                    debug_shade(&ray, hit.map(|(i, dist, _)| (i, dist)), sampling.shading, sampling.far, scene)
+                }
+                (Some(ray), RenderMode::Whitted) => {
+                    let wavelength = match scene.settings.dispersion {
+                        Dispersion::Split => Wavelength::All,
+                        Dispersion::Spectral => Wavelength::Picked(((self.rng.next_f64() * 3.0) as usize).min(2)),
+                    };
+                    let split = if sampling.split_light { Some(&mut split) } else { None };
+                    shade_split(&ray, hit, TraceDepth::default(), scene, split, Vector3D::ONE.map(|c| c * weight), wavelength)
+                }
+                (Some(ray), RenderMode::PathTrace) => {
+                    pathtrace_from(&ray, hit.map(|(i, dist, _)| (i, dist)), &sampling.emitters, scene, &mut self.rng)
+                }
+            };
+            let mut weighted = sample;
+            weighted.v3d_mul_scalar(weight);
+            self.color.v3d_add(weighted);
+            self.weights += weight;
+            self.taken += 1;
+
+            let l = sample.luminance();
+            if sampling.keep_brightest > 0 {
+                let at = self.brightest.partition_point(|&(b, _)| b >= l);
+                if at < sampling.keep_brightest {
+                    self.brightest.insert(at, (l, weighted));
+                    self.brightest.truncate(sampling.keep_brightest);
+                }
+            }
+
+            let delta = l - self.mean;
+            self.mean += delta / self.taken as f64;
+            self.m2 += delta * (l - self.mean);
+        }
+        self.sums = split.sums;
+    }
+
+    // Average the samples into the framebuffers of `tile`
+    fn finish(mut self, scene: &Scene, tile: &mut Tile) {
+        let weights = if self.weights > 0.0 { self.weights } else { 1.0 };
+
+        // Tone the outliers down to the brightness of the brightest sample
+        // that isn't one
+        if let Some(percent) = scene.settings.reject_outliers {
+            let n = outliers(self.taken, percent).min(self.brightest.len().saturating_sub(1));
+            if n > 0 {
+                let limit = self.brightest[n].0;
+                for &(l, sample) in self.brightest[..n].iter().filter(|(l, _)| *l > limit) {
+                    let mut toned = sample;
+                    toned.v3d_mul_scalar(limit / l - 1.0);
+                    self.color.v3d_add(toned);
+                }
+            }
+        }
+
+        let i = self.pixel - tile.start;
+        let mut color = self.color;
+        color.v3d_mul_scalar(1.0 / weights);
+        tile.color[i] = color;
+        tile.samples[i] = self.taken;
+        tile.coverage[i] = self.covered / weights;
+
+        for (aov, mut sum) in tile.aovs.iter_mut().zip(self.sums) {
+            sum.v3d_mul_scalar(1.0 / weights);
+            aov[i] = sum;
+        }
+    }
+}
+
+// How many of `samples` samples are the brightest `percent` percent
+fn outliers(samples: u32, percent: f64) -> usize {
+    (samples as f64 * percent / 100.0).ceil() as usize
+}
+
+// What the camera rays of the last render with `settings.geometry_cache`
+// hit, for the next one to use instead of tracing them again as long as
+// nothing that decides that changed (see `geometry_key`). Only where they
+// hit is kept, the point and normal are found again from that the same way
+// as the first time.
+pub struct GeometryCache {
+    key: u64,
+    // `Sampling::hit_slots` entries for every pixel, `None` for rays that
+    // weren't traced yet
+    hits: Vec<Option<SurfaceHit>>,
+}
+
+// Hash of everything that decides what the camera rays hit: the shape,
+// place and visibility of every primitive and the parts of its material
+// that punch holes in it or hide its back, the camera, the image size and
+// where the samples go. `None` if a primitive can't tell whether it changed.
+fn geometry_key(sampling: &Sampling, scene: &Scene) -> Option<u64> {
+    let s = &scene.settings;
+    let mut state = DefaultHasher::new();
+    ((sampling.width, sampling.height), sampling.samples, sampling.hit_slots, s.seed, s.cull_back_faces).hash(&mut state);
+    format!("{:?} {:?}", sampling.camera, s.sampler).hash(&mut state);
+    hash_floats(&mut state, [sampling.filter_radius, s.ray_epsilon]);
+
+    for (p, visibility) in scene.primitive_list.iter().zip(&scene.visibility) {
+        let m = material_of(p.as_ref(), scene);
+        format!("{} {:?} {:?} {:?}", p.kind(), visibility, m.sidedness, m.cutout).hash(&mut state);
+        if !p.hash_geometry(&mut state) {
+            return None;
+        }
+    }
+    Some(state.finish())
+}
+
+// What the camera ray `ray` hits: out of the geometry cache entry `slot`
+// if it's been traced before, and traced and put there otherwise
+fn cached_hit(ray: &Ray, slot: Option<&mut Option<SurfaceHit>>, scene: &Scene) -> SurfaceHit {
+    match slot {
+        Some(&mut Some(hit)) => hit,
+        Some(slot) => *slot.insert(surface_hit(ray, RayKind::Camera, scene)),
+        None => surface_hit(ray, RayKind::Camera, scene),
+    }
+}
+
+// The per-pixel buffers the render fills in, taken out of `GlobalSettings`
+// while the threads write to them
+struct Framebuffers {
+    color: Vec<Vector3D>,
+    depth: Vec<f64>,
+    normal: Vec<Vector3D>,
+    albedo: Vec<Vector3D>,
+    samples: Vec<u32>,
+    coverage: Vec<f64>,
+    aovs: Vec<Vec<Vector3D>>,
+    // `Sampling::hit_slots` entries of the geometry cache for every pixel,
+    // none without it
+    hits: Vec<Option<SurfaceHit>>,
+}
+
+// Rows of the image the render threads take one at a time. Short so that
+// a thread stuck on an expensive part of the image (all mirrors, say) holds
+// up little of it while the others take the rest.
+const TILE_ROWS: u32 = 4;
+
+// Rows `rows` of every framebuffer, for whichever render thread takes them
+// to write. No two tiles overlap, so the threads share the image without
+// waiting on each other.
+struct Tile<'a> {
+    rows: std::ops::Range<u32>,
+    // Index of the first pixel of the tile in the whole image
+    start: usize,
+    color: &'a mut [Vector3D],
+    depth: &'a mut [f64],
+    normal: &'a mut [Vector3D],
+    albedo: &'a mut [Vector3D],
+    samples: &'a mut [u32],
+    coverage: &'a mut [f64],
+    aovs: Vec<&'a mut [Vector3D]>,
+    hits: &'a mut [Option<SurfaceHit>],
+}
+
+impl Tile<'_> {
+    // Geometry cache entries of the tile's pixel `i`, none without the cache
+    fn hits(&mut self, i: usize, sampling: &Sampling) -> &mut [Option<SurfaceHit>] {
+        let n = sampling.hit_slots;
+        self.hits.get_mut(i * n..(i + 1) * n).unwrap_or_default()
+    }
+}
+
+impl Framebuffers {
+    fn take(globals: &mut GlobalSettings) -> Self {
+        Self {
+            color: std::mem::take(&mut globals.framebuffer),
+            depth: std::mem::take(&mut globals.depth_buffer),
+            normal: std::mem::take(&mut globals.normal_buffer),
+            albedo: std::mem::take(&mut globals.albedo_buffer),
+            samples: std::mem::take(&mut globals.sample_counts),
+            coverage: std::mem::take(&mut globals.coverage),
+            aovs: std::mem::take(&mut globals.light_aovs),
+            hits: Vec::new(),
+        }
+    }
+
+    fn put_back(self, globals: &mut GlobalSettings) {
+        globals.framebuffer = self.color;
+        globals.depth_buffer = self.depth;
+        globals.normal_buffer = self.normal;
+        globals.albedo_buffer = self.albedo;
+        globals.sample_counts = self.samples;
+        globals.coverage = self.coverage;
+        globals.light_aovs = self.aovs;
+    }
+
+    // Split into tiles of `rows` whole rows of `width` pixels, from the top
+    fn tiles(&mut self, width: u32, rows: u32) -> Vec<Tile<'_>> {
+        let height = self.color.len() as u32 / width;
+        let len = (rows * width) as usize;
+        let slots = self.hits.len() / self.color.len();
+
+        let mut depth = self.depth.chunks_mut(len);
+        let mut normal = self.normal.chunks_mut(len);
+        let mut albedo = self.albedo.chunks_mut(len);
+        let mut samples = self.samples.chunks_mut(len);
+        let mut coverage = self.coverage.chunks_mut(len);
+        let mut aovs: Vec<_> = self.aovs.iter_mut().map(|aov| aov.chunks_mut(len)).collect();
+        let mut hits = self.hits.chunks_mut((len * slots).max(1));
+        self.color
+            .chunks_mut(len)
+            .enumerate()
+            .map(|(i, color)| {
+                let first = i as u32 * rows;
+                Tile {
+                    rows: first..(first + rows).min(height),
+                    start: i * len,
+                    color,
+                    depth: depth.next().unwrap(),
+                    normal: normal.next().unwrap(),
+                    albedo: albedo.next().unwrap(),
+                    samples: samples.next().unwrap(),
+                    coverage: coverage.next().unwrap(),
+                    aovs: aovs.iter_mut().map(|aov| aov.next().unwrap()).collect(),
+                    hits: hits.next().unwrap_or_default(),
+                }
+            })
+            .collect()
+    }
+}
+
+// How many threads to render with
+fn thread_count(settings: &RenderSettings) -> u32 {
+    match settings.threads {
+        0 => std::thread::available_parallelism().map_or(1, |n| n.get() as u32),
+        n => n,
+    }
+}
+
+// Pixels of `tile` inside the region
+fn tile_pixels(tile: &Tile, width: u32, scene: &Scene) -> Vec<(u32, u32)> {
+    tile.rows
+        .clone()
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .filter(|&(x, y)| scene.in_region(x, y))
+        .collect()
+}
+
+// Run `work` on every one of `parts` on `threads` threads. The parts are a
+// queue: every thread takes the next one nobody has taken yet until there
+// are none left, so every part gets done exactly once and the threads
+// finish together however uneven the parts are. Returns what every thread
+// traced and how long it took (with `settings.stats` on), one per thread.
+fn in_parallel<T: Send>(parts: &mut [T], threads: u32, scene: &Scene, work: impl Fn(&mut T) + Sync) -> Vec<PassStats> {
+    // Every part is only ever locked by the one thread that took it
+    let parts: Vec<Mutex<&mut T>> = parts.iter_mut().map(Mutex::new).collect();
+    let next = AtomicUsize::new(0);
+    let (parts, next, work) = (&parts, &next, &work);
+
+    std::thread::scope(|scope| {
+        let threads: Vec<_> = (0..threads)
+            .map(|pass| {
+                scope.spawn(move || {
+                    let start = std::time::Instant::now();
+                    scene.counters.take();
+                    while let Some(part) = parts.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        work(&mut part.lock().unwrap());
+                    }
+                    PassStats {
+                        pass,
+                        counts: scene.counters.take(),
+                        time: start.elapsed(),
+                    }
+                })
+            })
+            .collect();
+        threads.into_iter().map(|t| t.join().unwrap()).collect()
+    })
+}
+
+// Render every pixel of `tile`, all its samples at once
+fn render_tile(tile: &mut Tile, sampling: &Sampling, scene: &Scene) {
+    for (x, y) in tile_pixels(tile, sampling.width, scene) {
+        let mut state = PixelState::new(x, y, sampling, scene, tile);
+        let i = state.pixel - tile.start;
+        state.sample(sampling.samples, sampling, scene, tile.hits(i, sampling));
+        state.finish(scene, tile);
+    }
+}
+
+// Why `render_budgeted` stopped before taking all the samples
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BudgetStop {
+    // Out of `RenderSettings::max_time`
+    Time,
+    // The noise got down to `RenderSettings::target_noise`
+    Noise,
+    // `GlobalSettings::cancel` was set. The render is unfinished and not
+    // post-processed or resolved.
+    Cancelled,
+}
+
+// Samples taken in one go by every pixel between the budget checks
+const BUDGET_BATCH: u32 = 4;
+
+// Render the tiles, a batch of samples per pixel at a time, until every
+// pixel has all its samples or one of the budgets runs out. The budgets are
+// only checked once every tile has finished the batch, so a stopped render
+// is the same as any other with that many samples. The time runs out at
+// `deadline`. Cancelling doesn't wait for the batch to finish. The pixels
+// start from the samples in `resumed`, and with `globals.checkpoint` are
+// saved there between batches and at the end.
+fn render_budgeted(
+    tiles: Vec<Tile>,
+    threads: u32,
+    sampling: &Sampling,
+    globals: &GlobalSettings,
+    deadline: Option<std::time::Instant>,
+    resumed: Option<checkpoint::Saved>,
+) -> (Option<BudgetStop>, Vec<PassStats>) {
+    // The threads only get to see the scene
+    let scene = &globals.scene;
+    let cancelled = || globals.cancel.as_ref().is_some_and(|c| c.load(Ordering::Relaxed));
+    let mut tiles: Vec<(Tile, Vec<PixelState>)> = tiles.into_iter().map(|tile| (tile, Vec::new())).collect();
+    let mut pass_stats: Vec<PassStats> = Vec::new();
+
+    let (start, saved) = resumed.map_or((0, Vec::new()), |r| (r.done, r.pixels));
+    let mut done = start;
+    let mut last_checkpoint = (done, std::time::Instant::now());
+    let stop = loop {
+        let until = (done + BUDGET_BATCH).min(sampling.samples);
+        let batch = in_parallel(&mut tiles, threads, scene, |(tile, states)| {
+            if done == start {
+                // What the centers see is found again either way, the
+                // samples come from the checkpoint if it has the pixel
+                let pixels = tile_pixels(tile, sampling.width, scene).into_iter();
+                *states = pixels
+                    .map(|(x, y)| {
+                        let state = PixelState::new(x, y, sampling, scene, tile);
+                        match saved.binary_search_by_key(&state.pixel, |s| s.pixel) {
+                            Ok(at) => saved[at].clone(),
+                            Err(_) => state,
+                        }
+                    })
+                    .collect();
+            }
+            for state in states.iter_mut() {
+                if cancelled() {
+                    break;
+                }
+                let i = state.pixel - tile.start;
+                state.sample(until, sampling, scene, tile.hits(i, sampling));
+            }
+        });
+        if globals.settings.stats {
+            if pass_stats.is_empty() {
+                pass_stats = batch;
+            } else {
+                for (before, pass) in pass_stats.iter_mut().zip(batch) {
+                    *before = PassStats {
+                        pass: pass.pass,
+                        ..stats::total(&[*before, pass])
+                    };
+                }
+            }
+        }
+        done = until;
+
+        if cancelled() {
+            break Some(BudgetStop::Cancelled);
+        }
+        let states = || tiles.iter().flat_map(|(_, states)| states);
+        if done >= sampling.samples || states().all(|s| s.converged) {
+            break None;
+        }
+        if deadline.is_some_and(|d| std::time::Instant::now() >= d) {
+            break Some(BudgetStop::Time);
+        }
+        if let Some(target) = globals.settings.target_noise {
+            let count = states().count().max(1);
+            let noise = states().map(|s| s.error().unwrap_or(f64::INFINITY)).sum::<f64>() / count as f64;
+            if noise <= target {
+                break Some(BudgetStop::Noise);
+            }
+        }
+
+        let due = match globals.settings.checkpoint_every {
+            CheckpointInterval::Time(t) => last_checkpoint.1.elapsed() >= t,
+            CheckpointInterval::Samples(n) => done - last_checkpoint.0 >= n,
+        };
+        if due {
+            save_checkpoint(&tiles, done, sampling, globals);
+            last_checkpoint = (done, std::time::Instant::now());
+        }
+    };
+    save_checkpoint(&tiles, done, sampling, globals);
+
+    for (mut tile, states) in tiles {
+        for state in states {
+            state.finish(scene, &mut tile);
+        }
+    }
+    (stop, pass_stats)
+}
+
+// Save the pixels of `tiles`, rendered up to `done` samples, to
+// `globals.checkpoint` if there is one. Not being able to is only worth a
+// warning, the render goes on.
+fn save_checkpoint(tiles: &[(Tile, Vec<PixelState>)], done: u32, sampling: &Sampling, globals: &GlobalSettings) {
+    if let Some(path) = &globals.checkpoint {
+        let start = std::time::Instant::now();
+        match checkpoint::save(path, sampling, globals, done, tiles.iter().flat_map(|(_, states)| states)) {
+            Ok(()) => debug!("Checkpoint of {} samples per pixel saved to {} in {:.2?}", done, path, start.elapsed()),
+            Err(e) => warn!("Couldn't save the checkpoint: {}", e),
+        }
+    }
+}
+
+// Emissive primitives, for the path tracer's light sampling
+fn emitters(scene: &Scene) -> Vec<usize> {
+    scene
+        .primitive_list
+        .iter()
+        .enumerate()
+        .filter(|(_, p)| is_emissive(&scene.material(p.material_ref())))
+        .map(|(i, _)| i)
+        .collect()
+}
+
+// Random number generator for the pixel with index `pixel`
+fn pixel_rng(scene: &Scene, pixel: usize) -> Rng {
+    Rng::new(scene.settings.seed ^ (pixel as u64).wrapping_mul(0x2545_F491_4F6C_DD1D))
+}
+
+// HDR color `camera` sees through the middle of pixel (x, y) of a `width` x
+// `height` image, traced the way the settings ask. That's one sample and no
+// post-processing, so it's handy for testing the shading without rendering a
+// whole image. Black outside the projection, like in `render`.
+pub fn trace_pixel(scene: &Scene, camera: &Camera, x: u32, y: u32, width: u32, height: u32) -> Vector3D {
+    let ray = match camera.pixel_ray(x as f64 + 0.5, y as f64 + 0.5, width, height, 0.0) {
+        Some(ray) => ray,
+        None => return Vector3D::ZERO,
+    };
+
+    let shading = scene.settings.shading;
+    match scene.settings.mode {
+        _ if shading != Shading::Full => debug_shade(&ray, closest_hit(&ray, RayKind::Camera, scene), shading, scene_depth(camera, scene), scene),
+        RenderMode::Whitted => trace(&ray, 0, scene),
+        RenderMode::PathTrace => {
+            let mut rng = pixel_rng(scene, (y * width + x) as usize);
+            pathtrace(&ray, &emitters(scene), scene, &mut rng)
+        }
+    }
+}
+
+// Render the whole image: all the `render` passes, then the post-processing
+// the settings ask for. The result ends up in `globals.img`. Fails without
+// rendering anything if the settings don't make sense.
+pub fn render_scene(globals: &mut GlobalSettings) -> Result<(), LuxError> {
+    globals.check_settings()?;
+
+    // The time budget covers everything from here on
+    let deadline = globals.settings.max_time.map(|t| std::time::Instant::now() + t);
+
+    let start = std::time::Instant::now();
+    globals.build_bvh();
+    debug!(
+        "{:?} BVH of {} nodes built in {:.2?}",
+        globals.settings.bvh,
+        globals.bvh.as_ref().map_or(0, |b| b.node_count()),
+        start.elapsed()
+    );
+
+    globals.find_behind_camera();
+    if let Some(behind) = &globals.behind_camera {
+        debug!("{} primitives behind the camera", behind.count());
+    }
+
+    debug!(
+        "{}x{} image, {} primitives, {} lights, {} samples per pixel",
+        globals.img.width(),
+        globals.img.height(),
+        globals.primitive_list.len(),
+        globals.light_list.len(),
+        globals.settings.samples.max(1)
+    );
+
+    if globals.settings.caustics && globals.settings.mode == RenderMode::Whitted {
+        let start = std::time::Instant::now();
+        globals.caustics = build_caustics(globals);
+        debug!("Caustics pass in {:.2?}", start.elapsed());
+    }
+
+    // A framebuffer per light group and one for the rest
+    globals.light_aovs.clear();
+    if globals.settings.light_aovs {
+        if globals.settings.mode == RenderMode::Whitted && globals.settings.shading == Shading::Full {
+            let pixels = globals.framebuffer.len();
+            let buffers = globals.light_groups().len() + 1;
+            globals.light_aovs = vec![vec![Vector3D::ZERO; pixels]; buffers];
+        } else {
+            warn!("Light AOVs only work with the Whitted tracer and full shading");
+        }
+    }
+
+    // Only count the rays of the passes below
+    globals.counters.set_enabled(globals.settings.stats);
+    globals.counters.take();
+
+    // The threads take tiles of rows from a queue and render them straight
+    // into their own part of the framebuffers
+    let start = std::time::Instant::now();
+    globals.budget_stop = None;
+    let threads = thread_count(&globals.settings);
+    let budgets = globals.settings.max_time.is_some() || globals.settings.target_noise.is_some();
+    let sampling = Sampling::new(globals);
+    let cache_key = globals.settings.geometry_cache.then(|| geometry_key(&sampling, globals)).flatten();
+    let resumed = match &globals.checkpoint {
+        Some(path) if globals.resume => match checkpoint::load(path, &sampling, globals)? {
+            Some(saved) => {
+                info!("Resuming from {} at {} samples per pixel", path, saved.done);
+                Some(saved)
+            }
+            None => {
+                info!("No checkpoint in {} yet, starting from scratch", path);
+                None
+            }
+        },
+        _ => None,
+    };
+    let mut buffers = Framebuffers::take(globals);
+    if let Some(key) = cache_key {
+        let len = buffers.color.len() * sampling.hit_slots;
+        buffers.hits = match globals.geometry_cache.take() {
+            Some(cache) if cache.key == key && cache.hits.len() == len => {
+                debug!("Geometry unchanged, reusing the camera hits");
+                cache.hits
+            }
+            _ => vec![None; len],
+        };
+    }
+    let tiles = buffers.tiles(globals.img.width(), TILE_ROWS);
+    let (stop, pass_stats) = if budgets || globals.cancel.is_some() || globals.checkpoint.is_some() {
+        render_budgeted(tiles, threads, &sampling, globals, deadline, resumed)
+    } else {
+        let scene = &globals.scene;
+        let mut tiles = tiles;
+        (None, in_parallel(&mut tiles, threads, scene, |tile| render_tile(tile, &sampling, scene)))
+    };
+    globals.geometry_cache = cache_key.map(|key| GeometryCache {
+        key,
+        hits: std::mem::take(&mut buffers.hits),
+    });
+    buffers.put_back(globals);
+    if globals.settings.stats {
+        globals.pass_stats.extend(pass_stats);
+    }
+    debug!("Traced in {:.2?} on {} threads", start.elapsed(), threads);
+
+    globals.budget_stop = stop;
+    if budgets || globals.cancel.is_some() {
+
+        let pixels: Vec<u32> = (0..globals.sample_counts.len() as u32)
+            .filter(|i| globals.in_region(i % globals.img.width(), i / globals.img.width()))
+            .map(|i| globals.sample_counts[i as usize])
+            .collect();
+        let average = pixels.iter().map(|&n| n as f64).sum::<f64>() / pixels.len().max(1) as f64;
+        match globals.budget_stop {
+            Some(BudgetStop::Time) => info!("Out of time after {:.1} samples per pixel", average),
+            Some(BudgetStop::Noise) => info!("Noise target reached after {:.1} samples per pixel", average),
+            Some(BudgetStop::Cancelled) => {
+                info!("Cancelled after {:.1} samples per pixel", average);
+                return Ok(());
+            }
+            None if budgets => info!("Finished all {:.1} samples per pixel within the budget", average),
+            None => {}
+        }
+    }
+
+    if globals.settings.denoise {
+        let start = std::time::Instant::now();
+        denoise(globals);
+        debug!("Denoised in {:.2?}", start.elapsed());
+    }
+
+    if globals.settings.edges {
+        overlay_edges(globals);
+    }
+
+    if let Some(length) = globals.settings.axes {
+        overlay_axes(globals, length);
+    }
+
+    resolve(globals);
+    Ok(())
+}
+
+// Render the scene in `globals` through `camera`, at the size and with the
+// render settings in `config`, and return the 8-bit pixels row by row.
+// Nothing is written to disk. `globals` keeps the render, so it can still
+// be resolved again or saved.
+pub fn render_to_buffer(globals: &mut GlobalSettings, camera: &Camera, config: &RenderConfig) -> Result<Vec<Rgba<u8>>, LuxError> {
+    render_with(globals, camera, config)?;
+    Ok(globals.img.pixels().copied().collect())
+}
+
+// Same as `render_to_buffer`, but the linear HDR colors: the framebuffer,
+// before the exposure and clamping (and times the alpha, see
+// `GlobalSettings::coverage`)
+pub fn render_to_hdr_buffer(globals: &mut GlobalSettings, camera: &Camera, config: &RenderConfig) -> Result<Vec<Vector3D>, LuxError> {
+    render_with(globals, camera, config)?;
+    Ok(globals.framebuffer.clone())
+}
+
+fn render_with(globals: &mut GlobalSettings, camera: &Camera, config: &RenderConfig) -> Result<(), LuxError> {
+    globals.settings = config.render;
+    globals.camera = *camera;
+    globals.set_size(config.width, config.height);
+    render_scene(globals)
+}
+
+// Render the scene once per eye and put the two images together as the
+// `mode` asks. The eyes are moved half the eye separation to each side along
+// the camera's right vector.
+pub fn render_stereo(globals: &mut GlobalSettings, mode: StereoMode) -> Result<RgbaImage, LuxError> {
+    let camera = globals.camera;
+    let (right, _) = camera.basis();
+    let half = globals.settings.eye_separation * 0.5;
+
+    let mut eyes = Vec::with_capacity(2);
+    for side in [-1.0, 1.0] {
+        let mut eye = camera;
+
+        let mut offset = right;
+        offset.v3d_mul_scalar(side * half);
+        eye.position.v3d_add(offset);
+
+        // Shift the window back towards the middle so both eyes see the
+        // convergence plane in the same place
+        if globals.settings.convergence > 0.0 {
+            eye.shift -= side * half / globals.settings.convergence;
+        }
+
+        debug!("Rendering the {} eye", if side < 0.0 { "left" } else { "right" });
+        globals.camera = eye;
+        let rendered = render_scene(globals);
+        globals.camera = camera;
+
+        rendered?;
+        eyes.push(globals.img.clone());
+    }
+
+    let (left, right) = (&eyes[0], &eyes[1]);
+    let (width, height) = left.dimensions();
+
+    let img = match mode {
+        StereoMode::Anaglyph => {
+            let mut img = right.clone();
+            for (x, y, pixel) in img.enumerate_pixels_mut() {
+                pixel[0] = left.get_pixel(x, y)[0];
+            }
+            img
+        }
+        StereoMode::SideBySide => {
+            let mut img = RgbaImage::new(width * 2, height);
+            for (x, y, pixel) in left.enumerate_pixels() {
+                img.put_pixel(x, y, *pixel);
+                img.put_pixel(x + width, y, *right.get_pixel(x, y));
+            }
+            img
+        }
+    };
+
+    Ok(img)
+}
+
+// Render the scene once for every field of view in `fovs` (degrees), with
+// `config` like `render_to_buffer`, and lay the images out in a grid as
+// close to square as it gets, left to right and top to bottom, each
+// labelled with its field of view in the top left corner. Cells left over
+// in the last row stay black. `globals` keeps the last render.
+pub fn render_fov_sheet(globals: &mut GlobalSettings, fovs: &[f64], config: &RenderConfig) -> Result<RgbaImage, LuxError> {
+    if fovs.is_empty() {
+        return Err(LuxError::InvalidSettings("the field of view sweep needs at least one field of view".to_string()));
+    }
+    if config.camera.projection != Projection::Perspective {
+        return Err(LuxError::InvalidSettings("the field of view sweep needs a perspective camera".to_string()));
+    }
+
+    let columns = (fovs.len() as f64).sqrt().ceil() as u32;
+    let rows = (fovs.len() as u32).div_ceil(columns);
+    let (width, height) = (config.width, config.height);
+    let mut sheet = RgbaImage::from_pixel(width * columns, height * rows, Rgba([0, 0, 0, 255]));
+
+    for (i, &fov) in fovs.iter().enumerate() {
+        debug!("Rendering at a field of view of {} degrees", fov);
+        let camera = Camera { fov, ..config.camera };
+        let pixels = render_to_buffer(globals, &camera, config)?;
+        if globals.budget_stop == Some(BudgetStop::Cancelled) {
+            return Ok(sheet);
+        }
+
+        let (left, top) = (i as u32 % columns * width, i as u32 / columns * height);
+        for (j, pixel) in pixels.into_iter().enumerate() {
+            sheet.put_pixel(left + j as u32 % width, top + j as u32 / width, pixel);
+        }
+        let scale = (height / 60).max(1);
+        draw_label(&mut sheet, left + scale, top + scale, &format!("{}°", fov), scale);
+    }
+
+    Ok(sheet)
+}
+
+// Rows of the 3 x 5 pixel glyph of `c`, top first, the high bit on the
+// left. Only what numbers need.
+fn glyph(c: char) -> Option<[u8; 5]> {
+    Some(match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '°' => [0b010, 0b101, 0b010, 0b000, 0b000],
+        _ => return None,
+    })
+}
+
+// Write `text` in white on a black box at (x, y) of `img`, every pixel of
+// the glyphs `scale` pixels wide. Characters without a glyph are skipped,
+// and whatever doesn't fit in the image is cut off.
+fn draw_label(img: &mut RgbaImage, x: u32, y: u32, text: &str, scale: u32) {
+    let glyphs: Vec<[u8; 5]> = text.chars().filter_map(glyph).collect();
+    let (width, height) = ((glyphs.len() as u32 * 4 + 1) * scale, 7 * scale);
+    let mut put = |px: u32, py: u32, color: [u8; 4]| {
+        if px < img.width() && py < img.height() {
+            img.put_pixel(px, py, Rgba(color));
+        }
+    };
+
+    for (dx, dy) in (0..width).flat_map(|dx| (0..height).map(move |dy| (dx, dy))) {
+        put(x + dx, y + dy, [0, 0, 0, 255]);
+    }
+    for (i, rows) in glyphs.iter().enumerate() {
+        for (row, bits) in rows.iter().enumerate() {
+            for column in (0..3).filter(|c| bits & (0b100 >> c) != 0) {
+                let (gx, gy) = (x + (i as u32 * 4 + 1 + column) * scale, y + (row as u32 + 1) * scale);
+                for (dx, dy) in (0..scale).flat_map(|dx| (0..scale).map(move |dy| (dx, dy))) {
+                    put(gx + dx, gy + dy, [255, 255, 255, 255]);
+                }
+            }
+        }
+    }
+}
+
+// Scale a color channel to 0..=max. The 8 and 16 bit outputs only differ in
+// `max`.
+fn quantize(c: f64, max: f64) -> f64 {
+    (c * max).clamp(0.0, max)
+}
+
+// 8-bit channels of `color`. `as u8` rounds down, so `offset` (0 to 1) is
+// the threshold for rounding up instead, see `resolve_into`.
+fn to_8bit(color: Vector3D, offset: f64) -> [u8; 3] {
+    let channel = |c: f64| (quantize(c, 255.0) + offset).min(255.0) as u8;
+    [channel(color.x), channel(color.y), channel(color.z)]
+}
+
+// Color of `pixel` in `buffer` with the exposure applied
+fn exposed(globals: &GlobalSettings, buffer: &[Vector3D], pixel: usize) -> Vector3D {
+    let mut color = buffer[pixel];
+    color.v3d_mul_scalar(globals.settings.exposure.exp2());
+
+    // Back from color times alpha to the color
+    let alpha = globals.coverage[pixel];
+    if alpha > 0.0 {
+        color.v3d_mul_scalar(1.0 / alpha);
+    }
+    color
+}
+
+// 4x4 Bayer matrix, the order in which the pixels of a 4x4 block round up
+const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+// Turn the float framebuffer into the 8-bit output image. The framebuffer
+// is kept, so this can run again with a different exposure.
+pub fn resolve(globals: &mut GlobalSettings) {
+    let mut img = std::mem::take(&mut globals.img);
+    resolve_into(globals, &globals.framebuffer, &mut img);
+    globals.img = img;
+}
+
+// 8-bit image of the light AOV `index` (see `GlobalSettings::light_aovs`)
+pub fn resolve_light_aov(globals: &GlobalSettings, index: usize) -> RgbaImage {
+    let mut img = RgbaImage::new(globals.img.width(), globals.img.height());
+    resolve_into(globals, &globals.light_aovs[index], &mut img);
+    img
+}
+
+// Quantize the float `buffer` into the pixels of `img` that are rendered
+fn resolve_into(globals: &GlobalSettings, buffer: &[Vector3D], img: &mut RgbaImage) {
+    let width = img.width();
+
+    for pixel in 0..buffer.len() {
+        let x = pixel as u32 % width;
+        let y = pixel as u32 / width;
+        let color = exposed(globals, buffer, pixel);
+
+        // `as u8` rounds down. Dithering adds a threshold between 0 and one
+        // step that varies over the 4x4 block, so a color between two steps
+        // rounds up in a share of the pixels that matches how close it is to
+        // the upper one.
+        let offset = match globals.settings.dither {
+            true => (BAYER[y as usize % 4][x as usize % 4] as f64 + 0.5) / 16.0,
+            false => 0.0,
+        };
+        let [r, g, b] = to_8bit(color, offset);
+
+        let alpha = quantize(globals.coverage[pixel], 255.0) as u8;
+        let cl: image::Rgba<u8> = image::Rgba([r, g, b, alpha]);
+        if globals.in_region(x, y) {
+            img.put_pixel(x, y, cl);
+        }
+    }
+}
+
+// Samples spent on every pixel, from blue for the fewest to red for the
+// most. Pixels outside the rendered region are transparent.
+pub fn sample_heatmap(globals: &GlobalSettings) -> RgbaImage {
+    let (width, height) = globals.img.dimensions();
+    let rendered: Vec<u32> = (0..width * height)
+        .filter(|i| globals.in_region(i % width, i / width))
+        .map(|i| globals.sample_counts[i as usize])
+        .collect();
+    let fewest = rendered.iter().min().copied().unwrap_or(0);
+    let most = rendered.iter().max().copied().unwrap_or(0);
+    let range = (most - fewest).max(1) as f64;
+
+    RgbaImage::from_fn(width, height, |x, y| {
+        if !globals.in_region(x, y) {
+            return Rgba([0, 0, 0, 0]);
+        }
+
+        let t = (globals.sample_counts[(y * width + x) as usize] - fewest) as f64 / range;
+        Rgba([quantize(t, 255.0) as u8, 0, quantize(1.0 - t, 255.0) as u8, 255])
+    })
+}
+
+// Diagnostic images of a finished render, for balancing the lights. They
+// only look at what the render left in the framebuffers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Overlay {
+    // The render, with the pixels where a channel is above 1 after the
+    // exposure (so gets clipped) in red and pure black ones in blue
+    Clipping,
+    // False color of the light the light with this index in `lights()`
+    // casts on what the camera sees, shadows left out, from blue for none
+    // through green to red for what would come out white. Brighter still
+    // is white, and pixels that see nothing are black.
+    Falloff(usize),
+}
+
+impl Overlay {
+    // Whether the overlay can be made for the scene in `globals`: fails for
+    // a light that doesn't exist
+    pub fn check(&self, globals: &GlobalSettings) -> Result<(), LuxError> {
+        match *self {
+            Overlay::Falloff(index) if index >= globals.light_list.len() => Err(LuxError::InvalidSettings(format!(
+                "there's no light {} to show the falloff of, the scene has {}",
+                index,
+                globals.light_list.len()
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+// Blue through green to red for `t` from 0 to 1, white above
+fn false_color(t: f64) -> Rgba<u8> {
+    if t > 1.0 {
+        return Rgba([255, 255, 255, 255]);
+    }
+    let t = t.max(0.0) * 2.0;
+    let (r, g, b) = match t < 1.0 {
+        true => (0.0, t, 1.0 - t),
+        false => (t - 1.0, 2.0 - t, 0.0),
+    };
+    Rgba([quantize(r, 255.0) as u8, quantize(g, 255.0) as u8, quantize(b, 255.0) as u8, 255])
+}
+
+// The `overlay` image of the render in `globals`. Pixels outside the
+// rendered region are transparent. Fails for a light that doesn't exist.
+pub fn overlay_image(globals: &GlobalSettings, overlay: Overlay) -> Result<RgbaImage, LuxError> {
+    let (width, height) = globals.img.dimensions();
+    overlay.check(globals)?;
+
+    Ok(RgbaImage::from_fn(width, height, |x, y| {
+        if !globals.in_region(x, y) {
+            return Rgba([0, 0, 0, 0]);
+        }
+        let pixel = (y * width + x) as usize;
+
+        match overlay {
+            Overlay::Clipping => {
+                let color = exposed(globals, &globals.framebuffer, pixel);
+                let shown = *globals.img.get_pixel(x, y);
+                if color.max_component() > 1.0 {
+                    Rgba([255, 0, 0, 255])
+                } else if shown.0[..3] == [0, 0, 0] {
+                    Rgba([0, 0, 255, 255])
+                } else {
+                    shown
+                }
+            }
+            Overlay::Falloff(index) => {
+                // The primary hit, from the depth and normal the render kept
+                let distance = globals.depth_buffer[pixel];
+                let ray = globals.camera.pixel_ray(x as f64 + 0.5, y as f64 + 0.5, width, height, 0.0);
+                let Some(ray) = ray.filter(|_| distance.is_finite()) else {
+                    return Rgba([0, 0, 0, 255]);
+                };
+                let mut point = ray.direction;
+                point.v3d_mul_scalar(distance);
+                point.v3d_add(ray.origin);
+                let mut n = globals.normal_buffer[pixel];
+                if n.v3d_dot_mul(ray.direction) > 0.0 {
+                    n.v3d_mul_scalar(-1.0);
+                }
+
+                let light = &globals.light_list[index];
+                let mut l = light.position;
+                l.v3d_sub(point);
+                l.v3d_norm();
+                let mut arriving = light.radiance_at(point);
+                arriving.v3d_mul_scalar(l.v3d_dot_mul(n).max(0.0) * globals.settings.exposure.exp2());
+                false_color(arriving.luminance())
+            }
+        }
+    }))
+}
+
+// Same as `resolve`, but to a 16-bit image for smoother gradients. The 8-bit
+// `globals.img` is left alone.
+pub fn resolve_16bit(globals: &GlobalSettings) -> ImageBuffer<Rgba<u16>, Vec<u16>> {
+    let (width, height) = globals.img.dimensions();
+
+    ImageBuffer::from_fn(width, height, |x, y| {
+        if !globals.in_region(x, y) {
+            return Rgba([0, 0, 0, 0]);
+        }
+
+        let color = exposed(globals, &globals.framebuffer, (y * width + x) as usize);
+
+        Rgba([
+            quantize(color.x, 65535.0) as u16,
+            quantize(color.y, 65535.0) as u16,
+            quantize(color.z, 65535.0) as u16,
+            quantize(globals.coverage[(y * width + x) as usize], 65535.0) as u16,
+        ])
+    })
+}
+
+// Save an 8-bit image as `path`, in the format its extension asks for.
+// PPMs are written by `save_ppm`, without the image crate's encoders.
+pub fn save_image(img: &RgbaImage, path: &str) -> Result<(), LuxError> {
+    if path.to_ascii_lowercase().ends_with(".ppm") {
+        return save_ppm(img, path);
+    }
+    img.save(path).map_err(|e| LuxError::from_image(path, e))
+}
+
+// Save pixels from `render_to_buffer` as a `width` x `height` image, in the
+// format the extension of `path` asks for
+pub fn save_buffer(pixels: &[Rgba<u8>], width: u32, height: u32, path: &str) -> Result<(), LuxError> {
+    let img = RgbaImage::from_fn(width, height, |x, y| pixels[(y * width + x) as usize]);
+    save_image(&img, path)
+}
+
+// Save the framebuffer with 16 bits per channel (see `resolve_16bit`)
+pub fn save_16bit(globals: &GlobalSettings, path: &str) -> Result<(), LuxError> {
+    resolve_16bit(globals).save(path).map_err(|e| LuxError::from_image(path, e))
+}
+
+// Save an 8-bit image as a binary PPM, whatever the extension. Alpha is
+// dropped.
+pub fn save_ppm(img: &RgbaImage, path: &str) -> Result<(), LuxError> {
+    let rgb: Vec<u8> = img.pixels().flat_map(|p| [p[0], p[1], p[2]]).collect();
+    write_p6(path, img.width(), img.height(), &rgb)
+}
+
+// Save a `width` x `height` float buffer (like `GlobalSettings::framebuffer`)
+// as a binary PPM, clamped to 8 bits the same way `resolve` does. Needs
+// nothing from the image crate.
+pub fn write_ppm(path: &str, pixels: &[Vector3D], width: u32, height: u32) -> Result<(), LuxError> {
+    if pixels.len() != (width * height) as usize {
+        return Err(LuxError::InvalidSettings(format!(
+            "{} pixels don't make a {}x{} image",
+            pixels.len(),
+            width,
+            height
+        )));
+    }
+
+    let rgb: Vec<u8> = pixels.iter().flat_map(|&c| to_8bit(c, 0.0)).collect();
+    write_p6(path, width, height, &rgb)
+}
+
+// Save a `width` x `height` float buffer as an OpenEXR image with 32-bit
+// float channels, whatever the extension. Nothing is clamped, so it keeps
+// HDR colors and negative numbers (like the components of normals).
+pub fn write_exr(path: &str, pixels: &[Vector3D], width: u32, height: u32) -> Result<(), LuxError> {
+    if pixels.len() != (width * height) as usize {
+        return Err(LuxError::InvalidSettings(format!(
+            "{} pixels don't make a {}x{} image",
+            pixels.len(),
+            width,
+            height
+        )));
+    }
+
+    let img = Rgb32FImage::from_fn(width, height, |x, y| {
+        let c = pixels[(y * width + x) as usize];
+        Rgb([c.x as f32, c.y as f32, c.z as f32])
+    });
+    img.save_with_format(path, ImageFormat::OpenExr).map_err(|e| LuxError::from_image(path, e))
+}
+
+// Binary PPM ("P6") of 8-bit RGB pixels, row by row
+fn write_p6(path: &str, width: u32, height: u32, rgb: &[u8]) -> Result<(), LuxError> {
+    let mut data = format!("P6\n{} {} 255\n", width, height).into_bytes();
+    data.extend_from_slice(rgb);
+    std::fs::write(path, data).map_err(|source| LuxError::Io {
+        path: path.to_string(),
+        source,
+    })
+}
+
+// Edge-aware À-trous wavelet filter over the float framebuffer. Each pass
+// blurs with a 5x5 B-spline kernel whose taps are spread 1, 2, 4, ... pixels
+// apart, and weighs every tap by how similar its color, normal and depth are
+// to the center pixel so silhouettes don't smear. Background pixels are
+// neither filtered nor used as taps.
+pub fn denoise(globals: &mut GlobalSettings) {
+    const KERNEL: [f64; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+    // How sharply normal and depth differences stop the filter
+    const NORMAL_POWER: f64 = 32.0;
+    const DEPTH_SIGMA: f64 = 0.05;
+
+    let width = globals.img.width() as i64;
+    let height = globals.img.height() as i64;
+    let mut sigma_color = globals.settings.denoise_strength.max(1e-6);
+
+    let mut step: i64 = 1;
+
+    while step <= (globals.settings.denoise_radius as i64).max(1) {
+        let input = globals.framebuffer.clone();
+
+        for y in 0..height {
+            for x in 0..width {
+                let i = (y * width + x) as usize;
+                let depth = globals.depth_buffer[i];
+
+                if !depth.is_finite() {
+                    continue;
+                }
+
+                let color = input[i];
+                let normal = globals.normal_buffer[i];
+
+                let mut sum = Vector3D::ZERO;
+                let mut total_weight = 0.0;
+
+                for (ky, wy) in KERNEL.iter().enumerate() {
+                    for (kx, wx) in KERNEL.iter().enumerate() {
+                        let qx = x + (kx as i64 - 2) * step;
+                        let qy = y + (ky as i64 - 2) * step;
+
+                        if qx < 0 || qy < 0 || qx >= width || qy >= height {
+                            continue;
+                        }
+
+                        let j = (qy * width + qx) as usize;
+                        let other_depth = globals.depth_buffer[j];
+
+                        if !other_depth.is_finite() {
+                            continue;
+                        }
+
+                        let mut diff = input[j];
+                        diff.v3d_sub(color);
+                        let w_color = (-diff.v3d_dot_mul(diff) / (sigma_color * sigma_color)).exp();
+
+                        let w_normal = normal.v3d_dot_mul(globals.normal_buffer[j]).max(0.0).powf(NORMAL_POWER);
+
+                        let w_depth = (-(other_depth - depth).abs() / (DEPTH_SIGMA * depth * step as f64)).exp();
+
+                        let weight = wx * wy * w_color * w_normal * w_depth;
+
+                        let mut tap = input[j];
+                        tap.v3d_mul_scalar(weight);
+                        sum.v3d_add(tap);
+                        total_weight += weight;
+                    }
+                }
+
+                // The center tap always has a weight, so this never divides by 0
+                sum.v3d_mul_scalar(1.0 / total_weight);
+                globals.framebuffer[i] = sum;
+            }
+        }
+
+        // Halve the color tolerance every pass, so later (wider) passes only
+        // smooth what's left of the noise
+        sigma_color *= 0.5;
+        step *= 2;
+    }
+}
+
+// Segments the axes are cut into. Lines through non-perspective
+// projections come out curved, and pieces behind the camera are left out.
+const AXIS_SEGMENTS: usize = 64;
+
+// Draw the world's x, y and z axes from the origin, `length` long, in red,
+// green and blue over everything else, to check which way the camera is
+// turned. The lines are one pixel wide and opaque, and come out in their
+// pure colors whatever the exposure.
+pub fn overlay_axes(globals: &mut GlobalSettings, length: f64) {
+    let width = globals.img.width();
+    let height = globals.img.height();
+    let scale = (-globals.settings.exposure).exp2();
+
+    for axis in 0..3 {
+        let mut color = Vector3D::ZERO;
+        color[axis] = scale;
+
+        let point = |k: usize| {
+            let mut p = Vector3D::ZERO;
+            p[axis] = length * k as f64 / AXIS_SEGMENTS as f64;
+            globals.camera.project(p, width, height)
+        };
+
+        let mut pixels = Vec::new();
+        for k in 0..AXIS_SEGMENTS {
+            let (Some(from), Some(to)) = (point(k), point(k + 1)) else {
+                continue;
+            };
+
+            // Step at most a pixel at a time
+            let steps = (to.0 - from.0).abs().max((to.1 - from.1).abs()).ceil().clamp(1.0, 4096.0) as usize;
+            for s in 0..=steps {
+                let t = s as f64 / steps as f64;
+                let x = (from.0 + (to.0 - from.0) * t).floor();
+                let y = (from.1 + (to.1 - from.1) * t).floor();
+                if x >= 0.0 && y >= 0.0 && x < width as f64 && y < height as f64 {
+                    pixels.push(y as usize * width as usize + x as usize);
+                }
+            }
+        }
+
+        for i in pixels {
+            globals.framebuffer[i] = color;
+            globals.coverage[i] = 1.0;
+        }
+    }
+}
+
+// Relative depth step and normal change (1 - cos) at which an edge line
+// starts. Lines fade in between the threshold and twice the threshold, which
+// keeps them anti-aliased.
+const EDGE_DEPTH_THRESHOLD: f64 = 0.05;
+const EDGE_NORMAL_THRESHOLD: f64 = 0.3;
+
+// Darken pixels that sit on a depth or normal discontinuity, giving a
+// toon/technical look. Runs on the depth and normal buffers after all the
+// `render` passes are done, and darkens the framebuffer before it's
+// resolved.
+pub fn overlay_edges(globals: &mut GlobalSettings) {
+    let width = globals.img.width() as i64;
+    let height = globals.img.height() as i64;
+
+    let ramp = |v: f64, threshold: f64| ((v - threshold) / threshold).clamp(0.0, 1.0);
+
+    for y in 0..height {
+        for x in 0..width {
+            let i = (y * width + x) as usize;
+            let depth = globals.depth_buffer[i];
+
+            // The background never gets a line
+            if !depth.is_finite() {
+                continue;
+            }
+
+            let normal = globals.normal_buffer[i];
+            let mut strength: f64 = 0.0;
+
+            for (nx, ny) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+                if nx < 0 || ny < 0 || nx >= width || ny >= height {
+                    continue;
+                }
+
+                let j = (ny * width + nx) as usize;
+                let other_depth = globals.depth_buffer[j];
+
+                // Only the nearer side of a depth step is marked, so
+                // silhouettes stay one pixel thin
+                if !other_depth.is_finite() {
+                    strength = 1.0;
+                    break;
+                }
+
+                let depth_edge = ramp((other_depth - depth) / depth, EDGE_DEPTH_THRESHOLD);
+                let crease = 1.0 - normal.v3d_dot_mul(globals.normal_buffer[j]);
+                let normal_edge = ramp(crease, EDGE_NORMAL_THRESHOLD);
+
+                strength = strength.max(depth_edge).max(normal_edge);
+            }
+
+            if strength > 0.0 {
+                globals.framebuffer[i].v3d_mul_scalar(1.0 - strength);
+            }
+        }
+    }
+}
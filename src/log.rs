@@ -0,0 +1,99 @@
+// Tiny leveled logger. Messages go to stderr through the `error!`, `warn!`,
+// `info!` and `debug!` macros. How much gets through is set once with
+// `init`, from the RUST_LOG environment variable:
+//
+//   RUST_LOG=debug lux      everything
+//   RUST_LOG=warn lux       only problems
+//   RUST_LOG=lux=off lux    nothing
+//
+// Without RUST_LOG only `info!` and up are shown.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+#[derive(Clone, Copy, PartialEq, PartialOrd, Debug)]
+pub enum Level {
+    Error = 1,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl Level {
+    fn name(self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+        }
+    }
+}
+
+// Most verbose level that is printed, 0 for nothing
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+
+// Parse a level name. `None` if it isn't one. "trace" counts as debug, since
+// there's nothing more verbose.
+fn parse_level(s: &str) -> Option<u8> {
+    match s.trim().to_ascii_lowercase().as_str() {
+        "off" => Some(0),
+        "error" => Some(Level::Error as u8),
+        "warn" => Some(Level::Warn as u8),
+        "info" => Some(Level::Info as u8),
+        "debug" | "trace" => Some(Level::Debug as u8),
+        _ => None,
+    }
+}
+
+// Set the level from RUST_LOG. Understands a plain level ("debug") and
+// env_logger style "lux=debug" entries; entries for other crates are
+// ignored.
+pub fn init() {
+    let spec = match std::env::var("RUST_LOG") {
+        Ok(spec) => spec,
+        Err(_) => return,
+    };
+
+    for directive in spec.split(',') {
+        let level = match directive.split_once('=') {
+            Some((target, level)) if target.trim() == "lux" => parse_level(level),
+            Some(_) => None,
+            None => parse_level(directive),
+        };
+
+        if let Some(level) = level {
+            MAX_LEVEL.store(level, Ordering::Relaxed);
+        }
+    }
+}
+
+pub fn enabled(level: Level) -> bool {
+    level as u8 <= MAX_LEVEL.load(Ordering::Relaxed)
+}
+
+// Used by the macros
+pub fn log(level: Level, args: std::fmt::Arguments) {
+    if enabled(level) {
+        eprintln!("[{:<5}] {}", level.name(), args);
+    }
+}
+
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)*) => { $crate::log::log($crate::log::Level::Error, format_args!($($arg)*)) };
+}
+
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)*) => { $crate::log::log($crate::log::Level::Warn, format_args!($($arg)*)) };
+}
+
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => { $crate::log::log($crate::log::Level::Info, format_args!($($arg)*)) };
+}
+
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => { $crate::log::log($crate::log::Level::Debug, format_args!($($arg)*)) };
+}
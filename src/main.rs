@@ -1,514 +1,923 @@
-// A very simple ray tracer by Bourbon
-// Assume BSD-style license
-//
-// The images in the 'renders' folder are results of experiments with the
-// camera's position (`camerapos` variable in `Render` function).
-//
-// ----------------------------------------------------------------------------
+// Command line front end for the ray tracer. Builds the demo scene (or another
+// built-in scene, or loads a scene file), renders it and writes test.png.
+// With --batch it renders a whole directory of scene files instead, and
+// with --watch it renders the scene file again every time it changes.
+
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use lux::*;
+
+// What to do besides the render settings
+#[derive(Default)]
+struct Options {
+    // Write a PPM here instead of test.png
+    dump: Option<String>,
+    // Scene file to render instead of the demo scene
+    scene: Option<String>,
+    // Only print information about the scene
+    inspect: bool,
+    // Don't render scenes with warnings
+    strict: bool,
+    // Also write an image of the samples spent per pixel here
+    heatmap: Option<String>,
+    // Render every scene file in this directory
+    batch: Option<String>,
+    // Also write the render statistics here, as JSON
+    stats_json: Option<String>,
+    // Write the render at each of these exposures (in stops) instead
+    bracket: Vec<f64>,
+    // Render at each of these fields of view and write them side by side
+    fov_sheet: Vec<f64>,
+    // Also write this diagnostic image next to the render
+    overlay: Option<Overlay>,
+    // Also write the albedo and normal buffers next to the render
+    aux: bool,
+    // Only print the render settings embedded in this PNG
+    show_metadata: Option<String>,
+    // Render the scene file again whenever it changes
+    watch: bool,
+    // Save the samples taken so far here now and then, and with --resume
+    // carry on from them
+    checkpoint: Option<String>,
+    resume: bool,
+}
+
+// Read the render and camera settings. They start out as the defaults, or
+// as the config file given with --config (render.toml in the working
+// directory if there is one). Flags on the command line override both:
 //
-// PRIM is for a primitive (Sphere here)
-
-use image::{ImageBuffer, RgbaImage};
-
-// Global constants
-// `u32` for compatibility with 
-const RESX: u32 = 1920;
-const RESY: u32 = 1080;
-const MAXPRIMCOUNT: u32 = 64;
-const MAXLIGHTCOUNT: u32 = 10;
-const MAXTHREADS: u32 = 4;
-
-// Three dimensional vector
-#[derive(Clone, Copy)]
-struct Vector3D {
-    x: f64,
-    y: f64,
-    z: f64,
+//   lux [--scene FILE|NAME | --inspect FILE | --batch DIR | --show-metadata FILE] [--watch] [--strict] [--dump FILE] [--config FILE]
+//       [--width N] [--height N] [--bit-depth 8|16]
+//       [--pathtrace] [--no-light-sampling] [--shading MODE] [--samples N] [--sampler random|stratified]
+//       [--filter box|tent|gaussian] [--filter-radius R]
+//       [--clamp|--clamp-indirect MAX] [--reject-outliers PERCENT] [--dispersion split|spectral] [--depth N] [--seed N] [--edges] [--axes LENGTH] [--toon-bands K] [--shadows] [--caustics] [--photons N]
+//       [--denoise] [--denoise-strength S] [--denoise-radius N] [--dither]
+//       [--exposure EV] [--bracket EV,EV,...] [--overlay clipping|falloff LIGHT] [--bvh median|sah]
+//       [--shutter T] [--camera-velocity X,Y,Z] [--fov DEGREES] [--fov-sheet DEGREES,...] [--roll DEGREES] [--look-at X,Y,Z]
+//       [--equirect] [--fisheye DEGREES] [--ray-epsilon E]
+//       [--stereo anaglyph|side-by-side] [--eye-separation D] [--convergence D]
+//       [--region X,Y,W,H] [--adaptive [BASE,MAX,THRESHOLD]] [--min-samples N] [--adaptive-threshold T]
+//       [--max-time TIME] [--target-noise T] [--heatmap FILE] [--conserve-energy] [--cull-back-faces]
+//       [--checkpoint FILE] [--checkpoint-every TIME|Nspp] [--resume]
+//       [--stats] [--stats-json FILE] [--light-aovs] [--aux] [--threads N]
+fn parse_args() -> (RenderConfig, Options) {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let path = match args.iter().position(|a| a == "--config") {
+        Some(i) => match args.get(i + 1) {
+            Some(path) => Some(path.as_str()),
+            None => {
+                eprintln!("--config expects a file name");
+                std::process::exit(1);
+            }
+        },
+        None => Some(config::DEFAULT_CONFIG_FILE)
+            .filter(|p| std::path::Path::new(p).exists()),
+    };
+
+    let mut config = match path.map(RenderConfig::load) {
+        Some(Ok(config)) => {
+            debug!("Loaded settings from {}", path.unwrap_or_default());
+            config
+        }
+        Some(Err(e)) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+        None => RenderConfig::default(),
+    };
+
+    let options = apply_args(&mut config, args.into_iter());
+    (config, options)
 }
 
-// Methods on Vectors
-impl Vector3D {
-    // Instantiate vector
-    fn v3d_new(coordinates: (f64, f64, f64)) -> Self {
-        Self {
-            x: coordinates.0,
-            y: coordinates.1,
-            z: coordinates.2,
+// Apply command line flags on top of `config`
+fn apply_args(config: &mut RenderConfig, args: impl Iterator<Item = String>) -> Options {
+    let mut args = args.peekable();
+    let mut options = Options::default();
+    let settings = &mut config.render;
+    let camera = &mut config.camera;
+
+    // Numeric value following a flag
+    fn value<T: std::str::FromStr>(flag: &str, arg: Option<String>) -> T {
+        match arg.as_deref().map(str::parse) {
+            Some(Ok(v)) => v,
+            _ => {
+                eprintln!("{} expects a number", flag);
+                std::process::exit(1);
+            }
         }
     }
 
-    // Clone `original_vector` into new vector `self`
-    // original_vector <---- self
-    // This is only because I was too lazy to implement `Clone` or `Copy`.
-    fn _v3d_clone_from(&mut self, original_vector: Vector3D) {
-        self.x = original_vector.x;
-        self.y = original_vector.y;
-        self.z = original_vector.z;
+    // File name following a flag
+    fn file(flag: &str, arg: Option<String>) -> String {
+        arg.unwrap_or_else(|| {
+            eprintln!("{} expects a file name", flag);
+            std::process::exit(1);
+        })
     }
 
-    // Print vector
-    // We could use `impl Display` but hey! :)
-    fn _v3d_print(&self) {
-        println!("x: {} y: {} z: {}", self.x, self.y, self.z);
-    }
+    // "x,y,z" vector following a flag
+    fn vector(flag: &str, arg: Option<String>) -> Vector3D {
+        let parts: Vec<f64> = arg
+            .unwrap_or_default()
+            .split(',')
+            .map(|p| p.trim().parse().unwrap_or(f64::NAN))
+            .collect();
+
+        if parts.len() != 3 || parts.iter().any(|p| p.is_nan()) {
+            eprintln!("{} expects a vector like 1,0,0", flag);
+            std::process::exit(1);
+        }
 
-    // Add to the vector
-    fn v3d_add(&mut self, other_vector: Vector3D) {
-        self.x += other_vector.x;
-        self.y += other_vector.y;
-        self.z += other_vector.z;
+        Vector3D::new(parts[0], parts[1], parts[2])
     }
 
-    // Subtract from the vector
-    fn v3d_sub(&mut self, other_vector: Vector3D) {
-        self.x -= other_vector.x;
-        self.y -= other_vector.y;
-        self.z -= other_vector.z;
+    // "a,b,c" list of numbers following a flag
+    fn numbers(flag: &str, arg: Option<String>) -> Vec<f64> {
+        let parts: Option<Vec<f64>> = arg
+            .unwrap_or_default()
+            .split(',')
+            .map(|p| p.trim().parse().ok())
+            .collect();
+
+        parts.unwrap_or_else(|| {
+            eprintln!("{} expects a list of numbers like -1,0,1", flag);
+            std::process::exit(1);
+        })
     }
 
-    // Multiply by a scalar
-    fn v3d_mul_scalar(&mut self, scalar: f64) {
-        self.x *= scalar;
-        self.y *= scalar;
-        self.z *= scalar;
-    }
+    // Pixel rectangle following a flag
+    fn region(flag: &str, arg: Option<String>) -> Region {
+        let parts: Vec<u32> = arg
+            .unwrap_or_default()
+            .split(',')
+            .map(|p| p.trim().parse().ok())
+            .collect::<Option<_>>()
+            .unwrap_or_default();
+
+        if parts.len() != 4 {
+            eprintln!("{} expects a pixel rectangle like 0,0,64,64", flag);
+            std::process::exit(1);
+        }
 
-    // Multiply by another vector
-    fn v3d_mul_v3d(&mut self, other_vector: Vector3D) {
-        self.x *= other_vector.x;
-        self.y *= other_vector.y;
-        self.z *= other_vector.z;
+        Region {
+            x: parts[0],
+            y: parts[1],
+            width: parts[2],
+            height: parts[3],
+        }
     }
 
-    // Dot multiplication
-    fn v3d_dot_mul(&self, other_vector: Vector3D) -> f64 {
-        self.x * other_vector.x + self.y * other_vector.y + self.z * other_vector.z
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => {
+                args.next();
+            }
+            "--dump" => options.dump = Some(file(&arg, args.next())),
+            "--batch" => options.batch = Some(file(&arg, args.next())),
+            "--scene" => options.scene = Some(file(&arg, args.next())),
+            "--inspect" => {
+                options.scene = Some(file(&arg, args.next()));
+                options.inspect = true;
+            }
+            "--strict" => options.strict = true,
+            "--watch" => options.watch = true,
+            "--show-metadata" => options.show_metadata = Some(file(&arg, args.next())),
+            "--width" => config.width = value(&arg, args.next()),
+            "--height" => config.height = value(&arg, args.next()),
+            "--bit-depth" => {
+                config.bit_depth = value(&arg, args.next());
+                if config.bit_depth != 8 && config.bit_depth != 16 {
+                    eprintln!("--bit-depth expects 8 or 16");
+                    std::process::exit(1);
+                }
+            }
+            "--pathtrace" => settings.mode = RenderMode::PathTrace,
+            "--no-light-sampling" => settings.light_sampling = false,
+            "--clamp" | "--clamp-indirect" => settings.clamp = Some(value(&arg, args.next())),
+            "--reject-outliers" => settings.reject_outliers = Some(value(&arg, args.next())),
+            "--shading" => {
+                settings.shading = match args.next().as_deref().and_then(Shading::from_name) {
+                    Some(shading) => shading,
+                    None => {
+                        eprintln!("--shading expects {}", Shading::NAMES);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "--samples" => settings.samples = value(&arg, args.next()),
+            "--sampler" => {
+                settings.sampler = match args.next().as_deref() {
+                    Some("random") => Sampler::Random,
+                    Some("stratified") => Sampler::Stratified,
+                    _ => {
+                        eprintln!("--sampler expects random or stratified");
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "--filter" => {
+                settings.filter = match args.next().as_deref() {
+                    Some("box") => Filter::Box,
+                    Some("tent") => Filter::Tent,
+                    Some("gaussian") => Filter::Gaussian,
+                    _ => {
+                        eprintln!("--filter expects box, tent or gaussian");
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "--filter-radius" => settings.filter_radius = Some(value(&arg, args.next())),
+            "--dispersion" => {
+                settings.dispersion = match args.next().as_deref() {
+                    Some("split") => Dispersion::Split,
+                    Some("spectral") => Dispersion::Spectral,
+                    _ => {
+                        eprintln!("--dispersion expects split or spectral");
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "--adaptive" => {
+                settings.adaptive = true;
+                // Optionally followed by the base and most samples and the
+                // threshold all at once
+                if args.peek().is_some_and(|a| !a.starts_with("--")) {
+                    match numbers(&arg, args.next())[..] {
+                        [base, max, threshold] if base >= 1.0 && max >= base && base.fract() == 0.0 && max.fract() == 0.0 => {
+                            settings.min_samples = base as u32;
+                            settings.samples = max as u32;
+                            settings.adaptive_threshold = threshold;
+                        }
+                        _ => {
+                            eprintln!("--adaptive expects nothing or BASE,MAX,THRESHOLD like 4,64,0.01");
+                            std::process::exit(1);
+                        }
+                    }
+                }
+            }
+            "--min-samples" => settings.min_samples = value(&arg, args.next()),
+            "--adaptive-threshold" => settings.adaptive_threshold = value(&arg, args.next()),
+            "--max-time" => {
+                settings.max_time = args.next().as_deref().and_then(config::duration);
+                if settings.max_time.is_none() {
+                    eprintln!("--max-time expects a time like 60s, 5m or 500ms");
+                    std::process::exit(1);
+                }
+            }
+            "--target-noise" => settings.target_noise = Some(value(&arg, args.next())),
+            "--checkpoint" => options.checkpoint = Some(file(&arg, args.next())),
+            "--checkpoint-every" => {
+                settings.checkpoint_every = match args.next().as_deref().and_then(config::checkpoint_interval) {
+                    Some(every) => every,
+                    None => {
+                        eprintln!("--checkpoint-every expects a time like 5m or samples per pixel like 16spp");
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "--resume" => options.resume = true,
+            "--heatmap" => options.heatmap = Some(file(&arg, args.next())),
+            "--depth" => settings.max_depth = value(&arg, args.next()),
+            "--seed" => settings.seed = value(&arg, args.next()),
+            "--edges" => settings.edges = true,
+            "--axes" => settings.axes = Some(value(&arg, args.next())),
+            "--toon-bands" => settings.toon_bands = Some(value(&arg, args.next())),
+            "--shadows" => settings.shadows = true,
+            "--caustics" => settings.caustics = true,
+            "--photons" => settings.photons = value(&arg, args.next()),
+            "--stats" => settings.stats = true,
+            "--stats-json" => {
+                settings.stats = true;
+                options.stats_json = Some(file(&arg, args.next()));
+            }
+            "--denoise" => settings.denoise = true,
+            "--denoise-strength" => settings.denoise_strength = value(&arg, args.next()),
+            "--denoise-radius" => settings.denoise_radius = value(&arg, args.next()),
+            "--dither" => settings.dither = true,
+            "--light-aovs" => settings.light_aovs = true,
+            "--aux" => options.aux = true,
+            "--bvh" => {
+                settings.bvh = match args.next().as_deref() {
+                    Some("median") => BvhBuild::Median,
+                    Some("sah") => BvhBuild::Sah,
+                    _ => {
+                        eprintln!("--bvh expects median or sah");
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "--exposure" => settings.exposure = value(&arg, args.next()),
+            "--bracket" | "--brackets" => options.bracket = numbers(&arg, args.next()),
+            "--fov-sheet" => options.fov_sheet = numbers(&arg, args.next()),
+            "--overlay" => {
+                options.overlay = match args.next().as_deref() {
+                    Some("clipping") => Some(Overlay::Clipping),
+                    Some("falloff") => Some(Overlay::Falloff(value("--overlay falloff", args.next()))),
+                    _ => {
+                        eprintln!("--overlay expects clipping or falloff LIGHT");
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "--shutter" => camera.shutter_time = value(&arg, args.next()),
+            "--camera-velocity" => camera.velocity = vector(&arg, args.next()),
+            "--fov" => camera.fov = value(&arg, args.next()),
+            "--roll" => camera.roll = value(&arg, args.next()),
+            "--look-at" => {
+                if let Err(e) = camera.look_at(vector(&arg, args.next()), Vector3D::UNIT_Y) {
+                    eprintln!("--look-at: {}", e);
+                    std::process::exit(1);
+                }
+            }
+            "--equirect" => camera.projection = Projection::Equirectangular,
+            "--fisheye" => camera.projection = Projection::Fisheye { fov_degrees: value(&arg, args.next()) },
+            "--ray-epsilon" => settings.ray_epsilon = value(&arg, args.next()),
+            "--stereo" => {
+                settings.stereo = match args.next().as_deref() {
+                    Some("anaglyph") => Some(StereoMode::Anaglyph),
+                    Some("side-by-side") => Some(StereoMode::SideBySide),
+                    _ => {
+                        eprintln!("--stereo expects anaglyph or side-by-side");
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "--eye-separation" => settings.eye_separation = value(&arg, args.next()),
+            "--convergence" => settings.convergence = value(&arg, args.next()),
+            "--conserve-energy" => settings.conserve_energy = true,
+            "--cull-back-faces" => settings.cull_back_faces = true,
+            "--region" => settings.region = Some(region(&arg, args.next())),
+            "--threads" => settings.threads = value(&arg, args.next()),
+            _ => {
+                eprintln!("Unknown option: {}", arg);
+                eprintln!("Usage: lux [--scene FILE|NAME | --inspect FILE | --batch DIR | --show-metadata FILE] [--watch] [--strict] [--dump FILE] [--config FILE]");
+                eprintln!("           [--width N] [--height N] [--bit-depth 8|16]");
+                eprintln!("           [--pathtrace] [--no-light-sampling] [--shading MODE] [--samples N] [--sampler random|stratified]");
+                eprintln!("           [--filter box|tent|gaussian] [--filter-radius R]");
+                eprintln!("           [--clamp|--clamp-indirect MAX] [--reject-outliers PERCENT] [--dispersion split|spectral] [--depth N] [--seed N] [--edges] [--axes LENGTH] [--toon-bands K] [--shadows] [--caustics] [--photons N]");
+                eprintln!("           [--denoise] [--denoise-strength S] [--denoise-radius N] [--dither]");
+                eprintln!("           [--exposure EV] [--bracket EV,EV,...] [--overlay clipping|falloff LIGHT] [--bvh median|sah]");
+                eprintln!("           [--shutter T] [--camera-velocity X,Y,Z] [--fov DEGREES] [--fov-sheet DEGREES,...] [--roll DEGREES] [--look-at X,Y,Z]");
+                eprintln!("           [--equirect] [--fisheye DEGREES] [--ray-epsilon E]");
+                eprintln!("           [--stereo anaglyph|side-by-side] [--eye-separation D] [--convergence D]");
+                eprintln!("           [--region X,Y,W,H] [--adaptive [BASE,MAX,THRESHOLD]] [--min-samples N] [--adaptive-threshold T]");
+                eprintln!("           [--max-time TIME] [--target-noise T] [--heatmap FILE] [--conserve-energy] [--cull-back-faces]");
+                eprintln!("           [--checkpoint FILE] [--checkpoint-every TIME|Nspp] [--resume]");
+                eprintln!("           [--stats] [--stats-json FILE] [--light-aovs] [--aux] [--threads N]");
+                std::process::exit(1);
+            }
+        }
     }
 
-    // Cross multiplication
-    fn _v3d_cross_mul(&mut self, b: Vector3D, c: Vector3D) {
-        self.x = b.y * c.z - b.z * c.y;
-        self.y = b.z * c.x - b.x * c.z;
-        self.z = b.x * c.y - b.y * c.x;
-    }
+    options
+}
 
-    // Length of vector
-    fn v3d_length(&self) -> f64 {
-        (self.x * self.x + self.y * self.y + self.z * self.z).sqrt()
+// Print what --inspect shows about the scene
+fn print_report(path: &str, config: &RenderConfig, globals: &GlobalSettings, warnings: &[scene::Warning]) {
+    let stats = globals.stats();
+    let kinds: Vec<String> = stats.primitives.iter().map(|(kind, n)| format!("{} {}", n, kind)).collect();
+    let camera = &config.camera;
+
+    println!("Scene {}", path);
+    println!("  primitives: {} ({})", globals.primitives().len(), kinds.join(", "));
+    println!("  lights:     {}", stats.lights);
+    match stats.bounds {
+        Some((min, max)) => println!(
+            "  bounds:     ({:.3}, {:.3}, {:.3}) to ({:.3}, {:.3}, {:.3})",
+            min.x, min.y, min.z, max.x, max.y, max.z
+        ),
+        None => println!("  bounds:     empty"),
     }
-
-    // Length of but without sqrt
-    fn _v3d_length_sqr(&self) -> f64 {
-        self.x * self.x + self.y * self.y + self.z * self.z
+    println!(
+        "  camera:     at ({:.3}, {:.3}, {:.3}), {}, roll {}°",
+        camera.position.x,
+        camera.position.y,
+        camera.position.z,
+        match camera.projection {
+            Projection::Perspective => format!("perspective, fov {:.1}°", camera.fov),
+            Projection::Equirectangular => "equirectangular".to_string(),
+            Projection::Fisheye { fov_degrees } => format!("fisheye, fov {:.1}°", fov_degrees),
+        },
+        camera.roll
+    );
+    println!(
+        "  image:      {}x{}, about {:.1} MiB",
+        config.width,
+        config.height,
+        stats.memory as f64 / (1024.0 * 1024.0)
+    );
+
+    if warnings.is_empty() {
+        println!("No warnings");
+    } else {
+        println!("Warnings:");
+        for w in warnings {
+            println!("  {}", w);
+        }
     }
+}
 
-    // Normalise vector
-    fn v3d_norm(&mut self) {
-        let l: f64 = 1.0 / self.v3d_length();
-        self.x *= l;
-        self.y *= l;
-        self.z *= l;
+// "out.png" with the exposure in the name, like "out_ev-1.png"
+fn bracket_path(path: &str, ev: f64) -> String {
+    match ev == 0.0 {
+        true => suffixed_path(path, "ev0"),
+        false => suffixed_path(path, &format!("ev{:+}", ev)),
     }
 }
 
-// Material properties and color
-#[derive(Clone, Copy)]
-struct Material {
-    specular: f64,
-    diffusive: f64,
-    reflective: f64,
-    color: Vector3D,
-}
+// "out.png" with `suffix` added to the name, like "out_suffix.png"
+fn suffixed_path(path: &str, suffix: &str) -> String {
+    let path = std::path::Path::new(path);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = format!("{}_{}", stem, suffix);
 
-// Sphere
-#[derive(Copy, Clone)]
-struct PrimSphere {
-    position: Vector3D,
-    radius: f64,
-    m: Material,
+    match path.extension() {
+        Some(ext) => path.with_file_name(format!("{}.{}", name, ext.to_string_lossy())),
+        None => path.with_file_name(name),
+    }
+    .display()
+    .to_string()
 }
 
-// Light ray
-#[derive(Copy, Clone)]
-struct Ray {
-    direction: Vector3D,
-    origin: Vector3D,
-}
+// Save the rendered `globals` as `path` with the bit depth `config` asks for,
+// with the settings it was rendered with embedded (see metadata.rs)
+fn save_render(globals: &GlobalSettings, config: &RenderConfig, path: &str, scene: &str, render_time: Duration) -> Result<(), LuxError> {
+    info!("Writing {}...", path);
 
-#[derive(Copy, Clone)]
-// Light source
-struct Light {
-    position: Vector3D,
-    color: Vector3D,
+    let metadata = metadata::describe(globals, scene, render_time);
+    if config.bit_depth == 16 {
+        metadata::save_16bit(globals, path, &metadata)
+    } else {
+        metadata::save_image(&globals.img, path, &metadata)
+    }
 }
 
-// Global struct
-#[derive(Clone)]
-struct GlobalSettings {
-    img: RgbaImage,
+// Render `globals`, made from `scene`, and save the image as `path`, as a
+// stereo pair, a sheet of fields of view (--fov-sheet) or with the bit depth
+// `config` asks for. With exposures to bracket (--bracket) the render is
+// resolved and saved once for each of them (relative to the exposure in the
+// settings) instead.
+fn render_to_file(globals: &mut GlobalSettings, config: &RenderConfig, path: &str, scene: &str, options: &Options) -> Result<(), LuxError> {
+    let start = Instant::now();
+    let bracket = &options.bracket;
+    if let Some(overlay) = options.overlay {
+        overlay.check(globals)?;
+    }
 
-    primitive_count: u32,
-    primitive_list: Vec<PrimSphere>,
+    // Both render more than once, and each render would take the
+    // checkpoint of the last one for its own
+    if globals.checkpoint.is_some() && (!options.fov_sheet.is_empty() || globals.settings.stereo.is_some()) {
+        warn!("Stereo pairs and field of view sheets can't be checkpointed");
+        globals.checkpoint = None;
+    }
 
-    light_count: u32,
-    light_list: Vec<Light>,
-}
+    if !options.fov_sheet.is_empty() {
+        if globals.settings.stereo.is_some() || !bracket.is_empty() || options.overlay.is_some() || options.aux {
+            warn!("The field of view sheet is written on its own, without stereo, brackets, overlays or albedo and normal buffers");
+        }
 
-// Methods for `Prim` 
-impl PrimSphere{
-	fn normal(&self, pos: Vector3D) -> Vector3D {
-		let mut ret = pos;
-		ret.v3d_sub(self.position);
-		let f = (1.0 / self.radius) as f64;
-		ret.v3d_mul_scalar(f);
-		ret.v3d_norm();
-
-		return ret;
-	}
-
-	fn intersect(&self, ray: Ray, dist: &mut f64) -> i32 {
-		let mut v_precalc = ray.origin;
-		v_precalc.v3d_sub(self.position);
-
-		let det_precalc: f64 = self.radius * self.radius - v_precalc.v3d_dot_mul(v_precalc);
-
-		let b = - v_precalc.v3d_dot_mul(ray.direction);
-		let mut det = b*b + det_precalc;
-
-		let mut retval: i32 = 0;
-
-		if det > 0.0 {
-			det = det.sqrt();
-			let i1 = b - det;
-			let i2 = b + det;
-
-			if i2 > 0.0 && i1 < 0.0 {
-				retval = -1;
-				*dist = i2;
-			} else if i2 > 0.0 && i1 >= 0.0 {
-				retval = 1;
-				*dist = i1;
-			}
-		}
-
-		return retval;
-	}
-}
+        let sheet = render_fov_sheet(globals, &options.fov_sheet, config)?;
+        if globals.budget_stop == Some(BudgetStop::Cancelled) {
+            return Ok(());
+        }
+        info!("Writing {}...", path);
+        return metadata::save_image(&sheet, path, &metadata::describe(globals, scene, start.elapsed()));
+    }
 
-// Spawn a sphere at the specified position `pos`.
-fn add_sphere(pos: &Vector3D, rad: f64, m: &Material, globals: &mut GlobalSettings) {
-    if globals.primitive_count < MAXPRIMCOUNT {
-        let p = PrimSphere {
-            position: Vector3D::v3d_new((pos.x, pos.y, pos.z)),
-            radius: rad,
-            m: m.clone(),
-        };
+    match globals.settings.stereo {
+        Some(mode) => {
+            if config.bit_depth == 16 {
+                warn!("Stereo images are always written with 8 bits per channel");
+            }
+            if !bracket.is_empty() {
+                warn!("Stereo images can't be bracketed");
+            }
+            if options.overlay.is_some() {
+                warn!("Stereo images don't get overlays");
+            }
+            if options.aux {
+                warn!("Stereo images don't get albedo and normal buffers");
+            }
 
-        globals.primitive_list.push(p);
-        globals.primitive_count += 1;
-    }
-}
+            let img = render_stereo(globals, mode)?;
+            if globals.budget_stop == Some(BudgetStop::Cancelled) {
+                return Ok(());
+            }
+            info!("Writing {}...", path);
+            metadata::save_image(&img, path, &metadata::describe(globals, scene, start.elapsed()))
+        }
+        None => {
+            let camera = globals.camera;
+            render_to_buffer(globals, &camera, config)?;
+            if globals.budget_stop == Some(BudgetStop::Cancelled) {
+                return Ok(());
+            }
+            let render_time = start.elapsed();
+            write_light_aovs(globals, path)?;
+            write_overlay(globals, path, options.overlay)?;
+            if options.aux {
+                write_aux(globals, path)?;
+            }
+            if bracket.is_empty() {
+                return save_render(globals, config, path, scene, render_time);
+            }
 
-// Spawn a light source at the specified position `pos`. Call this less than 
-// the `MAXLIGHTCOUNT` times in `main` to add more light sources.
-fn add_light(pos: Vector3D, color: Vector3D, globals: &mut GlobalSettings) {
-    if globals.light_count < MAXLIGHTCOUNT {
-        let l = Light {
-            position: Vector3D::v3d_new((pos.x, pos.y, pos.z)),
-            color: Vector3D::v3d_new((color.x, color.y, color.z)),
-        };
+            // Only the resolve runs again, the framebuffer stays as rendered
+            let exposure = globals.settings.exposure;
+            for &ev in bracket {
+                globals.settings.exposure = exposure + ev;
+                resolve(globals);
+                save_render(globals, config, &bracket_path(path, ev), scene, render_time)?;
+            }
 
-        globals.light_list.push(l);
-        globals.light_count += 1;
+            globals.settings.exposure = exposure;
+            resolve(globals);
+            Ok(())
+        }
     }
 }
 
-// Trace the ray
-fn trace(ray: &Ray, refl_depth: u32, globals: &mut GlobalSettings) -> Vector3D{
-    let mut color: Vector3D = Vector3D::v3d_new((0.02, 0.1, 0.17));
-
-    let mut dist: f64 = 1000000000.0;
-    let mut prim: Option<PrimSphere> = None;
-
-    // Make the ray bounce off every sphere 
-    for i in 0..globals.primitive_count {
-        let mut temp_dist: f64 = 0.0;
-        let p = globals.primitive_list[i as usize];
-
-        let res = p.intersect(*ray, &mut temp_dist);
+// Render every .toml scene file in `dir` to a PNG with the same name next to
+// it. A scene that can't be loaded or rendered is reported and skipped.
+// Returns how many of them failed.
+fn render_batch(dir: &str, config: &RenderConfig, options: &Options) -> usize {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            error!("{}: {}", dir, e);
+            std::process::exit(1);
+        }
+    };
 
-        if res == 0 {
+    let mut scenes: Vec<std::path::PathBuf> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == "toml"))
+        .collect();
+    scenes.sort();
+
+    let mut failed = 0;
+    for scene_path in &scenes {
+        let name = scene_path.display().to_string();
+        let out = scene_path.with_extension("png").display().to_string();
+        info!("Rendering {}...", name);
+
+        let mut globals = GlobalSettings::new(config.width, config.height, config.render, config.camera);
+        if let Err(e) = scene::load(&name, &mut globals) {
+            error!("{}", e);
+            failed += 1;
             continue;
         }
 
-        if temp_dist < dist {
-            prim = Some(p);
-            dist = temp_dist;
-            // result = ret;
+        let warnings = globals.validate();
+        for w in &warnings {
+            warn!("{}: {}", name, w);
+        }
+        if options.strict && !warnings.is_empty() {
+            error!("{}: not rendering a scene with warnings (--strict)", name);
+            failed += 1;
+            continue;
         }
-    }
-
-    match prim {
-        Some(_) => {},
-        None => {
-            let ret_vector = Vector3D{
-                x: color.x,
-                y: color.y,
-                z: color.z,
-            };
 
-            return ret_vector;
+        if let Err(e) = render_to_file(&mut globals, config, &out, &name, options) {
+            error!("{}", e);
+            failed += 1;
         }
     }
 
-    let prim = prim.unwrap();
+    info!("Rendered {} of {} scenes", scenes.len() - failed, scenes.len());
+    failed
+}
 
-    let mut pi = ray.direction;
-    pi.v3d_mul_scalar(dist);
-    pi.v3d_add(ray.origin);
+// Save an image per light group next to `path` ("out_light_key.png"), and
+// one of the light that didn't come from any light ("out_light_other.png"),
+// if --light-aovs asks for them
+fn write_light_aovs(globals: &GlobalSettings, path: &str) -> Result<(), LuxError> {
+    let groups = globals.light_groups();
+    let names = groups.iter().copied().chain(["other"]);
+
+    for (index, name) in names.enumerate().take(globals.light_aovs.len()) {
+        let out = suffixed_path(path, &format!("light_{}", name));
+        info!("Writing {}...", out);
+        save_image(&resolve_light_aov(globals, index), &out)?;
+    }
+    Ok(())
+}
 
-    let prim_color: Vector3D = prim.m.color;
+// Save the diagnostic image --overlay asks for next to `path`
+// ("out_overlay.png")
+fn write_overlay(globals: &GlobalSettings, path: &str, overlay: Option<Overlay>) -> Result<(), LuxError> {
+    match overlay {
+        Some(overlay) => {
+            let out = suffixed_path(path, "overlay");
+            info!("Writing {}...", out);
+            save_image(&overlay_image(globals, overlay)?, &out)
+        }
+        None => Ok(()),
+    }
+}
 
-    for i in 0..globals.light_count {
-        let light_iter = globals.light_list[i as usize];
+// Save the albedo and normal buffers next to `path` as OpenEXR images
+// ("out_albedo.exr" and "out_normal.exr"), for denoisers
+fn write_aux(globals: &GlobalSettings, path: &str) -> Result<(), LuxError> {
+    let (width, height) = globals.img.dimensions();
+    for (name, buffer) in [("albedo", &globals.albedo_buffer), ("normal", &globals.normal_buffer)] {
+        let out = Path::new(&suffixed_path(path, name)).with_extension("exr").display().to_string();
+        info!("Writing {}...", out);
+        write_exr(&out, buffer, width, height)?;
+    }
+    Ok(())
+}
 
-        let mut l: Vector3D = light_iter.position;
-        l.v3d_sub(pi);
-        l.v3d_norm();
+// Save the samples per pixel if --heatmap asks for it
+fn write_heatmap(options: &Options, globals: &GlobalSettings) -> Result<(), LuxError> {
+    match &options.heatmap {
+        Some(path) => {
+            info!("Writing {}...", path);
+            save_image(&sample_heatmap(globals), path)
+        }
+        None => Ok(()),
+    }
+}
 
-        let n: Vector3D = prim.normal(pi);
-        
-        if prim.m.diffusive > 0.0 {
-            let dot = l.v3d_dot_mul(n);
-            if dot > 0.0 {
-                let diff = dot * prim.m.diffusive;
+// Print the render statistics if --stats asks for them, and save them if
+// --stats-json does
+fn write_stats(options: &Options, globals: &GlobalSettings) -> Result<(), LuxError> {
+    if !globals.settings.stats {
+        return Ok(());
+    }
 
-                //color += ((lightiter)->Color * prim_color) * diff;
-                let mut color_add = light_iter.color;
-                color_add.v3d_mul_v3d(prim_color);
-                color_add.v3d_mul_scalar(diff);
-                color.v3d_add(color_add);
+    print!("{}", stats::table(&globals.pass_stats));
 
-            }
+    match &options.stats_json {
+        Some(path) => {
+            info!("Writing {}...", path);
+            std::fs::write(path, stats::json(&globals.pass_stats)).map_err(|source| LuxError::Io {
+                path: path.to_string(),
+                source,
+            })
         }
+        None => Ok(()),
+    }
+}
 
-        if prim.m.specular > 0.0 {
-            //FIXME: Maybe this is messed up.
-
-            // R = L -  N * L.Dot(N) * 2.0l;
-            let mut r1: Vector3D = n;
-            let r2: f64 = l.v3d_dot_mul(n) * 2.0;
-            r1.v3d_mul_scalar(r2);
-            let mut r: Vector3D = l;
-            r.v3d_sub(r1);
+// Load (or build) the scene and render it the way `options` asks
+fn run(config: &RenderConfig, options: &Options) -> Result<(), LuxError> {
+    info!("Creating scene...");
 
-            let mut dot: f64 = ray.direction.v3d_dot_mul(r);
-            if dot > 0.0 {
-                dot *= dot;
-                dot *= dot;
-                dot *= dot;
-                let spec = dot * prim.m.specular;
+    let mut globals = GlobalSettings::new(config.width, config.height, config.render, config.camera);
 
-                let mut color_add = light_iter.color;
-                color_add.v3d_mul_scalar(spec);
-                color.v3d_add(color_add);
-            }
+    // Built-in scenes go by name, anything else is a scene file
+    let built_in = match options.scene.as_deref().map(|s| (s, demo::find(s))) {
+        Some((_, Some(build))) => {
+            build(&mut globals);
+            true
         }
+        Some((path, None)) => {
+            scene::load(path, &mut globals)?;
+            false
+        }
+        None => {
+            demo::build_scene(&mut globals);
+            true
+        }
+    };
 
-        let refl = prim.m.reflective;
-        if refl > 0.0 && refl_depth < 4 {
-            prim.normal(pi);
-
+    // Built-in scenes can change the settings, like `floor` turning on the
+    // shadows
+    let config = &RenderConfig {
+        render: globals.settings,
+        ..*config
+    };
 
-            let mut r: Vector3D = ray.direction;
-            let mut r1: Vector3D = n;
+    // The built-in scenes keep their look, even where their materials add
+    // up to more than 1. Only the user's own scenes are worth a warning
+    // about that.
+    let mut warnings = globals.validate();
+    if built_in {
+        warnings.retain(|w| !matches!(w, scene::Warning::EnergyGain { .. }));
+    }
 
-            let r2 = ray.direction.v3d_dot_mul(n) * 2.0;
-            r1.v3d_mul_scalar(r2);
-            r.v3d_sub(r1);
+    if options.inspect {
+        print_report(options.scene.as_deref().unwrap_or_default(), config, &globals, &warnings);
+        return Ok(());
+    }
 
-            // newpi = pi + r * 0.0001
-            let mut newpi: Vector3D = r;
-            newpi.v3d_mul_scalar(0.0001);
-            newpi.v3d_add(pi);
+    for w in &warnings {
+        warn!("{}", w);
+    }
+    if options.strict && !warnings.is_empty() {
+        error!("Not rendering a scene with warnings (--strict)");
+        std::process::exit(1);
+    }
 
-            let tempr: Ray = Ray {
-                origin: newpi,
-                direction: r,
-            };
+    globals.checkpoint = options.checkpoint.clone();
+    globals.resume = options.resume;
 
-            let mut rcol:Vector3D = trace(&tempr, refl_depth +1, globals);
+    info!("Rendering...");
+    write_outputs(&mut globals, config, options, options.scene.as_deref().unwrap_or("demo"))
+}
 
-            rcol.v3d_mul_scalar(refl);
-            rcol.v3d_mul_v3d(prim_color);
-            color.v3d_add(rcol);
+// Render `globals`, made from `scene`, and write test.png (or the PPM of
+// --dump) and whatever else `options` asks for. A cancelled render writes
+// nothing.
+fn write_outputs(globals: &mut GlobalSettings, config: &RenderConfig, options: &Options, scene: &str) -> Result<(), LuxError> {
+    // Plain 8-bit PPM of the normal render, for comparing against the
+    // golden images in tests/golden
+    if let Some(path) = &options.dump {
+        render_scene(globals)?;
+        if globals.budget_stop == Some(BudgetStop::Cancelled) {
+            return Ok(());
         }
-    }
+        write_heatmap(options, globals)?;
+        write_stats(options, globals)?;
 
-    let ret_vector: Vector3D = Vector3D {
-        x: color.x,
-        y: color.y,
-        z: color.z,
-    };
+        info!("Writing {}...", path);
+        return save_ppm(&globals.img, path);
+    }
 
-    return ret_vector;
+    render_to_file(globals, config, "test.png", scene, options)?;
+    if globals.budget_stop == Some(BudgetStop::Cancelled) {
+        return Ok(());
+    }
+    write_heatmap(options, globals)?;
+    write_stats(options, globals)
 }
 
-// Boss function
-fn render(thread_id: u32, globals: &mut GlobalSettings) {
-    let camerapos = Vector3D::v3d_new((0.0, 0.0, -5.0));
-
-    let wx1: f64 = -2.0; let wx2: f64 = 2.0; 
-    let wy1: f64 = 1.5; let wy2: f64 = -1.5;
+// How often --watch looks at the scene file, and how long it waits after a
+// change before reading it, so an editor can finish writing it
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+const WATCH_SETTLE: Duration = Duration::from_millis(100);
 
-    let dx: f64 = (wx2 - wx1) as f64 / (globals.img.width()) as f64;
-    let dy: f64 = (wy2 - wy1) as f64 / (globals.img.height()) as f64;
-
-    let mut sy: f64 = wy1 + dy * (thread_id as f64);
+// When `path` was last changed, `None` if it can't be read
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
 
-    // Spawn rays
-    for y in (thread_id..globals.img.height()).step_by(MAXTHREADS as usize) {
-        let mut sx = wx1;
+// Current time of day (UTC) as "hh:mm:ss", for the lines --watch prints
+fn timestamp() -> String {
+    let secs = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_secs();
+    format!("{:02}:{:02}:{:02}", secs / 3600 % 24, secs / 60 % 60, secs % 60)
+}
 
-        for x in 0..globals.img.width() {
-            let camera_target = Vector3D::v3d_new((sx, sy, 0.0));
+// Render the scene file at `path`, then again every time it changes, until
+// the program is stopped. The file is polled for changes from a thread of
+// its own, which also cancels a render that's still going on when the file
+// changes. A scene that can't be loaded (or has warnings, with --strict)
+// is reported and the last good one stays; if its render was cut short,
+// it's rendered again. Each scene gets the geometry cache of the last good
+// one, so edits that don't move anything skip tracing the camera rays.
+fn watch(path: &str, config: &RenderConfig, options: &Options) -> ! {
+    let changed = Arc::new(AtomicBool::new(false));
+
+    let poll = changed.clone();
+    let file = Path::new(path).to_path_buf();
+    let mut last = modified(&file);
+    std::thread::spawn(move || loop {
+        std::thread::sleep(WATCH_INTERVAL);
+        let now = modified(&file);
+        if now != last {
+            last = now;
+            poll.store(true, Ordering::Relaxed);
+        }
+    });
+
+    let mut good: Option<GlobalSettings> = None;
+    loop {
+        info!("Loading {}...", path);
+        let render = match load_watched(path, config, options, &changed) {
+            Ok(mut globals) => {
+                globals.geometry_cache = good.take().and_then(|g| g.geometry_cache);
+                good = Some(globals);
+                true
+            }
+            Err(e) => {
+                error!("{}", e);
+                println!("[{}] {} not rendered, keeping the last good scene", timestamp(), path);
+                // Which only needs rendering again if it was cut short
+                good.as_ref().is_some_and(|g| g.budget_stop == Some(BudgetStop::Cancelled))
+            }
+        };
 
-            let mut ray = Ray {
-                origin: camerapos,
-                direction: camera_target,
+        if let Some(globals) = good.as_mut().filter(|_| render) {
+            // Scene files can change the settings
+            let config = &RenderConfig {
+                render: globals.settings,
+                ..*config
             };
+            let start = Instant::now();
+            match write_outputs(globals, config, options, path) {
+                Err(e) => error!("{}", e),
+                Ok(()) if globals.budget_stop == Some(BudgetStop::Cancelled) => {
+                    println!("[{}] {} changed, render cancelled", timestamp(), path)
+                }
+                Ok(()) => println!(
+                    "[{}] Rendered {} to {} in {:.2?}",
+                    timestamp(),
+                    path,
+                    options.dump.as_deref().unwrap_or("test.png"),
+                    start.elapsed()
+                ),
+            }
+        }
 
-            ray.direction.v3d_sub(ray.origin);
-            ray.direction.v3d_norm();
-
-            // Trace the ray
-            let color: Vector3D = trace(&ray, 0, globals);
+        while !changed.swap(false, Ordering::Relaxed) {
+            std::thread::sleep(WATCH_INTERVAL);
+        }
+        std::thread::sleep(WATCH_SETTLE);
+        changed.store(false, Ordering::Relaxed);
+    }
+}
 
+// Load the scene file at `path` for `watch`, set up to stop rendering when
+// `changed` is set. With --strict, warnings make it an error.
+fn load_watched(path: &str, config: &RenderConfig, options: &Options, changed: &Arc<AtomicBool>) -> Result<GlobalSettings, LuxError> {
+    let mut globals = GlobalSettings::new(config.width, config.height, config.render, config.camera);
+    scene::load(path, &mut globals)?;
 
-            // Normalize colors
-            let r: i32 = (color.x * 255.0) as i32;
-            let g: i32 = (color.y * 255.0) as i32;
-            let b: i32 = (color.z * 255.0) as i32;
+    let warnings = globals.validate();
+    for w in &warnings {
+        warn!("{}", w);
+    }
+    if options.strict && !warnings.is_empty() {
+        return Err(LuxError::Scene(format!("{}: not rendering a scene with warnings (--strict)", path)));
+    }
 
-            let r: u8 = match r {
-                r if r > 255 => 255,
-                r if r < 0 => 0,
-                _ => r as u8,
-            };
-            let g: u8 = match g {
-                g if g > 255 => 255,
-                g if g < 0 => 0,
-                _ => g as u8,
-            };
-            let b: u8 = match b {
-                b if b > 255 => 255,
-                b if b < 0 => 0,
-                _ => b as u8,
-            };
+    globals.cancel = Some(changed.clone());
+    globals.settings.geometry_cache = true;
+    Ok(globals)
+}
 
-            let cl: image::Rgba<u8> = image::Rgba([r, g, b, 255]);
-            globals.img.put_pixel(x, y, cl);
+// Print what `metadata::read` found in `path`, one entry per line. The
+// long dumps go below their keyword, indented.
+fn print_metadata(path: &str, entries: &metadata::Metadata) {
+    if entries.is_empty() {
+        println!("{}: no render settings embedded", path);
+        return;
+    }
 
-            sx += dx;
+    for (keyword, text) in entries {
+        if text.contains('\n') {
+            println!("{}:", keyword);
+            for line in text.lines() {
+                println!("    {}", line);
+            }
+        } else {
+            println!("{}: {}", keyword, text);
         }
-
-        sy += dy * (MAXTHREADS as f64);
     }
 }
 
 fn main() {
-    println!("Simple ray tracer by Bourbon! :)");
-    println!("Creating scene...\n");
-
-    let img: RgbaImage = ImageBuffer::new(RESX, RESY);
-    let primitive_list: Vec<PrimSphere> = Vec::new();
-    let light_list: Vec<Light> = Vec::new();
-
-    let mut globals: GlobalSettings = GlobalSettings {
-        img,
-        primitive_count: 0,
-        primitive_list,
-        light_count: 0,
-        light_list,
-    };
+    log::init();
+    let (config, options) = parse_args();
 
-    // Set three materials here.
-    let mirror = Material {
-        color: Vector3D::v3d_new((0.6, 0.6, 0.6)),
-        specular: 0.3,
-        diffusive: 0.2,
-        reflective: 0.8,
-    };
-
-    let green = Material {
-        color: Vector3D::v3d_new((0.1, 1.0, 0.1)),
-        specular: 0.1,
-        diffusive: 0.3,
-        reflective: 0.4,
-    };
-
-    let red = Material {
-        color: Vector3D::v3d_new((1.0, 0.1, 0.1)),
-        specular: 0.1,
-        diffusive: 0.3,
-        reflective: 0.4,
-    };
+    info!("Simple ray tracer by Bourbon! :)");
 
-    // FIXME: Use a single `Vec<char>` here, maybe
-    // All this because Rust can't index into strings :)))))))))))
-    // ----
-    // Position of spheres in the image. Edit this to alter the sphere pattern.
-    // But make sure that you enter 9 x 6 pattern. Otherwise, change the 
-    // `j` and `i` in the iterator below for a custom map.
-    let mut sphere_pos_map: Vec<Vec<char>> = Vec::new();
-    sphere_pos_map.push("g..g..r..".chars().collect());
-    sphere_pos_map.push("g..g.....".chars().collect());
-    sphere_pos_map.push("gggg..r..".chars().collect());
-    sphere_pos_map.push("g..g..r..".chars().collect());
-    sphere_pos_map.push("g..g..r..".chars().collect());
-    sphere_pos_map.push("......r..".chars().collect());
-
-    // Place colored spheres here and there.
-    for j in 0..6 {
-        for i in 0..9 {
-            let mut m = &mirror;
-            let mut z = 2.0_f64;
-            let sn = ((i + j) as f64).sin() * 0.8;
-
-            match sphere_pos_map[j][i] {
-                'g' => {
-                    z += -0.5 /*- sn * 0.4 */;
-                    m = &green;
-                },
-                'r' => {
-                    z += -0.5 /* - sn * 0.4 */;
-                    m = &red;
-                },
-
-                _ => {
-                    z += sn;
-                },
-            }
-
-            let sphere_pos = Vector3D::v3d_new((-2.0 + (i as f64) * 0.5, 1.25 - (j as f64) * 0.5, z));
-            add_sphere(&sphere_pos, 0.25, m, &mut globals);
+    if let Some(path) = &options.show_metadata {
+        match metadata::read(path) {
+            Ok(entries) => print_metadata(path, &entries),
+            Err(e) => {
+                error!("{}", e);
+                std::process::exit(1);
+            }
         }
+        return;
     }
 
-    // Add a single light source  
-    let lightpos: Vector3D = Vector3D::v3d_new((0.0, 0.0, 0.0));
-    let lightcolor: Vector3D = Vector3D::v3d_new((2.0, 2.0, 2.0));
-    add_light(lightpos, lightcolor, &mut globals);
+    if options.resume && options.checkpoint.is_none() {
+        error!("--resume needs a checkpoint to resume from (--checkpoint FILE)");
+        std::process::exit(1);
+    }
+    if options.checkpoint.is_some() && (options.batch.is_some() || options.watch) {
+        warn!("Only single renders are checkpointed, not --batch or --watch");
+    }
 
-    println!("Rendering...\n");
+    if let Some(dir) = &options.batch {
+        if render_batch(dir, &config, &options) > 0 {
+            std::process::exit(1);
+        }
+        return;
+    }
 
-    // Simulating 4 threads. Each 'thread' (call) completes a part of the image. 
-    // FIXME: Actually implement threads! :')
-    render(0, &mut globals);
-    render(1, &mut globals);
-    render(2, &mut globals);
-    render(3, &mut globals);
+    if options.watch {
+        let path = match options.scene.as_deref() {
+            Some(path) if demo::find(path).is_none() && !options.inspect => path,
+            _ => {
+                error!("--watch needs a scene file (--scene FILE)");
+                std::process::exit(1);
+            }
+        };
+        watch(path, &config, &options);
+    }
 
-    println!("Writing test.png image...");
-    globals.img.save("test.png").unwrap();
+    // Errors end the program with one line saying what went wrong
+    if let Err(e) = run(&config, &options) {
+        error!("{}", e);
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,803 @@
+// Math types shared by the whole tracer
+
+use crate::Rng;
+use std::fmt;
+use std::ops::{Index, IndexMut};
+
+// Three dimensional vector. `==` compares exactly, use `approx_eq` for
+// results of calculations.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Vector3D {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+// Methods on Vectors
+impl Vector3D {
+    pub const ZERO: Vector3D = Vector3D::new(0.0, 0.0, 0.0);
+    pub const ONE: Vector3D = Vector3D::new(1.0, 1.0, 1.0);
+    pub const UNIT_X: Vector3D = Vector3D::new(1.0, 0.0, 0.0);
+    pub const UNIT_Y: Vector3D = Vector3D::new(0.0, 1.0, 0.0);
+    pub const UNIT_Z: Vector3D = Vector3D::new(0.0, 0.0, 1.0);
+
+    // Instantiate vector
+    pub const fn new(x: f64, y: f64, z: f64) -> Self {
+        Self { x, y, z }
+    }
+
+    // Same as `new`, from a tuple. Kept for older code; `new` or `.into()`
+    // read better.
+    pub fn v3d_new(coordinates: (f64, f64, f64)) -> Self {
+        coordinates.into()
+    }
+
+    // Clone `original_vector` into new vector `self`
+    // original_vector <---- self
+    // This is only because I was too lazy to implement `Clone` or `Copy`.
+    pub fn _v3d_clone_from(&mut self, original_vector: Vector3D) {
+        self.x = original_vector.x;
+        self.y = original_vector.y;
+        self.z = original_vector.z;
+    }
+
+    // Print vector
+    pub fn _v3d_print(&self) {
+        println!("{}", self);
+    }
+
+    // Add to the vector
+    pub fn v3d_add(&mut self, other_vector: Vector3D) {
+        self.x += other_vector.x;
+        self.y += other_vector.y;
+        self.z += other_vector.z;
+    }
+
+    // Subtract from the vector
+    pub fn v3d_sub(&mut self, other_vector: Vector3D) {
+        self.x -= other_vector.x;
+        self.y -= other_vector.y;
+        self.z -= other_vector.z;
+    }
+
+    // Multiply by a scalar
+    pub fn v3d_mul_scalar(&mut self, scalar: f64) {
+        self.x *= scalar;
+        self.y *= scalar;
+        self.z *= scalar;
+    }
+
+    // Multiply by another vector
+    pub fn v3d_mul_v3d(&mut self, other_vector: Vector3D) {
+        self.x *= other_vector.x;
+        self.y *= other_vector.y;
+        self.z *= other_vector.z;
+    }
+
+    // Dot multiplication
+    pub fn v3d_dot_mul(&self, other_vector: Vector3D) -> f64 {
+        self.x * other_vector.x + self.y * other_vector.y + self.z * other_vector.z
+    }
+
+    // Cross product `a` x `b`
+    pub fn cross(a: Vector3D, b: Vector3D) -> Vector3D {
+        Vector3D {
+            x: a.y * b.z - a.z * b.y,
+            y: a.z * b.x - a.x * b.z,
+            z: a.x * b.y - a.y * b.x,
+        }
+    }
+
+    // Length of vector
+    pub fn v3d_length(&self) -> f64 {
+        (self.x * self.x + self.y * self.y + self.z * self.z).sqrt()
+    }
+
+    // Length of but without sqrt
+    pub fn _v3d_length_sqr(&self) -> f64 {
+        self.x * self.x + self.y * self.y + self.z * self.z
+    }
+
+    // Normalise vector
+    pub fn v3d_norm(&mut self) {
+        let l: f64 = 1.0 / self.v3d_length();
+        self.x *= l;
+        self.y *= l;
+        self.z *= l;
+    }
+
+    // Mirror the direction `incident` (pointing towards the surface) about
+    // the unit `normal`:
+    // R = I - N * I.Dot(N) * 2.0
+    pub fn reflect(incident: Vector3D, normal: Vector3D) -> Vector3D {
+        let mut r1 = normal;
+        r1.v3d_mul_scalar(incident.v3d_dot_mul(normal) * 2.0);
+
+        let mut r = incident;
+        r.v3d_sub(r1);
+        r
+    }
+
+    // Bend the unit direction `incident` through a surface with the unit
+    // `normal` pointing against it. `eta` is the ratio of refractive indices
+    // (outside / inside). Returns `None` on total internal reflection.
+    pub fn refract(incident: Vector3D, normal: Vector3D, eta: f64) -> Option<Vector3D> {
+        let cos_i = -incident.v3d_dot_mul(normal);
+        let k = 1.0 - eta * eta * (1.0 - cos_i * cos_i);
+
+        if k < 0.0 {
+            return None;
+        }
+
+        let mut t = incident;
+        t.v3d_mul_scalar(eta);
+
+        let mut n = normal;
+        n.v3d_mul_scalar(eta * cos_i - k.sqrt());
+        t.v3d_add(n);
+
+        Some(t)
+    }
+
+    // The components x, y and z in order
+    pub fn components(&self) -> impl Iterator<Item = f64> {
+        [self.x, self.y, self.z].into_iter()
+    }
+
+    // Vector with `f` applied to every component
+    pub fn map(&self, f: impl Fn(f64) -> f64) -> Vector3D {
+        Vector3D {
+            x: f(self.x),
+            y: f(self.y),
+            z: f(self.z),
+        }
+    }
+
+    // Vector with `f` applied to the matching components of `self` and
+    // `other`, e.g. the component wise minimum of two vectors
+    pub fn zip(&self, other: Vector3D, f: impl Fn(f64, f64) -> f64) -> Vector3D {
+        Vector3D {
+            x: f(self.x, other.x),
+            y: f(self.y, other.y),
+            z: f(self.z, other.z),
+        }
+    }
+
+    // Component wise minimum and maximum of two vectors. Each component is
+    // compared on its own, so the result can be neither `self` nor `other`.
+    pub fn min(&self, other: Vector3D) -> Vector3D {
+        self.zip(other, f64::min)
+    }
+
+    pub fn max(&self, other: Vector3D) -> Vector3D {
+        self.zip(other, f64::max)
+    }
+
+    // Every component clamped between the matching components of `min` and
+    // `max`
+    pub fn clamp(&self, min: Vector3D, max: Vector3D) -> Vector3D {
+        self.max(min).min(max)
+    }
+
+    // Largest and smallest component
+    pub fn max_component(&self) -> f64 {
+        self.x.max(self.y).max(self.z)
+    }
+
+    pub fn min_component(&self) -> f64 {
+        self.x.min(self.y).min(self.z)
+    }
+
+    // Perceived brightness of a linear rgb color (Rec. 709 weights)
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.x + 0.7152 * self.y + 0.0722 * self.z
+    }
+
+    // Whether every component is within `eps` of the one in `other`
+    pub fn approx_eq(&self, other: Vector3D, eps: f64) -> bool {
+        self.components().zip(other.components()).all(|(a, b)| (a - b).abs() <= eps)
+    }
+}
+
+// "(x, y, z)". The precision applies to every component, so `{:.2}` gives
+// "(1.00, 2.00, 3.00)".
+impl fmt::Display for Vector3D {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match f.precision() {
+            Some(p) => write!(f, "({:.*}, {:.*}, {:.*})", p, self.x, p, self.y, p, self.z),
+            None => write!(f, "({}, {}, {})", self.x, self.y, self.z),
+        }
+    }
+}
+
+impl From<(f64, f64, f64)> for Vector3D {
+    fn from((x, y, z): (f64, f64, f64)) -> Self {
+        Vector3D::new(x, y, z)
+    }
+}
+
+impl From<[f64; 3]> for Vector3D {
+    fn from([x, y, z]: [f64; 3]) -> Self {
+        Vector3D::new(x, y, z)
+    }
+}
+
+// Component by axis: 0 is x, 1 is y and 2 is z. Anything else panics.
+impl Index<usize> for Vector3D {
+    type Output = f64;
+
+    fn index(&self, axis: usize) -> &f64 {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vector3D axis {} out of range", axis),
+        }
+    }
+}
+
+impl IndexMut<usize> for Vector3D {
+    fn index_mut(&mut self, axis: usize) -> &mut f64 {
+        match axis {
+            0 => &mut self.x,
+            1 => &mut self.y,
+            2 => &mut self.z,
+            _ => panic!("Vector3D axis {} out of range", axis),
+        }
+    }
+}
+
+// 4x4 matrix for affine transforms, row major. Points are column vectors, so
+// `a * b` applies `b` first.
+#[derive(Clone, Copy)]
+pub struct Mat4 {
+    pub m: [[f64; 4]; 4],
+}
+
+impl Mat4 {
+    pub fn identity() -> Mat4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            row[i] = 1.0;
+        }
+        Mat4 { m }
+    }
+
+    pub fn translate(offset: Vector3D) -> Mat4 {
+        let mut t = Mat4::identity();
+        for i in 0..3 {
+            t.m[i][3] = offset[i];
+        }
+        t
+    }
+
+    pub fn scale(factors: Vector3D) -> Mat4 {
+        let mut s = Mat4::identity();
+        for i in 0..3 {
+            s.m[i][i] = factors[i];
+        }
+        s
+    }
+
+    // Counter-clockwise rotations (looking down the axis towards the origin)
+    // by `degrees` around the x, y and z axis
+    pub fn rotate_x(degrees: f64) -> Mat4 {
+        Mat4::rotate(1, 2, degrees)
+    }
+
+    pub fn rotate_y(degrees: f64) -> Mat4 {
+        Mat4::rotate(2, 0, degrees)
+    }
+
+    pub fn rotate_z(degrees: f64) -> Mat4 {
+        Mat4::rotate(0, 1, degrees)
+    }
+
+    // Rotation turning axis `a` towards axis `b`
+    fn rotate(a: usize, b: usize, degrees: f64) -> Mat4 {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+
+        let mut r = Mat4::identity();
+        r.m[a][a] = cos;
+        r.m[a][b] = -sin;
+        r.m[b][a] = sin;
+        r.m[b][b] = cos;
+        r
+    }
+
+    pub fn transpose(&self) -> Mat4 {
+        let mut t = Mat4 { m: [[0.0; 4]; 4] };
+        for i in 0..4 {
+            for j in 0..4 {
+                t.m[i][j] = self.m[j][i];
+            }
+        }
+        t
+    }
+
+    // Gauss-Jordan elimination with partial pivoting. `None` if the matrix
+    // can't be inverted.
+    pub fn inverse(&self) -> Option<Mat4> {
+        let mut a = self.m;
+        let mut inv = Mat4::identity().m;
+
+        for col in 0..4 {
+            let pivot = (col..4).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+
+            let p = 1.0 / a[col][col];
+            for j in 0..4 {
+                a[col][j] *= p;
+                inv[col][j] *= p;
+            }
+
+            for row in 0..4 {
+                if row != col {
+                    let f = a[row][col];
+                    for j in 0..4 {
+                        a[row][j] -= f * a[col][j];
+                        inv[row][j] -= f * inv[col][j];
+                    }
+                }
+            }
+        }
+
+        Some(Mat4 { m: inv })
+    }
+
+    // Transform a position, translation included
+    pub fn transform_point(&self, p: Vector3D) -> Vector3D {
+        let mut r = self.transform_vector(p);
+        r.v3d_add(Vector3D::new(self.m[0][3], self.m[1][3], self.m[2][3]));
+        r
+    }
+
+    // Transform a direction, translation left out
+    pub fn transform_vector(&self, v: Vector3D) -> Vector3D {
+        let row = |i: usize| self.m[i][0] * v.x + self.m[i][1] * v.y + self.m[i][2] * v.z;
+        Vector3D::new(row(0), row(1), row(2))
+    }
+}
+
+impl std::ops::Mul for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, other: Mat4) -> Mat4 {
+        let mut r = Mat4 { m: [[0.0; 4]; 4] };
+        for i in 0..4 {
+            for j in 0..4 {
+                r.m[i][j] = (0..4).map(|k| self.m[i][k] * other.m[k][j]).sum();
+            }
+        }
+        r
+    }
+}
+
+// Rotation as a unit quaternion w + xi + yj + zk. Unlike angles around the
+// axes it has no gimbal lock, and `slerp` turns from one rotation to another
+// at a constant speed. `a * b` applies `b` first, like `Mat4`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quaternion {
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Quaternion {
+    pub const IDENTITY: Quaternion = Quaternion {
+        w: 1.0,
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+    };
+
+    // Counter-clockwise rotation by `degrees` around `axis` (looking down the
+    // axis towards the origin), like `Mat4::rotate_x` and friends. The axis
+    // doesn't need to be normalized.
+    pub fn from_axis_angle(axis: Vector3D, degrees: f64) -> Quaternion {
+        let mut axis = axis;
+        axis.v3d_norm();
+        let (sin, cos) = (degrees.to_radians() * 0.5).sin_cos();
+        Quaternion {
+            w: cos,
+            x: axis.x * sin,
+            y: axis.y * sin,
+            z: axis.z * sin,
+        }
+    }
+
+    // Rotation turning +z towards `forward` and +y towards `up`, as far as
+    // it can while staying perpendicular to `forward`. Looking (almost)
+    // straight along `up` the top goes where it would if the view had been
+    // tilted there from +z (or +x, if `up` is about z): towards it looking
+    // down, away from it looking up. `None` if `forward` or `up` is zero.
+    pub fn look_at(forward: Vector3D, up: Vector3D) -> Option<Quaternion> {
+        if forward.v3d_length() == 0.0 || up.v3d_length() == 0.0 {
+            return None;
+        }
+        let mut f = forward;
+        f.v3d_norm();
+        let mut up = up;
+        up.v3d_norm();
+
+        let mut r = Vector3D::cross(up, f);
+        if r.v3d_length() < 1e-6 {
+            let mut tilted = if up.z.abs() < 0.9 { Vector3D::UNIT_Z } else { Vector3D::UNIT_X };
+            tilted.v3d_mul_scalar(-f.v3d_dot_mul(up).signum());
+            r = Vector3D::cross(tilted, f);
+        }
+        r.v3d_norm();
+        let u = Vector3D::cross(f, r);
+
+        // The rotation matrix has r, u and f as its columns
+        let m = [[r.x, u.x, f.x], [r.y, u.y, f.y], [r.z, u.z, f.z]];
+        Some(Quaternion::from_matrix(m))
+    }
+
+    // Quaternion of a 3x3 rotation matrix (Shepperd's method: start from
+    // the largest of w, x, y and z so nothing is divided by almost 0)
+    fn from_matrix(m: [[f64; 3]; 3]) -> Quaternion {
+        let trace = m[0][0] + m[1][1] + m[2][2];
+        let mut q = if trace > 0.0 {
+            let s = 2.0 * (trace + 1.0).sqrt();
+            Quaternion {
+                w: s / 4.0,
+                x: (m[2][1] - m[1][2]) / s,
+                y: (m[0][2] - m[2][0]) / s,
+                z: (m[1][0] - m[0][1]) / s,
+            }
+        } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+            let s = 2.0 * (1.0 + m[0][0] - m[1][1] - m[2][2]).sqrt();
+            Quaternion {
+                w: (m[2][1] - m[1][2]) / s,
+                x: s / 4.0,
+                y: (m[0][1] + m[1][0]) / s,
+                z: (m[0][2] + m[2][0]) / s,
+            }
+        } else if m[1][1] > m[2][2] {
+            let s = 2.0 * (1.0 + m[1][1] - m[0][0] - m[2][2]).sqrt();
+            Quaternion {
+                w: (m[0][2] - m[2][0]) / s,
+                x: (m[0][1] + m[1][0]) / s,
+                y: s / 4.0,
+                z: (m[1][2] + m[2][1]) / s,
+            }
+        } else {
+            let s = 2.0 * (1.0 + m[2][2] - m[0][0] - m[1][1]).sqrt();
+            Quaternion {
+                w: (m[1][0] - m[0][1]) / s,
+                x: (m[0][2] + m[2][0]) / s,
+                y: (m[1][2] + m[2][1]) / s,
+                z: s / 4.0,
+            }
+        };
+        q.normalize();
+        q
+    }
+
+    // The opposite rotation, for unit quaternions
+    pub fn conjugate(&self) -> Quaternion {
+        Quaternion {
+            w: self.w,
+            x: -self.x,
+            y: -self.y,
+            z: -self.z,
+        }
+    }
+
+    pub fn dot(&self, other: Quaternion) -> f64 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn length(&self) -> f64 {
+        self.dot(*self).sqrt()
+    }
+
+    // Scale to length 1, so rounding errors from many multiplications don't
+    // turn the rotation into a scaling
+    pub fn normalize(&mut self) {
+        let f = 1.0 / self.length();
+        self.w *= f;
+        self.x *= f;
+        self.y *= f;
+        self.z *= f;
+    }
+
+    // Rotate the vector `v`
+    pub fn rotate(&self, v: Vector3D) -> Vector3D {
+        // v + 2w (q x v) + 2 q x (q x v), with q the vector part
+        let q = Vector3D::new(self.x, self.y, self.z);
+        let mut t = Vector3D::cross(q, v);
+        t.v3d_mul_scalar(2.0);
+
+        let mut r = v;
+        let mut wt = t;
+        wt.v3d_mul_scalar(self.w);
+        r.v3d_add(wt);
+        r.v3d_add(Vector3D::cross(q, t));
+        r
+    }
+
+    // The same rotation as a matrix
+    pub fn to_mat4(&self) -> Mat4 {
+        let mut m = Mat4::identity();
+        for (j, axis) in [Vector3D::UNIT_X, Vector3D::UNIT_Y, Vector3D::UNIT_Z].into_iter().enumerate() {
+            let column = self.rotate(axis);
+            for i in 0..3 {
+                m.m[i][j] = column[i];
+            }
+        }
+        m
+    }
+
+    // Rotation `t` of the way from `a` to `b` along the shortest arc, turning
+    // at a constant speed as `t` goes from 0 to 1
+    pub fn slerp(a: Quaternion, b: Quaternion, t: f64) -> Quaternion {
+        // q and -q are the same rotation; pick the one closer to `a`
+        let mut b = b;
+        let mut cos = a.dot(b);
+        if cos < 0.0 {
+            b = Quaternion {
+                w: -b.w,
+                x: -b.x,
+                y: -b.y,
+                z: -b.z,
+            };
+            cos = -cos;
+        }
+
+        // Almost the same rotation: blending linearly is as good and avoids
+        // dividing by sin of almost 0
+        let (wa, wb) = if cos > 0.9995 {
+            (1.0 - t, t)
+        } else {
+            let angle = cos.acos();
+            let sin = angle.sin();
+            (((1.0 - t) * angle).sin() / sin, (t * angle).sin() / sin)
+        };
+
+        let mut q = Quaternion {
+            w: wa * a.w + wb * b.w,
+            x: wa * a.x + wb * b.x,
+            y: wa * a.y + wb * b.y,
+            z: wa * a.z + wb * b.z,
+        };
+        q.normalize();
+        q
+    }
+
+    // Whether `other` is the same rotation, up to `eps` in every component
+    pub fn approx_eq(&self, other: Quaternion, eps: f64) -> bool {
+        // q and -q rotate the same way
+        let close = |s: f64| {
+            (self.w - s * other.w).abs() <= eps
+                && (self.x - s * other.x).abs() <= eps
+                && (self.y - s * other.y).abs() <= eps
+                && (self.z - s * other.z).abs() <= eps
+        };
+        close(1.0) || close(-1.0)
+    }
+}
+
+impl Default for Quaternion {
+    fn default() -> Self {
+        Quaternion::IDENTITY
+    }
+}
+
+impl std::ops::Mul for Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, o: Quaternion) -> Quaternion {
+        Quaternion {
+            w: self.w * o.w - self.x * o.x - self.y * o.y - self.z * o.z,
+            x: self.w * o.x + self.x * o.w + self.y * o.z - self.z * o.y,
+            y: self.w * o.y - self.x * o.z + self.y * o.w + self.z * o.x,
+            z: self.w * o.z + self.x * o.y - self.y * o.x + self.z * o.w,
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+//
+// Direction sampling
+//
+// Random directions around a unit vector, for the features that scatter
+// rays: bounces off diffuse and rough surfaces, lights seen as a cone,
+// photons. All of them take the random numbers from an `Rng` so renders stay
+// reproducible for a seed.
+
+// Two unit vectors that make an orthonormal basis together with the unit
+// vector `n` (Duff et al., "Building an Orthonormal Basis, Revisited").
+pub fn orthonormal_basis(n: Vector3D) -> (Vector3D, Vector3D) {
+    let sign = 1.0_f64.copysign(n.z);
+    let a = -1.0 / (sign + n.z);
+    let b = n.x * n.y * a;
+
+    let t = Vector3D::new(1.0 + sign * n.x * n.x * a, sign * b, -sign * n.x);
+    let bt = Vector3D::new(b, sign + n.y * n.y * a, -n.y);
+    (t, bt)
+}
+
+// Direction at angle acos(`cos_theta`) from the unit vector `axis`, rotated by
+// `phi` around it.
+pub fn dir_around(axis: Vector3D, cos_theta: f64, phi: f64) -> Vector3D {
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let (t, b) = orthonormal_basis(axis);
+
+    let mut dir = t;
+    dir.v3d_mul_scalar(sin_theta * phi.cos());
+
+    let mut b = b;
+    b.v3d_mul_scalar(sin_theta * phi.sin());
+    dir.v3d_add(b);
+
+    let mut a = axis;
+    a.v3d_mul_scalar(cos_theta);
+    dir.v3d_add(a);
+
+    dir
+}
+
+// Direction on the side of the unit `normal`, more likely the closer it is to
+// it: the pdf is cos(theta) / pi, which matches the light a diffuse surface
+// takes in
+pub fn cosine_weighted_hemisphere(normal: Vector3D, rng: &mut Rng) -> Vector3D {
+    let phi = 2.0 * std::f64::consts::PI * rng.next_f64();
+    dir_around(normal, rng.next_f64().sqrt(), phi)
+}
+
+// Direction on the side of the unit `normal`, every one as likely (pdf
+// 1 / 2 pi)
+pub fn uniform_hemisphere(normal: Vector3D, rng: &mut Rng) -> Vector3D {
+    sample_cone_cos(normal, 0.0, rng)
+}
+
+// Direction at most `angle` radians from the unit vector `axis`, uniform
+// over the cone's solid angle
+pub fn sample_cone(axis: Vector3D, angle: f64, rng: &mut Rng) -> Vector3D {
+    sample_cone_cos(axis, angle.cos(), rng)
+}
+
+// `sample_cone` for callers that have the cosine of the angle at hand
+pub fn sample_cone_cos(axis: Vector3D, cos_max: f64, rng: &mut Rng) -> Vector3D {
+    let cos_theta = 1.0 - rng.next_f64() * (1.0 - cos_max);
+    let phi = 2.0 * std::f64::consts::PI * rng.next_f64();
+    dir_around(axis, cos_theta, phi)
+}
+
+// ----------------------------------------------------------------------------
+//
+// Polynomial roots
+//
+// Real roots of polynomials up to degree four, smallest first. Coefficients
+// are given from the highest power down, so `solve_quadratic(a, b, c)` solves
+// a x^2 + b x + c = 0. A leading coefficient of 0 drops to the next lower
+// degree. Repeated roots may show up once or several times.
+
+// Roots of a x^2 + b x + c
+pub fn solve_quadratic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    if a == 0.0 {
+        return if b == 0.0 { Vec::new() } else { vec![-c / b] };
+    }
+
+    let disc = b * b - 4.0 * a * c;
+    if disc < 0.0 {
+        return Vec::new();
+    }
+
+    // Avoids subtracting two nearly equal numbers (Numerical Recipes 5.6)
+    let q = -0.5 * (b + disc.sqrt().copysign(b));
+    let mut roots = if q == 0.0 { vec![0.0, 0.0] } else { vec![q / a, c / q] };
+    roots.sort_by(f64::total_cmp);
+    roots
+}
+
+// Roots of a x^3 + b x^2 + c x + d
+pub fn solve_cubic(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    if a == 0.0 {
+        return solve_quadratic(b, c, d);
+    }
+
+    let (b, c, d) = (b / a, c / a, d / a);
+    let q = (b * b - 3.0 * c) / 9.0;
+    let r = (2.0 * b * b * b - 9.0 * b * c + 27.0 * d) / 54.0;
+    let shift = b / 3.0;
+
+    let mut roots = if r * r < q * q * q {
+        // Three real roots
+        let theta = (r / (q * q * q).sqrt()).clamp(-1.0, 1.0).acos();
+        let s = -2.0 * q.sqrt();
+        let turn = 2.0 * std::f64::consts::PI;
+
+        vec![
+            s * (theta / 3.0).cos() - shift,
+            s * ((theta + turn) / 3.0).cos() - shift,
+            s * ((theta - turn) / 3.0).cos() - shift,
+        ]
+    } else {
+        let u = -(r.abs() + (r * r - q * q * q).sqrt()).cbrt().copysign(r);
+        let v = if u == 0.0 { 0.0 } else { q / u };
+
+        let mut roots = vec![u + v - shift];
+        // The other two roots are complex, unless they coincide
+        if (u - v).abs() <= 1e-12 * u.abs().max(1.0) {
+            roots.push(-0.5 * (u + v) - shift);
+        }
+        roots
+    };
+
+    roots.sort_by(f64::total_cmp);
+    roots
+}
+
+// Roots of a x^4 + b x^3 + c x^2 + d x + e, with Ferrari's method. Every
+// root is polished with a few Newton steps afterwards, since the closed form
+// loses precision when roots are close together (a ray grazing a torus).
+pub fn solve_quartic(a: f64, b: f64, c: f64, d: f64, e: f64) -> Vec<f64> {
+    if a == 0.0 {
+        return solve_cubic(b, c, d, e);
+    }
+
+    let (b, c, d, e) = (b / a, c / a, d / a, e / a);
+
+    // Substituting x = y - b/4 gets rid of the cubic term:
+    // y^4 + p y^2 + q y + r = 0
+    let shift = b / 4.0;
+    let b2 = b * b;
+    let p = c - 3.0 * b2 / 8.0;
+    let q = d - b * c / 2.0 + b2 * b / 8.0;
+    let r = e - b * d / 4.0 + b2 * c / 16.0 - 3.0 * b2 * b2 / 256.0;
+
+    let mut ys = Vec::new();
+    if q.abs() < 1e-12 {
+        // Quadratic in y^2
+        for z in solve_quadratic(1.0, p, r) {
+            if z >= 0.0 {
+                ys.push(z.sqrt());
+                ys.push(-z.sqrt());
+            }
+        }
+    } else {
+        // Any root m > 0 of the resolvent cubic splits the quartic into
+        // two quadratics. The largest one is the most accurate.
+        let m = match solve_cubic(1.0, p, p * p / 4.0 - r, -q * q / 8.0).last() {
+            Some(&m) if m > 0.0 => m,
+            _ => return Vec::new(),
+        };
+
+        let s = (2.0 * m).sqrt();
+        ys.extend(solve_quadratic(1.0, -s, p / 2.0 + m + q / (2.0 * s)));
+        ys.extend(solve_quadratic(1.0, s, p / 2.0 + m - q / (2.0 * s)));
+    }
+
+    let f = |x: f64| (((x + b) * x + c) * x + d) * x + e;
+    let df = |x: f64| ((4.0 * x + 3.0 * b) * x + 2.0 * c) * x + d;
+
+    let mut roots: Vec<f64> = ys
+        .into_iter()
+        .map(|y| {
+            let mut x = y - shift;
+            for _ in 0..3 {
+                let slope = df(x);
+                if slope == 0.0 {
+                    break;
+                }
+                let next = x - f(x) / slope;
+                if f(next).abs() >= f(x).abs() {
+                    break;
+                }
+                x = next;
+            }
+            x
+        })
+        .collect();
+
+    roots.sort_by(f64::total_cmp);
+    roots
+}
@@ -0,0 +1,127 @@
+// Fog filling the whole scene. Light going `d` through it keeps
+// exp(-density * d) of itself (the transmittance, Beer-Lambert) and the
+// share it loses is made up by the fog's own color, so far away things fade
+// into the fog. Every ray segment is fogged like that, so a mirror shows
+// what it reflects as foggy as it would look from there.
+//
+// On top of that camera rays can gather light the fog scatters towards the
+// camera at a few points along them (single scattering). Where a sphere
+// stands between the light and the fog, that light is missing, which shows
+// up as light shafts.
+
+use crate::{closest_hit, Ray, RayKind, Scene, Vector3D};
+
+// Transmittance below which the fog counts as opaque, for how far the light
+// shafts are gathered along rays that don't hit anything
+const OPAQUE: f64 = 0.01;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fog {
+    // How much of the light the fog takes away per unit of distance. 0 is
+    // no fog at all.
+    pub density: f64,
+    pub color: Vector3D,
+    // Points along every camera ray where light scattered towards the
+    // camera is gathered. 0 leaves out the light shafts.
+    pub shafts: u32,
+}
+
+impl Default for Fog {
+    fn default() -> Self {
+        Self {
+            density: 0.0,
+            color: Vector3D::new(0.5, 0.5, 0.5),
+            shafts: 0,
+        }
+    }
+}
+
+// Share of the light that makes it `distance` through fog of `density`.
+// Exactly 1 without fog, even for rays that go on forever.
+pub fn transmittance(density: f64, distance: f64) -> f64 {
+    if density == 0.0 {
+        return 1.0;
+    }
+    (-density * distance).exp()
+}
+
+impl Fog {
+    pub fn is_clear(&self) -> bool {
+        self.density == 0.0
+    }
+
+    pub fn transmittance(&self, distance: f64) -> f64 {
+        transmittance(self.density, distance)
+    }
+
+    // Light the fog itself adds over `distance`: its color, times the share
+    // of the light behind it that it takes away
+    pub fn glow(&self, distance: f64) -> Vector3D {
+        let mut c = self.color;
+        c.v3d_mul_scalar(1.0 - self.transmittance(distance));
+        c
+    }
+
+    // `color` seen from `distance` away through the fog
+    pub fn apply(&self, color: Vector3D, distance: f64) -> Vector3D {
+        if self.is_clear() {
+            return color;
+        }
+
+        let mut c = color;
+        c.v3d_mul_scalar(self.transmittance(distance));
+        c.v3d_add(self.glow(distance));
+        c
+    }
+
+    // How far along a ray of `length` the light shafts are gathered: no
+    // further than where the fog is about opaque
+    fn shaft_length(&self, length: f64) -> f64 {
+        length.min(-OPAQUE.ln() / self.density)
+    }
+
+    // Light the fog scatters towards the start of `ray` from the first
+    // `length` of it, which runs into a surface there or goes on forever.
+    // Gathered at `shafts` evenly spread points, from every light that
+    // isn't blocked; `add` gets every light's share with its index. The
+    // fog scatters the same in every direction.
+    pub fn in_scattered(&self, ray: &Ray, length: f64, scene: &Scene, mut add: impl FnMut(usize, Vector3D)) {
+        if self.is_clear() || self.shafts == 0 {
+            return;
+        }
+
+        let length = self.shaft_length(length);
+        let step = length / self.shafts as f64;
+        let phase = 1.0 / (4.0 * std::f64::consts::PI);
+
+        for k in 0..self.shafts {
+            let s = (k as f64 + 0.5) * step;
+            let mut p = ray.direction;
+            p.v3d_mul_scalar(s);
+            p.v3d_add(ray.origin);
+
+            for (i, light) in scene.light_list.iter().enumerate() {
+                let mut to_light = light.position;
+                to_light.v3d_sub(p);
+                let light_dist = to_light.v3d_length();
+                to_light.v3d_norm();
+
+                scene.counters.shadow();
+                let towards = Ray {
+                    origin: p,
+                    direction: to_light,
+                    time: ray.time,
+                };
+                if closest_hit(&towards, RayKind::Shadow, scene).is_some_and(|(_, d)| d < light_dist) {
+                    continue;
+                }
+
+                let mut c = light.radiance_at(p);
+                c.v3d_mul_scalar(
+                    self.density * phase * step * self.transmittance(light_dist) * self.transmittance(s),
+                );
+                add(i, c);
+            }
+        }
+    }
+}
@@ -0,0 +1,155 @@
+// Render settings embedded in the PNGs lux writes, so an image still says
+// how it was made long after the command line is forgotten. Every entry is
+// a keyword and a text, stored as a PNG text chunk: short ones as plain
+// tEXt, the full settings and camera dumps compressed as zTXt, and anything
+// that isn't Latin-1 (like some scene paths) as UTF-8 iTXt.
+//
+// The image crate's `save` can't write text chunks, so PNGs go through the
+// png crate's encoder directly.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::time::Duration;
+
+use image::{ImageBuffer, Rgba, RgbaImage};
+
+use crate::{resolve_16bit, GlobalSettings, LuxError, RenderMode};
+
+// Keywords of the entries written compressed
+const COMPRESSED: [&str; 2] = ["Settings", "Camera"];
+
+// Keyword and text of every entry, in the order they're written
+pub type Metadata = Vec<(String, String)>;
+
+// What to embed for a render of `globals` that took `render_time`. `scene`
+// is the scene file or built-in scene it was made from; files also get a
+// hash of their contents, so a changed scene can be told apart from the
+// one that was rendered.
+pub fn describe(globals: &GlobalSettings, scene: &str, render_time: Duration) -> Metadata {
+    let settings = &globals.settings;
+    let camera = &globals.camera;
+
+    let mut entries = vec![
+        ("Software", format!("lux {}", env!("CARGO_PKG_VERSION"))),
+        ("Resolution", format!("{}x{}", globals.img.width(), globals.img.height())),
+        ("Samples per pixel", settings.samples.max(1).to_string()),
+        ("Seed", settings.seed.to_string()),
+        ("Max depth", settings.max_depth.to_string()),
+        (
+            "Mode",
+            match settings.mode {
+                RenderMode::Whitted => "whitted",
+                RenderMode::PathTrace => "pathtrace",
+            }
+            .to_string(),
+        ),
+        ("Camera position", camera.position.to_string()),
+        ("Camera direction", camera.forward().to_string()),
+        ("Field of view", camera.fov.to_string()),
+        ("Scene", scene.to_string()),
+    ];
+
+    if let Ok(bytes) = std::fs::read(scene) {
+        entries.push(("Scene hash", format!("fnv1a64:{:016x}", fnv1a(&bytes))));
+    }
+
+    entries.push(("Render time", format!("{:.2?}", render_time)));
+    entries.push(("Settings", format!("{:#?}", settings)));
+    entries.push(("Camera", format!("{:#?}", camera)));
+
+    entries.into_iter().map(|(k, v)| (k.to_string(), v)).collect()
+}
+
+// Save an 8-bit image with `metadata` in it. Only PNGs can hold it, other
+// formats are saved like `save_image` does.
+pub fn save_image(img: &RgbaImage, path: &str, metadata: &Metadata) -> Result<(), LuxError> {
+    if !is_png(path) {
+        return crate::save_image(img, path);
+    }
+    write_png(path, img.width(), img.height(), png::BitDepth::Eight, img.as_raw(), metadata)
+}
+
+// Save the framebuffer with 16 bits per channel, like `save_16bit`, with
+// `metadata` in it if it's a PNG
+pub fn save_16bit(globals: &GlobalSettings, path: &str, metadata: &Metadata) -> Result<(), LuxError> {
+    if !is_png(path) {
+        return crate::save_16bit(globals, path);
+    }
+
+    // PNG keeps 16-bit samples big endian
+    let img: ImageBuffer<Rgba<u16>, Vec<u16>> = resolve_16bit(globals);
+    let data: Vec<u8> = img.as_raw().iter().flat_map(|c| c.to_be_bytes()).collect();
+    write_png(path, img.width(), img.height(), png::BitDepth::Sixteen, &data, metadata)
+}
+
+// Entries embedded in the PNG at `path`, plain ones first, then the
+// compressed ones, then the UTF-8 ones. Images without any give an empty
+// list.
+pub fn read(path: &str) -> Result<Metadata, LuxError> {
+    let file = File::open(path).map_err(|source| LuxError::Io {
+        path: path.to_string(),
+        source,
+    })?;
+    let reader = png::Decoder::new(file)
+        .read_info()
+        .map_err(|e| LuxError::from_png_decoding(path, e))?;
+    let info = reader.info();
+
+    let mut entries: Metadata = info
+        .uncompressed_latin1_text
+        .iter()
+        .map(|c| (c.keyword.clone(), c.text.clone()))
+        .collect();
+
+    for chunk in &info.compressed_latin1_text {
+        let mut chunk = chunk.clone();
+        chunk.decompress_text().map_err(|e| LuxError::from_png_decoding(path, e))?;
+        let text = chunk.get_text().map_err(|e| LuxError::from_png_decoding(path, e))?;
+        entries.push((chunk.keyword, text));
+    }
+
+    for chunk in &info.utf8_text {
+        let mut chunk = chunk.clone();
+        chunk.decompress_text().map_err(|e| LuxError::from_png_decoding(path, e))?;
+        let text = chunk.get_text().map_err(|e| LuxError::from_png_decoding(path, e))?;
+        entries.push((chunk.keyword, text));
+    }
+
+    Ok(entries)
+}
+
+fn is_png(path: &str) -> bool {
+    path.to_ascii_lowercase().ends_with(".png")
+}
+
+fn write_png(path: &str, width: u32, height: u32, depth: png::BitDepth, data: &[u8], metadata: &Metadata) -> Result<(), LuxError> {
+    let io_error = |source| LuxError::Io {
+        path: path.to_string(),
+        source,
+    };
+    let png_error = |e| LuxError::from_png_encoding(path, e);
+
+    let file = File::create(path).map_err(io_error)?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(depth);
+
+    for (keyword, text) in metadata {
+        let latin1 = text.chars().all(|c| (c as u32) < 256);
+        match (COMPRESSED.contains(&keyword.as_str()), latin1) {
+            (_, false) => encoder.add_itxt_chunk(keyword.clone(), text.clone()),
+            (true, true) => encoder.add_ztxt_chunk(keyword.clone(), text.clone()),
+            (false, true) => encoder.add_text_chunk(keyword.clone(), text.clone()),
+        }
+        .map_err(png_error)?;
+    }
+
+    let mut writer = encoder.write_header().map_err(png_error)?;
+    writer.write_image_data(data).map_err(png_error)?;
+    writer.finish().map_err(png_error)
+}
+
+// 64-bit FNV-1a hash
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |h, &b| (h ^ b as u64).wrapping_mul(0x0100_0000_01b3))
+}
@@ -0,0 +1,686 @@
+// Scene files, and checks on a scene before it's rendered.
+//
+// A scene file uses the same small TOML subset as the config file (see
+// toml.rs). Materials are named tables, everything else is a list:
+//
+//   [material.red]
+//   color = [1.0, 0.1, 0.1]
+//   diffusive = 0.3
+//   specular = 0.1
+//   reflective = 0.4
+//   roughness = 0.2            # blurry reflections, path tracer only
+//   refractive = 0.9           # glass, bent by ior (1.5 if left out)
+//   bump = "bricks"            # or "waves", with bump_scale and bump_depth
+//   cutout = "checker"         # or "dots": holes rays go through, with
+//                              # cutout_scale and cutout_threshold
+//   wrap = 0.3                 # light wrapping past the terminator (wax)
+//   dispersion = 0.03          # glass splitting light into colors
+//   translucency = 0.5         # diffuse light let through from behind
+//   max_reflection_depth = 2   # how deep trace follows mirror rays and
+//   max_refraction_depth = 10  # refractions off it, instead of max_depth
+//   sidedness = "front_only"   # or "two_sided", the default
+//
+//   [[sphere]]
+//   position = [0.0, 0.0, 2.0]
+//   radius = 0.5
+//   material = "red"
+//
+//   [[quad]]
+//   origin = [-2.0, -1.0, 0.0]
+//   edge_u = [4.0, 0.0, 0.0]
+//   edge_v = [0.0, 0.0, 4.0]
+//   material = "red"
+//
+//   [[torus]]
+//   center = [0.0, 0.0, 3.0]
+//   axis = [0.0, 1.0, 0.0]      # through the hole, up if left out
+//   major_radius = 1.0
+//   minor_radius = 0.25
+//   material = "red"
+//
+//   [[sdf]]
+//   shape = "blend"             # two spheres melting into each other
+//   center_a = [-0.4, 0.0, 3.0]
+//   radius_a = 0.5
+//   center_b = [0.4, 0.0, 3.0]
+//   radius_b = 0.4
+//   smoothness = 0.3
+//   material = "red"
+//
+//   [[sdf]]
+//   shape = "rounded_box"
+//   center = [0.0, 0.0, 3.0]
+//   size = [1.0, 0.5, 1.0]
+//   radius = 0.1                # rounding of the edges
+//   material = "red"
+//
+//   [[light]]
+//   position = [0.0, 3.0, 0.0]
+//   color = [1.0, 1.0, 1.0]    # or temperature = 6500.0
+//   intensity = 2.0
+//
+//   [world]
+//   ambient = [0.05, 0.05, 0.05]  # light every surface gets from everywhere
+//   environment = [0.5, 0.7, 1.0] # sky seen by reflections and bounces
+//   environment_map = "sky.hdr"   # or an image all around, lighting the
+//                                 # scene (relative to the scene file)
+//   camera_background = "transparent"  # or a color, or "environment"
+//   fog_density = 0.1             # fog everywhere, 0 (none) by default
+//   fog_color = [0.5, 0.5, 0.5]
+//   fog_shafts = 16               # points per camera ray for light shafts
+//
+// SDF shapes are ray marched; `max_steps` and `epsilon` (the distance that
+// counts as a hit) trade speed for accuracy.
+//
+// Any primitive can be hidden from some kinds of rays with `camera = false`
+// (camera rays), `shadow = false` (it casts no shadows) or `reflection =
+// false` (reflections, refractions and bounces don't see it).
+//
+// Spheres, quads, tori and SDF shapes can be moved with `translate = [x, y, z]`, turned
+// with `rotate = [x, y, z]` (degrees around each axis, x first) and resized
+// with `scale`, either one number or one per axis. Scaling happens first and
+// moving last.
+//
+// A sphere with a `velocity` moves that far per unit of time and is blurred
+// along its path while the camera shutter is open (`shutter_time`).
+//
+// A light with a `direction` is a spot light, with optional `inner_angle` and
+// `outer_angle` in degrees. `group` names the light group it's in (see
+// `Light`). Material keys that are left out are 0, except the color, which
+// is white, and `ior`.
+
+use std::path::Path;
+
+use crate::toml::{self, boolean, number, string, vector, Entry, Table, Value};
+use crate::*;
+
+// Read a scene file and add everything in it to `globals`
+pub fn load(path: &str, globals: &mut GlobalSettings) -> Result<(), LuxError> {
+    let text = toml::read(path)?;
+    let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+    parse(&text, dir, globals).map_err(|e| e.in_file(path))
+}
+
+// Add everything in the scene description `text` to `globals`. Files it
+// refers to are relative to the working directory.
+pub fn from_toml(text: &str, globals: &mut GlobalSettings) -> Result<(), LuxError> {
+    parse(text, Path::new(""), globals)
+}
+
+// `from_toml`, with files relative to `dir`
+fn parse(text: &str, dir: &Path, globals: &mut GlobalSettings) -> Result<(), LuxError> {
+    let tables = toml::parse(text)?;
+
+    // Materials first, so primitives can refer to materials defined after them
+    for table in &tables {
+        if let Some(name) = table.name.strip_prefix("material.") {
+            if table.array {
+                return Err(format!("line {}: materials are [material.name] tables", table.line).into());
+            }
+            globals.add_material(name, material(table)?);
+        }
+    }
+
+    for table in &tables {
+        match (table.name.as_str(), table.array) {
+            ("", _) => {
+                if let Some(e) = table.entries.first() {
+                    return Err(format!("line {}: '{}' must be inside a section", e.line, e.key).into());
+                }
+            }
+            (name, false) if name.starts_with("material.") => {}
+            ("world", false) => world(table, dir, globals)?,
+            ("sphere", true) => sphere(table, globals)?,
+            ("quad", true) => quad(table, globals)?,
+            ("torus", true) => torus(table, globals)?,
+            ("sdf", true) => sdf(table, globals)?,
+            ("light", true) => light(table, globals)?,
+            (name, _) => return Err(format!("line {}: unknown section '{}'", table.line, name).into()),
+        }
+    }
+
+    Ok(())
+}
+
+// Entry for `key`, or an error naming the table if it's missing
+fn required<'a>(table: &'a Table, key: &str) -> Result<&'a Entry, String> {
+    table
+        .entries
+        .iter()
+        .find(|e| e.key == key)
+        .ok_or_else(|| format!("line {}: [[{}]] needs '{}'", table.line, table.name, key))
+}
+
+fn optional<'a>(table: &'a Table, key: &str) -> Option<&'a Entry> {
+    table.entries.iter().find(|e| e.key == key)
+}
+
+// Error out on keys that aren't in `known`
+fn check_keys(table: &Table, known: &[&str]) -> Result<(), String> {
+    match table.entries.iter().find(|e| !known.contains(&e.key.as_str())) {
+        Some(e) => Err(format!("line {}: unknown key '{}' in [{}]", e.line, e.key, table.name)),
+        None => Ok(()),
+    }
+}
+
+// Prefix errors with the line of the entry they're about
+fn at<T>(e: &Entry, r: Result<T, String>) -> Result<T, String> {
+    r.map_err(|err| format!("line {}: {}", e.line, err))
+}
+
+fn material(table: &Table) -> Result<Material, String> {
+    check_keys(
+        table,
+        &[
+            "color", "specular", "diffusive", "reflective", "roughness", "refractive", "ior", "emission", "bump", "bump_scale",
+            "bump_depth", "cutout", "cutout_scale", "cutout_threshold", "sidedness", "wrap", "dispersion",
+            "translucency", "max_reflection_depth", "max_refraction_depth",
+        ],
+    )?;
+
+    let mut m = Material::default();
+
+    // The bump pattern needs all three keys, so they're read together
+    let mut bump = None;
+    let (mut scale, mut depth) = (8.0, 0.05);
+    // Same for the cutout
+    let mut cutout = None;
+    let (mut cutout_scale, mut threshold) = (8.0, 0.5);
+
+    for e in &table.entries {
+        match e.key.as_str() {
+            "color" => m.color = at(e, vector(&e.key, &e.value))?,
+            "specular" => m.specular = at(e, number(&e.key, &e.value))?,
+            "diffusive" => m.diffusive = at(e, number(&e.key, &e.value))?,
+            "reflective" => m.reflective = at(e, number(&e.key, &e.value))?,
+            "roughness" => m.roughness = at(e, number(&e.key, &e.value))?,
+            "refractive" => m.refractive = at(e, number(&e.key, &e.value))?,
+            "ior" => {
+                m.ior = at(e, number(&e.key, &e.value))?;
+                if m.ior <= 0.0 {
+                    return Err(format!("line {}: 'ior' must be above 0", e.line));
+                }
+            }
+            "bump" => bump = Some((e, at(e, string(&e.key, &e.value))?)),
+            "bump_scale" => scale = at(e, number(&e.key, &e.value))?,
+            "bump_depth" => depth = at(e, number(&e.key, &e.value))?,
+            "cutout" => cutout = Some((e, at(e, string(&e.key, &e.value))?)),
+            "wrap" => {
+                m.wrap = at(e, number(&e.key, &e.value))?;
+                if m.wrap < 0.0 {
+                    return Err(format!("line {}: 'wrap' can't be below 0", e.line));
+                }
+            }
+            "dispersion" => {
+                m.dispersion = at(e, number(&e.key, &e.value))?;
+                if m.dispersion < 0.0 {
+                    return Err(format!("line {}: 'dispersion' can't be below 0", e.line));
+                }
+            }
+            "translucency" => {
+                m.translucency = at(e, number(&e.key, &e.value))?;
+                if m.translucency < 0.0 {
+                    return Err(format!("line {}: 'translucency' can't be below 0", e.line));
+                }
+            }
+            "max_reflection_depth" => m.max_reflection_depth = Some(at(e, depth_cap(&e.key, &e.value))?),
+            "max_refraction_depth" => m.max_refraction_depth = Some(at(e, depth_cap(&e.key, &e.value))?),
+            "cutout_scale" => cutout_scale = at(e, number(&e.key, &e.value))?,
+            "cutout_threshold" => threshold = at(e, number(&e.key, &e.value))?,
+            "sidedness" => {
+                m.sidedness = match at(e, string(&e.key, &e.value))? {
+                    "two_sided" => Sidedness::TwoSided,
+                    "front_only" => Sidedness::FrontOnly,
+                    _ => return Err(format!("line {}: 'sidedness' expects \"two_sided\" or \"front_only\"", e.line)),
+                }
+            }
+            _ => m.emission = at(e, vector(&e.key, &e.value))?,
+        }
+    }
+
+    m.bump = match bump {
+        None => None,
+        Some((_, "bricks")) => Some(Bump::Bricks { scale, depth }),
+        Some((_, "waves")) => Some(Bump::Waves { scale, depth }),
+        Some((e, _)) => return Err(format!("line {}: 'bump' expects \"bricks\" or \"waves\"", e.line)),
+    };
+
+    m.cutout = match cutout {
+        None => None,
+        Some((_, "checker")) => Some(Cutout::Checker { scale: cutout_scale, threshold }),
+        Some((_, "dots")) => Some(Cutout::Dots { scale: cutout_scale, threshold }),
+        Some((e, _)) => return Err(format!("line {}: 'cutout' expects \"checker\" or \"dots\"", e.line)),
+    };
+
+    Ok(m)
+}
+
+// Depth cap of a material: a whole number, 0 or more
+fn depth_cap(key: &str, value: &Value) -> Result<u32, String> {
+    let depth = number(key, value)?;
+    if depth < 0.0 || depth.fract() != 0.0 {
+        return Err(format!("'{}' must be a whole number, 0 or more", key));
+    }
+    Ok(depth as u32)
+}
+
+fn material_name(table: &Table) -> Result<&str, String> {
+    let e = required(table, "material")?;
+    at(e, string(&e.key, &e.value))
+}
+
+const TRANSFORM_KEYS: [&str; 3] = ["translate", "rotate", "scale"];
+
+// The transform given by the table's `translate`, `rotate` and `scale`, if
+// it has any of them
+fn transform(table: &Table) -> Result<Option<Mat4>, String> {
+    if !table.entries.iter().any(|e| TRANSFORM_KEYS.contains(&e.key.as_str())) {
+        return Ok(None);
+    }
+
+    let mut m = Mat4::identity();
+
+    if let Some(e) = optional(table, "scale") {
+        let factors = match &e.value {
+            Value::Number(s) => Vector3D::new(*s, *s, *s),
+            v => at(e, vector(&e.key, v))?,
+        };
+        m = Mat4::scale(factors);
+    }
+
+    if let Some(e) = optional(table, "rotate") {
+        let r = at(e, vector(&e.key, &e.value))?;
+        m = Mat4::rotate_z(r.z) * Mat4::rotate_y(r.y) * Mat4::rotate_x(r.x) * m;
+    }
+
+    if let Some(e) = optional(table, "translate") {
+        m = Mat4::translate(at(e, vector(&e.key, &e.value))?) * m;
+    }
+
+    Ok(Some(m))
+}
+
+// Add `p` with the table's transform, material and visibility
+fn add(table: &Table, p: Box<dyn Primitive>, globals: &mut GlobalSettings) -> Result<(), LuxError> {
+    let p: Box<dyn Primitive> = match transform(table)? {
+        Some(m) => Box::new(Instance::new(p.into(), m).map_err(|e| e.at_line(table.line))?),
+        None => p,
+    };
+
+    let mut flags = VisibilityFlags::default();
+    for (key, flag) in [("camera", &mut flags.camera), ("shadow", &mut flags.shadow), ("reflection", &mut flags.reflection)] {
+        if let Some(e) = optional(table, key) {
+            *flag = at(e, boolean(&e.key, &e.value))?;
+        }
+    }
+
+    let line = required(table, "material")?.line;
+    add_primitive(p, globals).map_err(|e| e.at_line(line))?;
+    let index = globals.primitives().len() - 1;
+    globals.set_visibility(index, flags);
+    Ok(())
+}
+
+fn sphere(table: &Table, globals: &mut GlobalSettings) -> Result<(), LuxError> {
+    check_keys(table, &["position", "velocity", "radius", "material", "translate", "rotate", "scale", "camera", "shadow", "reflection"])?;
+
+    let e = required(table, "position")?;
+    let position = at(e, vector(&e.key, &e.value))?;
+    let velocity = match optional(table, "velocity") {
+        Some(e) => at(e, vector(&e.key, &e.value))?,
+        None => Vector3D::ZERO,
+    };
+    let e = required(table, "radius")?;
+    let radius = at(e, number(&e.key, &e.value))?;
+    if radius <= 0.0 {
+        return Err(format!("line {}: 'radius' must be above 0", e.line).into());
+    }
+    let m = material_name(table)?;
+
+    let p = PrimSphere {
+        position,
+        velocity,
+        radius,
+        m: m.into(),
+    };
+    add(table, Box::new(p), globals)
+}
+
+fn quad(table: &Table, globals: &mut GlobalSettings) -> Result<(), LuxError> {
+    check_keys(table, &["origin", "edge_u", "edge_v", "material", "translate", "rotate", "scale", "camera", "shadow", "reflection"])?;
+
+    let mut corners = Vec::new();
+    for key in ["origin", "edge_u", "edge_v"] {
+        let e = required(table, key)?;
+        corners.push(at(e, vector(&e.key, &e.value))?);
+    }
+    let m = material_name(table)?;
+
+    let p = PrimQuad {
+        origin: corners[0],
+        edge_u: corners[1],
+        edge_v: corners[2],
+        m: m.into(),
+    };
+    add(table, Box::new(p), globals)
+}
+
+fn torus(table: &Table, globals: &mut GlobalSettings) -> Result<(), LuxError> {
+    check_keys(
+        table,
+        &["center", "axis", "major_radius", "minor_radius", "material", "translate", "rotate", "scale", "camera", "shadow", "reflection"],
+    )?;
+
+    let e = required(table, "center")?;
+    let center = at(e, vector(&e.key, &e.value))?;
+    let mut axis = match optional(table, "axis") {
+        Some(e) => at(e, vector(&e.key, &e.value))?,
+        None => Vector3D::UNIT_Y,
+    };
+    if axis.v3d_length() == 0.0 {
+        return Err(format!("line {}: the torus axis can't be zero", table.line).into());
+    }
+    axis.v3d_norm();
+
+    let e = required(table, "major_radius")?;
+    let major_radius = at(e, number(&e.key, &e.value))?;
+    let e = required(table, "minor_radius")?;
+    let minor_radius = at(e, number(&e.key, &e.value))?;
+    let m = material_name(table)?;
+
+    let p = PrimTorus {
+        center,
+        axis,
+        major_radius,
+        minor_radius,
+        m: m.into(),
+    };
+    add(table, Box::new(p), globals)
+}
+
+fn sdf(table: &Table, globals: &mut GlobalSettings) -> Result<(), LuxError> {
+    let e = required(table, "shape")?;
+    let shape = at(e, string(&e.key, &e.value))?;
+    let keys: &[&str] = match shape {
+        "blend" => &["center_a", "radius_a", "center_b", "radius_b", "smoothness"],
+        "rounded_box" => &["center", "size", "radius"],
+        _ => return Err(format!("line {}: unknown sdf shape '{}', expected blend or rounded_box", e.line, shape).into()),
+    };
+    let common = ["shape", "max_steps", "epsilon", "material", "translate", "rotate", "scale", "camera", "shadow", "reflection"];
+    check_keys(table, &[keys, &common[..]].concat())?;
+
+    let vec = |key| required(table, key).and_then(|e| at(e, vector(&e.key, &e.value)));
+    let num = |key| required(table, key).and_then(|e| at(e, number(&e.key, &e.value)));
+    let m = material_name(table)?;
+
+    let mut p = match shape {
+        "blend" => {
+            let smoothness = match optional(table, "smoothness") {
+                Some(e) => at(e, number(&e.key, &e.value))?,
+                None => 0.0,
+            };
+            PrimSdf::blend((vec("center_a")?, num("radius_a")?), (vec("center_b")?, num("radius_b")?), smoothness, m)
+        }
+        _ => {
+            let radius = match optional(table, "radius") {
+                Some(e) => at(e, number(&e.key, &e.value))?,
+                None => 0.0,
+            };
+            PrimSdf::rounded_box(vec("center")?, vec("size")?, radius, m)
+        }
+    };
+
+    if let Some(e) = optional(table, "max_steps") {
+        let steps = at(e, number(&e.key, &e.value))?;
+        if steps < 1.0 {
+            return Err(format!("line {}: 'max_steps' must be at least 1", e.line).into());
+        }
+        p.max_steps = steps as u32;
+    }
+    if let Some(e) = optional(table, "epsilon") {
+        p.epsilon = at(e, number(&e.key, &e.value))?;
+        if p.epsilon <= 0.0 {
+            return Err(format!("line {}: 'epsilon' must be above 0", e.line).into());
+        }
+    }
+    add(table, Box::new(p), globals)
+}
+
+fn world(table: &Table, dir: &Path, globals: &mut GlobalSettings) -> Result<(), LuxError> {
+    check_keys(
+        table,
+        &["ambient", "environment", "environment_map", "camera_background", "fog_density", "fog_color", "fog_shafts"],
+    )?;
+
+    if let Some(e) = optional(table, "ambient") {
+        globals.ambient = at(e, vector(&e.key, &e.value))?;
+    }
+    if let Some(e) = optional(table, "environment") {
+        globals.environment = at(e, vector(&e.key, &e.value))?;
+    }
+    if let Some(e) = optional(table, "environment_map") {
+        let path = dir.join(at(e, string(&e.key, &e.value))?);
+        let map = EnvMap::load(&path.to_string_lossy()).map_err(|err| err.at_line(e.line))?;
+        globals.environment_map = Some(map);
+    }
+    if let Some(e) = optional(table, "camera_background") {
+        globals.camera_background = match &e.value {
+            Value::Str(s) if s == "environment" => CameraBackground::Environment,
+            Value::Str(s) if s == "transparent" => CameraBackground::Transparent,
+            Value::Str(_) => {
+                return Err(format!("line {}: 'camera_background' expects a color, \"environment\" or \"transparent\"", e.line).into())
+            }
+            _ => CameraBackground::Color(at(e, vector(&e.key, &e.value))?),
+        };
+    }
+    if let Some(e) = optional(table, "fog_density") {
+        globals.fog.density = at(e, number(&e.key, &e.value))?;
+        if !(globals.fog.density >= 0.0 && globals.fog.density.is_finite()) {
+            return Err(format!("line {}: 'fog_density' can't be below 0", e.line).into());
+        }
+    }
+    if let Some(e) = optional(table, "fog_color") {
+        globals.fog.color = at(e, vector(&e.key, &e.value))?;
+    }
+    if let Some(e) = optional(table, "fog_shafts") {
+        let shafts = at(e, number(&e.key, &e.value))?;
+        if shafts < 0.0 {
+            return Err(format!("line {}: 'fog_shafts' can't be below 0", e.line).into());
+        }
+        globals.fog.shafts = shafts as u32;
+    }
+    Ok(())
+}
+
+fn light(table: &Table, globals: &mut GlobalSettings) -> Result<(), LuxError> {
+    check_keys(
+        table,
+        &["position", "color", "temperature", "intensity", "direction", "inner_angle", "outer_angle", "group"],
+    )?;
+
+    let e = required(table, "position")?;
+    let position = at(e, vector(&e.key, &e.value))?;
+
+    let intensity = match optional(table, "intensity") {
+        Some(e) => at(e, number(&e.key, &e.value))?,
+        None => 1.0,
+    };
+
+    let color = match (optional(table, "color"), optional(table, "temperature")) {
+        (Some(_), Some(e)) => return Err(format!("line {}: give either a color or a temperature", e.line).into()),
+        (Some(e), None) => at(e, vector(&e.key, &e.value))?,
+        (None, Some(e)) => Light::from_temperature(at(e, number(&e.key, &e.value))?, intensity).color,
+        (None, None) => Vector3D::ONE,
+    };
+
+    let added = match optional(table, "direction") {
+        Some(e) => {
+            let direction = at(e, vector(&e.key, &e.value))?;
+
+            let mut angles = [30.0, 45.0];
+            for (angle, key) in angles.iter_mut().zip(["inner_angle", "outer_angle"]) {
+                if let Some(e) = optional(table, key) {
+                    *angle = at(e, number(&e.key, &e.value))?;
+                }
+            }
+
+            add_spot_light(position, direction, angles[0], angles[1], color, intensity, globals)
+        }
+        None => add_light(position, color, intensity, globals),
+    };
+
+    added.map_err(|e| e.at_line(table.line))?;
+
+    if let Some(e) = optional(table, "group") {
+        let group = at(e, string(&e.key, &e.value))?;
+        globals.light_list.last_mut().unwrap().group = group.to_string();
+    }
+    Ok(())
+}
+
+// Something about a scene that is probably a mistake
+pub enum Warning {
+    // Two primitives of the same kind in exactly the same place
+    Duplicate { first: usize, second: usize },
+    // A light inside a primitive can't light anything outside of it
+    LightInside { light: usize, primitive: usize },
+    // A light color above 1, where the brightness belongs in the intensity
+    BrightColor { light: usize },
+    // A material that sends out more light than it receives
+    EnergyGain { material: String, total: f64 },
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Warning::Duplicate { first, second } => {
+                write!(f, "primitives {} and {} are identical and overlap", first, second)
+            }
+            Warning::LightInside { light, primitive } => {
+                write!(f, "light {} is inside primitive {}", light, primitive)
+            }
+            Warning::BrightColor { light } => {
+                write!(f, "light {} has a color above 1, set its brightness with the intensity", light)
+            }
+            Warning::EnergyGain { material, total } => write!(
+                f,
+                "material {} has diffusive + specular + reflective + refractive = {:.2}, above 1 it gains energy \
+                 (--conserve-energy scales it down)",
+                material, total
+            ),
+        }
+    }
+}
+
+// Numbers worth knowing about a scene before rendering it
+pub struct SceneStats {
+    // Number of primitives of each kind, sorted by kind
+    pub primitives: Vec<(&'static str, usize)>,
+    pub lights: usize,
+    // Box around all primitives, `None` for an empty scene
+    pub bounds: Option<(Vector3D, Vector3D)>,
+    // Rough size of the scene and the image buffers, in bytes
+    pub memory: usize,
+}
+
+impl GlobalSettings {
+    pub fn stats(&self) -> SceneStats {
+        let mut primitives: Vec<(&'static str, usize)> = Vec::new();
+        let mut memory = 0;
+
+        for p in &self.primitive_list {
+            match primitives.iter_mut().find(|(kind, _)| *kind == p.kind()) {
+                Some((_, count)) => *count += 1,
+                None => primitives.push((p.kind(), 1)),
+            }
+
+            memory += std::mem::size_of_val(&**p) + std::mem::size_of_val(p);
+        }
+        primitives.sort();
+
+        let pixels = (self.img.width() * self.img.height()) as usize;
+        memory += pixels
+            * (std::mem::size_of::<Vector3D>() * 2
+                + std::mem::size_of::<f64>()
+                + std::mem::size_of::<u32>()
+                + std::mem::size_of::<image::Rgba<u8>>());
+        memory += self.light_list.len() * std::mem::size_of::<Light>();
+        memory += self
+            .materials
+            .keys()
+            .map(|name| name.len() + std::mem::size_of::<Material>())
+            .sum::<usize>();
+
+        SceneStats {
+            primitives,
+            lights: self.light_list.len(),
+            bounds: self.bounds(),
+            memory,
+        }
+    }
+}
+
+impl Scene {
+    // Box around all primitives, `None` for an empty scene
+    pub fn bounds(&self) -> Option<(Vector3D, Vector3D)> {
+        self.primitive_list.iter().map(|p| p.bounds()).reduce(|(lo, hi), (min, max)| (lo.min(min), hi.max(max)))
+    }
+
+    // Look for likely mistakes in the scene
+    pub fn validate(&self) -> Vec<Warning> {
+        let mut warnings = Vec::new();
+        let same = |a: Vector3D, b: Vector3D| (0..3).all(|i| (a[i] - b[i]).abs() < 1e-9);
+
+        for (i, a) in self.primitive_list.iter().enumerate() {
+            for (j, b) in self.primitive_list.iter().enumerate().skip(i + 1) {
+                let (a_min, a_max) = a.bounds();
+                let (b_min, b_max) = b.bounds();
+
+                if a.kind() == b.kind() && same(a_min, b_min) && same(a_max, b_max) {
+                    warnings.push(Warning::Duplicate { first: i, second: j });
+                }
+            }
+        }
+
+        for (i, light) in self.light_list.iter().enumerate() {
+            if light.color.max_component() > 1.0 {
+                warnings.push(Warning::BrightColor { light: i });
+            }
+        }
+
+        // A ray leaving the light from inside a primitive hits it from the
+        // inside, whatever direction it takes
+        for (i, light) in self.light_list.iter().enumerate() {
+            let ray = Ray {
+                origin: light.position,
+                direction: Vector3D::UNIT_Y,
+                time: 0.0,
+            };
+
+            for (j, p) in self.primitive_list.iter().enumerate() {
+                let mut dist = 0.0;
+                if p.intersect(ray, 0.0, &mut dist) == -1 {
+                    warnings.push(Warning::LightInside { light: i, primitive: j });
+                }
+            }
+        }
+
+        let mut names: Vec<&String> = self.materials.keys().collect();
+        names.sort();
+        let named = names.into_iter().map(|name| (format!("'{}'", name), &self.materials[name]));
+        let registered = self.registry.iter().enumerate().map(|(i, m)| (format!("#{}", i), m));
+        let inline = self.primitive_list.iter().enumerate().filter_map(|(i, p)| match p.material_ref() {
+            MaterialRef::Inline(m) => Some((format!("of primitive {}", i), m)),
+            MaterialRef::Named(_) | MaterialRef::Id(_) => None,
+        });
+
+        for (material, m) in named.chain(registered).chain(inline) {
+            let total = m.reflectance();
+            if total > 1.0 && !self.settings.conserve_energy {
+                warnings.push(Warning::EnergyGain { material, total });
+            }
+        }
+
+        warnings
+    }
+}
@@ -0,0 +1,206 @@
+// Shapes given by a signed distance function (SDF): a function that tells
+// how far a point is from the surface, negative inside. Rays find the
+// surface by sphere tracing: from wherever the ray is, nothing is closer
+// than the distance the function returns, so the ray can safely move that
+// far and ask again, until it's close enough to count as a hit.
+//
+// That works for any shape with a distance function, including ones with
+// no closed form intersection like blobs melting into each other.
+
+use crate::{MaterialRef, Primitive, Ray, Rng, Vector3D};
+
+// Distance from a point to the surface, negative inside. It may be smaller
+// than the real distance (the ray then takes more steps), never larger.
+pub type SdfFn = Box<dyn Fn(Vector3D) -> f64 + Send + Sync>;
+
+// Steps a ray takes by default before giving up on hitting anything
+pub const SDF_MAX_STEPS: u32 = 256;
+
+// Default distance to the surface that counts as a hit
+pub const SDF_EPSILON: f64 = 1e-5;
+
+pub struct PrimSdf {
+    pub sdf: SdfFn,
+    // Corners of a box the whole surface is inside, smallest first. Rays
+    // only march inside it.
+    pub min: Vector3D,
+    pub max: Vector3D,
+    pub max_steps: u32,
+    pub epsilon: f64,
+    pub m: MaterialRef,
+}
+
+impl PrimSdf {
+    // Surface of `sdf`, which must lie between `min` and `max`
+    pub fn new(sdf: SdfFn, min: Vector3D, max: Vector3D, m: impl Into<MaterialRef>) -> Self {
+        Self {
+            sdf,
+            min,
+            max,
+            max_steps: SDF_MAX_STEPS,
+            epsilon: SDF_EPSILON,
+            m: m.into(),
+        }
+    }
+
+    // Plain sphere. `PrimSphere` is faster; this one is for comparing.
+    pub fn sphere(center: Vector3D, radius: f64, m: impl Into<MaterialRef>) -> Self {
+        let sdf = move |p: Vector3D| {
+            let mut d = p;
+            d.v3d_sub(center);
+            d.v3d_length() - radius
+        };
+        let (min, max) = around(center, Vector3D::new(radius, radius, radius));
+        Self::new(Box::new(sdf), min, max, m)
+    }
+
+    // Two spheres blended into one blob. Up to `smoothness` away from where
+    // they'd meet, the surfaces bend smoothly into each other; 0 is a plain
+    // union.
+    pub fn blend(a: (Vector3D, f64), b: (Vector3D, f64), smoothness: f64, m: impl Into<MaterialRef>) -> Self {
+        let k = smoothness.max(0.0);
+        let sdf = move |p: Vector3D| {
+            let (mut da, mut db) = (p, p);
+            da.v3d_sub(a.0);
+            db.v3d_sub(b.0);
+            smooth_min(da.v3d_length() - a.1, db.v3d_length() - b.1, k)
+        };
+
+        // The blend pushes the surface out by up to k / 4
+        let grow = |r: f64| r + k / 4.0;
+        let (min_a, max_a) = around(a.0, Vector3D::ONE.map(|_| grow(a.1)));
+        let (min_b, max_b) = around(b.0, Vector3D::ONE.map(|_| grow(b.1)));
+        Self::new(Box::new(sdf), min_a.min(min_b), max_a.max(max_b), m)
+    }
+
+    // Box of the given edge lengths around `center`, with its edges and
+    // corners rounded off with `radius`
+    pub fn rounded_box(center: Vector3D, size: Vector3D, radius: f64, m: impl Into<MaterialRef>) -> Self {
+        let half = size.map(|s| s / 2.0);
+        let radius = radius.clamp(0.0, half.min_component());
+        let inner = half.map(|h| h - radius);
+
+        let sdf = move |p: Vector3D| {
+            let mut q = p;
+            q.v3d_sub(center);
+            let q = q.zip(inner, |c, h| c.abs() - h);
+            q.max(Vector3D::ZERO).v3d_length() + q.max_component().min(0.0) - radius
+        };
+        let (min, max) = around(center, half);
+        Self::new(Box::new(sdf), min, max, m)
+    }
+
+    fn at(&self, ray: &Ray, t: f64) -> Vector3D {
+        let mut p = ray.direction;
+        p.v3d_mul_scalar(t);
+        p.v3d_add(ray.origin);
+        p
+    }
+
+    // Part of `ray` inside the bounding box, if it passes through it
+    fn clip(&self, ray: &Ray) -> Option<(f64, f64)> {
+        let (mut near, mut far) = (f64::NEG_INFINITY, f64::INFINITY);
+        for axis in 0..3 {
+            let inv = 1.0 / ray.direction[axis];
+            let a = (self.min[axis] - ray.origin[axis]) * inv;
+            let b = (self.max[axis] - ray.origin[axis]) * inv;
+            near = near.max(a.min(b));
+            far = far.min(a.max(b));
+        }
+        (near <= far).then_some((near, far))
+    }
+}
+
+impl Primitive for PrimSdf {
+    fn intersect(&self, ray: Ray, t_min: f64, dist: &mut f64) -> i32 {
+        let (near, far) = match self.clip(&ray) {
+            Some(span) => span,
+            None => return 0,
+        };
+
+        // March towards the surface from whichever side the ray starts on.
+        // A ray from outside the box starts outside the shape.
+        let outside = near > t_min;
+        let mut t = near.max(t_min);
+        let side = match !outside && (self.sdf)(self.at(&ray, t)) < 0.0 {
+            true => -1.0,
+            false => 1.0,
+        };
+
+        // A ray leaving the surface starts within epsilon of it, and only
+        // hits once it has got away from it
+        let mut away = outside;
+        for _ in 0..self.max_steps {
+            if t > far {
+                return 0;
+            }
+
+            let d = side * (self.sdf)(self.at(&ray, t));
+            if d < self.epsilon {
+                if away {
+                    *dist = t;
+                    return if side < 0.0 { -1 } else { 1 };
+                }
+                t += self.epsilon;
+            } else {
+                away = true;
+                t += d;
+            }
+        }
+        0
+    }
+
+    // Gradient of the distance, by central differences
+    fn normal(&self, pos: Vector3D, _time: f64) -> Vector3D {
+        let h = self.epsilon;
+        let mut n = Vector3D::ZERO;
+        for axis in 0..3 {
+            let (mut plus, mut minus) = (pos, pos);
+            plus[axis] += h;
+            minus[axis] -= h;
+            n[axis] = (self.sdf)(plus) - (self.sdf)(minus);
+        }
+        n.v3d_norm();
+        n
+    }
+
+    fn material_ref(&self) -> &MaterialRef {
+        &self.m
+    }
+
+    fn kind(&self) -> &'static str {
+        "sdf"
+    }
+
+    fn bounds(&self) -> (Vector3D, Vector3D) {
+        (self.min, self.max)
+    }
+
+    // Not sampled directly as a light; the path tracer still finds emissive
+    // SDFs by chance
+    fn sample_direction(&self, _from: Vector3D, _time: f64, _rng: &mut Rng) -> Option<(Vector3D, f64)> {
+        None
+    }
+
+    fn direction_pdf(&self, _from: Vector3D, _hit: Vector3D, _time: f64) -> f64 {
+        0.0
+    }
+}
+
+// Box `extent` out from `center` in every direction
+fn around(center: Vector3D, extent: Vector3D) -> (Vector3D, Vector3D) {
+    let (mut min, mut max) = (center, center);
+    min.v3d_sub(extent);
+    max.v3d_add(extent);
+    (min, max)
+}
+
+// Minimum that rounds off the corner where `a` and `b` cross, over a width
+// of `k` (Quilez, "smooth minimum", polynomial version)
+fn smooth_min(a: f64, b: f64, k: f64) -> f64 {
+    if k <= 0.0 {
+        return a.min(b);
+    }
+    let h = (k - (a - b).abs()).max(0.0) / k;
+    a.min(b) - h * h * k / 4.0
+}
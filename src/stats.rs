@@ -0,0 +1,173 @@
+// Render statistics: how many rays of each kind were traced, how many
+// primitives they were tested against, how many BVH nodes they visited and
+// how deep the bounces went, per
+// render pass (worker thread, see `render_scene`) and for the whole image.
+//
+// The tracers bump the counters in `Scene::counters` as they go.
+// With `RenderSettings::stats` off every bump is a single well predicted
+// branch, so leaving the calls in the hot loops costs next to nothing.
+// Every thread counts on its own, so the workers don't fight over shared
+// counters and each can tell what it traced.
+
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+thread_local! {
+    // What this thread counted since it last took its counts
+    static COUNTS: Cell<RayCounts> = Cell::default();
+}
+
+// Switch for the counting. The counts themselves are kept per thread.
+#[derive(Default)]
+pub struct RayCounters {
+    enabled: AtomicBool,
+}
+
+// Counts taken out of `RayCounters`
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct RayCounts {
+    // Camera rays, one per sample
+    pub primary: u64,
+    // Rays testing whether a light can be seen
+    pub shadow: u64,
+    // Reflected, refracted and bounced rays
+    pub secondary: u64,
+    // Ray-primitive intersection tests
+    pub tests: u64,
+    // BVH nodes visited
+    pub nodes: u64,
+    // Deepest bounce of any ray, 0 for camera rays
+    pub max_depth: u32,
+}
+
+// Counts and time of one render pass
+#[derive(Clone, Copy, Debug)]
+pub struct PassStats {
+    pub pass: u32,
+    pub counts: RayCounts,
+    pub time: Duration,
+}
+
+impl RayCounters {
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    fn count(&self, f: impl FnOnce(&mut RayCounts)) {
+        if self.enabled.load(Ordering::Relaxed) {
+            COUNTS.with(|counts| {
+                let mut c = counts.get();
+                f(&mut c);
+                counts.set(c);
+            });
+        }
+    }
+
+    pub fn primary(&self) {
+        self.count(|c| c.primary += 1);
+    }
+
+    pub fn shadow(&self) {
+        self.count(|c| c.shadow += 1);
+    }
+
+    // A secondary ray `depth` bounces away from the camera
+    pub fn secondary(&self, depth: u32) {
+        self.count(|c| {
+            c.secondary += 1;
+            c.max_depth = c.max_depth.max(depth);
+        });
+    }
+
+    pub fn tests(&self, n: usize) {
+        self.count(|c| c.tests += n as u64);
+    }
+
+    pub fn nodes(&self, n: u32) {
+        self.count(|c| c.nodes += n as u64);
+    }
+
+    // What the calling thread counted since it last called this, resetting
+    // its counts to 0
+    pub fn take(&self) -> RayCounts {
+        COUNTS.with(|counts| counts.take())
+    }
+}
+
+impl RayCounts {
+    pub fn rays(&self) -> u64 {
+        self.primary + self.shadow + self.secondary
+    }
+}
+
+// Sum of all the passes
+pub fn total(passes: &[PassStats]) -> PassStats {
+    let mut total = PassStats {
+        pass: 0,
+        counts: RayCounts::default(),
+        time: Duration::ZERO,
+    };
+
+    for p in passes {
+        let (t, c) = (&mut total.counts, &p.counts);
+        t.primary += c.primary;
+        t.shadow += c.shadow;
+        t.secondary += c.secondary;
+        t.tests += c.tests;
+        t.nodes += c.nodes;
+        t.max_depth = t.max_depth.max(c.max_depth);
+        total.time += p.time;
+    }
+
+    total
+}
+
+// Table with a row per pass and the total, for printing
+pub fn table(passes: &[PassStats]) -> String {
+    let mut out = format!(
+        "{:<6} {:>12} {:>12} {:>12} {:>14} {:>14} {:>6} {:>10}\n",
+        "pass", "primary", "shadow", "secondary", "tests", "nodes", "depth", "time"
+    );
+
+    let total = total(passes);
+    let rows = passes.iter().map(|p| (p.pass.to_string(), p)).chain([("total".to_string(), &total)]);
+    for (name, p) in rows {
+        let c = &p.counts;
+        out += &format!(
+            "{:<6} {:>12} {:>12} {:>12} {:>14} {:>14} {:>6} {:>10.2?}\n",
+            name, c.primary, c.shadow, c.secondary, c.tests, c.nodes, c.max_depth, p.time
+        );
+    }
+
+    // The passes run side by side, so this is how fast one thread traces
+    let secs = total.time.as_secs_f64();
+    if secs > 0.0 {
+        out += &format!("{:.0} rays/s per thread\n", total.counts.rays() as f64 / secs);
+    }
+    out
+}
+
+// The same numbers as JSON, times in seconds
+pub fn json(passes: &[PassStats]) -> String {
+    let object = |p: &PassStats| {
+        let c = &p.counts;
+        format!(
+            "{{\"primary\": {}, \"shadow\": {}, \"secondary\": {}, \"tests\": {}, \"nodes\": {}, \"max_depth\": {}, \"seconds\": {}}}",
+            c.primary,
+            c.shadow,
+            c.secondary,
+            c.tests,
+            c.nodes,
+            c.max_depth,
+            p.time.as_secs_f64()
+        )
+    };
+
+    let passes_json: Vec<String> = passes.iter().map(|p| format!("    {}", object(p))).collect();
+    format!(
+        "{{\n  \"passes\": [\n{}\n  ],\n  \"total\": {}\n}}\n",
+        passes_json.join(",\n"),
+        object(&total(passes))
+    )
+}
@@ -0,0 +1,168 @@
+// Just enough TOML for the config and scene files: `[section]` and
+// `[[array]]` headers (names may be dotted), `#` comments and `key = value`
+// with numbers, booleans, strings and arrays of numbers.
+
+use crate::{LuxError, Vector3D};
+
+// A parsed value
+pub enum Value {
+    Number(f64),
+    Bool(bool),
+    Str(String),
+    Array(Vec<f64>),
+}
+
+// The keys under one header. Keys before the first header end up in a table
+// with an empty name.
+pub struct Table {
+    pub name: String,
+    // Whether it was a `[[name]]` header
+    pub array: bool,
+    // Line of the header, for error messages
+    pub line: usize,
+    pub entries: Vec<Entry>,
+}
+
+pub struct Entry {
+    pub key: String,
+    pub value: Value,
+    pub line: usize,
+}
+
+impl Value {
+    fn parse(s: &str) -> Option<Value> {
+        if let Some(inner) = s.strip_prefix('"') {
+            return inner.strip_suffix('"').map(|v| Value::Str(v.to_string()));
+        }
+
+        if let Some(inner) = s.strip_prefix('[') {
+            let inner = inner.strip_suffix(']')?.trim();
+            if inner.is_empty() {
+                return Some(Value::Array(Vec::new()));
+            }
+            return inner
+                .split(',')
+                .map(|p| p.trim().replace('_', "").parse().ok())
+                .collect::<Option<Vec<f64>>>()
+                .map(Value::Array);
+        }
+
+        match s {
+            "true" => Some(Value::Bool(true)),
+            "false" => Some(Value::Bool(false)),
+            _ => s.replace('_', "").parse().ok().map(Value::Number),
+        }
+    }
+}
+
+// Strip a `#` comment, leaving `#` inside strings alone
+fn strip_comment(line: &str) -> &str {
+    let mut in_str = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_str = !in_str,
+            '#' if !in_str => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+// Read the text file `path`
+pub fn read(path: &str) -> Result<String, LuxError> {
+    std::fs::read_to_string(path).map_err(|source| match source.kind() {
+        std::io::ErrorKind::InvalidData => LuxError::Decode(format!("{}: not a UTF-8 text file", path)),
+        _ => LuxError::Io {
+            path: path.to_string(),
+            source,
+        },
+    })
+}
+
+// Split `text` into its tables, in file order
+pub fn parse(text: &str) -> Result<Vec<Table>, String> {
+    let mut tables = vec![Table {
+        name: String::new(),
+        array: false,
+        line: 0,
+        entries: Vec::new(),
+    }];
+
+    for (n, line) in text.lines().enumerate() {
+        let n = n + 1;
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if line.starts_with('[') {
+            let (name, array) = match line.strip_prefix("[[").and_then(|l| l.strip_suffix("]]")) {
+                Some(name) => (name, true),
+                None => match line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                    Some(name) => (name, false),
+                    None => return Err(format!("line {}: bad section header", n)),
+                },
+            };
+
+            tables.push(Table {
+                name: name.trim().to_string(),
+                array,
+                line: n,
+                entries: Vec::new(),
+            });
+            continue;
+        }
+
+        let (key, value) = match line.split_once('=') {
+            Some((k, v)) => (k.trim(), v.trim()),
+            None => return Err(format!("line {}: expected key = value", n)),
+        };
+
+        let value = Value::parse(value)
+            .ok_or_else(|| format!("line {}: can't read the value of '{}'", n, key))?;
+
+        tables.last_mut().unwrap().entries.push(Entry {
+            key: key.to_string(),
+            value,
+            line: n,
+        });
+    }
+
+    Ok(tables)
+}
+
+pub fn number(key: &str, v: &Value) -> Result<f64, String> {
+    match v {
+        Value::Number(n) => Ok(*n),
+        _ => Err(format!("'{}' expects a number", key)),
+    }
+}
+
+pub fn whole<T: TryFrom<u64>>(key: &str, v: &Value) -> Result<T, String> {
+    let n = number(key, v)?;
+    if n < 0.0 || n.fract() != 0.0 {
+        return Err(format!("'{}' expects a whole number", key));
+    }
+    T::try_from(n as u64).map_err(|_| format!("'{}' is too large", key))
+}
+
+pub fn boolean(key: &str, v: &Value) -> Result<bool, String> {
+    match v {
+        Value::Bool(b) => Ok(*b),
+        _ => Err(format!("'{}' expects true or false", key)),
+    }
+}
+
+pub fn string<'a>(key: &str, v: &'a Value) -> Result<&'a str, String> {
+    match v {
+        Value::Str(s) => Ok(s),
+        _ => Err(format!("'{}' expects a string", key)),
+    }
+}
+
+pub fn vector(key: &str, v: &Value) -> Result<Vector3D, String> {
+    match v {
+        Value::Array(a) if a.len() == 3 => Ok(Vector3D::new(a[0], a[1], a[2])),
+        _ => Err(format!("'{}' expects an array of three numbers", key)),
+    }
+}
@@ -0,0 +1,66 @@
+// Adaptive sampling test. Flat background pixels stop at the base samples
+// while pixels on an edge keep going, and for the same number of samples in
+// total the image comes out closer to a clean render than with the same
+// samples for every pixel.
+
+use lux::*;
+
+const WIDTH: u32 = 32;
+const HEIGHT: u32 = 24;
+
+fn render(samples: u32, adaptive: bool) -> GlobalSettings {
+    let settings = RenderSettings {
+        samples,
+        adaptive,
+        min_samples: 4,
+        adaptive_threshold: 0.01,
+        seed: 3,
+        ..Default::default()
+    };
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, settings, Camera::default());
+    let white = Material {
+        diffusive: 1.0,
+        ..Default::default()
+    };
+    add_sphere(&Vector3D::ZERO, 1.0, white, &mut globals).unwrap();
+    add_light(Vector3D::new(0.0, 0.0, -5.0), Vector3D::ONE, 1.0, &mut globals).unwrap();
+    render_scene(&mut globals).unwrap();
+    globals
+}
+
+fn error(image: &[Vector3D], reference: &[Vector3D]) -> f64 {
+    image
+        .iter()
+        .zip(reference)
+        .map(|(a, b)| {
+            let d = a.luminance() - b.luminance();
+            d * d
+        })
+        .sum()
+}
+
+#[test]
+fn edges_get_more_samples_than_the_background() {
+    let globals = render(64, true);
+    let counts = &globals.sample_counts;
+
+    // The corner only sees the background
+    assert_eq!(counts[0], 4);
+    // The sphere's left edge crosses the middle row somewhere
+    let row = (HEIGHT / 2 * WIDTH) as usize;
+    assert!(counts[row..row + WIDTH as usize].iter().any(|&n| n > 4));
+}
+
+#[test]
+fn adaptive_beats_uniform_for_the_same_samples() {
+    let reference = render(256, false).framebuffer;
+
+    let adaptive = render(64, true);
+    let total: u32 = adaptive.sample_counts.iter().sum();
+    let per_pixel = total / (WIDTH * HEIGHT);
+    let uniform = render(per_pixel, false);
+    assert!(uniform.sample_counts.iter().sum::<u32>() >= total - WIDTH * HEIGHT);
+
+    let (a, u) = (error(&adaptive.framebuffer, &reference), error(&uniform.framebuffer, &reference));
+    assert!(a <= u, "adaptive {} vs uniform {} at {} samples per pixel", a, u, per_pixel);
+}
@@ -0,0 +1,58 @@
+// Ambient light test. Turning up `Scene::ambient` brightens every
+// surface by the same amount times its diffuse color, but rays that miss
+// still see the plain background.
+
+use lux::*;
+
+const WIDTH: u32 = 32;
+const HEIGHT: u32 = 24;
+
+fn render(ambient: Vector3D) -> GlobalSettings {
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, RenderSettings::default(), Camera::default());
+    globals.ambient = ambient;
+
+    let orange = Material {
+        color: Vector3D::new(1.0, 0.5, 0.1),
+        diffusive: 0.5,
+        ..Default::default()
+    };
+    add_sphere(&Vector3D::new(0.0, 0.0, 3.0), 1.0, orange, &mut globals).unwrap();
+    add_light(Vector3D::new(1.0, 1.0, 0.0), Vector3D::ONE, 1.0, &mut globals).unwrap();
+    render_scene(&mut globals).unwrap();
+    globals
+}
+
+#[test]
+fn ambient_brightens_surfaces_but_not_the_background() {
+    let dark = render(Vector3D::ZERO);
+    let bright = render(Vector3D::new(0.2, 0.2, 0.2));
+
+    // 0.2 times the diffuse color
+    let step = Vector3D::new(0.1, 0.05, 0.01);
+
+    let mut surfaces = 0;
+    for (i, (d, b)) in dark.framebuffer.iter().zip(&bright.framebuffer).enumerate() {
+        if dark.depth_buffer[i].is_finite() {
+            surfaces += 1;
+            let mut gain = *b;
+            gain.v3d_sub(*d);
+            assert!(gain.approx_eq(step, 1e-12), "pixel {}: {}", i, gain);
+        } else {
+            assert_eq!(*d, BACKGROUND);
+            assert_eq!(*b, BACKGROUND);
+        }
+    }
+    assert!(surfaces > 0);
+}
+
+#[test]
+fn ambient_from_the_scene_file() {
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, RenderSettings::default(), Camera::default());
+    assert_eq!(globals.ambient, AMBIENT);
+
+    scene::from_toml("[world]\nambient = [0.1, 0.2, 0.3]\n", &mut globals).unwrap();
+    assert_eq!(globals.ambient, Vector3D::new(0.1, 0.2, 0.3));
+
+    let result = scene::from_toml("[world]\nambeint = [0.1, 0.2, 0.3]\n", &mut globals);
+    assert!(matches!(result, Err(LuxError::Parse(_))), "{:?}", result.err());
+}
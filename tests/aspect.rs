@@ -0,0 +1,32 @@
+// Aspect ratio test. The view window follows the shape of the image, so a
+// sphere comes out round in a wide image as well as in a square one.
+
+use lux::*;
+
+// Width and height in pixels of what a sphere in the middle of a `width` x
+// `height` render covers
+fn sphere_size(width: u32, height: u32) -> (u32, u32) {
+    let mut globals = GlobalSettings::new(width, height, RenderSettings::default(), Camera::default());
+    let white = Material {
+        diffusive: 1.0,
+        ..Default::default()
+    };
+    add_sphere(&Vector3D::new(0.0, 0.0, 0.0), 1.0, white, &mut globals).unwrap();
+    render_scene(&mut globals).unwrap();
+
+    let hits: Vec<(u32, u32)> = (0..width * height).filter(|&i| globals.depth_buffer[i as usize].is_finite()).map(|i| (i % width, i / width)).collect();
+    let span = |of: fn(&(u32, u32)) -> u32| hits.iter().map(of).max().unwrap() - hits.iter().map(of).min().unwrap() + 1;
+    (span(|p| p.0), span(|p| p.1))
+}
+
+#[test]
+fn spheres_stay_round_in_wide_images() {
+    for (width, height) in [(100, 100), (200, 100)] {
+        let (w, h) = sphere_size(width, height);
+        assert!(w > 10, "{}x{}", width, height);
+        assert!(w.abs_diff(h) <= 1, "{}x{} pixels in a {}x{} image", w, h, width, height);
+    }
+
+    // And the same size, the height deciding the field of view
+    assert_eq!(sphere_size(100, 100).1, sphere_size(200, 100).1);
+}
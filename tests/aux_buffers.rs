@@ -0,0 +1,77 @@
+// Auxiliary buffer tests. The albedo and normal buffers hold what the
+// primary ray hit, not the shaded color: a red sphere is plain red in the
+// albedo however it's lit, and its normals point out of it. Written as
+// OpenEXR they come back as the same floats.
+
+use lux::*;
+
+const WIDTH: u32 = 32;
+const HEIGHT: u32 = 24;
+const RED: Vector3D = Vector3D::new(1.0, 0.0, 0.0);
+
+fn red_sphere() -> GlobalSettings {
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, RenderSettings::default(), Camera::default());
+    let red = Material {
+        color: RED,
+        specular: 0.5,
+        diffusive: 0.7,
+        reflective: 0.2,
+        ..Default::default()
+    };
+    add_sphere(&Vector3D::new(0.0, 0.0, 2.0), 1.5, red, &mut globals).unwrap();
+    add_light(Vector3D::new(-2.0, 2.0, -1.0), Vector3D::new(0.5, 0.5, 1.0), 1.0, &mut globals).unwrap();
+    render_scene(&mut globals).unwrap();
+    globals
+}
+
+#[test]
+fn albedo_and_normal_come_from_the_primary_hit() {
+    let globals = red_sphere();
+    let center = Vector3D::new(0.0, 0.0, 2.0);
+
+    let mut on_sphere = 0;
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            let i = (y * WIDTH + x) as usize;
+            match globals.pick(&globals.camera, x, y, WIDTH, HEIGHT) {
+                Some(hit) => {
+                    on_sphere += 1;
+                    assert_eq!(globals.albedo_buffer[i], RED);
+                    // The shaded color isn't plain red
+                    assert_ne!(globals.framebuffer[i], RED);
+
+                    let mut normal = hit.point;
+                    normal.v3d_sub(center);
+                    normal.v3d_norm();
+                    assert!(globals.normal_buffer[i].approx_eq(normal, 1e-9), "({}, {})", x, y);
+                }
+                None => {
+                    assert_eq!(globals.albedo_buffer[i], Vector3D::ZERO);
+                    assert_eq!(globals.normal_buffer[i], Vector3D::ZERO);
+                }
+            }
+        }
+    }
+    assert!(on_sphere > 100, "{}", on_sphere);
+
+    // Straight ahead the sphere faces the camera
+    let middle = (HEIGHT / 2 * WIDTH + WIDTH / 2) as usize;
+    assert!(globals.normal_buffer[middle].approx_eq(Vector3D::new(0.0, 0.0, -1.0), 0.05));
+}
+
+#[test]
+fn exr_keeps_the_floats() {
+    let globals = red_sphere();
+    let path = format!("{}/aux_normal.exr", env!("CARGO_TARGET_TMPDIR"));
+    write_exr(&path, &globals.normal_buffer, WIDTH, HEIGHT).unwrap();
+
+    let read = image::open(&path).unwrap().into_rgb32f();
+    assert_eq!(read.dimensions(), (WIDTH, HEIGHT));
+    for (pixel, normal) in read.pixels().zip(&globals.normal_buffer) {
+        for c in 0..3 {
+            assert_eq!(pixel[c], normal[c] as f32);
+        }
+    }
+
+    assert!(matches!(write_exr(&path, &globals.normal_buffer, WIDTH, WIDTH), Err(LuxError::InvalidSettings(_))));
+}
@@ -0,0 +1,104 @@
+// Axis overlay tests. `Camera::project` is where a world point lands in the
+// image, and `--axes` draws the world's axes through it: x red, y green and
+// z blue.
+
+use lux::*;
+
+const WIDTH: u32 = 64;
+const HEIGHT: u32 = 48;
+
+fn axis_end(axis: usize) -> Vector3D {
+    let mut p = Vector3D::ZERO;
+    p[axis] = 1.0;
+    p
+}
+
+#[test]
+fn default_camera_sees_the_origin_in_the_middle() {
+    let camera = Camera::default();
+    let (x, y) = camera.project(Vector3D::ZERO, WIDTH, HEIGHT).unwrap();
+    assert!((x - 32.0).abs() < 1e-9 && (y - 24.0).abs() < 1e-9, "({}, {})", x, y);
+
+    // x goes right, y up, and z straight away from the camera
+    let (xx, xy) = camera.project(axis_end(0), WIDTH, HEIGHT).unwrap();
+    assert!(xx > x + 1.0 && (xy - y).abs() < 1e-9);
+    let (yx, yy) = camera.project(axis_end(1), WIDTH, HEIGHT).unwrap();
+    assert!(yy < y - 1.0 && (yx - x).abs() < 1e-9);
+    let (zx, zy) = camera.project(axis_end(2), WIDTH, HEIGHT).unwrap();
+    assert!((zx - x).abs() < 1e-9 && (zy - y).abs() < 1e-9);
+}
+
+#[test]
+fn axes_point_the_right_way_from_a_turned_camera() {
+    // Up, right and in front of the origin, looking back at it
+    let mut camera = Camera {
+        position: Vector3D::new(3.0, 2.0, -4.0),
+        ..Default::default()
+    };
+    camera.look_at(Vector3D::ZERO, Vector3D::UNIT_Y).unwrap();
+
+    let (ox, oy) = camera.project(Vector3D::ZERO, WIDTH, HEIGHT).unwrap();
+    assert!((ox - 32.0).abs() < 1e-9 && (oy - 24.0).abs() < 1e-9, "({}, {})", ox, oy);
+
+    let offset = |axis: usize| {
+        let (x, y) = camera.project(axis_end(axis), WIDTH, HEIGHT).unwrap();
+        (x - ox, y - oy)
+    };
+
+    // Seen from the front right, x comes towards the camera down and to the
+    // right and z goes away up and to the right; y goes straight up
+    let (dx, dy) = offset(0);
+    assert!(dx > 1.0 && dy > 1.0, "x axis goes ({}, {})", dx, dy);
+    let (dx, dy) = offset(1);
+    assert!(dy < -1.0 && dx.abs() < 1e-9, "y axis goes ({}, {})", dx, dy);
+    let (dx, dy) = offset(2);
+    assert!(dx > 1.0 && dy < -1.0, "z axis goes ({}, {})", dx, dy);
+
+    // Behind the camera there's nothing to see
+    assert!(camera.project(Vector3D::new(6.0, 4.0, -8.0), WIDTH, HEIGHT).is_none());
+}
+
+#[test]
+fn project_inverts_pixel_ray() {
+    let cameras = [
+        Camera {
+            roll: 20.0,
+            shift: 0.1,
+            ..Default::default()
+        },
+        Camera {
+            projection: Projection::Equirectangular,
+            ..Default::default()
+        },
+        Camera {
+            projection: Projection::Fisheye { fov_degrees: 180.0 },
+            ..Default::default()
+        },
+    ];
+    let point = Vector3D::new(0.7, -0.4, 1.0);
+
+    for camera in cameras {
+        let (x, y) = camera.project(point, WIDTH, HEIGHT).unwrap();
+        let ray = camera.pixel_ray(x, y, WIDTH, HEIGHT, 0.0).unwrap();
+
+        let mut to_point = point;
+        to_point.v3d_sub(ray.origin);
+        to_point.v3d_norm();
+        assert!(to_point.approx_eq(ray.direction, 1e-9), "{} vs {}", to_point, ray.direction);
+    }
+}
+
+#[test]
+fn axes_are_drawn_over_the_render() {
+    let settings = RenderSettings {
+        axes: Some(1.0),
+        ..Default::default()
+    };
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, settings, Camera::default());
+    render_scene(&mut globals).unwrap();
+
+    let pixel = |x: u32, y: u32| globals.img.get_pixel(x, y).0;
+    assert_eq!(pixel(40, 24), [255, 0, 0, 255]);
+    assert_eq!(pixel(32, 16), [0, 255, 0, 255]);
+    assert_ne!(pixel(40, 30), [255, 0, 0, 255]);
+}
@@ -0,0 +1,97 @@
+// Background test. Camera rays that miss see the camera background and
+// everything else the environment, so a mirror sphere over a transparent
+// background still reflects the sky.
+
+use lux::*;
+
+const WIDTH: u32 = 16;
+const HEIGHT: u32 = 12;
+
+const SKY: Vector3D = Vector3D::new(0.3, 0.5, 0.9);
+
+fn mirror_sphere(camera_background: CameraBackground) -> GlobalSettings {
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, RenderSettings::default(), Camera::default());
+    globals.environment = SKY;
+    globals.camera_background = camera_background;
+
+    let mirror = Material {
+        reflective: 1.0,
+        ..Default::default()
+    };
+    add_sphere(&Vector3D::new(0.0, 0.0, 0.0), 2.0, mirror, &mut globals).unwrap();
+    render_scene(&mut globals).unwrap();
+    globals
+}
+
+fn pixel(x: u32, y: u32) -> usize {
+    (y * WIDTH + x) as usize
+}
+
+#[test]
+fn transparent_background_still_shows_in_reflections() {
+    let globals = mirror_sphere(CameraBackground::Transparent);
+
+    // The middle of the sphere reflects the sky straight back
+    let middle = globals.framebuffer[pixel(WIDTH / 2, HEIGHT / 2)];
+    assert!(middle.approx_eq(SKY, 1e-9), "{}", middle);
+    assert_eq!(globals.img.get_pixel(WIDTH / 2, HEIGHT / 2)[3], 255);
+
+    // Around it there's nothing
+    for (x, y) in [(0, 0), (WIDTH - 1, 0), (0, HEIGHT - 1), (WIDTH - 1, HEIGHT - 1)] {
+        assert_eq!(globals.coverage[pixel(x, y)], 0.0);
+        assert_eq!(globals.img.get_pixel(x, y)[3], 0);
+    }
+}
+
+#[test]
+fn camera_background_can_differ_from_the_environment() {
+    let black = mirror_sphere(CameraBackground::Color(Vector3D::ZERO));
+
+    assert!(black.framebuffer[pixel(WIDTH / 2, HEIGHT / 2)].approx_eq(SKY, 1e-9));
+    assert_eq!(black.framebuffer[pixel(0, 0)], Vector3D::ZERO);
+    assert_eq!(black.img.get_pixel(0, 0)[3], 255);
+}
+
+#[test]
+fn by_default_the_camera_sees_the_environment() {
+    let globals = mirror_sphere(CameraBackground::default());
+
+    assert_eq!(globals.framebuffer[pixel(0, 0)], SKY);
+    assert!(globals.coverage.iter().all(|&a| a == 1.0));
+}
+
+#[test]
+fn edges_are_partly_transparent() {
+    let settings = RenderSettings {
+        samples: 16,
+        ..Default::default()
+    };
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, settings, Camera::default());
+    globals.camera_background = CameraBackground::Transparent;
+    let white = Material {
+        diffusive: 1.0,
+        ..Default::default()
+    };
+    add_sphere(&Vector3D::new(0.0, 0.0, 0.0), 2.0, white, &mut globals).unwrap();
+    render_scene(&mut globals).unwrap();
+
+    assert!(globals.coverage.iter().any(|&a| a > 0.0 && a < 1.0));
+}
+
+#[test]
+fn scene_file_sets_the_backgrounds() {
+    let path = format!("{}/backgrounds.toml", env!("CARGO_TARGET_TMPDIR"));
+    std::fs::write(&path, "[world]\nenvironment = [0.3, 0.5, 0.9]\ncamera_background = \"transparent\"\n").unwrap();
+
+    let mut globals = GlobalSettings::new(4, 4, RenderSettings::default(), Camera::default());
+    scene::load(&path, &mut globals).unwrap();
+    assert_eq!(globals.environment, SKY);
+    assert_eq!(globals.camera_background, CameraBackground::Transparent);
+
+    std::fs::write(&path, "[world]\ncamera_background = [0.0, 0.0, 0.0]\n").unwrap();
+    scene::load(&path, &mut globals).unwrap();
+    assert_eq!(globals.camera_background, CameraBackground::Color(Vector3D::ZERO));
+
+    std::fs::write(&path, "[world]\ncamera_background = \"sky\"\n").unwrap();
+    assert!(matches!(scene::load(&path, &mut globals), Err(LuxError::Parse(_))));
+}
@@ -0,0 +1,48 @@
+// Batch rendering test. `--batch` renders every scene file in a directory
+// in one run, each to a PNG of the same name next to it, and carries on
+// past a scene that can't be loaded.
+
+use std::path::Path;
+use std::process::{Command, ExitStatus};
+
+const SPHERE: &str = "[material.white]\ndiffusive = 1.0\n\n[[sphere]]\nposition = [0.0, 0.0, 2.0]\nradius = 0.5\nmaterial = \"white\"\n";
+
+// Fresh directory `name` holding the scene files `scenes`
+fn scene_dir(name: &str, scenes: &[(&str, &str)]) -> String {
+    let dir = format!("{}/{}", env!("CARGO_TARGET_TMPDIR"), name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    for (file, text) in scenes {
+        std::fs::write(Path::new(&dir).join(file), text).unwrap();
+    }
+    dir
+}
+
+fn batch(dir: &str) -> ExitStatus {
+    Command::new(env!("CARGO_BIN_EXE_lux"))
+        .args(["--batch", dir, "--width", "12", "--height", "8"])
+        .current_dir(env!("CARGO_TARGET_TMPDIR"))
+        .env("RUST_LOG", "error")
+        .status()
+        .unwrap()
+}
+
+#[test]
+fn every_scene_in_the_directory_is_rendered() {
+    let dir = scene_dir("batch", &[("one.toml", SPHERE), ("two.toml", SPHERE), ("notes.txt", "not a scene")]);
+    assert!(batch(&dir).success());
+
+    for name in ["one.png", "two.png"] {
+        let img = image::open(Path::new(&dir).join(name)).unwrap();
+        assert_eq!((img.width(), img.height()), (12, 8), "{}", name);
+    }
+    assert!(!Path::new(&dir).join("notes.png").exists());
+}
+
+#[test]
+fn broken_scenes_are_skipped_and_reported() {
+    let dir = scene_dir("batch_broken", &[("good.toml", SPHERE), ("broken.toml", "[[sphere]]\nradius = \n")]);
+    assert!(!batch(&dir).success());
+    assert!(Path::new(&dir).join("good.png").exists());
+    assert!(!Path::new(&dir).join("broken.png").exists());
+}
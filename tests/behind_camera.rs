@@ -0,0 +1,82 @@
+// Culling test for primitives behind the camera. Camera rays never test
+// them, but a sphere only partly behind the camera still gets hit, and
+// reflections still see the ones behind it.
+
+use lux::*;
+
+const WIDTH: u32 = 16;
+const HEIGHT: u32 = 12;
+
+fn matte(color: Vector3D) -> Material {
+    Material {
+        color,
+        diffusive: 1.0,
+        ..Default::default()
+    }
+}
+
+// The default camera at z = -5, looking down +z
+fn scene() -> GlobalSettings {
+    let settings = RenderSettings {
+        stats: true,
+        ..Default::default()
+    };
+    GlobalSettings::new(WIDTH, HEIGHT, settings, Camera::default())
+}
+
+fn camera_rays(globals: &GlobalSettings) -> Vec<Ray> {
+    (0..WIDTH * HEIGHT)
+        .filter_map(|i| {
+            let (x, y) = ((i % WIDTH) as f64 + 0.5, (i / WIDTH) as f64 + 0.5);
+            globals.camera.pixel_ray(x, y, WIDTH, HEIGHT, 0.0)
+        })
+        .collect()
+}
+
+#[test]
+fn sphere_behind_the_camera_is_never_tested() {
+    let mut globals = scene();
+    add_sphere(&Vector3D::new(0.0, 0.0, 2.0), 1.0, matte(Vector3D::ONE), &mut globals).unwrap();
+    add_sphere(&Vector3D::new(0.0, 0.0, -8.0), 1.0, matte(Vector3D::ONE), &mut globals).unwrap();
+    render_scene(&mut globals).unwrap();
+
+    // Without the BVH every ray would test every primitive
+    globals.bvh = None;
+    let rays = camera_rays(&globals);
+    globals.counters.take();
+    for ray in &rays {
+        assert_ne!(closest_hit(ray, RayKind::Camera, &globals).map(|h| h.0), Some(1));
+    }
+    assert_eq!(globals.counters.take().tests, rays.len() as u64);
+}
+
+#[test]
+fn sphere_across_the_camera_plane_is_hit() {
+    let mut globals = scene();
+    // Sticks out half a unit in front of the camera
+    add_sphere(&Vector3D::new(0.0, 0.0, -5.5), 1.0, matte(Vector3D::ONE), &mut globals).unwrap();
+    render_scene(&mut globals).unwrap();
+    globals.bvh = None;
+
+    for ray in camera_rays(&globals) {
+        assert_eq!(closest_hit(&ray, RayKind::Camera, &globals).map(|h| h.0), Some(0));
+    }
+}
+
+#[test]
+fn reflections_see_behind_the_camera() {
+    let mut globals = scene();
+    let mirror = Material {
+        color: Vector3D::ONE,
+        diffusive: 0.0,
+        reflective: 1.0,
+        ..matte(Vector3D::ONE)
+    };
+    add_quad(Vector3D::new(-10.0, -10.0, 0.0), Vector3D::new(20.0, 0.0, 0.0), Vector3D::new(0.0, 20.0, 0.0), mirror, &mut globals).unwrap();
+    add_sphere(&Vector3D::new(0.0, 0.0, -8.0), 1.0, matte(Vector3D::new(1.0, 0.0, 0.0)), &mut globals).unwrap();
+    add_light(Vector3D::new(0.0, 3.0, -5.0), Vector3D::ONE, 1.0, &mut globals).unwrap();
+    render_scene(&mut globals).unwrap();
+
+    let middle = globals.framebuffer[(HEIGHT / 2 * WIDTH + WIDTH / 2) as usize];
+    assert!(middle.x > 0.1 && middle.x > 2.0 * middle.z, "{}", middle);
+}
@@ -0,0 +1,44 @@
+// 16-bit output test. A smooth ramp saved with 16 bits per channel comes
+// back with far more than the 256 levels 8 bits could hold, in order, both
+// from the plain writer and from the one adding metadata.
+
+use lux::*;
+
+// One pixel per step from black to white
+const STEPS: u32 = 2048;
+
+fn ramp() -> GlobalSettings {
+    let mut globals = GlobalSettings::new(STEPS, 1, RenderSettings::default(), Camera::default());
+    for x in 0..STEPS as usize {
+        let c = x as f64 / (STEPS - 1) as f64;
+        globals.framebuffer[x] = Vector3D::new(c, c, c);
+        globals.coverage[x] = 1.0;
+    }
+    globals
+}
+
+// Red channel of every pixel of the 16-bit PNG at `path`
+fn reds(path: &str) -> Vec<u16> {
+    let img = image::open(path).unwrap();
+    assert_eq!(img.color(), image::ColorType::Rgba16);
+    img.to_rgba16().pixels().map(|p| p[0]).collect()
+}
+
+#[test]
+fn a_ramp_keeps_more_than_256_levels() {
+    let globals = ramp();
+    let plain = format!("{}/ramp16.png", env!("CARGO_TARGET_TMPDIR"));
+    let tagged = format!("{}/ramp16_tagged.png", env!("CARGO_TARGET_TMPDIR"));
+    save_16bit(&globals, &plain).unwrap();
+    metadata::save_16bit(&globals, &tagged, &vec![("Software".to_string(), "lux".to_string())]).unwrap();
+
+    for path in [plain, tagged] {
+        let levels = reds(&path);
+        assert_eq!((levels[0], levels[levels.len() - 1]), (0, 65535));
+        assert!(levels.windows(2).all(|w| w[0] < w[1]), "{} isn't increasing", path);
+
+        let mut distinct = levels.clone();
+        distinct.dedup();
+        assert!(distinct.len() > 256, "{} levels in {}", distinct.len(), path);
+    }
+}
@@ -0,0 +1,140 @@
+// Render budgets: `max_time` and `target_noise` stop a render between
+// batches of samples, and a stopped render is just as deterministic as one
+// that took all its samples. Setting `cancel` stops it too, unfinished.
+
+use lux::*;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
+
+const WIDTH: u32 = 16;
+const HEIGHT: u32 = 12;
+const SAMPLES: u32 = 32;
+
+// Path traced matte sphere under a glowing one, noisy enough that the
+// pixels don't agree after a few samples
+fn render(max_time: Option<Duration>, target_noise: Option<f64>) -> GlobalSettings {
+    render_with_cancel(max_time, target_noise, None)
+}
+
+fn render_with_cancel(max_time: Option<Duration>, target_noise: Option<f64>, cancel: Option<bool>) -> GlobalSettings {
+    let settings = RenderSettings {
+        mode: RenderMode::PathTrace,
+        samples: SAMPLES,
+        max_time,
+        target_noise,
+        ..Default::default()
+    };
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, settings, Camera::default());
+    globals.cancel = cancel.map(|c| Arc::new(AtomicBool::new(c)));
+
+    let matte = Material {
+        color: Vector3D::new(0.8, 0.6, 0.4),
+        diffusive: 1.0,
+        ..Default::default()
+    };
+    let lamp = Material {
+        emission: Vector3D::new(4.0, 4.0, 4.0),
+        ..matte
+    };
+    add_sphere(&Vector3D::new(0.0, -0.5, 3.0), 1.0, matte, &mut globals).unwrap();
+    add_sphere(&Vector3D::new(0.0, 2.0, 3.0), 0.5, lamp, &mut globals).unwrap();
+
+    render_scene(&mut globals).unwrap();
+    globals
+}
+
+#[test]
+fn unused_budget_matches_a_plain_render() {
+    let plain = render(None, None);
+    let budgeted = render(Some(Duration::from_secs(3600)), Some(0.0));
+
+    assert_eq!(budgeted.budget_stop, None);
+    assert!(budgeted.sample_counts.iter().all(|&n| n == SAMPLES));
+    assert_eq!(budgeted.framebuffer, plain.framebuffer);
+}
+
+#[test]
+fn no_time_stops_after_one_batch() {
+    let globals = render(Some(Duration::ZERO), None);
+
+    assert_eq!(globals.budget_stop, Some(BudgetStop::Time));
+    let taken = globals.sample_counts[0];
+    assert!(taken > 0 && taken < SAMPLES);
+    assert!(globals.sample_counts.iter().all(|&n| n == taken));
+}
+
+#[test]
+fn stopped_render_is_deterministic() {
+    let a = render(Some(Duration::ZERO), None);
+    let b = render(Some(Duration::ZERO), None);
+
+    assert_eq!(a.framebuffer, b.framebuffer);
+    assert_eq!(a.img, b.img);
+}
+
+#[test]
+fn reaching_the_noise_target_stops_the_render() {
+    let loose = render(None, Some(1e9));
+    assert_eq!(loose.budget_stop, Some(BudgetStop::Noise));
+    assert!(loose.sample_counts.iter().all(|&n| n < SAMPLES));
+
+    // The whole image never gets that clean
+    let strict = render(None, Some(0.0));
+    assert_eq!(strict.budget_stop, None);
+    assert!(strict.sample_counts.iter().all(|&n| n == SAMPLES));
+}
+
+#[test]
+fn cancelled_render_stops_unfinished() {
+    let globals = render_with_cancel(None, None, Some(true));
+
+    assert_eq!(globals.budget_stop, Some(BudgetStop::Cancelled));
+    assert!(globals.sample_counts.iter().all(|&n| n < SAMPLES));
+}
+
+#[test]
+fn cancel_flag_left_alone_matches_a_plain_render() {
+    let plain = render(None, None);
+    let watched = render_with_cancel(None, None, Some(false));
+
+    assert_eq!(watched.budget_stop, None);
+    assert_eq!(watched.framebuffer, plain.framebuffer);
+    assert_eq!(watched.img, plain.img);
+}
+
+#[test]
+fn negative_noise_target_is_invalid() {
+    let settings = RenderSettings {
+        target_noise: Some(-1.0),
+        ..Default::default()
+    };
+    let mut globals = GlobalSettings::new(4, 4, settings, Camera::default());
+
+    assert!(matches!(render_scene(&mut globals), Err(LuxError::InvalidSettings(_))));
+}
+
+#[test]
+fn durations() {
+    assert_eq!(config::duration("60s"), Some(Duration::from_secs(60)));
+    assert_eq!(config::duration("1.5m"), Some(Duration::from_secs(90)));
+    assert_eq!(config::duration("250ms"), Some(Duration::from_millis(250)));
+    assert_eq!(config::duration("2"), Some(Duration::from_secs(2)));
+    assert_eq!(config::duration("1h"), Some(Duration::from_secs(3600)));
+
+    assert_eq!(config::duration("soon"), None);
+    assert_eq!(config::duration("5 days"), None);
+    assert_eq!(config::duration("-1s"), None);
+}
+
+#[test]
+fn budgets_from_the_config_file() {
+    let config = RenderConfig::from_toml("[render]\nmax_time = \"2m\"\ntarget_noise = 0.02\n").unwrap();
+    assert_eq!(config.render.max_time, Some(Duration::from_secs(120)));
+    assert_eq!(config.render.target_noise, Some(0.02));
+
+    let config = RenderConfig::from_toml("[render]\nmax_time = 30\n").unwrap();
+    assert_eq!(config.render.max_time, Some(Duration::from_secs(30)));
+
+    assert!(RenderConfig::from_toml("[render]\nmax_time = \"later\"\n").is_err());
+}
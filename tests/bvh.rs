@@ -0,0 +1,106 @@
+// BVH test. Both ways of building the hierarchy have to find exactly the
+// hits a plain loop over all primitives finds. The node visits each of them
+// took are printed (`cargo test --test bvh -- --nocapture`).
+
+use lux::*;
+
+const RAYS: u32 = 4000;
+
+// Two tight clusters of small spheres far apart, a big floor and one big
+// sphere: the kind of scene a median split handles badly
+fn clustered_scene(build: BvhBuild) -> GlobalSettings {
+    let settings = RenderSettings {
+        bvh: build,
+        stats: true,
+        ..Default::default()
+    };
+    let mut globals = GlobalSettings::new(4, 4, settings, Camera::default());
+    let matte = Material {
+        color: Vector3D::new(0.8, 0.8, 0.8),
+        diffusive: 1.0,
+        ..Default::default()
+    };
+    globals.add_material("matte", matte);
+
+    let mut rng = Rng::new(7);
+    for cluster in [Vector3D::new(-3.0, 0.0, 4.0), Vector3D::new(3.0, 1.0, 9.0)] {
+        for _ in 0..30 {
+            let mut pos = Vector3D::new(rng.next_f64(), rng.next_f64(), rng.next_f64());
+            pos.v3d_sub(Vector3D::new(0.5, 0.5, 0.5));
+            pos.v3d_add(cluster);
+            add_sphere(&pos, 0.05 + 0.1 * rng.next_f64(), "matte", &mut globals).unwrap();
+        }
+    }
+
+    let floor = (
+        Vector3D::new(-20.0, -2.0, -5.0),
+        Vector3D::new(0.0, 0.0, 30.0),
+        Vector3D::new(40.0, 0.0, 0.0),
+    );
+    add_quad(floor.0, floor.1, floor.2, "matte", &mut globals).unwrap();
+    add_sphere(&Vector3D::new(0.0, 2.0, 15.0), 3.0, "matte", &mut globals).unwrap();
+
+    globals
+}
+
+// Rays from around the camera aimed at random points of the scene
+fn rays() -> Vec<Ray> {
+    let mut rng = Rng::new(11);
+    let mut point = |scale: f64, offset: (f64, f64, f64)| {
+        Vector3D::new(
+            offset.0 + scale * (rng.next_f64() - 0.5),
+            offset.1 + scale * (rng.next_f64() - 0.5),
+            offset.2 + scale * (rng.next_f64() - 0.5),
+        )
+    };
+
+    (0..RAYS)
+        .map(|_| {
+            let origin = point(2.0, (0.0, 0.0, -5.0));
+            let mut direction = point(14.0, (0.0, 0.0, 8.0));
+            direction.v3d_sub(origin);
+            direction.v3d_norm();
+            Ray {
+                origin,
+                direction,
+                time: 0.0,
+            }
+        })
+        .collect()
+}
+
+// Closest hits of all the rays, and how many BVH nodes and primitive tests
+// they took
+fn hits(globals: &GlobalSettings) -> (Vec<Option<(usize, f64)>>, stats::RayCounts) {
+    globals.counters.set_enabled(true);
+    globals.counters.take();
+
+    let hits = rays().iter().map(|ray| closest_hit(ray, RayKind::Camera, globals)).collect();
+    (hits, globals.counters.take())
+}
+
+#[test]
+fn bvh_builds_find_the_same_hits() {
+    // Without a BVH every primitive is tested
+    let (expected, plain) = hits(&clustered_scene(BvhBuild::Median));
+    assert!(expected.iter().filter(|h| h.is_some()).count() > RAYS as usize / 2);
+
+    for build in [BvhBuild::Median, BvhBuild::Sah] {
+        let mut globals = clustered_scene(build);
+        globals.build_bvh();
+
+        let (found, counts) = hits(&globals);
+        assert_eq!(found, expected, "{:?} BVH", build);
+        assert!(counts.tests < plain.tests / 4, "{:?} BVH: {} tests", build, counts.tests);
+
+        println!(
+            "{:?}: {} nodes, {} node visits and {} primitive tests for {} rays (without a BVH: {} tests)",
+            build,
+            globals.bvh.as_ref().unwrap().node_count(),
+            counts.nodes,
+            counts.tests,
+            RAYS,
+            plain.tests
+        );
+    }
+}
@@ -0,0 +1,95 @@
+// Checkpoint tests. A render resumed from a checkpoint comes out the same as
+// one that took all its samples in one go, and checkpoints of some other
+// render or garbled ones are refused.
+
+use lux::*;
+
+const WIDTH: u32 = 32;
+const HEIGHT: u32 = 24;
+
+fn path(name: &str) -> String {
+    format!("{}/{}", env!("CARGO_TARGET_TMPDIR"), name)
+}
+
+// The demo scene path traced with `samples` samples per pixel, saving to
+// or resuming from `checkpoint`
+fn demo(samples: u32, checkpoint: Option<&str>, resume: bool) -> GlobalSettings {
+    let settings = RenderSettings {
+        mode: RenderMode::PathTrace,
+        samples,
+        seed: 7,
+        ..Default::default()
+    };
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, settings, Camera::default());
+    demo::build_scene(&mut globals);
+    globals.checkpoint = checkpoint.map(str::to_string);
+    globals.resume = resume;
+    globals
+}
+
+#[test]
+fn four_samples_resumed_with_four_more_match_eight_in_one_go() {
+    let checkpoint = path("four_plus_four.ckpt");
+    let _ = std::fs::remove_file(&checkpoint);
+
+    let mut first = demo(4, Some(&checkpoint), false);
+    render_scene(&mut first).unwrap();
+    assert!(first.sample_counts.iter().all(|&n| n == 4));
+
+    // Only the four new samples are taken
+    let mut resumed = demo(8, Some(&checkpoint), true);
+    resumed.settings.stats = true;
+    render_scene(&mut resumed).unwrap();
+    assert_eq!(stats::total(&resumed.pass_stats).counts.primary, (WIDTH * HEIGHT * 4) as u64);
+    let mut whole = demo(8, None, false);
+    render_scene(&mut whole).unwrap();
+
+    assert_eq!(resumed.sample_counts, whole.sample_counts);
+    assert_eq!(resumed.framebuffer, whole.framebuffer);
+    assert_eq!(resumed.coverage, whole.coverage);
+    assert_eq!(resumed.depth_buffer, whole.depth_buffer);
+    assert_ne!(first.framebuffer, whole.framebuffer);
+}
+
+#[test]
+fn checkpoints_are_saved_along_the_way() {
+    // Every batch of samples, with the last one holding them all
+    let checkpoint = path("along_the_way.ckpt");
+    let _ = std::fs::remove_file(&checkpoint);
+
+    let mut globals = demo(12, Some(&checkpoint), false);
+    globals.settings.checkpoint_every = CheckpointInterval::Samples(4);
+    render_scene(&mut globals).unwrap();
+
+    // Resuming a finished render takes no more samples
+    let mut again = demo(12, Some(&checkpoint), true);
+    again.settings.stats = true;
+    render_scene(&mut again).unwrap();
+    assert_eq!(again.framebuffer, globals.framebuffer);
+    assert_eq!(stats::total(&again.pass_stats).counts.primary, 0);
+
+    globals.settings.checkpoint_every = CheckpointInterval::Samples(0);
+    assert!(matches!(render_scene(&mut globals), Err(LuxError::InvalidSettings(_))));
+}
+
+#[test]
+fn missing_checkpoints_start_over_and_others_are_refused() {
+    let checkpoint = path("refused.ckpt");
+    let _ = std::fs::remove_file(&checkpoint);
+
+    let mut fresh = demo(4, Some(&checkpoint), true);
+    render_scene(&mut fresh).unwrap();
+    assert!(fresh.sample_counts.iter().all(|&n| n == 4));
+
+    // Another seed makes other samples that don't belong with these
+    let mut other = demo(8, Some(&checkpoint), true);
+    other.settings.seed += 1;
+    assert!(matches!(render_scene(&mut other), Err(LuxError::InvalidSettings(_))));
+
+    // And a file that's cut short isn't a checkpoint
+    let bytes = std::fs::read(&checkpoint).unwrap();
+    std::fs::write(&checkpoint, &bytes[..bytes.len() - 5]).unwrap();
+    assert!(matches!(render_scene(&mut demo(8, Some(&checkpoint), true)), Err(LuxError::Decode(_))));
+    std::fs::write(&checkpoint, "not a checkpoint at all").unwrap();
+    assert!(matches!(render_scene(&mut demo(8, Some(&checkpoint), true)), Err(LuxError::Decode(_))));
+}
@@ -0,0 +1,56 @@
+// Config file tests. A config file sets the image, render and camera
+// settings and leaves the rest at their defaults, and flags on the command
+// line win over it.
+
+use std::process::Command;
+
+use lux::config::RenderConfig;
+use lux::*;
+
+fn write(name: &str, text: &str) -> String {
+    let path = format!("{}/{}", env!("CARGO_TARGET_TMPDIR"), name);
+    std::fs::write(&path, text).unwrap();
+    path
+}
+
+#[test]
+fn config_files_set_what_they_mention() {
+    let path = write(
+        "settings.toml",
+        "[image]\nwidth = 320\nheight = 200\nbit_depth = 16\n\n[render]\nmode = \"pathtrace\"\nsamples = 32\nseed = 9\nmax_time = \"2m\"\n\n[camera]\nposition = [1.0, 2.0, -3.0]\nfov = 60.0\n",
+    );
+    let config = RenderConfig::load(&path).unwrap();
+
+    assert_eq!((config.width, config.height, config.bit_depth), (320, 200, 16));
+    assert_eq!(config.render.mode, RenderMode::PathTrace);
+    assert_eq!(config.render.samples, 32);
+    assert_eq!(config.render.seed, 9);
+    assert_eq!(config.render.max_time, Some(std::time::Duration::from_secs(120)));
+    assert_eq!(config.camera.position, Vector3D::new(1.0, 2.0, -3.0));
+    assert_eq!(config.camera.fov, 60.0);
+
+    // The rest keeps its defaults
+    let defaults = RenderConfig::default();
+    assert_eq!(config.render.max_depth, defaults.render.max_depth);
+    assert_eq!(config.camera.shutter_time, defaults.camera.shutter_time);
+
+    let broken = write("broken.toml", "[render]\nsamples = \"many\"\n");
+    assert!(matches!(RenderConfig::load(&broken), Err(LuxError::Parse(_))));
+}
+
+#[test]
+fn flags_override_the_config_file() {
+    let config = write("small.toml", "[image]\nwidth = 20\nheight = 10\n");
+    let out = format!("{}/overridden.ppm", env!("CARGO_TARGET_TMPDIR"));
+    let status = Command::new(env!("CARGO_BIN_EXE_lux"))
+        .args(["--config", &config, "--width", "30", "--dump", &out])
+        .current_dir(env!("CARGO_TARGET_TMPDIR"))
+        .env("RUST_LOG", "error")
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    // The width from the command line, the height from the file
+    let ppm = std::fs::read(&out).unwrap();
+    assert!(ppm.starts_with(b"P6\n30 10 255\n"), "{:?}", String::from_utf8_lossy(&ppm[..12]));
+}
@@ -0,0 +1,62 @@
+// Random cover scene tests. The scene is generated from a seed, so the same
+// seed has to give exactly the same scene every time.
+
+use lux::*;
+
+fn cover(seed: u64) -> GlobalSettings {
+    let mut globals = GlobalSettings::new(16, 12, RenderSettings::default(), Camera::default());
+    demo::random_cover(seed, &mut globals);
+    globals
+}
+
+// Center of primitive `i`'s bounding box
+fn center(globals: &GlobalSettings, i: usize) -> Vector3D {
+    let (mut min, max) = globals.primitives()[i].bounds();
+    min.v3d_add(max);
+    min.v3d_mul_scalar(0.5);
+    min
+}
+
+// The ground and the three big balls come first, then the small ones
+const FIRST_SMALL: usize = 1 + demo::COVER_BIG_BALLS.len();
+
+#[test]
+fn same_seed_gives_the_same_scene() {
+    let a = cover(42);
+    let b = cover(42);
+
+    assert_eq!(a.primitives().len(), b.primitives().len());
+    assert_eq!(center(&a, FIRST_SMALL), center(&b, FIRST_SMALL));
+    for i in 0..a.primitives().len() {
+        assert_eq!(a.primitives()[i].bounds(), b.primitives()[i].bounds(), "primitive {}", i);
+    }
+}
+
+#[test]
+fn other_seeds_give_other_scenes() {
+    let a = cover(1);
+    let b = cover(2);
+
+    assert_ne!(center(&a, FIRST_SMALL), center(&b, FIRST_SMALL));
+}
+
+#[test]
+fn hundreds_of_small_balls_stay_clear_of_the_big_ones() {
+    let globals = cover(7);
+    let count = globals.primitives().len();
+    assert!(count > 200 && count <= MAXPRIMCOUNT as usize, "{} primitives", count);
+
+    for i in FIRST_SMALL..count {
+        let p = center(&globals, i);
+        assert_eq!(globals.primitives()[i].kind(), "sphere");
+        // Resting on the ground
+        assert!((p.y + 0.8).abs() < 1e-9, "ball {} at {}", i, p);
+
+        for &(x, _, z) in &demo::COVER_BIG_BALLS {
+            assert!((p.x - x).hypot(p.z - z) >= 1.2, "ball {} at {}", i, p);
+        }
+    }
+
+    let warnings: Vec<String> = globals.validate().iter().map(|w| w.to_string()).collect();
+    assert!(warnings.is_empty(), "{:?}", warnings);
+}
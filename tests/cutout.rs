@@ -0,0 +1,128 @@
+// Cutout test. A quad with a checkerboard cutout in front of a sphere: rays
+// through its holes hit the sphere behind, and light shining through it
+// makes a checkered shadow.
+
+use lux::*;
+
+// Checkerboard of 1x1 squares on a 2x2 quad
+fn fence() -> Material {
+    Material {
+        color: Vector3D::new(0.2, 0.8, 0.2),
+        specular: 0.0,
+        diffusive: 1.0,
+        reflective: 0.0,
+        roughness: 0.0,
+        refractive: 0.0,
+        ior: 1.5,
+        emission: Vector3D::ZERO,
+        bump: None,
+        cutout: Some(Cutout::Checker {
+            scale: 2.0,
+            threshold: 0.5,
+        }),
+        sidedness: Sidedness::TwoSided,
+        wrap: 0.0,
+        dispersion: 0.0,
+        translucency: 0.0,
+        max_reflection_depth: None,
+        max_refraction_depth: None,
+    }
+}
+
+fn matte() -> Material {
+    Material {
+        cutout: None,
+        color: Vector3D::ONE,
+        ..fence()
+    }
+}
+
+fn scene() -> GlobalSettings {
+    GlobalSettings::new(16, 12, RenderSettings::default(), Camera::default())
+}
+
+// Closest primitive a ray along +z from (x, y, 0) hits
+fn hit_along_z(x: f64, y: f64, globals: &GlobalSettings) -> Option<usize> {
+    let ray = Ray {
+        origin: Vector3D::new(x, y, 0.0),
+        direction: Vector3D::UNIT_Z,
+        time: 0.0,
+    };
+    closest_hit(&ray, RayKind::Camera, globals).map(|(i, _)| i)
+}
+
+#[test]
+fn rays_go_through_the_holes() {
+    let mut globals = scene();
+    add_quad(Vector3D::new(-1.0, -1.0, 2.0), Vector3D::new(2.0, 0.0, 0.0), Vector3D::new(0.0, 2.0, 0.0), fence(), &mut globals).unwrap();
+    add_sphere(&Vector3D::new(0.0, 0.0, 5.0), 2.0, matte(), &mut globals).unwrap();
+
+    // Texture coordinates (0.25, 0.25) and (0.75, 0.75) are solid squares,
+    // the other two are holes
+    assert_eq!(hit_along_z(-0.5, -0.5, &globals), Some(0));
+    assert_eq!(hit_along_z(0.5, 0.5, &globals), Some(0));
+    assert_eq!(hit_along_z(0.5, -0.5, &globals), Some(1));
+    assert_eq!(hit_along_z(-0.5, 0.5, &globals), Some(1));
+}
+
+#[test]
+fn all_holes_is_invisible() {
+    let mut globals = scene();
+    let ghost = Material {
+        cutout: Some(Cutout::Dots {
+            scale: 3.0,
+            threshold: 2.0,
+        }),
+        ..matte()
+    };
+    add_sphere(&Vector3D::new(0.0, 0.0, 3.0), 1.0, ghost, &mut globals).unwrap();
+
+    assert_eq!(hit_along_z(0.0, 0.0, &globals), None);
+    assert_eq!(hit_along_z(0.3, -0.2, &globals), None);
+}
+
+#[test]
+fn shadow_is_checkered() {
+    let settings = RenderSettings {
+        shadows: true,
+        ..Default::default()
+    };
+    let mut globals = GlobalSettings::new(16, 12, settings, Camera::default());
+
+    // Floor at y = -1, the fence above it and a light straight above both
+    add_quad(Vector3D::new(-4.0, -1.0, -4.0), Vector3D::new(0.0, 0.0, 8.0), Vector3D::new(8.0, 0.0, 0.0), matte(), &mut globals).unwrap();
+    add_quad(Vector3D::new(-1.0, 0.0, -1.0), Vector3D::new(2.0, 0.0, 0.0), Vector3D::new(0.0, 0.0, 2.0), fence(), &mut globals).unwrap();
+    add_light(Vector3D::new(0.0, 100.0, 0.0), Vector3D::ONE, 1.0, &mut globals).unwrap();
+
+    // Look down at the floor from just above it, below the fence
+    let floor = |x: f64, z: f64| {
+        let ray = Ray {
+            origin: Vector3D::new(x, -0.5, z),
+            direction: Vector3D::new(0.0, -1.0, 0.0),
+            time: 0.0,
+        };
+        trace(&ray, 0, &globals).luminance()
+    };
+
+    let (solid, hole) = (floor(-0.5, -0.5), floor(0.5, -0.5));
+    assert!(hole > solid + 0.5, "under a hole {}, under a square {}", hole, solid);
+    assert_eq!(floor(0.5, 0.5), solid);
+    assert_eq!(floor(-0.5, 0.5), hole);
+}
+
+#[test]
+fn cutout_from_the_scene_file() {
+    let mut globals = scene();
+    let text = "[material.fence]\ncutout = \"dots\"\ncutout_scale = 4.0\ncutout_threshold = 0.3\n";
+    scene::from_toml(text, &mut globals).unwrap();
+    assert_eq!(
+        globals.materials["fence"].cutout,
+        Some(Cutout::Dots {
+            scale: 4.0,
+            threshold: 0.3
+        })
+    );
+
+    let result = scene::from_toml("[material.fence]\ncutout = \"lace\"\n", &mut scene());
+    assert!(matches!(result, Err(LuxError::Parse(_))), "{:?}", result.err());
+}
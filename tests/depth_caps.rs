@@ -0,0 +1,175 @@
+// Per-material depth caps. Glass followed deep among mirrors that stop
+// early renders with far fewer rays and looks the same on the mirrors, glass
+// can go deeper than `max_depth`, and a capped ray sees the environment.
+
+use lux::*;
+
+const WIDTH: u32 = 64;
+const HEIGHT: u32 = 48;
+const ENVIRONMENT: Vector3D = Vector3D::new(0.2, 0.3, 0.5);
+
+fn mirror() -> Material {
+    Material {
+        color: Vector3D::new(0.9, 0.8, 0.6),
+        specular: 0.2,
+        diffusive: 0.6,
+        reflective: 0.3,
+        ..Default::default()
+    }
+}
+
+fn glass() -> Material {
+    Material {
+        color: Vector3D::ONE,
+        specular: 0.5,
+        diffusive: 0.0,
+        reflective: 0.1,
+        refractive: 0.9,
+        ..mirror()
+    }
+}
+
+fn scene(max_depth: u32, mirror: Material, glass: Material) -> GlobalSettings {
+    let settings = RenderSettings {
+        max_depth,
+        stats: true,
+        ..Default::default()
+    };
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, settings, Camera::default());
+    globals.environment = ENVIRONMENT;
+    globals.add_material("mirror", mirror);
+
+    // Fifty mirror balls spread all around, reflecting each other, and a
+    // glass one in front of the camera
+    let golden_angle = std::f64::consts::PI * (3.0 - 5.0f64.sqrt());
+    for i in 0..50 {
+        let y = 1.0 - 2.0 * (i as f64 + 0.5) / 50.0;
+        let (r, phi) = ((1.0 - y * y).sqrt(), i as f64 * golden_angle);
+        let center = Vector3D::new(12.0 * r * phi.cos(), 12.0 * y, 12.0 * r * phi.sin());
+        add_sphere(&center, 3.0, "mirror", &mut globals).unwrap();
+    }
+    add_sphere(&Vector3D::new(0.0, 0.0, 2.0), 0.8, glass, &mut globals).unwrap();
+    add_light(Vector3D::new(-3.0, 4.0, -2.0), Vector3D::ONE, 1.0, &mut globals).unwrap();
+
+    render_scene(&mut globals).unwrap();
+    globals
+}
+
+fn secondary_rays(globals: &GlobalSettings) -> u64 {
+    stats::total(&globals.pass_stats).counts.secondary
+}
+
+#[test]
+fn shallow_mirrors_around_deep_glass_look_the_same_for_less() {
+    let uniform = scene(10, mirror(), glass());
+    let capped = scene(
+        10,
+        Material {
+            max_reflection_depth: Some(2),
+            ..mirror()
+        },
+        Material {
+            max_refraction_depth: Some(10),
+            ..glass()
+        },
+    );
+
+    let (before, after) = (secondary_rays(&uniform), secondary_rays(&capped));
+    assert!(after * 2 < before, "{} rays with the caps, {} without", after, before);
+
+    // The mirrors look the same: what they'd show past the second
+    // reflection is dim, and the environment stands in for it
+    let camera = uniform.camera;
+    let (mut mirrors, mut total) = (0, 0);
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            let Some(hit) = uniform.pick(&camera, x, y, WIDTH, HEIGHT) else {
+                continue;
+            };
+            if hit.primitive_index >= 50 {
+                continue;
+            }
+            mirrors += 1;
+            let (a, b) = (uniform.img.get_pixel(x, y), capped.img.get_pixel(x, y));
+            for c in 0..3 {
+                let diff = a[c].abs_diff(b[c]);
+                assert!(diff <= 4, "({}, {}): {:?} vs {:?}", x, y, a, b);
+                total += diff as u32;
+            }
+        }
+    }
+    assert!(mirrors > 100, "{}", mirrors);
+    assert!(total < mirrors * 3, "off by {} on average", total as f64 / (mirrors * 3) as f64);
+}
+
+#[test]
+fn glass_goes_deeper_than_max_depth() {
+    // Only refracting, in front of mirrors that don't reflect anything
+    // past the environment, so `max_depth` only matters for the glass
+    let glass = Material {
+        reflective: 0.0,
+        ..glass()
+    };
+    let deep_glass = Material {
+        max_refraction_depth: Some(10),
+        ..glass
+    };
+    let mirror = Material {
+        max_reflection_depth: Some(0),
+        ..mirror()
+    };
+    let shallow = scene(1, mirror, glass);
+    let capped = scene(1, mirror, deep_glass);
+    let deep = scene(10, mirror, glass);
+
+    // Through the middle of the glass ball a ray needs two refractions to
+    // get out again
+    let middle = (HEIGHT / 2 * WIDTH + WIDTH / 2) as usize;
+    assert_eq!(capped.framebuffer[middle], deep.framebuffer[middle]);
+    assert!(!shallow.framebuffer[middle].approx_eq(deep.framebuffer[middle], 1e-2));
+}
+
+#[test]
+fn capped_rays_see_the_environment() {
+    // A mirror filling the view, reflecting nothing but the environment
+    let render = |max_depth, max_reflection_depth| {
+        let mut globals = GlobalSettings::new(8, 8, RenderSettings { max_depth, ..Default::default() }, Camera::default());
+        globals.environment = ENVIRONMENT;
+        globals.ambient = Vector3D::ZERO;
+        let m = Material {
+            specular: 0.0,
+            diffusive: 0.0,
+            max_reflection_depth,
+            ..mirror()
+        };
+        add_quad(Vector3D::new(-5.0, -5.0, 0.0), Vector3D::new(0.0, 10.0, 0.0), Vector3D::new(10.0, 0.0, 0.0), m, &mut globals).unwrap();
+        render_scene(&mut globals).unwrap();
+        globals.framebuffer[0]
+    };
+
+    // Stopped by `max_depth` it's black, stopped by its own cap the
+    // reflection shows the environment
+    let mut reflected = ENVIRONMENT;
+    reflected.v3d_mul_v3d(mirror().color);
+    reflected.v3d_mul_scalar(mirror().reflective);
+    assert_eq!(render(0, None), Vector3D::ZERO);
+    assert!(render(0, Some(0)).approx_eq(reflected, 1e-12));
+    assert!(render(4, Some(0)).approx_eq(reflected, 1e-12));
+    assert!(render(4, None).approx_eq(reflected, 1e-12));
+}
+
+#[test]
+fn caps_from_scene_files() {
+    let path = format!("{}/depth_caps.toml", env!("CARGO_TARGET_TMPDIR"));
+    let mut globals = GlobalSettings::new(4, 4, RenderSettings::default(), Camera::default());
+
+    std::fs::write(&path, "[material.glass]\nrefractive = 0.9\nmax_refraction_depth = 10\nmax_reflection_depth = 0\n").unwrap();
+    scene::load(&path, &mut globals).unwrap();
+    assert_eq!(globals.materials["glass"].max_refraction_depth, Some(10));
+    assert_eq!(globals.materials["glass"].max_reflection_depth, Some(0));
+
+    for bad in ["-1", "2.5", "\"deep\""] {
+        std::fs::write(&path, format!("[material.glass]\nmax_refraction_depth = {}\n", bad)).unwrap();
+        assert!(matches!(scene::load(&path, &mut globals), Err(LuxError::Parse(_))), "{}", bad);
+    }
+}
@@ -0,0 +1,71 @@
+// Dispersion test. A white card behind a glass ball over a black
+// background: plain glass shows it in shades of gray, dispersive glass
+// fringes its edge with color, whether every channel gets its own ray or
+// every sample picks one.
+
+use lux::*;
+
+const WIDTH: u32 = 32;
+const HEIGHT: u32 = 24;
+
+fn render(dispersion: f64, mode: Dispersion, samples: u32) -> GlobalSettings {
+    let settings = RenderSettings {
+        samples,
+        dispersion: mode,
+        stats: true,
+        ..Default::default()
+    };
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, settings, Camera::default());
+    globals.ambient = Vector3D::ZERO;
+    globals.environment = Vector3D::ZERO;
+
+    let card = Material {
+        diffusive: 1.0,
+        ..Default::default()
+    };
+    let glass = Material {
+        diffusive: 0.0,
+        refractive: 1.0,
+        dispersion,
+        ..card
+    };
+    // The card's edge runs down the middle of the ball
+    add_quad(Vector3D::new(0.0, -10.0, 6.0), Vector3D::new(0.0, 20.0, 0.0), Vector3D::new(10.0, 0.0, 0.0), card, &mut globals).unwrap();
+    add_sphere(&Vector3D::ZERO, 1.5, glass, &mut globals).unwrap();
+    add_light(Vector3D::new(0.0, 0.0, -2.0), Vector3D::ONE, 1.0, &mut globals).unwrap();
+    render_scene(&mut globals).unwrap();
+    globals
+}
+
+// Biggest difference between two channels of any pixel
+fn fringe(globals: &GlobalSettings) -> f64 {
+    globals
+        .framebuffer
+        .iter()
+        .map(|c| c.max_component() - c.min_component())
+        .fold(0.0, f64::max)
+}
+
+#[test]
+fn plain_glass_keeps_white_white() {
+    assert!(fringe(&render(0.0, Dispersion::Split, 1)) < 1e-12);
+}
+
+#[test]
+fn dispersive_glass_fringes_edges_with_color() {
+    let split = fringe(&render(0.2, Dispersion::Split, 1));
+    let spectral = fringe(&render(0.2, Dispersion::Spectral, 16));
+
+    assert!(split > 0.1, "{}", split);
+    assert!(spectral > 0.1, "{}", spectral);
+}
+
+#[test]
+fn plain_glass_costs_nothing_extra() {
+    let rays = |dispersion, mode| stats::total(&render(dispersion, mode, 1).pass_stats).counts.secondary;
+
+    let plain = rays(0.0, Dispersion::Split);
+    assert_eq!(rays(0.0, Dispersion::Spectral), plain);
+    assert_eq!(rays(0.2, Dispersion::Spectral), plain);
+    assert!(rays(0.2, Dispersion::Split) > plain);
+}
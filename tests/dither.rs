@@ -0,0 +1,39 @@
+// Dithering test. A gradient too shallow for 8 bits comes out as a few wide
+// bands; dithered, neighbouring pixels keep flipping between the two steps
+// around the true color.
+
+use lux::*;
+
+const WIDTH: u32 = 64;
+const HEIGHT: u32 = 8;
+
+// Adjacent pixel pairs in a row that got different red values
+fn steps_in_gradient(dither: bool) -> usize {
+    let settings = RenderSettings {
+        dither,
+        ..Default::default()
+    };
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, settings, Camera::default());
+
+    // Goes up by 4 steps over the whole width
+    for (pixel, color) in globals.framebuffer.iter_mut().enumerate() {
+        let t = (pixel as u32 % WIDTH) as f64 / WIDTH as f64;
+        *color = Vector3D::new(0.5 + t * 4.0 / 255.0, 0.5, 0.5);
+    }
+    resolve(&mut globals);
+
+    let img = &globals.img;
+    (0..HEIGHT)
+        .flat_map(|y| (1..WIDTH).map(move |x| (x, y)))
+        .filter(|&(x, y)| img.get_pixel(x - 1, y)[0] != img.get_pixel(x, y)[0])
+        .count()
+}
+
+#[test]
+fn dithering_breaks_up_bands() {
+    let plain = steps_in_gradient(false);
+    let dithered = steps_in_gradient(true);
+
+    assert!(plain <= 4 * HEIGHT as usize, "{} steps without dithering", plain);
+    assert!(dithered > 4 * plain, "{} steps dithered, {} plain", dithered, plain);
+}
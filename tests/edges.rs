@@ -0,0 +1,52 @@
+// Edge overlay tests. A flat card in front of the sky gets a dark line all
+// around its silhouette, while the pixels inside it and the sky around it
+// stay as they were.
+
+use lux::*;
+
+const WIDTH: u32 = 32;
+const HEIGHT: u32 = 24;
+
+fn render(edges: bool) -> GlobalSettings {
+    let settings = RenderSettings {
+        edges,
+        ..Default::default()
+    };
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, settings, Camera::default());
+    let card = Material {
+        color: Vector3D::new(0.8, 0.7, 0.6),
+        diffusive: 1.0,
+        ..Default::default()
+    };
+    add_quad(Vector3D::new(-1.0, -1.0, 0.0), Vector3D::new(0.0, 2.0, 0.0), Vector3D::new(2.0, 0.0, 0.0), card, &mut globals).unwrap();
+    add_light(Vector3D::new(0.0, 0.0, -5.0), Vector3D::ONE, 1.0, &mut globals).unwrap();
+    render_scene(&mut globals).unwrap();
+    globals
+}
+
+#[test]
+fn silhouettes_are_marked_and_flat_insides_are_not() {
+    let (plain, outlined) = (render(false), render(true));
+    let hit = |x: i64, y: i64| {
+        x >= 0 && y >= 0 && x < WIDTH as i64 && y < HEIGHT as i64 && plain.depth_buffer[(y * WIDTH as i64 + x) as usize].is_finite()
+    };
+
+    let (mut silhouette, mut inside) = (0, 0);
+    for y in 0..HEIGHT as i64 {
+        for x in 0..WIDTH as i64 {
+            let i = (y * WIDTH as i64 + x) as usize;
+            let (before, after) = (plain.framebuffer[i], outlined.framebuffer[i]);
+            if !hit(x, y) {
+                assert_eq!(after, before, "sky at ({}, {})", x, y);
+            } else if [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)].iter().all(|&(nx, ny)| hit(nx, ny)) {
+                assert_eq!(after, before, "inside at ({}, {})", x, y);
+                inside += 1;
+            } else {
+                assert!(before.luminance() > 0.0);
+                assert_eq!(after, Vector3D::ZERO, "silhouette at ({}, {})", x, y);
+                silhouette += 1;
+            }
+        }
+    }
+    assert!(silhouette > 20 && inside > 20, "{} silhouette and {} inside pixels", silhouette, inside);
+}
@@ -0,0 +1,87 @@
+// Energy conservation tests. A material whose parts add up to more than 1
+// makes a surface brighter than the light falling on it; conserving energy
+// scales it down so it can't. Scene files with such materials get a
+// warning, the built-in scenes don't.
+
+use std::process::Command;
+
+use lux::*;
+
+const SIZE: u32 = 16;
+
+// Everything turned up, adding up to 3.5, and too bright a color
+fn greedy() -> Material {
+    Material {
+        diffusive: 1.0,
+        specular: 1.0,
+        reflective: 1.0,
+        refractive: 0.5,
+        color: Vector3D::new(2.0, 1.0, 0.5),
+        ..Default::default()
+    }
+}
+
+// Brightest pixel of a wall facing the camera, lit head on by a white light
+fn brightest(conserve_energy: bool) -> f64 {
+    let settings = RenderSettings {
+        conserve_energy,
+        ..Default::default()
+    };
+    let mut globals = GlobalSettings::new(SIZE, SIZE, settings, Camera::default());
+    globals.ambient = Vector3D::ZERO;
+    globals.environment = Vector3D::ZERO;
+    add_quad(Vector3D::new(-5.0, -5.0, 0.0), Vector3D::new(0.0, 10.0, 0.0), Vector3D::new(10.0, 0.0, 0.0), greedy(), &mut globals).unwrap();
+    add_light(Vector3D::new(0.0, 0.0, -5.0), Vector3D::ONE, 1.0, &mut globals).unwrap();
+    render_scene(&mut globals).unwrap();
+
+    globals.framebuffer.iter().flat_map(|c| [c.x, c.y, c.z]).fold(0.0, f64::max)
+}
+
+#[test]
+fn conserved_materials_add_up_to_1_at_most() {
+    let m = greedy().conserved();
+    assert!((m.reflectance() - 1.0).abs() < 1e-12, "{}", m.reflectance());
+    // In the same proportions as before
+    assert!((m.diffusive - 1.0 / 3.5).abs() < 1e-12 && (m.refractive - 0.5 / 3.5).abs() < 1e-12);
+    assert_eq!(m.color, Vector3D::new(1.0, 1.0, 0.5));
+
+    // Materials that already conserve energy are left alone
+    let matte = Material {
+        diffusive: 0.7,
+        color: Vector3D::new(0.2, 0.4, 0.6),
+        ..Default::default()
+    };
+    let kept = matte.conserved();
+    assert_eq!((kept.diffusive, kept.color), (matte.diffusive, matte.color));
+}
+
+#[test]
+fn conserved_surfaces_are_no_brighter_than_their_light() {
+    assert!(brightest(false) > 1.0, "{}", brightest(false));
+    assert!(brightest(true) <= 1.0 + 1e-9, "{}", brightest(true));
+}
+
+// Whether rendering `scene` (the demo without one) warns about a material
+// gaining energy
+fn warns_about_energy(scene: Option<&str>) -> bool {
+    let out = format!("{}/energy.ppm", env!("CARGO_TARGET_TMPDIR"));
+    let mut command = Command::new(env!("CARGO_BIN_EXE_lux"));
+    command.args(["--width", "8", "--height", "6", "--dump", &out]).env("RUST_LOG", "warn");
+    if let Some(scene) = scene {
+        command.args(["--scene", scene]);
+    }
+    let output = command.current_dir(env!("CARGO_TARGET_TMPDIR")).output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8_lossy(&output.stderr).contains("gains energy")
+}
+
+#[test]
+fn only_scene_files_warn_about_gaining_energy() {
+    assert!(!warns_about_energy(None));
+    assert!(!warns_about_energy(Some("grid")));
+
+    let path = format!("{}/greedy.toml", env!("CARGO_TARGET_TMPDIR"));
+    let text = "[material.greedy]\ndiffusive = 1.0\nspecular = 1.0\n\n[[sphere]]\nposition = [0.0, 0.0, 2.0]\nradius = 0.5\nmaterial = \"greedy\"\n";
+    std::fs::write(&path, text).unwrap();
+    assert!(warns_about_energy(Some(&path)));
+}
@@ -0,0 +1,219 @@
+// Environment map tests. Rays that miss everything see the map, a diffuse
+// object under it takes on its colors in both tracers, and the path tracer
+// finds a small bright sun in it by sampling the map like a light. Going
+// round, the right edge of the map runs on into the left one behind the
+// camera.
+
+use std::f64::consts::PI;
+
+use lux::*;
+
+const WIDTH: u32 = 16;
+const HEIGHT: u32 = 12;
+const TINT: Vector3D = Vector3D::new(0.2, 0.5, 0.9);
+const SKY: Vector3D = Vector3D::new(0.05, 0.08, 0.12);
+const SUN: Vector3D = Vector3D::new(2000.0, 1800.0, 1500.0);
+// Pixel of the sun in the 64 x 32 map, high up behind the camera
+const SUN_PIXEL: (u32, u32) = (0, 8);
+
+fn white() -> Material {
+    Material {
+        diffusive: 0.8,
+        ..Default::default()
+    }
+}
+
+// Direction to the middle of pixel (x, y) of a `width` x `height` map
+fn pixel_direction(x: u32, y: u32, width: u32, height: u32) -> Vector3D {
+    let lon = ((x as f64 + 0.5) / width as f64 - 0.5) * 2.0 * PI;
+    let lat = (0.5 - (y as f64 + 0.5) / height as f64) * PI;
+    Vector3D::new(lat.cos() * lon.sin(), lat.sin(), lat.cos() * lon.cos())
+}
+
+// A dim sky with a small, very bright sun
+fn sky_with_sun() -> EnvMap {
+    let (width, height) = (64, 32);
+    let pixels = (0..width * height)
+        .map(|i| match (i % width, i / width) == SUN_PIXEL {
+            true => SUN,
+            false => SKY,
+        })
+        .collect();
+    EnvMap::from_pixels(width, height, pixels).unwrap()
+}
+
+// A diffuse ball in the middle of the picture under `map`, rendered
+fn ball(map: EnvMap, settings: RenderSettings) -> GlobalSettings {
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, settings, Camera::default());
+    globals.ambient = Vector3D::ZERO;
+    globals.environment_map = Some(map);
+    add_sphere(&Vector3D::ZERO, 1.5, white(), &mut globals).unwrap();
+    render_scene(&mut globals).unwrap();
+    globals
+}
+
+fn middle(globals: &GlobalSettings) -> Vector3D {
+    globals.framebuffer[(HEIGHT / 2 * WIDTH + WIDTH / 2) as usize]
+}
+
+#[test]
+fn the_edges_of_the_map_meet_behind_the_camera() {
+    // Every column a color of its own
+    let (width, height) = (32, 4);
+    let pixels: Vec<Vector3D> = (0..width * height).map(|i| Vector3D::new((i % width) as f64, 0.0, 0.0)).collect();
+    let map = EnvMap::from_pixels(width, height, pixels).unwrap();
+    let column = |lon: f64| map.radiance(Vector3D::new(lon.sin(), 0.0, lon.cos())).x as u32;
+
+    // Going round once in small steps from just past the middle, through the
+    // edges behind the camera and back, every column comes next to the one
+    // before it, with none skipped
+    let steps = width * 16;
+    let mut seen = vec![false; width as usize];
+    let mut last = column(0.01);
+    for i in 1..=steps {
+        let next = column(0.01 + i as f64 / steps as f64 * 2.0 * PI);
+        assert!(next == last || next == (last + 1) % width, "column {} after {}", next, last);
+        seen[next as usize] = true;
+        last = next;
+    }
+    assert!(seen.iter().all(|&s| s));
+
+    // Either side of straight behind is either edge, and straight behind is
+    // one of them
+    assert_eq!(column(PI - 1e-6), width - 1);
+    assert_eq!(column(-PI + 1e-6), 0);
+    assert!([0, width - 1].contains(&(map.radiance(Vector3D::new(0.0, 0.0, -1.0)).x as u32)));
+}
+
+#[test]
+fn misses_see_the_map() {
+    let (width, height) = (8, 4);
+    let pixels: Vec<Vector3D> = (0..width * height).map(|i| Vector3D::new(i as f64, 0.5, 1.0)).collect();
+    let mut globals = GlobalSettings::new(4, 4, RenderSettings::default(), Camera::default());
+    globals.environment_map = Some(EnvMap::from_pixels(width, height, pixels.clone()).unwrap());
+
+    for y in 0..height {
+        for x in 0..width {
+            let ray = Ray {
+                origin: Vector3D::ZERO,
+                direction: pixel_direction(x, y, width, height),
+                time: 0.0,
+            };
+            // Camera rays, and everything else
+            assert_eq!(trace(&ray, 0, &globals), pixels[(y * width + x) as usize]);
+            assert_eq!(trace(&ray, 1, &globals), pixels[(y * width + x) as usize]);
+        }
+    }
+
+    // Unless the camera has its own background
+    globals.camera_background = CameraBackground::Color(TINT);
+    let ahead = Ray {
+        origin: Vector3D::ZERO,
+        direction: Vector3D::UNIT_Z,
+        time: 0.0,
+    };
+    assert_eq!(trace(&ahead, 0, &globals), TINT);
+}
+
+#[test]
+fn diffuse_ball_takes_on_the_tint_of_the_map() {
+    let mut expected = TINT;
+    expected.v3d_mul_scalar(0.8);
+
+    // The ball sees all of a map that's the same all around, so every bit
+    // of it reflects the map's color times its own
+    let whitted = middle(&ball(EnvMap::solid(TINT), RenderSettings::default()));
+    assert!(whitted.approx_eq(expected, 1e-3 * TINT.z), "{} vs {}", whitted, expected);
+
+    let settings = RenderSettings {
+        mode: RenderMode::PathTrace,
+        samples: 64,
+        max_depth: 2,
+        denoise: false,
+        ..Default::default()
+    };
+    let traced = ball(EnvMap::solid(TINT), settings);
+    let traced = middle(&traced);
+    assert!(traced.approx_eq(expected, 0.05 * TINT.z), "{} vs {}", traced, expected);
+    // Tinted, not just brighter or darker
+    assert!(traced.x < traced.y && traced.y < traced.z);
+}
+
+#[test]
+fn sampling_follows_the_bright_parts() {
+    let map = sky_with_sun();
+    let mut rng = Rng::new(5);
+
+    // Light from all of the map, summed up exactly and estimated from
+    // samples
+    let (width, height) = (64, 32);
+    let mut exact = 0.0;
+    for y in 0..height {
+        let band = ((y as f64 / height as f64 * PI).cos() - ((y + 1) as f64 / height as f64 * PI).cos()) * 2.0 * PI / width as f64;
+        for x in 0..width {
+            exact += map.radiance(pixel_direction(x, y, width, height)).luminance() * band;
+        }
+    }
+
+    let count = 20000;
+    let mut estimate = 0.0;
+    let mut in_sun = 0;
+    for _ in 0..count {
+        let (dir, pdf) = map.sample(&mut rng).unwrap();
+        assert!((pdf - map.pdf(dir)).abs() <= 1e-9 * pdf);
+        estimate += map.radiance(dir).luminance() / pdf / count as f64;
+        if map.radiance(dir).x > 1.0 {
+            in_sun += 1;
+        }
+    }
+    assert!((estimate - exact).abs() < 1e-6 * exact, "{} vs {}", estimate, exact);
+    // The sun is a 2048th of the pixels, but most of the light
+    assert!(in_sun > count / 2, "{}", in_sun);
+
+    // Nothing to pick on a black map
+    assert!(EnvMap::solid(Vector3D::ZERO).sample(&mut rng).is_none());
+}
+
+#[test]
+fn sampling_the_map_finds_the_sun() {
+    let settings = RenderSettings {
+        mode: RenderMode::PathTrace,
+        samples: 64,
+        max_depth: 1,
+        denoise: false,
+        ..Default::default()
+    };
+    let lit = middle(&ball(sky_with_sun(), settings));
+
+    // The middle of the ball faces the camera (-z). It reflects the sky all
+    // around plus the sun, which is small enough to count as one direction.
+    let (x, y) = SUN_PIXEL;
+    let sun_angle = ((y as f64 / 32.0 * PI).cos() - ((y + 1) as f64 / 32.0 * PI).cos()) * 2.0 * PI / 64.0;
+    let cos = -pixel_direction(x, y, 64, 32).z;
+    let mut expected = SUN;
+    expected.v3d_mul_scalar(sun_angle * cos / PI);
+    expected.v3d_add(SKY);
+    expected.v3d_mul_scalar(0.8);
+
+    assert!(lit.approx_eq(expected, 0.1 * expected.z), "{} vs {}", lit, expected);
+}
+
+#[test]
+fn scene_files_load_maps_next_to_them() {
+    let dir = env!("CARGO_TARGET_TMPDIR");
+    let file = std::fs::File::create(format!("{}/sky.hdr", dir)).unwrap();
+    let pixels = [image::Rgb([0.25f32, 0.5, 1.0]), image::Rgb([4.0f32, 2.0, 1.0])];
+    image::codecs::hdr::HdrEncoder::new(file).encode(&pixels, 2, 1).unwrap();
+
+    let path = format!("{}/sky.toml", dir);
+    std::fs::write(&path, "[world]\nenvironment_map = \"sky.hdr\"\n").unwrap();
+    let mut globals = GlobalSettings::new(4, 4, RenderSettings::default(), Camera::default());
+    scene::load(&path, &mut globals).unwrap();
+
+    let map = globals.environment_map.as_ref().unwrap();
+    assert_eq!(map.radiance(Vector3D::new(-1.0, 0.0, 0.0)), Vector3D::new(0.25, 0.5, 1.0));
+    assert_eq!(map.radiance(Vector3D::new(1.0, 0.0, 0.0)), Vector3D::new(4.0, 2.0, 1.0));
+
+    std::fs::write(&path, "[world]\nenvironment_map = \"missing.hdr\"\n").unwrap();
+    assert!(matches!(scene::load(&path, &mut globals), Err(LuxError::Io { .. })));
+}
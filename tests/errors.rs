@@ -0,0 +1,105 @@
+// Errors from the public API come out as the right `LuxError` variant.
+
+use lux::*;
+
+// Write `text` to a scene file in the test scratch directory
+fn scene_file(name: &str, text: &str) -> String {
+    let path = format!("{}/{}", env!("CARGO_TARGET_TMPDIR"), name);
+    std::fs::write(&path, text).unwrap();
+    path
+}
+
+fn empty_scene() -> GlobalSettings {
+    GlobalSettings::new(4, 4, RenderSettings::default(), Camera::default())
+}
+
+#[test]
+fn malformed_scene_is_a_parse_error() {
+    let path = scene_file("malformed.toml", "[[sphere]]\nposition = [0.0, 0.0\nradius = 1.0\n");
+
+    match scene::load(&path, &mut empty_scene()) {
+        Err(LuxError::Parse(message)) => assert!(message.contains("line 2"), "{}", message),
+        Err(e) => panic!("expected a parse error, got {:?}", e),
+        Ok(()) => panic!("malformed scene loaded"),
+    }
+}
+
+#[test]
+fn missing_scene_is_an_io_error() {
+    let result = scene::load("no/such/scene.toml", &mut empty_scene());
+    assert!(matches!(result, Err(LuxError::Io { .. })));
+}
+
+#[test]
+fn full_scene_is_a_capacity_error() {
+    let mut globals = empty_scene();
+    let pos = Vector3D::ZERO;
+    let color = Vector3D::ONE;
+
+    for _ in 0..MAXLIGHTCOUNT {
+        add_light(pos, color, 1.0, &mut globals).unwrap();
+    }
+
+    let result = add_light(pos, color, 1.0, &mut globals);
+    assert!(matches!(result, Err(LuxError::SceneCapacity { kind: "light", .. })));
+}
+
+#[test]
+fn truncated_scene_is_a_parse_error() {
+    let text = "[material.red]\ncolor = [1.0, 0.1, 0.1]\n\n[[sphere]]\nposition = [0.0, 0.0, 2.0]\nradius = 0.5\nmaterial = \"red\"\n";
+    let path = scene_file("truncated.toml", &text[..text.len() / 2]);
+
+    let result = scene::load(&path, &mut empty_scene());
+    assert!(matches!(result, Err(LuxError::Parse(_))), "{:?}", result);
+}
+
+#[test]
+fn saving_to_a_missing_directory_is_an_io_error() {
+    let img = image::RgbaImage::new(4, 4);
+    let path = format!("{}/no/such/dir/out.png", env!("CARGO_TARGET_TMPDIR"));
+
+    assert!(matches!(save_image(&img, &path), Err(LuxError::Io { .. })));
+    assert!(matches!(save_ppm(&img, &path), Err(LuxError::Io { .. })));
+}
+
+#[test]
+fn region_outside_the_image_is_invalid() {
+    let settings = RenderSettings {
+        region: Some(Region {
+            x: 2,
+            y: 2,
+            width: 4,
+            height: 4,
+        }),
+        ..Default::default()
+    };
+    let mut globals = GlobalSettings::new(4, 4, settings, Camera::default());
+
+    assert!(matches!(render_scene(&mut globals), Err(LuxError::InvalidSettings(_))));
+}
+
+#[test]
+fn spheres_without_a_positive_radius_are_refused() {
+    let mut globals = empty_scene();
+    demo::build_scene(&mut globals);
+    let count = globals.primitives().len();
+    let pos = Vector3D::new(0.0, 0.0, 3.0);
+
+    for radius in [0.0, -1.0, f64::NAN] {
+        let result = add_sphere(&pos, radius, "red", &mut globals);
+        assert!(matches!(result, Err(LuxError::Scene(_))), "{}: {:?}", radius, result);
+        let result = add_moving_sphere(&pos, &Vector3D::UNIT_X, radius, "red", &mut globals);
+        assert!(matches!(result, Err(LuxError::Scene(_))), "{}: {:?}", radius, result);
+    }
+    assert_eq!(globals.primitives().len(), count);
+
+    add_sphere(&pos, 0.5, "red", &mut globals).unwrap();
+    assert_eq!(globals.primitives().len(), count + 1);
+
+    let path = scene_file("zero_radius.toml", "[[sphere]]\nposition = [0.0, 0.0, 2.0]\nradius = 0.0\n");
+    match scene::load(&path, &mut globals) {
+        Err(LuxError::Parse(message)) => assert!(message.contains("line 3"), "{}", message),
+        result => panic!("expected a parse error, got {:?}", result),
+    }
+    assert_eq!(globals.primitives().len(), count + 1);
+}
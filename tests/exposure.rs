@@ -0,0 +1,53 @@
+// Exposure tests. Resolving keeps the float framebuffer, so the same render
+// can be resolved again at another exposure, one stop doubling the light.
+
+use lux::*;
+
+#[test]
+fn exposure_is_applied_when_resolving() {
+    let mut globals = GlobalSettings::new(4, 4, RenderSettings::default(), Camera::default());
+    for color in globals.framebuffer.iter_mut() {
+        *color = Vector3D::new(0.25, 0.125, 0.0);
+    }
+
+    let mut red = Vec::new();
+    for ev in [-1.0, 0.0, 1.0] {
+        globals.settings.exposure = ev;
+        resolve(&mut globals);
+        red.push(globals.img.get_pixel(0, 0)[0]);
+    }
+    assert_eq!(red, [31, 63, 127]);
+
+    let bit16 = resolve_16bit(&globals);
+    assert_eq!(bit16.get_pixel(0, 0)[1], (0.25 * 65535.0) as u16);
+}
+
+#[test]
+fn one_stop_up_doubles_a_rendered_image() {
+    let mut globals = GlobalSettings::new(32, 24, RenderSettings::default(), Camera::default());
+    demo::build_scene(&mut globals);
+    render_scene(&mut globals).unwrap();
+    let framebuffer = globals.framebuffer.clone();
+
+    let mut brackets = Vec::new();
+    for ev in [0.0, 1.0] {
+        globals.settings.exposure = ev;
+        resolve(&mut globals);
+        brackets.push(globals.img.clone());
+    }
+    assert_eq!(globals.framebuffer, framebuffer);
+
+    // Wherever the brighter one isn't clamped, it's twice the other, give
+    // or take the rounding to 8 bits
+    let mut compared = 0;
+    for (dim, bright) in brackets[0].pixels().zip(brackets[1].pixels()) {
+        for c in 0..3 {
+            if bright[c] < 255 && dim[c] > 8 {
+                let ratio = bright[c] as f64 / dim[c] as f64;
+                assert!((ratio - 2.0).abs() < 0.25, "{:?} vs {:?}", dim, bright);
+                compared += 1;
+            }
+        }
+    }
+    assert!(compared > 100, "{}", compared);
+}
@@ -0,0 +1,161 @@
+// C interface tests (`--features ffi`). Scenes built and rendered through
+// it come out the same as through the Rust API, and failures come back as
+// -1 with a message instead of a panic.
+
+#![cfg(feature = "ffi")]
+
+use std::ffi::{CStr, CString};
+use std::ptr;
+
+use lux::ffi::*;
+use lux::*;
+
+const WIDTH: u32 = 32;
+const HEIGHT: u32 = 24;
+
+fn last_error(scene: *const LuxScene) -> Option<String> {
+    let message = unsafe { lux_last_error(scene) };
+    (!message.is_null()).then(|| unsafe { CStr::from_ptr(message) }.to_string_lossy().into_owned())
+}
+
+#[test]
+fn builtin_scene_renders_like_the_rust_api() {
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, RenderSettings::default(), Camera::default());
+    demo::build_scene(&mut globals);
+    render_scene(&mut globals).unwrap();
+
+    let scene = lux_scene_new(WIDTH, HEIGHT);
+    let name = CString::new("grid").unwrap();
+    let mut pixels = vec![0u8; (WIDTH * HEIGHT * 4) as usize];
+    unsafe {
+        assert_eq!(lux_scene_add_builtin(scene, name.as_ptr()), 0);
+        assert_eq!(lux_render(scene, pixels.as_mut_ptr(), pixels.len()), 0);
+    }
+    assert_eq!(last_error(scene), None);
+    assert_eq!(pixels, globals.img.as_raw()[..]);
+
+    // And again into a buffer of its own
+    let (mut buffer, mut len) = (ptr::null_mut(), 0);
+    unsafe {
+        assert_eq!(lux_render_alloc(scene, &mut buffer, &mut len), 0);
+        assert!(!buffer.is_null());
+        assert_eq!(std::slice::from_raw_parts(buffer, len), &pixels[..]);
+        lux_buffer_free(buffer, len);
+        lux_scene_free(scene);
+    }
+}
+
+#[test]
+fn scene_built_piece_by_piece() {
+    let red = LuxMaterial {
+        color: LuxVector { x: 1.0, y: 0.1, z: 0.1 },
+        specular: 0.2,
+        diffusive: 0.7,
+        reflective: 0.0,
+        roughness: 0.0,
+        refractive: 0.0,
+        ior: 1.5,
+        emission: LuxVector { x: 0.0, y: 0.0, z: 0.0 },
+        wrap: 0.0,
+        dispersion: 0.0,
+        translucency: 0.0,
+    };
+    let camera = LuxCamera {
+        position: LuxVector { x: 0.0, y: 1.0, z: -4.0 },
+        look_at: LuxVector { x: 0.0, y: 0.0, z: 3.0 },
+        up: LuxVector { x: 0.0, y: 1.0, z: 0.0 },
+        fov: 50.0,
+    };
+    let settings = LuxRenderSettings {
+        shadows: true,
+        exposure: 0.5,
+        ..lux_render_settings_default()
+    };
+
+    // The same through the Rust API
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, RenderSettings::default(), Camera::default());
+    globals.settings.shadows = true;
+    globals.settings.exposure = 0.5;
+    globals.camera.position = Vector3D::new(0.0, 1.0, -4.0);
+    globals.camera.fov = 50.0;
+    globals.camera.look_at(Vector3D::new(0.0, 0.0, 3.0), Vector3D::UNIT_Y).unwrap();
+    globals.add_material("red", red.into());
+    add_sphere(&Vector3D::new(0.0, 0.0, 3.0), 1.0, "red", &mut globals).unwrap();
+    add_light(Vector3D::new(2.0, 3.0, 0.0), Vector3D::ONE, 1.5, &mut globals).unwrap();
+    render_scene(&mut globals).unwrap();
+
+    let scene = lux_scene_new(WIDTH, HEIGHT);
+    let name = CString::new("red").unwrap();
+    let mut pixels = vec![0u8; (WIDTH * HEIGHT * 4) as usize];
+    unsafe {
+        assert_eq!(lux_add_material(scene, name.as_ptr(), red), 0);
+        assert_eq!(lux_add_sphere(scene, LuxVector { x: 0.0, y: 0.0, z: 3.0 }, 1.0, name.as_ptr()), 0);
+        assert_eq!(lux_add_light(scene, LuxVector { x: 2.0, y: 3.0, z: 0.0 }, LuxVector { x: 1.0, y: 1.0, z: 1.0 }, 1.5), 0);
+        assert_eq!(lux_set_camera(scene, camera), 0);
+        assert_eq!(lux_set_render_settings(scene, settings), 0);
+        assert_eq!(lux_render(scene, pixels.as_mut_ptr(), pixels.len()), 0);
+        lux_scene_free(scene);
+    }
+    assert_eq!(pixels, globals.img.as_raw()[..]);
+}
+
+#[test]
+fn defaults_match_a_new_scene() {
+    let settings = lux_render_settings_default();
+    let defaults = RenderSettings::default();
+    assert!(!settings.path_trace);
+    assert_eq!(settings.samples, defaults.samples);
+    assert_eq!(settings.seed, defaults.seed);
+
+    let camera = lux_camera_default();
+    assert_eq!(Vector3D::from(camera.position), Camera::default().position);
+    assert_eq!(camera.fov, Camera::default().fov);
+}
+
+#[test]
+fn failures_come_back_as_errors() {
+    let scene = lux_scene_new(WIDTH, HEIGHT);
+    let unknown = CString::new("chrome").unwrap();
+    let origin = LuxVector { x: 0.0, y: 0.0, z: 0.0 };
+
+    unsafe {
+        assert_eq!(lux_add_sphere(scene, origin, 1.0, unknown.as_ptr()), -1);
+        assert_eq!(last_error(scene).as_deref(), Some("unknown material 'chrome'"));
+
+        // A call that works clears it
+        assert_eq!(lux_add_light(scene, origin, origin, 1.0), 0);
+        assert_eq!(last_error(scene), None);
+
+        assert_eq!(lux_scene_add_builtin(scene, unknown.as_ptr()), -1);
+        assert_eq!(lux_add_sphere(scene, origin, 1.0, ptr::null()), -1);
+        assert!(last_error(scene).is_some());
+
+        let mut small = [0u8; 16];
+        assert_eq!(lux_render(scene, small.as_mut_ptr(), small.len()), -1);
+        assert!(last_error(scene).unwrap().contains("buffer"));
+
+        let camera = LuxCamera {
+            look_at: lux_camera_default().position,
+            ..lux_camera_default()
+        };
+        assert_eq!(lux_set_camera(scene, camera), -1);
+
+        // Without a place for the length, there's no buffer to hand back
+        let mut buffer = ptr::null_mut();
+        assert_eq!(lux_render_alloc(scene, &mut buffer, ptr::null_mut()), -1);
+        assert!(buffer.is_null());
+        assert!(last_error(scene).unwrap().contains("length"));
+        let mut len = 0;
+        assert_eq!(lux_render_alloc(scene, ptr::null_mut(), &mut len), -1);
+        assert_eq!(len, 0);
+
+        // Null scenes are refused, not dereferenced
+        assert_eq!(lux_add_light(ptr::null_mut(), origin, origin, 1.0), -1);
+        assert!(lux_last_error(ptr::null()).is_null());
+        lux_scene_free(ptr::null_mut());
+
+        lux_scene_free(scene);
+    }
+
+    assert!(lux_scene_new(0, 10).is_null());
+}
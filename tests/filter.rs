@@ -0,0 +1,109 @@
+// Reconstruction filter tests. The box weights every sample of a pixel the
+// same and stays inside the pixel; the tent and the Gaussian weight samples
+// by how far from the pixel's center they land and reach into the
+// neighbors.
+
+use lux::*;
+
+// Odd, so the middle of the image is the center of pixel (7, 5)
+const WIDTH: u32 = 15;
+const HEIGHT: u32 = 11;
+
+#[test]
+fn box_weights_are_flat() {
+    let w = |dx, dy| Filter::Box.weight(dx, dy, 0.5);
+
+    assert_eq!(w(0.0, 0.0), 1.0);
+    assert_eq!(w(0.4, -0.3), 1.0);
+    assert_eq!(w(-0.5, 0.5), 1.0);
+    assert_eq!(w(0.6, 0.0), 0.0);
+}
+
+#[test]
+fn tent_and_gaussian_weights_fall_off() {
+    for filter in [Filter::Tent, Filter::Gaussian] {
+        let radius = filter.default_radius();
+        let w = |dx, dy| filter.weight(dx, dy, radius);
+
+        let mut last = w(0.0, 0.0);
+        for k in 1..=10 {
+            let d = radius * k as f64 / 10.0;
+            let next = w(d, 0.0);
+            assert!(next < last, "{:?} at {}: {} after {}", filter, d, next, last);
+            assert_eq!(next, w(-d, 0.0));
+            assert_eq!(next, w(0.0, d));
+            last = next;
+        }
+
+        // Nothing at the radius and past it, and the axes multiply
+        assert_eq!(w(radius, 0.0), 0.0);
+        assert_eq!(w(radius * 1.01, 0.0), 0.0);
+        assert!((w(0.3, 0.4) - w(0.3, 0.0) * w(0.0, 0.4) / w(0.0, 0.0)).abs() < 1e-12);
+    }
+
+    // Gaussian: a standard deviation of a third of the radius, less the
+    // value at the radius
+    let g = |d: f64| (-d * d / (2.0 * 0.5 * 0.5)).exp() - (-4.5_f64).exp();
+    let w = Filter::Gaussian.weight(1.0, 0.0, 1.5);
+    assert!((w - g(1.0) * g(0.0)).abs() < 1e-12, "{}", w);
+}
+
+// Brightness of every pixel when a small lamp sits in the middle of pixel
+// (7, 5) and nothing else is there
+fn lamp(filter: Filter) -> Vec<f64> {
+    let settings = RenderSettings {
+        mode: RenderMode::PathTrace,
+        samples: 1024,
+        sampler: Sampler::Stratified,
+        filter,
+        denoise: false,
+        ..Default::default()
+    };
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, settings, Camera::default());
+    globals.environment = Vector3D::ZERO;
+    globals.ambient = Vector3D::ZERO;
+
+    let glow = Material {
+        color: Vector3D::ZERO,
+        emission: Vector3D::ONE,
+        ..Default::default()
+    };
+    // About a third of a pixel across
+    add_sphere(&Vector3D::ZERO, 0.04, glow, &mut globals).unwrap();
+    render_scene(&mut globals).unwrap();
+
+    globals.framebuffer.iter().map(|c| c.luminance()).collect()
+}
+
+fn at(image: &[f64], x: u32, y: u32) -> f64 {
+    image[(y * WIDTH + x) as usize]
+}
+
+#[test]
+fn box_keeps_a_small_lamp_in_its_pixel() {
+    let image = lamp(Filter::Box);
+
+    assert!(at(&image, 7, 5) > 0.0);
+    for (x, y) in [(6, 5), (8, 5), (7, 4), (7, 6), (8, 6)] {
+        assert_eq!(at(&image, x, y), 0.0, "pixel ({}, {})", x, y);
+    }
+}
+
+#[test]
+fn gaussian_spreads_a_small_lamp_into_the_neighbors() {
+    let image = lamp(Filter::Gaussian);
+    let center = at(&image, 7, 5);
+
+    // Next to it gets some, diagonally less, and two pixels away is past
+    // the radius
+    for (x, y) in [(6, 5), (8, 5), (7, 4), (7, 6)] {
+        let side = at(&image, x, y);
+        assert!(side > 0.0 && side < center, "pixel ({}, {}): {} vs {}", x, y, side, center);
+    }
+    for (x, y) in [(6, 4), (8, 4), (6, 6), (8, 6)] {
+        let corner = at(&image, x, y);
+        assert!(corner > 0.0 && corner < at(&image, 8, 5), "pixel ({}, {}): {}", x, y, corner);
+    }
+    assert_eq!(at(&image, 9, 5), 0.0);
+    assert_eq!(at(&image, 7, 3), 0.0);
+}
@@ -0,0 +1,135 @@
+// Firefly suppression tests. A floor lit by a tiny, very bright lamp that
+// bounces only find by chance (no light sampling) is speckled with blinding
+// pixels. Clamping the light found after the first bounce, or toning down
+// each pixel's brightest samples, gets rid of most of them. The clamp leaves
+// direct light and samples below it alone.
+
+use lux::*;
+
+const WIDTH: u32 = 64;
+const HEIGHT: u32 = 48;
+
+// Brightness a pixel needs to count as a firefly
+const HOT: f64 = 4.0;
+
+fn render(clamp: Option<f64>, reject_outliers: Option<f64>) -> Vec<Vector3D> {
+    render_with(RenderSettings {
+        mode: RenderMode::PathTrace,
+        samples: 8,
+        max_depth: 2,
+        light_sampling: false,
+        seed: 7,
+        clamp,
+        reject_outliers,
+        ..Default::default()
+    })
+}
+
+fn render_with(settings: RenderSettings) -> Vec<Vector3D> {
+    let mut camera = Camera {
+        position: Vector3D::new(0.0, 1.0, -2.0),
+        ..Default::default()
+    };
+    camera.look_at(Vector3D::new(0.0, -0.5, 2.0), Vector3D::UNIT_Y).unwrap();
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, settings, camera);
+
+    let floor = floor();
+    let lamp = Material {
+        color: Vector3D::ZERO,
+        diffusive: 0.0,
+        emission: Vector3D::new(400.0, 400.0, 400.0),
+        ..floor
+    };
+    add_quad(Vector3D::new(-3.0, -1.0, -1.0), Vector3D::new(0.0, 0.0, 6.0), Vector3D::new(6.0, 0.0, 0.0), floor, &mut globals).unwrap();
+    add_sphere(&Vector3D::new(0.0, 1.5, 1.5), 0.15, lamp, &mut globals).unwrap();
+
+    render_scene(&mut globals).unwrap();
+    globals.framebuffer
+}
+
+fn floor() -> Material {
+    Material {
+        color: Vector3D::new(0.8, 0.8, 0.8),
+        diffusive: 1.0,
+        ..Default::default()
+    }
+}
+
+fn fireflies(image: &[Vector3D]) -> usize {
+    image.iter().filter(|c| c.luminance() > HOT).count()
+}
+
+#[test]
+fn clamping_removes_fireflies() {
+    let raw = fireflies(&render(None, None));
+    let clamped = fireflies(&render(Some(10.0), None));
+
+    assert!(raw >= 10, "only {} fireflies to begin with", raw);
+    assert!(clamped * 5 <= raw, "{} fireflies with the clamp, {} without", clamped, raw);
+}
+
+#[test]
+fn rejecting_outliers_removes_fireflies() {
+    let raw = fireflies(&render(None, None));
+    let rejected = fireflies(&render(None, Some(5.0)));
+
+    assert!(raw >= 10, "only {} fireflies to begin with", raw);
+    assert!(rejected * 2 <= raw, "{} fireflies with outliers rejected, {} without", rejected, raw);
+}
+
+#[test]
+fn suppression_only_takes_light_away() {
+    let raw = render(None, None);
+    for image in [render(Some(10.0), None), render(None, Some(5.0))] {
+        for (a, b) in raw.iter().zip(&image) {
+            assert!(b.luminance() <= a.luminance() + 1e-9, "{} brighter than {}", b, a);
+        }
+    }
+}
+
+#[test]
+fn clamp_above_every_sample_changes_nothing() {
+    let raw = render(None, None);
+    let clamped = render(Some(1e9), None);
+    for (a, b) in raw.iter().zip(&clamped) {
+        assert!(a.approx_eq(*b, 1e-9 * a.max_component().max(1.0)), "{} vs {}", a, b);
+    }
+}
+
+#[test]
+fn direct_light_is_not_clamped() {
+    // A floor under a bright point light in the dark: all the light there
+    // is comes straight from the light to the first hit
+    let render = |clamp| {
+        let settings = RenderSettings {
+            mode: RenderMode::PathTrace,
+            samples: 4,
+            max_depth: 2,
+            clamp,
+            ..Default::default()
+        };
+        let mut globals = GlobalSettings::new(WIDTH, HEIGHT, settings, Camera::default());
+        globals.environment = Vector3D::ZERO;
+        add_quad(Vector3D::new(-3.0, -1.0, -1.0), Vector3D::new(0.0, 0.0, 6.0), Vector3D::new(6.0, 0.0, 0.0), floor(), &mut globals).unwrap();
+        add_light(Vector3D::new(0.0, 0.0, 2.0), Vector3D::ONE, 50.0, &mut globals).unwrap();
+        render_scene(&mut globals).unwrap();
+        globals.framebuffer
+    };
+
+    let raw = render(None);
+    assert!(raw.iter().any(|c| c.max_component() > 1.0));
+    assert_eq!(raw, render(Some(0.01)));
+}
+
+#[test]
+fn bad_settings_are_invalid() {
+    for (clamp, reject_outliers) in [(Some(0.0), None), (Some(-1.0), None), (None, Some(0.0)), (None, Some(100.0))] {
+        let settings = RenderSettings {
+            clamp,
+            reject_outliers,
+            ..Default::default()
+        };
+        let mut globals = GlobalSettings::new(4, 4, settings, Camera::default());
+        assert!(matches!(render_scene(&mut globals), Err(LuxError::InvalidSettings(_))));
+    }
+}
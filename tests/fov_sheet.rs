@@ -0,0 +1,85 @@
+// Field of view sheet tests. The renders are laid out in a grid as close to
+// square as it gets, every one of them different, and the sweep needs
+// fields of view and a perspective camera.
+
+use image::RgbaImage;
+use lux::*;
+
+const WIDTH: u32 = 40;
+const HEIGHT: u32 = 30;
+
+fn config() -> RenderConfig {
+    RenderConfig {
+        width: WIDTH,
+        height: HEIGHT,
+        ..Default::default()
+    }
+}
+
+fn sheet(fovs: &[f64]) -> Result<RgbaImage, LuxError> {
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, RenderSettings::default(), Camera::default());
+    demo::build_scene(&mut globals);
+    render_fov_sheet(&mut globals, fovs, &config())
+}
+
+// Pixels of cell (column, row) below its label
+fn cell(sheet: &RgbaImage, column: u32, row: u32) -> Vec<[u8; 4]> {
+    let (left, top) = (column * WIDTH, row * HEIGHT);
+    (top + HEIGHT / 2..top + HEIGHT)
+        .flat_map(|y| (left..left + WIDTH).map(move |x| (x, y)))
+        .map(|(x, y)| sheet.get_pixel(x, y).0)
+        .collect()
+}
+
+#[test]
+fn renders_are_laid_out_in_a_grid() {
+    for (fovs, columns, rows) in [(&[40.0][..], 1, 1), (&[20.0, 35.0, 50.0, 90.0], 2, 2), (&[20.0, 35.0, 50.0, 70.0, 90.0], 3, 2)] {
+        let sheet = sheet(fovs).unwrap();
+        assert_eq!(sheet.dimensions(), (WIDTH * columns, HEIGHT * rows), "{:?}", fovs);
+    }
+}
+
+#[test]
+fn every_field_of_view_looks_different() {
+    let fovs = [20.0, 35.0, 50.0, 90.0];
+    let sheet = sheet(&fovs).unwrap();
+    let cells: Vec<_> = (0..4).map(|i| cell(&sheet, i % 2, i / 2)).collect();
+
+    for i in 0..cells.len() {
+        for j in i + 1..cells.len() {
+            assert_ne!(cells[i], cells[j], "{} and {} degrees", fovs[i], fovs[j]);
+        }
+    }
+
+    // The first one is the same render as on its own
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, RenderSettings::default(), Camera::default());
+    demo::build_scene(&mut globals);
+    let camera = Camera {
+        fov: 20.0,
+        ..Camera::default()
+    };
+    let pixels = render_to_buffer(&mut globals, &camera, &config()).unwrap();
+    let alone: Vec<[u8; 4]> = pixels[(WIDTH * HEIGHT / 2) as usize..].iter().map(|p| p.0).collect();
+    assert_eq!(cells[0], alone);
+}
+
+#[test]
+fn labels_are_drawn_in_the_corners() {
+    let sheet = sheet(&[20.0, 90.0]).unwrap();
+    for left in [0, WIDTH] {
+        let corner: Vec<_> = (1..8).flat_map(|y| (left + 1..left + 13).map(move |x| (x, y))).map(|(x, y)| sheet.get_pixel(x, y).0).collect();
+        assert!(corner.contains(&[255, 255, 255, 255]));
+        assert!(corner.contains(&[0, 0, 0, 255]));
+    }
+}
+
+#[test]
+fn sweep_needs_fields_of_view_and_a_perspective_camera() {
+    assert!(matches!(sheet(&[]), Err(LuxError::InvalidSettings(_))));
+    assert!(matches!(sheet(&[40.0, 200.0]), Err(LuxError::InvalidSettings(_))));
+
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, RenderSettings::default(), Camera::default());
+    let mut config = config();
+    config.camera.projection = Projection::Equirectangular;
+    assert!(matches!(render_fov_sheet(&mut globals, &[40.0], &config), Err(LuxError::InvalidSettings(_))));
+}
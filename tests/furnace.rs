@@ -0,0 +1,69 @@
+// Furnace tests for the path tracer. In a world of the same light all
+// around the answer is known without rendering: a diffuse ball of albedo a
+// under a sky of radiance L sends back a * L, and inside a glowing shell of
+// albedo a and emission E every bounce adds a times the last, E (1 + a +
+// a^2 + ...) up to the bounces allowed. The path tracer has to get there
+// on average, whatever the light sampling does along the way.
+
+use lux::*;
+
+const WIDTH: u32 = 16;
+const HEIGHT: u32 = 12;
+
+fn settings(max_depth: u32, light_sampling: bool) -> RenderSettings {
+    RenderSettings {
+        mode: RenderMode::PathTrace,
+        samples: 64,
+        max_depth,
+        light_sampling,
+        ..Default::default()
+    }
+}
+
+fn gray(albedo: f64) -> Material {
+    Material {
+        color: Vector3D::new(albedo, albedo, albedo),
+        diffusive: 1.0,
+        ..Default::default()
+    }
+}
+
+// Mean of every channel of the pixels whose camera ray hit something
+fn mean_of_hits(globals: &GlobalSettings) -> f64 {
+    let hits: Vec<&Vector3D> = globals.framebuffer.iter().zip(&globals.depth_buffer).filter(|(_, d)| d.is_finite()).map(|(c, _)| c).collect();
+    assert!(hits.len() > 20, "{} pixels hit", hits.len());
+    hits.iter().map(|c| c.x + c.y + c.z).sum::<f64>() / (3 * hits.len()) as f64
+}
+
+#[test]
+fn diffuse_ball_under_a_uniform_sky_sends_back_its_albedo() {
+    for light_sampling in [true, false] {
+        let mut globals = GlobalSettings::new(WIDTH, HEIGHT, settings(4, light_sampling), Camera::default());
+        globals.environment = Vector3D::new(0.5, 0.5, 0.5);
+        add_sphere(&Vector3D::new(0.0, 0.0, 2.0), 1.5, gray(0.8), &mut globals).unwrap();
+        render_scene(&mut globals).unwrap();
+
+        let mean = mean_of_hits(&globals);
+        assert!((mean - 0.4).abs() < 0.01, "light sampling {}: {}", light_sampling, mean);
+    }
+}
+
+#[test]
+fn glowing_shell_adds_up_every_bounce() {
+    let (albedo, emission) = (0.5, 0.2);
+    for (max_depth, light_sampling) in [(0, true), (1, true), (3, true), (3, false), (8, true)] {
+        // The camera in the middle of the shell, so every ray hits it
+        let mut globals = GlobalSettings::new(WIDTH, HEIGHT, settings(max_depth, light_sampling), Camera::default());
+        globals.environment = Vector3D::ZERO;
+        let shell = Material {
+            emission: Vector3D::new(emission, emission, emission),
+            ..gray(albedo)
+        };
+        add_sphere(&Camera::default().position, 10.0, shell, &mut globals).unwrap();
+        render_scene(&mut globals).unwrap();
+
+        let expected = emission * (1.0 - albedo.powi(max_depth as i32 + 1)) / (1.0 - albedo);
+        let mean = mean_of_hits(&globals);
+        assert!((mean - expected).abs() < expected * 0.02, "{} bounces, light sampling {}: {} instead of {}", max_depth, light_sampling, mean, expected);
+    }
+}
@@ -0,0 +1,113 @@
+// Geometry cache tests. A render after a material change shades from the
+// camera hits of the last one and comes out exactly like a render from
+// scratch, and moving the camera or the geometry or changing the samples
+// traces them again.
+
+use lux::*;
+
+const WIDTH: u32 = 48;
+const HEIGHT: u32 = 36;
+
+fn demo(settings: RenderSettings) -> GlobalSettings {
+    let settings = RenderSettings {
+        stats: true,
+        geometry_cache: true,
+        ..settings
+    };
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, settings, Camera::default());
+    demo::build_scene(&mut globals);
+    globals
+}
+
+// Renders `globals` and returns the intersection tests it took
+fn render(globals: &mut GlobalSettings) -> u64 {
+    globals.pass_stats.clear();
+    render_scene(globals).unwrap();
+    stats::total(&globals.pass_stats).counts.tests
+}
+
+fn recolor(globals: &mut GlobalSettings) {
+    globals.materials.get_mut("red").unwrap().color = Vector3D::new(0.2, 0.3, 0.9);
+}
+
+#[test]
+fn material_edits_reuse_the_hits_and_look_the_same() {
+    let path_traced = RenderSettings {
+        mode: RenderMode::PathTrace,
+        ..Default::default()
+    };
+    let transparent = |mut g: GlobalSettings| {
+        g.camera_background = CameraBackground::Transparent;
+        g
+    };
+    let cases = [
+        ("one sample", demo(RenderSettings::default())),
+        ("supersampled", demo(RenderSettings { samples: 4, ..Default::default() })),
+        ("path traced", demo(RenderSettings { samples: 3, ..path_traced })),
+        ("albedo shading", demo(RenderSettings { samples: 2, shading: Shading::Albedo, ..Default::default() })),
+        ("transparent", transparent(demo(RenderSettings::default()))),
+    ];
+
+    for (name, mut warm) in cases {
+        let first = render(&mut warm);
+        recolor(&mut warm);
+        let again = render(&mut warm);
+        assert!(again < first, "{}: {} tests after the edit, {} before", name, again, first);
+
+        // A new scene with the edit in from the start
+        let mut cold = GlobalSettings::new(WIDTH, HEIGHT, warm.settings, warm.camera);
+        cold.camera_background = warm.camera_background;
+        demo::build_scene(&mut cold);
+        recolor(&mut cold);
+        render(&mut cold);
+        assert_eq!(warm.framebuffer, cold.framebuffer, "{}", name);
+        assert_eq!(warm.depth_buffer, cold.depth_buffer, "{}", name);
+        assert_eq!(warm.normal_buffer, cold.normal_buffer, "{}", name);
+        assert_eq!(warm.albedo_buffer, cold.albedo_buffer, "{}", name);
+        assert_eq!(warm.coverage, cold.coverage, "{}", name);
+    }
+}
+
+#[test]
+fn changes_to_what_the_camera_sees_trace_again() {
+    type Change = fn(&mut GlobalSettings);
+    let changes: [(&str, Change); 6] = [
+        ("camera", |g| g.camera.position.x += 0.1),
+        ("size", |g| g.set_size(WIDTH / 2, HEIGHT / 2)),
+        ("samples", |g| g.settings.samples = 2),
+        ("seed", |g| {
+            g.settings.samples = 2;
+            g.settings.seed += 1;
+        }),
+        ("new sphere", |g| {
+            add_sphere(&Vector3D::new(0.0, 0.0, 2.0), 0.5, "green", g).unwrap();
+        }),
+        ("front only", |g| g.materials.get_mut("red").unwrap().sidedness = Sidedness::FrontOnly),
+    ];
+
+    for (name, change) in changes {
+        let mut warm = demo(RenderSettings::default());
+        render(&mut warm);
+        change(&mut warm);
+        let again = render(&mut warm);
+
+        let mut cold = demo(RenderSettings::default());
+        change(&mut cold);
+        assert_eq!(again, render(&mut cold), "{}", name);
+        assert_eq!(warm.framebuffer, cold.framebuffer, "{}", name);
+    }
+}
+
+#[test]
+fn off_unless_asked_for() {
+    let mut globals = demo(RenderSettings::default());
+    globals.settings.geometry_cache = false;
+    render(&mut globals);
+    assert!(globals.geometry_cache.is_none());
+    recolor(&mut globals);
+
+    let mut cold = GlobalSettings::new(WIDTH, HEIGHT, globals.settings, Camera::default());
+    demo::build_scene(&mut cold);
+    recolor(&mut cold);
+    assert_eq!(render(&mut globals), render(&mut cold));
+}
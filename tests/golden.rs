@@ -0,0 +1,154 @@
+// Golden image tests. The built-in scenes are rendered at a small size and
+// compared against the images in tests/golden, so changes to the shading math don't go
+// unnoticed. If a change is meant to alter the output, look at the new image
+// and regenerate the golden file with the command printed by the failing
+// test, or rerun the tests with UPDATE_GOLDEN=1 to rewrite all of them.
+//
+// A failing test saves the render and an image of the differences next to
+// the other test output, and prints the error of every channel.
+
+use image::RgbImage;
+use lux::*;
+
+const WIDTH: u32 = 160;
+const HEIGHT: u32 = 120;
+
+// How far (out of 255) a channel may be off before the pixel counts as
+// different, and how many different pixels are tolerated. Leaves room for
+// floating point differences between platforms.
+const CHANNEL_TOLERANCE: u8 = 2;
+const MAX_DIFFERENT_PIXELS: usize = (WIDTH * HEIGHT / 200) as usize;
+
+// How much brighter the differences are drawn in the diff image
+const DIFF_GAIN: u8 = 8;
+
+// Render the built-in scene called `scene`
+fn render_demo(scene: &str, settings: RenderSettings) -> RgbImage {
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, settings, Camera::default());
+    demo::find(scene).unwrap()(&mut globals);
+    render_scene(&mut globals).unwrap();
+
+    image::DynamicImage::ImageRgba8(globals.img).to_rgb8()
+}
+
+fn compare(name: &str, img: &RgbImage, flags: &str) {
+    let path = format!("{}/tests/golden/{}.ppm", env!("CARGO_MANIFEST_DIR"), name);
+    let regenerate = format!(
+        "cargo run --release -- --dump tests/golden/{}.ppm --width {} --height {}{}",
+        name, WIDTH, HEIGHT, flags
+    );
+
+    if std::env::var("UPDATE_GOLDEN").is_ok_and(|v| v == "1") {
+        img.save_with_format(&path, image::ImageFormat::Pnm).unwrap();
+        eprintln!("{}: wrote {}", name, path);
+        return;
+    }
+
+    let golden = match image::open(&path) {
+        Ok(golden) => golden.to_rgb8(),
+        Err(e) => panic!(
+            "Can't read {}: {}\nCreate it with: {}\nor rerun the tests with UPDATE_GOLDEN=1",
+            path, e, regenerate
+        ),
+    };
+
+    assert_eq!(golden.dimensions(), img.dimensions(), "{}: wrong size", name);
+
+    let mut different = 0;
+    let mut max = [0u8; 3];
+    let mut sum = [0u64; 3];
+    let mut diff_img = RgbImage::new(WIDTH, HEIGHT);
+    for ((a, b), d) in golden.pixels().zip(img.pixels()).zip(diff_img.pixels_mut()) {
+        for c in 0..3 {
+            let diff = a[c].abs_diff(b[c]);
+            max[c] = max[c].max(diff);
+            sum[c] += diff as u64;
+            d[c] = diff.saturating_mul(DIFF_GAIN);
+        }
+        if (0..3).any(|c| d[c] > CHANNEL_TOLERANCE.saturating_mul(DIFF_GAIN)) {
+            different += 1;
+        }
+    }
+
+    if different <= MAX_DIFFERENT_PIXELS {
+        return;
+    }
+
+    let dir = env!("CARGO_TARGET_TMPDIR");
+    let actual = format!("{}/golden-{}.ppm", dir, name);
+    let diff = format!("{}/golden-{}-diff.png", dir, name);
+    img.save_with_format(&actual, image::ImageFormat::Pnm).unwrap();
+    diff_img.save(&diff).unwrap();
+
+    let pixels = (WIDTH * HEIGHT) as f64;
+    let mean = sum.map(|s| s as f64 / pixels);
+    panic!(
+        "{}: {} pixels differ from the golden image.\n\
+         Max error (r, g, b): {:?}, mean error: ({:.3}, {:.3}, {:.3})\n\
+         Render saved to {}, differences ({}x) to {}\n\
+         If the change is intended, regenerate it with: {}\n\
+         or rerun the tests with UPDATE_GOLDEN=1",
+        name, different, max, mean[0], mean[1], mean[2], actual, DIFF_GAIN, diff, regenerate
+    );
+}
+
+#[test]
+fn whitted_matches_golden() {
+    let img = render_demo("grid", RenderSettings::default());
+    compare("whitted", &img, "");
+}
+
+#[test]
+fn pathtrace_matches_golden() {
+    let settings = RenderSettings {
+        mode: RenderMode::PathTrace,
+        samples: 4,
+        ..Default::default()
+    };
+
+    let img = render_demo("grid", settings);
+    compare("pathtrace", &img, " --pathtrace --samples 4");
+}
+
+#[test]
+fn cornell_matches_golden() {
+    // Lit by an emissive quad only, so path traced
+    let settings = RenderSettings {
+        mode: RenderMode::PathTrace,
+        samples: 16,
+        ..Default::default()
+    };
+
+    let img = render_demo("cornell", settings);
+    compare("cornell", &img, " --scene cornell --pathtrace --samples 16");
+}
+
+#[test]
+fn glass_matches_golden() {
+    let img = render_demo("glass", RenderSettings::default());
+    compare("glass", &img, " --scene glass");
+}
+
+#[test]
+fn mirror_hall_matches_golden() {
+    let img = render_demo("mirror-hall", RenderSettings::default());
+    compare("mirror-hall", &img, " --scene mirror-hall");
+}
+
+#[test]
+fn floor_matches_golden() {
+    let img = render_demo("floor", RenderSettings::default());
+    compare("floor", &img, " --scene floor");
+}
+
+#[test]
+fn fog_matches_golden() {
+    let img = render_demo("fog", RenderSettings::default());
+    compare("fog", &img, " --scene fog");
+}
+
+#[test]
+fn cover_matches_golden() {
+    let img = render_demo("cover", RenderSettings::default());
+    compare("cover", &img, " --scene cover");
+}
@@ -0,0 +1,45 @@
+// Sphere grid test. Text art turns into spheres where the characters have a
+// material, laid out like the text.
+
+use std::collections::HashMap;
+
+use lux::*;
+
+#[test]
+fn two_by_two_grid_places_mapped_spheres() {
+    let mut globals = GlobalSettings::new(4, 4, RenderSettings::default(), Camera::default());
+    globals.add_material("red", matte(Vector3D::new(1.0, 0.0, 0.0)));
+    globals.add_material("blue", matte(Vector3D::new(0.0, 0.0, 1.0)));
+
+    let materials = HashMap::from([('r', "red".into()), ('b', "blue".into())]);
+    let grid = SphereGrid {
+        origin: Vector3D::new(1.0, 2.0, 3.0),
+        spacing: 0.5,
+        radius: 0.25,
+        ..Default::default()
+    };
+    add_sphere_grid(&["r.", ".b"], &materials, &grid, &mut globals).unwrap();
+
+    assert_eq!(globals.primitives().len(), 2);
+
+    let expected = [("red", (1.0, 2.0, 3.0)), ("blue", (1.5, 1.5, 3.0))];
+    for (p, (name, center)) in globals.primitives().iter().zip(expected) {
+        match p.material_ref() {
+            MaterialRef::Named(n) => assert_eq!(n, name),
+            _ => panic!("expected the material {}", name),
+        }
+
+        let (min, max) = p.bounds();
+        let got = ((min.x + max.x) / 2.0, (min.y + max.y) / 2.0, (min.z + max.z) / 2.0);
+        assert_eq!(got, center);
+        assert_eq!(max.x - min.x, 0.5);
+    }
+}
+
+fn matte(color: Vector3D) -> Material {
+    Material {
+        color,
+        diffusive: 1.0,
+        ..Default::default()
+    }
+}
@@ -0,0 +1,57 @@
+// Rays starting inside a sphere. They leave through the far wall instead of
+// hitting the sphere again right where they start, and the wall is shaded
+// from the inside, with its normal turned back towards them.
+
+use lux::*;
+
+const RADIUS: f64 = 2.0;
+
+fn sphere() -> PrimSphere {
+    PrimSphere {
+        position: Vector3D::ZERO,
+        velocity: Vector3D::ZERO,
+        radius: RADIUS,
+        m: MaterialRef::Named("white".to_string()),
+    }
+}
+
+#[test]
+fn rays_from_inside_hit_the_far_wall() {
+    let s = sphere();
+    let direction = Vector3D::UNIT_Z;
+
+    // From anywhere inside, even from right at the near wall, the hit is the
+    // exit on the other side
+    for z in [0.5, 0.0, -RADIUS + 1e-6] {
+        let origin = Vector3D::new(0.0, 0.0, z);
+        let mut dist = 0.0;
+        assert_eq!(s.intersect(Ray { origin, direction, time: 0.0 }, 1e-4, &mut dist), -1, "from {}", z);
+        assert!((dist - (RADIUS - z)).abs() < 1e-9, "{} from {}", dist, z);
+
+        // The normal there points out, along the ray
+        let exit = Vector3D::new(0.0, 0.0, RADIUS);
+        assert!(s.normal(exit, 0.0).approx_eq(direction, 1e-12));
+    }
+}
+
+#[test]
+fn the_inside_wall_is_lit_from_inside() {
+    let (width, height) = (16, 16);
+    let mut globals = GlobalSettings::new(width, height, RenderSettings::default(), Camera::default());
+    globals.ambient = Vector3D::ZERO;
+    let white = Material {
+        diffusive: 1.0,
+        ..Default::default()
+    };
+    add_sphere(&Vector3D::ZERO, 20.0, white, &mut globals).unwrap();
+    add_light(Vector3D::new(0.0, 0.0, 5.0), Vector3D::ONE, 1.0, &mut globals).unwrap();
+
+    let camera = globals.camera;
+    let hit = globals.pick(&camera, width / 2, height / 2, width, height).unwrap();
+    assert!(hit.distance > 1.0, "hit itself at {}", hit.distance);
+
+    // Facing the light inside, the wall in front of the camera isn't black
+    render_scene(&mut globals).unwrap();
+    let color = globals.framebuffer[(height / 2 * width + width / 2) as usize];
+    assert!(color.luminance() > 0.1, "{:?}", color);
+}
@@ -0,0 +1,74 @@
+// Instance tests. A unit sphere moved by a transform is hit where it was
+// moved to and no longer where it started, with normals pointing out of it
+// there, both on its own and placed in a scene.
+
+use std::sync::Arc;
+
+use lux::*;
+
+const OFFSET: Vector3D = Vector3D::new(5.0, 0.0, 0.0);
+
+fn unit_sphere() -> Arc<dyn Primitive> {
+    Arc::new(PrimSphere {
+        position: Vector3D::ZERO,
+        velocity: Vector3D::ZERO,
+        radius: 1.0,
+        m: MaterialRef::Named("white".to_string()),
+    })
+}
+
+fn ray(origin: Vector3D, towards: Vector3D) -> Ray {
+    let mut direction = towards;
+    direction.v3d_sub(origin);
+    direction.v3d_norm();
+    Ray { origin, direction, time: 0.0 }
+}
+
+#[test]
+fn a_translated_sphere_is_hit_where_it_was_moved_to() {
+    let moved = Instance::new(unit_sphere(), Mat4::translate(OFFSET)).unwrap();
+    let start = Vector3D::new(0.0, 0.0, -10.0);
+
+    for z in [-0.5, 0.0, 0.5] {
+        let target = Vector3D::new(5.0, 0.0, z);
+        let r = ray(start, target);
+        let mut dist = 0.0;
+        assert_ne!(moved.intersect(r, 1e-4, &mut dist), 0, "missed {:?}", target);
+
+        // On the surface around (5, 0, 0), facing the ray
+        let mut point = r.direction;
+        point.v3d_mul_scalar(dist);
+        point.v3d_add(r.origin);
+        let mut out = point;
+        out.v3d_sub(OFFSET);
+        assert!((out.v3d_length() - 1.0).abs() < 1e-9, "{:?}", point);
+        out.v3d_norm();
+        assert!(moved.normal(point, 0.0).approx_eq(out, 1e-9));
+        assert!(out.v3d_dot_mul(r.direction) < 0.0);
+    }
+
+    // Where the sphere was before, there's nothing
+    let mut dist = 0.0;
+    assert_eq!(moved.intersect(ray(start, Vector3D::ZERO), 1e-4, &mut dist), 0);
+}
+
+#[test]
+fn placed_instances_show_up_in_the_scene() {
+    let camera = Camera {
+        position: Vector3D::new(2.5, 0.0, -10.0),
+        ..Default::default()
+    };
+    let (width, height) = (64, 32);
+    let mut globals = GlobalSettings::new(width, height, RenderSettings::default(), camera);
+    let white = Material {
+        diffusive: 1.0,
+        ..Default::default()
+    };
+    globals.materials.insert("white".to_string(), white);
+    add_instance(&unit_sphere(), Mat4::translate(OFFSET), None, &mut globals).unwrap();
+
+    let (x, y) = camera.project(OFFSET, width, height).unwrap();
+    assert_eq!(globals.pick(&camera, x as u32, y as u32, width, height).map(|hit| hit.primitive_index), Some(0));
+    let (x, y) = camera.project(Vector3D::ZERO, width, height).unwrap();
+    assert!(globals.pick(&camera, x as u32, y as u32, width, height).is_none());
+}
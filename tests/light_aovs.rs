@@ -0,0 +1,39 @@
+// Light AOV test. Two light groups lighting the same shiny scene: the group
+// images and the rest (background) have to add up to the render.
+
+use lux::*;
+
+const WIDTH: u32 = 40;
+const HEIGHT: u32 = 30;
+
+#[test]
+fn light_groups_add_up_to_the_render() {
+    let settings = RenderSettings {
+        light_aovs: true,
+        samples: 2,
+        ..Default::default()
+    };
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, settings, Camera::default());
+    demo::build_scene(&mut globals);
+    add_light(Vector3D::new(3.0, -2.0, -1.0), Vector3D::new(1.0, 0.6, 0.3), 1.0, &mut globals).unwrap();
+    globals.lights_mut()[1].group = "fill".to_string();
+    render_scene(&mut globals).unwrap();
+
+    assert_eq!(globals.light_groups(), ["default", "fill"]);
+    assert_eq!(globals.light_aovs.len(), 3);
+
+    for (pixel, color) in globals.framebuffer.iter().enumerate() {
+        let mut sum = Vector3D::ZERO;
+        for aov in &globals.light_aovs {
+            sum.v3d_add(aov[pixel]);
+        }
+        for c in 0..3 {
+            assert!((sum[c] - color[c]).abs() < 1e-9, "pixel {}: {} vs {}", pixel, sum[c], color[c]);
+        }
+    }
+
+    // Both lights show up on their own
+    for aov in &globals.light_aovs[..2] {
+        assert!(aov.iter().any(|c| c.max_component() > 0.1));
+    }
+}
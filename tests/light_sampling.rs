@@ -0,0 +1,78 @@
+// Next-event estimation test. A floor lit only by a small emissive quad is
+// very noisy when bounces have to find the light by chance; sampling the
+// light directly at every bounce gives a far cleaner image for the same
+// number of samples.
+
+use lux::*;
+
+const WIDTH: u32 = 24;
+const HEIGHT: u32 = 18;
+
+fn render(light_sampling: bool, seed: u64) -> Vec<Vector3D> {
+    let settings = RenderSettings {
+        mode: RenderMode::PathTrace,
+        samples: 16,
+        max_depth: 2,
+        light_sampling,
+        seed,
+        ..Default::default()
+    };
+    let mut camera = Camera {
+        position: Vector3D::new(0.0, 1.0, -2.0),
+        ..Default::default()
+    };
+    camera.look_at(Vector3D::new(0.0, -1.0, 2.0), Vector3D::UNIT_Y).unwrap();
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, settings, camera);
+
+    let floor = Material {
+        color: Vector3D::new(0.8, 0.8, 0.8),
+        diffusive: 1.0,
+        ..Default::default()
+    };
+    let lamp = Material {
+        color: Vector3D::ZERO,
+        diffusive: 0.0,
+        emission: Vector3D::new(40.0, 40.0, 40.0),
+        ..floor
+    };
+    add_quad(Vector3D::new(-3.0, -1.0, -1.0), Vector3D::new(0.0, 0.0, 6.0), Vector3D::new(6.0, 0.0, 0.0), floor, &mut globals).unwrap();
+    // Facing down, a quarter of a unit across
+    add_quad(Vector3D::new(-0.125, 1.0, 1.875), Vector3D::new(0.25, 0.0, 0.0), Vector3D::new(0.0, 0.0, 0.25), lamp, &mut globals).unwrap();
+
+    render_scene(&mut globals).unwrap();
+    globals.framebuffer
+}
+
+// Mean squared difference between two renders with different seeds, which
+// is twice the variance of a pixel
+fn noise(light_sampling: bool) -> f64 {
+    let (a, b) = (render(light_sampling, 1), render(light_sampling, 2));
+    let sum: f64 = a
+        .iter()
+        .zip(&b)
+        .map(|(p, q)| {
+            let d = p.luminance() - q.luminance();
+            d * d
+        })
+        .sum();
+    sum / a.len() as f64
+}
+
+#[test]
+fn light_sampling_is_much_less_noisy() {
+    let with = noise(true);
+    let without = noise(false);
+
+    assert!(without > 0.0);
+    assert!(with * 10.0 < without, "with light sampling {}, without {}", with, without);
+}
+
+#[test]
+fn light_sampling_doesnt_change_the_average() {
+    let mean = |pixels: &[Vector3D]| pixels.iter().map(|p| p.luminance()).sum::<f64>() / pixels.len() as f64;
+
+    let with: f64 = (0..4).map(|seed| mean(&render(true, seed))).sum::<f64>() / 4.0;
+    let without: f64 = (0..4).map(|seed| mean(&render(false, seed))).sum::<f64>() / 4.0;
+
+    assert!((with - without).abs() < 0.15 * with, "with light sampling {}, without {}", with, without);
+}
@@ -0,0 +1,59 @@
+// Light intensity test. A light's `color` is only its hue and `intensity`
+// how bright it is, so turning up the intensity should scale the light a
+// surface gets without tinting it. Lights made from a color temperature
+// go from warm to blue the way black bodies do.
+
+use lux::*;
+
+const WIDTH: u32 = 16;
+const HEIGHT: u32 = 12;
+
+// Light a matte orange sphere adds to the middle pixel, before clamping
+fn diffuse_light(intensity: f64) -> Vector3D {
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, RenderSettings::default(), Camera::default());
+    globals.ambient = Vector3D::ZERO;
+    let orange = Material {
+        color: Vector3D::new(1.0, 0.5, 0.1),
+        diffusive: 0.5,
+        ..Default::default()
+    };
+    add_sphere(&Vector3D::new(0.0, 0.0, 3.0), 1.0, orange, &mut globals).unwrap();
+    add_light(Vector3D::ZERO, Vector3D::ONE, intensity, &mut globals).unwrap();
+    render_scene(&mut globals).unwrap();
+
+    globals.framebuffer[(HEIGHT / 2 * WIDTH + WIDTH / 2) as usize]
+}
+
+#[test]
+fn double_intensity_doubles_diffuse_light() {
+    let single = diffuse_light(2.0);
+    let double = diffuse_light(4.0);
+
+    // Bright enough that clamping would have cut it off
+    assert!(double.x > 1.0);
+
+    for c in 0..3 {
+        assert!((double[c] - 2.0 * single[c]).abs() < 1e-9, "channel {}: {} vs {}", c, double[c], single[c]);
+    }
+
+    // Same hue: the channels keep their ratios
+    assert!((double.y / double.x - single.y / single.x).abs() < 1e-9);
+    assert!((double.z / double.x - single.z / single.x).abs() < 1e-9);
+}
+
+#[test]
+fn color_temperatures_go_from_warm_to_blue() {
+    let warm = Light::from_temperature(2700.0, 1.0).color;
+    assert!(warm.x > warm.z, "{:?}", warm);
+
+    // Daylight is about white
+    let daylight = Light::from_temperature(6500.0, 1.0).color;
+    for c in [daylight.x, daylight.y, daylight.z] {
+        assert!((c - 1.0).abs() < 0.05, "{:?}", daylight);
+    }
+
+    let blue = Light::from_temperature(10000.0, 1.0).color;
+    assert!(blue.z > blue.x, "{:?}", blue);
+
+    assert_eq!(Light::from_temperature(2700.0, 3.0).intensity, 3.0);
+}
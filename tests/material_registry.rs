@@ -0,0 +1,65 @@
+// Material registry tests. Primitives given the same `MaterialId` share the
+// material, so changing it after the scene is built changes all of them,
+// and handles from another scene are refused.
+
+use lux::*;
+
+const WIDTH: u32 = 24;
+const HEIGHT: u32 = 16;
+
+fn matte(color: Vector3D) -> Material {
+    Material {
+        color,
+        diffusive: 1.0,
+        ..Default::default()
+    }
+}
+
+// Pixel in the middle of where `center` shows up
+fn pixel_at(globals: &GlobalSettings, center: Vector3D) -> Vector3D {
+    let (x, y) = globals.camera.project(center, WIDTH, HEIGHT).unwrap();
+    globals.framebuffer[(y as u32 * WIDTH + x as u32) as usize]
+}
+
+#[test]
+fn changing_a_registered_material_changes_every_primitive_using_it() {
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, RenderSettings::default(), Camera::default());
+    let shared = globals.register_material(matte(Vector3D::new(1.0, 0.0, 0.0)));
+    let other = globals.register_material(matte(Vector3D::new(0.0, 0.0, 1.0)));
+
+    let centers = [Vector3D::new(-2.0, 0.0, 6.0), Vector3D::new(0.0, 0.0, 6.0), Vector3D::new(2.0, 0.0, 6.0)];
+    add_sphere(&centers[0], 0.8, shared, &mut globals).unwrap();
+    add_sphere(&centers[1], 0.8, shared, &mut globals).unwrap();
+    add_sphere(&centers[2], 0.8, other, &mut globals).unwrap();
+    add_light(Vector3D::new(0.0, 3.0, 0.0), Vector3D::ONE, 1.0, &mut globals).unwrap();
+
+    render_scene(&mut globals).unwrap();
+    let before: Vec<Vector3D> = centers.iter().map(|&c| pixel_at(&globals, c)).collect();
+    assert!(before[0].x > 0.0 && before[0].y == 0.0);
+    assert!(before[1].x > 0.0 && before[1].y == 0.0);
+
+    globals.registered_material_mut(shared).unwrap().color = Vector3D::new(0.0, 1.0, 0.0);
+    render_scene(&mut globals).unwrap();
+    let after: Vec<Vector3D> = centers.iter().map(|&c| pixel_at(&globals, c)).collect();
+
+    // Both spheres sharing it turn green, just as bright
+    for (a, b) in after.iter().zip(&before).take(2) {
+        assert_eq!(a.x, 0.0);
+        assert_eq!(a.y, b.x);
+    }
+    // The one with a material of its own stays the same
+    assert_eq!(after[2], before[2]);
+}
+
+#[test]
+fn handles_from_another_scene_are_refused() {
+    let mut other = GlobalSettings::new(4, 4, RenderSettings::default(), Camera::default());
+    other.register_material(matte(Vector3D::ONE));
+    let foreign = other.register_material(matte(Vector3D::ONE));
+
+    let mut globals = GlobalSettings::new(4, 4, RenderSettings::default(), Camera::default());
+    assert!(globals.registered_material(foreign).is_none());
+    let result = add_sphere(&Vector3D::new(0.0, 0.0, 3.0), 1.0, foreign, &mut globals);
+    assert!(matches!(result, Err(LuxError::Scene(_))));
+    assert!(globals.primitives().is_empty());
+}
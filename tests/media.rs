@@ -0,0 +1,203 @@
+// Fog tests. Light going through fog keeps exp(-density * distance) of
+// itself, every ray segment is fogged (so mirrors show foggy reflections),
+// and fog of density 0 changes nothing at all.
+
+use lux::media::transmittance;
+use lux::*;
+
+const WIDTH: u32 = 15;
+const HEIGHT: u32 = 11;
+
+fn matte(color: Vector3D) -> Material {
+    Material {
+        color,
+        diffusive: 1.0,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn transmittance_follows_beer_lambert() {
+    assert_eq!(transmittance(0.3, 0.0), 1.0);
+    assert!((transmittance(0.5, 2.0) - (-1.0_f64).exp()).abs() < 1e-15);
+    assert_eq!(transmittance(0.5, f64::INFINITY), 0.0);
+
+    // Two stretches one after the other take as much as both together
+    let (a, b) = (1.3, 2.9);
+    assert!((transmittance(0.4, a) * transmittance(0.4, b) - transmittance(0.4, a + b)).abs() < 1e-15);
+
+    let mut last = 1.0;
+    for k in 1..20 {
+        let t = transmittance(0.2, k as f64);
+        assert!(t < last && t > 0.0);
+        last = t;
+    }
+
+    // No fog lets everything through, however far
+    for d in [0.0, 1.0, 1e300, f64::INFINITY] {
+        assert_eq!(transmittance(0.0, d), 1.0);
+    }
+}
+
+#[test]
+fn fog_blends_towards_its_color() {
+    let fog = Fog {
+        density: 0.5,
+        color: Vector3D::new(0.2, 0.4, 0.6),
+        shafts: 0,
+    };
+    let red = Vector3D::new(1.0, 0.0, 0.0);
+
+    assert_eq!(fog.apply(red, 0.0), red);
+    assert!(fog.apply(red, f64::INFINITY).approx_eq(fog.color, 1e-15));
+
+    let t = (-1.0_f64).exp();
+    let expected = Vector3D::new(t + 0.2 * (1.0 - t), 0.4 * (1.0 - t), 0.6 * (1.0 - t));
+    assert!(fog.apply(red, 2.0).approx_eq(expected, 1e-15));
+
+    let clear = Fog {
+        density: 0.0,
+        ..fog
+    };
+    assert_eq!(clear.apply(red, f64::INFINITY), red);
+    assert_eq!(clear.glow(5.0), Vector3D::ZERO);
+}
+
+fn demo(settings: RenderSettings, fog: Fog) -> Vec<Vector3D> {
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, settings, Camera::default());
+    demo::build_scene(&mut globals);
+    globals.fog = fog;
+    render_scene(&mut globals).unwrap();
+    globals.framebuffer
+}
+
+#[test]
+fn zero_density_changes_nothing() {
+    let thin_air = Fog {
+        density: 0.0,
+        color: Vector3D::new(0.9, 0.1, 0.1),
+        shafts: 8,
+    };
+    let path_traced = RenderSettings {
+        mode: RenderMode::PathTrace,
+        samples: 4,
+        ..Default::default()
+    };
+
+    for settings in [RenderSettings::default(), path_traced] {
+        assert!(demo(settings, thin_air) == demo(settings, Fog::default()));
+    }
+}
+
+#[test]
+fn fog_dims_both_legs_of_a_reflection() {
+    // A mirror facing the camera 5 away, showing a red ball behind the
+    // camera whose near side is 7 from the mirror. Only the ambient light
+    // shows the ball, and the fog is black.
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, RenderSettings::default(), Camera::default());
+    globals.ambient = Vector3D::ONE;
+    globals.fog = Fog {
+        density: 0.1,
+        color: Vector3D::ZERO,
+        shafts: 0,
+    };
+
+    let mirror = Material {
+        diffusive: 0.0,
+        reflective: 1.0,
+        ..matte(Vector3D::ONE)
+    };
+    add_quad(Vector3D::new(-10.0, -10.0, 0.0), Vector3D::new(20.0, 0.0, 0.0), Vector3D::new(0.0, 20.0, 0.0), mirror, &mut globals).unwrap();
+    add_sphere(&Vector3D::new(0.0, 0.0, -8.0), 1.0, matte(Vector3D::new(1.0, 0.0, 0.0)), &mut globals).unwrap();
+    render_scene(&mut globals).unwrap();
+
+    let center = globals.framebuffer[(HEIGHT / 2 * WIDTH + WIDTH / 2) as usize];
+    // The reflected ray starts the ray epsilon off the mirror
+    let expected = transmittance(0.1, 5.0 + 7.0 - globals.settings.ray_epsilon);
+    assert!((center.x - expected).abs() < 1e-9, "{} vs {}", center.x, expected);
+    assert_eq!((center.y, center.z), (0.0, 0.0));
+}
+
+#[test]
+fn blockers_cast_shafts_into_the_fog() {
+    // A light over the middle of a ray along x, with a ball in between
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, RenderSettings::default(), Camera::default());
+    globals.fog = Fog {
+        density: 0.1,
+        color: Vector3D::ZERO,
+        shafts: 64,
+    };
+    add_sphere(&Vector3D::new(0.0, 0.0, 2.5), 0.5, matte(Vector3D::ONE), &mut globals).unwrap();
+    add_light(Vector3D::new(0.0, 0.0, 5.0), Vector3D::ONE, 1.0, &mut globals).unwrap();
+    globals.build_bvh();
+
+    let ray = Ray {
+        origin: Vector3D::new(-3.0, 0.0, 0.0),
+        direction: Vector3D::UNIT_X,
+        time: 0.0,
+    };
+    let gathered = |globals: &GlobalSettings| {
+        let mut total = Vector3D::ZERO;
+        globals.fog.in_scattered(&ray, 6.0, globals, |light, c| {
+            assert_eq!(light, 0);
+            total.v3d_add(c);
+        });
+        total.x
+    };
+
+    let shadowed = gathered(&globals);
+    globals.set_visibility(0, VisibilityFlags {
+        shadow: false,
+        ..Default::default()
+    });
+    let lit = gathered(&globals);
+    assert!(shadowed > 0.0 && shadowed < lit, "{} vs {}", shadowed, lit);
+
+    // Without shafts nothing is gathered
+    globals.fog.shafts = 0;
+    assert_eq!(gathered(&globals), 0.0);
+}
+
+#[test]
+fn scene_files_set_the_fog() {
+    let path = format!("{}/fog.toml", env!("CARGO_TARGET_TMPDIR"));
+    std::fs::write(&path, "[world]\nfog_density = 0.25\nfog_color = [0.1, 0.2, 0.3]\nfog_shafts = 12\n").unwrap();
+
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, RenderSettings::default(), Camera::default());
+    scene::load(&path, &mut globals).unwrap();
+    assert_eq!(
+        globals.fog,
+        Fog {
+            density: 0.25,
+            color: Vector3D::new(0.1, 0.2, 0.3),
+            shafts: 12,
+        }
+    );
+
+    std::fs::write(&path, "[world]\nfog_density = -1.0\n").unwrap();
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, RenderSettings::default(), Camera::default());
+    assert!(matches!(scene::load(&path, &mut globals), Err(LuxError::Parse(_))));
+}
+
+#[test]
+fn light_aovs_still_add_up_in_fog() {
+    let settings = RenderSettings {
+        light_aovs: true,
+        denoise: false,
+        ..Default::default()
+    };
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, settings, Camera::default());
+    demo::fog(&mut globals);
+    render_scene(&mut globals).unwrap();
+
+    for (pixel, color) in globals.framebuffer.iter().enumerate() {
+        let mut sum = Vector3D::ZERO;
+        for aov in &globals.light_aovs {
+            sum.v3d_add(aov[pixel]);
+        }
+        assert!(sum.approx_eq(*color, 1e-9), "pixel {}: {} vs {}", pixel, sum, color);
+    }
+
+    // The shafts count for the light that makes them
+    assert!(globals.light_aovs[0].iter().any(|c| c.max_component() > 0.0));
+}
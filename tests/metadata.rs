@@ -0,0 +1,136 @@
+// PNG metadata tests. Every entry `metadata::describe` comes up with is
+// written into the PNG as a text chunk and read back unchanged, whatever
+// chunk type it ends up in.
+
+use std::time::Duration;
+
+use lux::*;
+
+const WIDTH: u32 = 8;
+const HEIGHT: u32 = 6;
+
+fn out(name: &str) -> String {
+    format!("{}/{}", env!("CARGO_TARGET_TMPDIR"), name)
+}
+
+fn rendered() -> GlobalSettings {
+    let settings = RenderSettings {
+        samples: 4,
+        seed: 7,
+        max_depth: 3,
+        ..Default::default()
+    };
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, settings, Camera::default());
+    demo::build_scene(&mut globals);
+    render_scene(&mut globals).unwrap();
+    globals
+}
+
+fn sorted(mut entries: metadata::Metadata) -> metadata::Metadata {
+    entries.sort();
+    entries
+}
+
+fn get<'a>(entries: &'a metadata::Metadata, keyword: &str) -> &'a str {
+    entries.iter().find(|(k, _)| k == keyword).map(|(_, v)| v.as_str()).unwrap_or_else(|| panic!("no {}", keyword))
+}
+
+#[test]
+fn every_entry_survives_a_round_trip() {
+    let scene = out("metadata_scene.toml");
+    std::fs::write(&scene, "[[sphere]]\nposition = [0.0, 0.0, 2.0]\nradius = 0.5\n").unwrap();
+
+    let globals = rendered();
+    let entries = metadata::describe(&globals, &scene, Duration::from_millis(1234));
+    let path = out("metadata.png");
+    metadata::save_image(&globals.img, &path, &entries).unwrap();
+
+    let read = metadata::read(&path).unwrap();
+    assert_eq!(read, entries);
+
+    for keyword in [
+        "Software",
+        "Resolution",
+        "Samples per pixel",
+        "Seed",
+        "Max depth",
+        "Mode",
+        "Camera position",
+        "Camera direction",
+        "Field of view",
+        "Scene",
+        "Scene hash",
+        "Render time",
+        "Settings",
+        "Camera",
+    ] {
+        assert!(!get(&read, keyword).is_empty(), "{}", keyword);
+    }
+    assert_eq!(get(&read, "Software"), format!("lux {}", env!("CARGO_PKG_VERSION")));
+    assert_eq!(get(&read, "Resolution"), "8x6");
+    assert_eq!(get(&read, "Samples per pixel"), "4");
+    assert_eq!(get(&read, "Seed"), "7");
+    assert_eq!(get(&read, "Max depth"), "3");
+    assert_eq!(get(&read, "Mode"), "whitted");
+    assert_eq!(get(&read, "Camera position"), "(0, 0, -5)");
+    assert_eq!(get(&read, "Scene"), scene);
+    assert_eq!(get(&read, "Render time"), "1.23s");
+    assert!(get(&read, "Settings").contains("seed: 7"));
+
+    // The image itself is untouched
+    let img = image::open(&path).unwrap().to_rgba8();
+    assert_eq!(img, globals.img);
+}
+
+#[test]
+fn scene_hash_follows_the_file() {
+    let scene = out("metadata_hashed.toml");
+    let globals = rendered();
+    let hash = |text: &str| {
+        std::fs::write(&scene, text).unwrap();
+        get(&metadata::describe(&globals, &scene, Duration::ZERO), "Scene hash").to_string()
+    };
+
+    let a = hash("[[sphere]]\nposition = [0.0, 0.0, 2.0]\nradius = 0.5\n");
+    let b = hash("[[sphere]]\nposition = [0.0, 0.0, 2.0]\nradius = 0.6\n");
+    assert_ne!(a, b);
+    assert_eq!(a, hash("[[sphere]]\nposition = [0.0, 0.0, 2.0]\nradius = 0.5\n"));
+
+    // Built-in scenes have no file to hash
+    let entries = metadata::describe(&globals, "demo", Duration::ZERO);
+    assert!(entries.iter().all(|(k, _)| k != "Scene hash"));
+}
+
+#[test]
+fn text_outside_latin1_survives() {
+    let globals = rendered();
+    let entries = metadata::describe(&globals, "szenen/würfel→kugel.toml", Duration::ZERO);
+    let path = out("metadata_utf8.png");
+    metadata::save_image(&globals.img, &path, &entries).unwrap();
+
+    // Written as UTF-8 text, which comes back after the others
+    let read = metadata::read(&path).unwrap();
+    assert_eq!(sorted(read), sorted(entries));
+}
+
+#[test]
+fn sixteen_bit_images_keep_their_metadata() {
+    let globals = rendered();
+    let entries = metadata::describe(&globals, "demo", Duration::ZERO);
+    let path = out("metadata_16bit.png");
+    metadata::save_16bit(&globals, &path, &entries).unwrap();
+
+    assert_eq!(metadata::read(&path).unwrap(), entries);
+    let img = image::open(&path).unwrap().to_rgba16();
+    assert_eq!(img, resolve_16bit(&globals));
+}
+
+#[test]
+fn images_without_metadata_read_as_empty() {
+    let globals = rendered();
+    let path = out("metadata_none.png");
+    save_image(&globals.img, &path).unwrap();
+
+    assert!(metadata::read(&path).unwrap().is_empty());
+    assert!(matches!(metadata::read(&out("metadata_missing.png")), Err(LuxError::Io { .. })));
+}
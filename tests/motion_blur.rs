@@ -0,0 +1,120 @@
+// Motion blur tests. A sphere moving while the shutter is open leaves a
+// streak from where it is when the shutter opens to where it is when it
+// closes, and no sample is taken outside that interval, so nothing shows
+// up past either end. Wrapped in an instance, it streaks the same way, and a
+// sphere that doesn't move comes out the same whether the shutter is open
+// or not.
+
+use std::sync::Arc;
+
+use lux::*;
+
+const WIDTH: u32 = 96;
+const HEIGHT: u32 = 32;
+const RADIUS: f64 = 0.3;
+
+fn camera(shutter_time: f64) -> Camera {
+    Camera {
+        shutter_time,
+        ..Default::default()
+    }
+}
+
+fn white() -> Material {
+    Material {
+        diffusive: 1.0,
+        ..Default::default()
+    }
+}
+
+// `add` rendered with the shutter open for `shutter_time`
+fn render(shutter_time: f64, add: impl FnOnce(&mut GlobalSettings)) -> GlobalSettings {
+    let settings = RenderSettings {
+        samples: 64,
+        ..Default::default()
+    };
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, settings, camera(shutter_time));
+    globals.camera_background = CameraBackground::Transparent;
+    add(&mut globals);
+    render_scene(&mut globals).unwrap();
+    globals
+}
+
+fn middle_row(globals: &GlobalSettings) -> Vec<f64> {
+    let row = (HEIGHT / 2 * WIDTH) as usize;
+    globals.coverage[row..row + WIDTH as usize].to_vec()
+}
+
+// Share of the samples of every pixel in the middle row that hit the
+// sphere, which moves from x = -1.5 two units to the right per unit of time
+fn coverage_of_middle_row(shutter_time: f64) -> Vec<f64> {
+    middle_row(&render(shutter_time, |globals| {
+        add_moving_sphere(&Vector3D::new(-1.5, 0.0, 0.0), &Vector3D::new(2.0, 0.0, 0.0), RADIUS, white(), globals).unwrap();
+    }))
+}
+
+// Column of the image the point (x, 0, 0) shows up in
+fn column(x: f64) -> f64 {
+    camera(0.0).project(Vector3D::new(x, 0.0, 0.0), WIDTH, HEIGHT).unwrap().0
+}
+
+#[test]
+fn a_moving_sphere_leaves_a_streak_along_its_path() {
+    let still = coverage_of_middle_row(0.0);
+    let moving = coverage_of_middle_row(1.0);
+
+    // Still, it covers a few pixels fully. Moving, it covers more of them,
+    // each for part of the time.
+    let covered = |row: &[f64]| row.iter().filter(|&&c| c > 0.0).count();
+    assert!(covered(&moving) > 3 * covered(&still), "{} vs {}", covered(&moving), covered(&still));
+    let middle = column(-0.5) as usize;
+    assert!(moving[middle] > 0.05 && moving[middle] < 0.5, "{}", moving[middle]);
+    assert_eq!(still[middle], 0.0);
+}
+
+#[test]
+fn samples_stay_within_the_shutter_interval() {
+    // Open for half a unit, the sphere gets from -1.5 to -0.5
+    let coverage = coverage_of_middle_row(0.5);
+    let (first, last) = (column(-1.5 - RADIUS), column(-0.5 + RADIUS));
+
+    for (x, &c) in coverage.iter().enumerate() {
+        let x = x as f64 + 0.5;
+        if x < first - 1.0 || x > last + 1.0 {
+            assert_eq!(c, 0.0, "pixel {} covered outside the path {}..{}", x, first, last);
+        }
+    }
+
+    // Both ends of the path are reached
+    assert!(coverage[(first + 2.0) as usize] > 0.0);
+    assert!(coverage[(last - 2.0) as usize] > 0.0);
+}
+
+#[test]
+fn moving_instances_streak_like_moving_spheres() {
+    // The same sphere and motion, with the start moved by the instance
+    let geometry: Arc<dyn Primitive> = Arc::new(PrimSphere {
+        position: Vector3D::ZERO,
+        velocity: Vector3D::new(2.0, 0.0, 0.0),
+        radius: RADIUS,
+        m: MaterialRef::Named("white".to_string()),
+    });
+    let instanced = middle_row(&render(1.0, |globals| {
+        globals.materials.insert("white".to_string(), white());
+        add_instance(&geometry, Mat4::translate(Vector3D::new(-1.5, 0.0, 0.0)), None, globals).unwrap();
+    }));
+
+    let direct = coverage_of_middle_row(1.0);
+    for (x, (a, b)) in instanced.iter().zip(&direct).enumerate() {
+        assert!((a - b).abs() < 1e-9, "pixel {}: {} vs {}", x, a, b);
+    }
+}
+
+#[test]
+fn still_spheres_look_the_same_with_the_shutter_open() {
+    let sphere = |globals: &mut GlobalSettings| add_sphere(&Vector3D::ZERO, 0.5, white(), globals).unwrap();
+    let closed = render(0.0, sphere);
+    let open = render(1.0, sphere);
+    assert!(closed.framebuffer == open.framebuffer);
+    assert!(closed.coverage == open.coverage);
+}
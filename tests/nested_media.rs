@@ -0,0 +1,179 @@
+// Nested media tests. A ray refracted into one glass sphere and then into
+// another inside it is bent by the ratio of their indices at the inner
+// surface, not by that of the inner sphere against air. Where the rays come
+// out of the balls matches Snell's law with those indices.
+
+use lux::*;
+
+const WIDTH: u32 = 64;
+const HEIGHT: u32 = 48;
+// Middle of both balls
+const CENTER: Vector3D = Vector3D::new(0.0, 0.0, 2.0);
+
+// Size of the map in the last test, and how far apart the colors of
+// neighbouring pixels of it are at most
+const MAP_WIDTH: u32 = 64;
+const MAP_HEIGHT: u32 = 32;
+const TEXEL: f64 = 1.0 / MAP_HEIGHT as f64 + 1e-9;
+
+// Only lets light through, bent by `ior`
+fn clear(ior: f64) -> Material {
+    Material {
+        refractive: 1.0,
+        ior,
+        ..Default::default()
+    }
+}
+
+// A glass ball of index 1.5 in front of a checkered wall, with a ball of
+// index `inner` in the middle of it if there is one
+fn render(inner: Option<f64>) -> Vec<Vector3D> {
+    let settings = RenderSettings {
+        max_depth: 8,
+        ..Default::default()
+    };
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, settings, Camera::default());
+    globals.environment = Vector3D::new(0.1, 0.2, 0.4);
+
+    let wall = Material {
+        color: Vector3D::new(0.9, 0.6, 0.2),
+        diffusive: 1.0,
+        refractive: 0.0,
+        cutout: Some(Cutout::Checker {
+            scale: 12.0,
+            threshold: 0.5,
+        }),
+        ..clear(1.0)
+    };
+    add_quad(Vector3D::new(-6.0, -6.0, 8.0), Vector3D::new(12.0, 0.0, 0.0), Vector3D::new(0.0, 12.0, 0.0), wall, &mut globals).unwrap();
+    add_light(Vector3D::new(0.0, 0.0, 0.0), Vector3D::ONE, 1.0, &mut globals).unwrap();
+
+    add_sphere(&CENTER, 1.5, clear(1.5), &mut globals).unwrap();
+    if let Some(ior) = inner {
+        add_sphere(&CENTER, 0.8, clear(ior), &mut globals).unwrap();
+    }
+
+    render_scene(&mut globals).unwrap();
+    globals.framebuffer
+}
+
+fn differing(a: &[Vector3D], b: &[Vector3D]) -> usize {
+    a.iter().zip(b).filter(|(a, b)| !a.approx_eq(**b, 1e-6)).count()
+}
+
+#[test]
+fn inner_ball_of_the_same_index_bends_nothing() {
+    // Between two media of the same index the ratio is 1, so the inner
+    // ball can't be seen. Against air it would act as glass of its own.
+    let without = render(None);
+    let same = render(Some(1.5));
+    assert!(differing(&without, &same) <= 2, "{} pixels differ", differing(&without, &same));
+}
+
+#[test]
+fn lower_index_inside_a_higher_one_bends_away() {
+    let without = render(None);
+
+    // An air bubble in glass bends light, although air into air wouldn't
+    let bubble = render(Some(1.0));
+    assert!(differing(&without, &bubble) > 100, "{} pixels differ", differing(&without, &bubble));
+
+    // Glass of a lower index is a weaker bubble: at 1.2 against 1.5 it
+    // doesn't look like 1.2 glass in the air would
+    let weaker = render(Some(1.2));
+    assert!(differing(&without, &weaker) > 100);
+    assert!(differing(&bubble, &weaker) > 100);
+}
+
+#[test]
+fn a_ray_through_the_middle_goes_straight() {
+    // Through the centers every surface is hit head on, so whatever the
+    // indices the wall behind is seen where it would be without the balls
+    let middle = (HEIGHT / 2 * WIDTH + WIDTH / 2) as usize;
+    let without = render(None);
+    for ior in [1.0, 1.2, 1.5, 2.0] {
+        assert!(render(Some(ior))[middle].approx_eq(without[middle], 1e-9), "{}", ior);
+    }
+}
+
+// Where a ray leaving `origin` along `direction` next meets the sphere of
+// `radius` around `CENTER`, if it does
+fn meet(origin: Vector3D, direction: Vector3D, radius: f64) -> Option<Vector3D> {
+    let mut oc = origin;
+    oc.v3d_sub(CENTER);
+    let b = oc.v3d_dot_mul(direction);
+    let disc = b * b - (oc.v3d_dot_mul(oc) - radius * radius);
+    if disc < 0.0 {
+        return None;
+    }
+    let t = [-b - disc.sqrt(), -b + disc.sqrt()].into_iter().find(|&t| t > 1e-6)?;
+    let mut p = direction;
+    p.v3d_mul_scalar(t);
+    p.v3d_add(origin);
+    Some(p)
+}
+
+// `direction` bent by `eta` where it goes through a sphere around `CENTER`
+// at `point`, unless it can't get through
+fn bend(direction: Vector3D, point: Vector3D, eta: f64) -> Option<Vector3D> {
+    let mut n = point;
+    n.v3d_sub(CENTER);
+    n.v3d_norm();
+    if n.v3d_dot_mul(direction) > 0.0 {
+        n.v3d_mul_scalar(-1.0);
+    }
+    Vector3D::refract(direction, n, eta)
+}
+
+// Where a ray through both balls goes once it's out of them again, if it
+// goes into the inner one and gets out, with glass of index `around`
+// taken to be around the inner ball where the ray goes in and out of it
+fn through_both(ray: &Ray, around: f64) -> Option<Vector3D> {
+    let p = meet(ray.origin, ray.direction, 1.5)?;
+    let d = bend(ray.direction, p, 1.0 / 1.5)?;
+    let q = meet(p, d, 0.8)?;
+    let d = bend(d, q, around / 1.2)?;
+    let r = meet(q, d, 0.8)?;
+    let d = bend(d, r, 1.2 / around)?;
+    let s = meet(r, d, 1.5)?;
+    bend(d, s, 1.5)
+}
+
+#[test]
+fn the_inner_surface_bends_by_the_ratio_of_the_two_indices() {
+    // A map with a color of its own in every pixel, so what a pixel sees
+    // of it tells which way its ray left the balls
+    let (width, height) = (MAP_WIDTH, MAP_HEIGHT);
+    let pixels = (0..width * height).map(|i| Vector3D::new((i % width) as f64 / width as f64, (i / width) as f64 / height as f64, 0.5)).collect();
+    let map = EnvMap::from_pixels(width, height, pixels).unwrap();
+
+    let settings = RenderSettings {
+        max_depth: 8,
+        ..Default::default()
+    };
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, settings, Camera::default());
+    globals.environment_map = Some(map);
+    add_sphere(&CENTER, 1.5, clear(1.5), &mut globals).unwrap();
+    add_sphere(&CENTER, 0.8, clear(1.2), &mut globals).unwrap();
+    let map = globals.environment_map.as_ref().unwrap();
+
+    // Every pixel that sees the inner ball sees the map where the ratio of
+    // 1.5 to 1.2 at its surface sends the ray, give or take a pixel of the
+    // map for the grazing ones, and for most of them that's nowhere near
+    // where 1.2 against air would
+    let camera = globals.camera;
+    let (mut inner, mut against_air) = (0, 0);
+    for (x, y) in (0..HEIGHT).flat_map(|y| (0..WIDTH).map(move |x| (x, y))) {
+        let ray = camera.pixel_ray(x as f64 + 0.5, y as f64 + 0.5, WIDTH, HEIGHT, 0.0).unwrap();
+        let (Some(nested), Some(air)) = (through_both(&ray, 1.5), through_both(&ray, 1.0)) else {
+            continue;
+        };
+        inner += 1;
+        let seen = trace_pixel(&globals, &camera, x, y, WIDTH, HEIGHT);
+        assert!(seen.approx_eq(map.radiance(nested), TEXEL), "({}, {}) sees {:?}", x, y, seen);
+        if !seen.approx_eq(map.radiance(air), TEXEL) {
+            against_air += 1;
+        }
+    }
+    assert!(inner > 100 && against_air * 2 > inner, "{} of {} pixels tell them apart", against_air, inner);
+}
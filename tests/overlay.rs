@@ -0,0 +1,89 @@
+// Diagnostic overlay tests. The clipping overlay marks the channels that
+// clip in red and black pixels in blue and leaves the rest as rendered; the
+// falloff overlay shows one light alone, brightest where it shines straight
+// on, and stays the same when the other lights change.
+
+use image::Rgba;
+use lux::*;
+
+const WIDTH: u32 = 32;
+const HEIGHT: u32 = 24;
+
+const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+const BLUE: Rgba<u8> = Rgba([0, 0, 255, 255]);
+
+fn matte() -> Material {
+    Material {
+        color: Vector3D::new(0.8, 0.8, 0.8),
+        diffusive: 1.0,
+        ..Default::default()
+    }
+}
+
+// A wall filling the view, lit by `lights` (position, intensity), over a
+// black background
+fn wall(lights: &[(Vector3D, f64)]) -> GlobalSettings {
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, RenderSettings::default(), Camera::default());
+    globals.environment = Vector3D::ZERO;
+    globals.ambient = Vector3D::ZERO;
+    add_quad(Vector3D::new(-4.0, -3.0, 2.0), Vector3D::new(0.0, 6.0, 0.0), Vector3D::new(8.0, 0.0, 0.0), matte(), &mut globals).unwrap();
+    for &(position, intensity) in lights {
+        add_light(position, Vector3D::ONE, intensity, &mut globals).unwrap();
+    }
+    render_scene(&mut globals).unwrap();
+    globals
+}
+
+#[test]
+fn clipping_marks_bright_and_black_pixels() {
+    // Bright enough to clip where it shines straight on, not at the edges
+    let globals = wall(&[(Vector3D::new(0.0, 0.0, 1.5), 1.5)]);
+    let overlay = overlay_image(&globals, Overlay::Clipping).unwrap();
+
+    let (mut clipped, mut kept, mut black) = (0, 0, 0);
+    for (x, y, &pixel) in overlay.enumerate_pixels() {
+        let shown = *globals.img.get_pixel(x, y);
+        let color = globals.framebuffer[(y * WIDTH + x) as usize];
+        if color.max_component() > 1.0 {
+            assert_eq!(pixel, RED, "({}, {})", x, y);
+            clipped += 1;
+        } else if shown.0[..3] == [0, 0, 0] {
+            assert_eq!(pixel, BLUE, "({}, {})", x, y);
+            black += 1;
+        } else {
+            assert_eq!(pixel, shown, "({}, {})", x, y);
+            kept += 1;
+        }
+    }
+    assert!(clipped > 0 && kept > 0, "{} clipped, {} kept", clipped, kept);
+    assert_eq!(black, 0);
+    assert_eq!(overlay.get_pixel(WIDTH / 2, HEIGHT / 2), &RED);
+
+    // An unlit scene is black all over
+    let dark = wall(&[]);
+    let overlay = overlay_image(&dark, Overlay::Clipping).unwrap();
+    assert!(overlay.pixels().all(|&p| p == BLUE));
+}
+
+#[test]
+fn falloff_shows_one_light_alone() {
+    let key = (Vector3D::new(-2.0, 0.0, 0.0), 0.8);
+    let alone = wall(&[key]);
+    let with_fill = wall(&[key, (Vector3D::new(2.0, 1.0, 0.0), 5.0)]);
+
+    let falloff = overlay_image(&alone, Overlay::Falloff(0)).unwrap();
+    assert_eq!(falloff, overlay_image(&with_fill, Overlay::Falloff(0)).unwrap());
+    assert_ne!(falloff, overlay_image(&with_fill, Overlay::Falloff(1)).unwrap());
+
+    // The key light is on the left, so the left of the wall is redder and
+    // the right bluer
+    let (left, right) = (falloff.get_pixel(2, HEIGHT / 2), falloff.get_pixel(WIDTH - 3, HEIGHT / 2));
+    assert!(left[0] > right[0] && left[2] < right[2], "{:?} vs {:?}", left, right);
+}
+
+#[test]
+fn falloff_of_a_missing_light_fails() {
+    let globals = wall(&[(Vector3D::ZERO, 1.0)]);
+    assert!(matches!(overlay_image(&globals, Overlay::Falloff(1)), Err(LuxError::InvalidSettings(_))));
+    assert!(Overlay::Falloff(0).check(&globals).is_ok());
+}
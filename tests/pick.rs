@@ -0,0 +1,52 @@
+// Picking tests. The pixel over a sphere picks that sphere, at the point and
+// normal where the camera ray through the pixel's center meets it, and a
+// pixel showing only background picks nothing.
+
+use lux::*;
+
+const WIDTH: u32 = 40;
+const HEIGHT: u32 = 30;
+
+fn white() -> Material {
+    Material {
+        diffusive: 1.0,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn picking_finds_the_sphere_under_the_pixel() {
+    let camera = Camera::default();
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, RenderSettings::default(), camera);
+    let left = Vector3D::new(-1.0, 0.0, 0.0);
+    let right = Vector3D::new(1.0, 0.0, 0.0);
+    add_sphere(&left, 0.5, white(), &mut globals).unwrap();
+    add_sphere(&right, 0.5, white(), &mut globals).unwrap();
+
+    for (index, center) in [(0, left), (1, right)] {
+        let (x, y) = camera.project(center, WIDTH, HEIGHT).unwrap();
+        let hit = globals.pick(&camera, x as u32, y as u32, WIDTH, HEIGHT).unwrap();
+        assert_eq!(hit.primitive_index, index);
+
+        // On the side facing the camera, the normal pointing out there
+        let mut out = hit.point;
+        out.v3d_sub(center);
+        assert!((out.v3d_length() - 0.5).abs() < 1e-9);
+        out.v3d_norm();
+        assert!(out.approx_eq(hit.normal, 1e-9), "{:?} vs {:?}", out, hit.normal);
+        let mut to_camera = camera.position;
+        to_camera.v3d_sub(hit.point);
+        assert!((to_camera.v3d_length() - hit.distance).abs() < 1e-9);
+        assert!(to_camera.v3d_dot_mul(hit.normal) > 0.0);
+    }
+}
+
+#[test]
+fn picking_the_background_finds_nothing() {
+    let camera = Camera::default();
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, RenderSettings::default(), camera);
+    add_sphere(&Vector3D::ZERO, 0.5, white(), &mut globals).unwrap();
+
+    assert!(globals.pick(&camera, 0, 0, WIDTH, HEIGHT).is_none());
+    assert!(globals.pick(&camera, WIDTH - 1, HEIGHT / 2, WIDTH, HEIGHT).is_none());
+}
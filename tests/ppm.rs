@@ -0,0 +1,58 @@
+// PPM output test. `write_ppm` writes a float buffer as a binary PPM without
+// the image crate, clamped the same way as the PNG path.
+
+use lux::*;
+
+// Header fields and pixel bytes of a binary PPM
+fn parse_p6(data: &[u8]) -> (Vec<String>, &[u8]) {
+    let mut fields = Vec::new();
+    let mut at = 0;
+    while fields.len() < 4 {
+        let start = at;
+        while !data[at].is_ascii_whitespace() {
+            at += 1;
+        }
+        fields.push(String::from_utf8(data[start..at].to_vec()).unwrap());
+        at += 1;
+    }
+    (fields, &data[at..])
+}
+
+#[test]
+fn two_by_two_ppm_reads_back() {
+    let path = format!("{}/two_by_two.ppm", env!("CARGO_TARGET_TMPDIR"));
+    let pixels = [
+        Vector3D::new(1.0, 0.0, 0.0),
+        Vector3D::new(0.0, 0.5, 0.0),
+        // Out of range channels are clamped
+        Vector3D::new(-1.0, 2.0, 0.25),
+        Vector3D::ONE,
+    ];
+    write_ppm(&path, &pixels, 2, 2).unwrap();
+
+    let data = std::fs::read(&path).unwrap();
+    let (header, bytes) = parse_p6(&data);
+    assert_eq!(header, ["P6", "2", "2", "255"]);
+    assert_eq!(bytes, [255, 0, 0, 0, 127, 0, 0, 255, 63, 255, 255, 255]);
+}
+
+#[test]
+fn ppm_matches_the_resolved_image() {
+    let mut globals = GlobalSettings::new(8, 6, RenderSettings::default(), Camera::default());
+    demo::glass(&mut globals);
+    render_scene(&mut globals).unwrap();
+
+    let floats = format!("{}/floats.ppm", env!("CARGO_TARGET_TMPDIR"));
+    let resolved = format!("{}/resolved.ppm", env!("CARGO_TARGET_TMPDIR"));
+    write_ppm(&floats, &globals.framebuffer, 8, 6).unwrap();
+    save_image(&globals.img, &resolved).unwrap();
+
+    assert_eq!(std::fs::read(&floats).unwrap(), std::fs::read(&resolved).unwrap());
+}
+
+#[test]
+fn wrong_pixel_count_is_invalid() {
+    let path = format!("{}/short.ppm", env!("CARGO_TARGET_TMPDIR"));
+    let result = write_ppm(&path, &[Vector3D::ZERO; 3], 2, 2);
+    assert!(matches!(result, Err(LuxError::InvalidSettings(_))));
+}
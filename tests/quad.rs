@@ -0,0 +1,68 @@
+// Quad intersection tests. Rays through the parallelogram hit it at the
+// distance to the plane, rays beside it or running along it miss, and the
+// edges count as part of it.
+
+use lux::*;
+
+// Two units across at z = 2, facing the origin
+fn quad() -> PrimQuad {
+    PrimQuad {
+        origin: Vector3D::new(-1.0, -1.0, 2.0),
+        edge_u: Vector3D::new(2.0, 0.0, 0.0),
+        edge_v: Vector3D::new(0.0, 2.0, 0.0),
+        m: MaterialRef::Named("white".to_string()),
+    }
+}
+
+fn ray(origin: Vector3D, towards: Vector3D) -> Ray {
+    let mut direction = towards;
+    direction.v3d_sub(origin);
+    direction.v3d_norm();
+    Ray { origin, direction, time: 0.0 }
+}
+
+// Distance to the hit, `None` on a miss
+fn hit(r: Ray) -> Option<f64> {
+    let mut dist = 0.0;
+    (quad().intersect(r, 1e-4, &mut dist) != 0).then_some(dist)
+}
+
+#[test]
+fn rays_inside_the_bounds_hit_at_the_plane() {
+    for target in [Vector3D::new(0.0, 0.0, 2.0), Vector3D::new(0.3, -0.4, 2.0), Vector3D::new(-0.9, 0.95, 2.0)] {
+        let expected = target.v3d_length();
+        let t = hit(ray(Vector3D::ZERO, target)).unwrap_or_else(|| panic!("missed {:?}", target));
+        assert!((t - expected).abs() < 1e-12, "{} instead of {}", t, expected);
+    }
+
+    // From behind too, the quad has no front only side by itself
+    let t = hit(ray(Vector3D::new(0.0, 0.0, 5.0), Vector3D::new(0.5, 0.5, 2.0))).unwrap();
+    assert!((t - Vector3D::new(0.5, 0.5, -3.0).v3d_length()).abs() < 1e-12);
+}
+
+#[test]
+fn rays_outside_the_bounds_miss() {
+    for target in [Vector3D::new(1.5, 0.0, 2.0), Vector3D::new(0.0, -1.01, 2.0), Vector3D::new(3.0, 3.0, 2.0)] {
+        assert_eq!(hit(ray(Vector3D::ZERO, target)), None, "{:?}", target);
+    }
+
+    // Pointing away from it
+    assert_eq!(hit(ray(Vector3D::ZERO, Vector3D::new(0.0, 0.0, -1.0))), None);
+}
+
+#[test]
+fn grazing_rays_and_edges() {
+    // Along the plane of the quad, right over its middle, there's nothing
+    // to hit
+    assert_eq!(hit(ray(Vector3D::new(-3.0, 0.0, 2.0), Vector3D::new(3.0, 0.0, 2.0))), None);
+
+    // Nearly along it, the ray comes down on the quad far from its start
+    let r = ray(Vector3D::new(-3.0, 0.0, 2.001), Vector3D::new(3.0, 0.0, 1.999));
+    let t = hit(r).unwrap();
+    assert!((r.origin.x + r.direction.x * t).abs() < 1e-9, "{}", t);
+
+    // The edges belong to the quad, a hair past them doesn't
+    assert!(hit(ray(Vector3D::ZERO, Vector3D::new(1.0, 0.0, 2.0))).is_some());
+    assert!(hit(ray(Vector3D::ZERO, Vector3D::new(-1.0, -1.0, 2.0))).is_some());
+    assert_eq!(hit(ray(Vector3D::ZERO, Vector3D::new(1.0 + 1e-9, 0.0, 2.0))), None);
+}
@@ -0,0 +1,177 @@
+// Quaternion rotations and the camera orientation built on them.
+
+use lux::*;
+
+const EPS: f64 = 1e-12;
+
+#[test]
+fn quarter_turn_around_z_takes_x_to_y() {
+    let q = Quaternion::from_axis_angle(Vector3D::UNIT_Z, 90.0);
+    assert!(q.rotate(Vector3D::UNIT_X).approx_eq(Vector3D::UNIT_Y, EPS));
+    assert!(q.rotate(Vector3D::UNIT_Z).approx_eq(Vector3D::UNIT_Z, EPS));
+
+    // Same as the matrix
+    let m = Mat4::rotate_z(90.0);
+    let v = Vector3D::new(0.3, -1.2, 2.0);
+    assert!(q.rotate(v).approx_eq(m.transform_vector(v), EPS));
+    assert!(q.to_mat4().transform_vector(v).approx_eq(m.transform_vector(v), EPS));
+}
+
+#[test]
+fn slerp_halfway_is_half_the_angle() {
+    let quarter = Quaternion::from_axis_angle(Vector3D::UNIT_Z, 90.0);
+    let half = Quaternion::slerp(Quaternion::IDENTITY, quarter, 0.5);
+    assert!(half.approx_eq(Quaternion::from_axis_angle(Vector3D::UNIT_Z, 45.0), EPS));
+
+    assert!(Quaternion::slerp(Quaternion::IDENTITY, quarter, 0.0).approx_eq(Quaternion::IDENTITY, EPS));
+    assert!(Quaternion::slerp(Quaternion::IDENTITY, quarter, 1.0).approx_eq(quarter, EPS));
+
+    // Steady speed: every tenth of the way turns 9 degrees
+    let mut last = Vector3D::UNIT_X;
+    for i in 1..=10 {
+        let v = Quaternion::slerp(Quaternion::IDENTITY, quarter, i as f64 / 10.0).rotate(Vector3D::UNIT_X);
+        assert!((last.v3d_dot_mul(v) - 9.0_f64.to_radians().cos()).abs() < 1e-9);
+        last = v;
+    }
+}
+
+#[test]
+fn rotation_times_conjugate_is_identity() {
+    let q = Quaternion::from_axis_angle(Vector3D::new(1.0, -2.0, 0.5), 73.0);
+    assert!((q * q.conjugate()).approx_eq(Quaternion::IDENTITY, EPS));
+    assert!((q.conjugate() * q).approx_eq(Quaternion::IDENTITY, EPS));
+
+    // Composing applies the right one first
+    let a = Quaternion::from_axis_angle(Vector3D::UNIT_X, 30.0);
+    let b = Quaternion::from_axis_angle(Vector3D::UNIT_Y, 50.0);
+    let v = Vector3D::new(1.0, 2.0, 3.0);
+    assert!((a * b).rotate(v).approx_eq(a.rotate(b.rotate(v)), EPS));
+
+    let mut long = q;
+    long.w *= 3.0;
+    long.normalize();
+    assert!((long.length() - 1.0).abs() < EPS);
+}
+
+#[test]
+fn look_at_round_trips() {
+    let mut camera = Camera {
+        position: Vector3D::new(1.0, 2.0, -4.0),
+        ..Default::default()
+    };
+    let target = Vector3D::new(-0.5, 0.0, 3.0);
+    camera.look_at(target, Vector3D::UNIT_Y).unwrap();
+
+    let (forward, up) = camera.view();
+    let mut expected = target;
+    expected.v3d_sub(camera.position);
+    expected.v3d_norm();
+    assert!(forward.approx_eq(expected, EPS));
+    assert!(up.v3d_dot_mul(forward).abs() < EPS);
+    assert!(up.y > 0.0);
+
+    // Turning back to the view found gives the same orientation
+    let mut again = camera;
+    again.orientation = Quaternion::IDENTITY;
+    let mut ahead = camera.position;
+    ahead.v3d_add(forward);
+    again.look_at(ahead, up).unwrap();
+    assert!(again.orientation.approx_eq(camera.orientation, 1e-9));
+
+    // Looking straight down +z is the default orientation
+    let mut plain = Camera::default();
+    plain.look_at(Vector3D::new(0.0, 0.0, 10.0), Vector3D::UNIT_Y).unwrap();
+    assert!(plain.orientation.approx_eq(Quaternion::IDENTITY, EPS));
+
+    assert!(plain.look_at(plain.position, Vector3D::UNIT_Y).is_err());
+    assert!(plain.look_at(Vector3D::new(0.0, 0.0, 10.0), Vector3D::ZERO).is_err());
+}
+
+#[test]
+fn top_down_camera_has_a_proper_basis() {
+    // Straight down, and a hair off it
+    for target in [Vector3D::ZERO, Vector3D::new(1e-9, 0.0, -1e-9)] {
+        let mut camera = Camera {
+            position: Vector3D::new(0.0, 5.0, 0.0),
+            ..Default::default()
+        };
+        camera.look_at(target, Vector3D::UNIT_Y).unwrap();
+
+        let forward = camera.forward();
+        let (right, up) = camera.basis();
+        for v in [forward, right, up] {
+            assert!(v.x.is_finite() && v.y.is_finite() && v.z.is_finite());
+            assert!((v.v3d_length() - 1.0).abs() < EPS);
+        }
+        assert!(forward.approx_eq(Vector3D::new(0.0, -1.0, 0.0), 1e-9));
+        assert!(right.v3d_dot_mul(up).abs() < EPS && right.v3d_dot_mul(forward).abs() < EPS);
+        assert!(Vector3D::cross(right, up).approx_eq(forward, 1e-9));
+
+        // As if the default camera had tilted down: the top is +z
+        assert!(up.approx_eq(Vector3D::UNIT_Z, 1e-9));
+        assert!(right.approx_eq(Vector3D::UNIT_X, 1e-9));
+    }
+
+    // Looking up instead the top is -z, and with another up vector the
+    // same goes for that one
+    let mut camera = Camera::default();
+    camera.look_at(Vector3D::new(0.0, 10.0, -5.0), Vector3D::UNIT_Y).unwrap();
+    assert!(camera.basis().1.approx_eq(Vector3D::new(0.0, 0.0, -1.0), 1e-9));
+    camera.look_at(Vector3D::new(0.0, 0.0, 10.0), Vector3D::UNIT_Z).unwrap();
+    assert!(camera.forward().approx_eq(Vector3D::UNIT_Z, 1e-9));
+    assert!(camera.basis().1.approx_eq(Vector3D::new(-1.0, 0.0, 0.0), 1e-9));
+}
+
+#[test]
+fn quarter_roll_turns_the_image() {
+    const SIZE: u32 = 16;
+
+    // Spheres of different colors off to the sides, so any turn shows
+    let render = |roll: f64| {
+        let camera = Camera {
+            roll,
+            ..Default::default()
+        };
+        let mut globals = GlobalSettings::new(SIZE, SIZE, RenderSettings::default(), camera);
+        for (x, y, color) in [(1.0, 0.5, Vector3D::new(1.0, 0.0, 0.0)), (-0.8, 1.2, Vector3D::new(0.0, 1.0, 0.0)), (0.3, -1.0, Vector3D::new(0.0, 0.0, 1.0))] {
+            let m = Material {
+                color,
+                diffusive: 1.0,
+                ..Default::default()
+            };
+            add_sphere(&Vector3D::new(x, y, 2.0), 0.6, m, &mut globals).unwrap();
+        }
+        add_light(Vector3D::new(0.0, 0.0, -3.0), Vector3D::ONE, 1.0, &mut globals).unwrap();
+        render_scene(&mut globals).unwrap();
+        globals.framebuffer
+    };
+
+    let level = render(0.0);
+    let rolled = render(90.0);
+    assert!(level.iter().any(|c| c.x > 0.0) && level.iter().any(|c| c.y > 0.0) && level.iter().any(|c| c.z > 0.0));
+
+    // Tilting the camera a quarter turn counter-clockwise turns what it
+    // sees a quarter turn clockwise: the top row ends up on the right
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            let before = level[(y * SIZE + x) as usize];
+            let after = rolled[(x * SIZE + SIZE - 1 - y) as usize];
+            assert!(before.approx_eq(after, 1e-9), "({}, {}): {} vs {}", x, y, before, after);
+        }
+    }
+}
+
+#[test]
+fn keyframes_turn_with_slerp() {
+    let start = Camera::default();
+    let mut end = start;
+    end.position = Vector3D::new(2.0, 0.0, -5.0);
+    end.orientation = Quaternion::from_axis_angle(Vector3D::UNIT_Y, 90.0);
+
+    let mid = start.interpolate(&end, 0.5);
+    assert!(mid.position.approx_eq(Vector3D::new(1.0, 0.0, -5.0), EPS));
+
+    // Half way through a quarter turn to the right
+    let s = 45.0_f64.to_radians().sin();
+    assert!(mid.forward().approx_eq(Vector3D::new(s, 0.0, s), EPS));
+}
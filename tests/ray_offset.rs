@@ -0,0 +1,119 @@
+// Secondary ray origins. Rays spawned at a surface start just off it along
+// its geometric normal, on the side they leave to, so they never hit the
+// surface they start on again, whichever way they go.
+
+use lux::*;
+
+// Much less than the default ray epsilon, to show the offset is enough
+// however close to the surface a ray leaves
+const EPS: f64 = 1e-7;
+
+fn matte() -> Material {
+    Material {
+        diffusive: 1.0,
+        ..Default::default()
+    }
+}
+
+// Uniformly spread direction
+fn random_direction(rng: &mut Rng) -> Vector3D {
+    let z = 2.0 * rng.next_f64() - 1.0;
+    let phi = 2.0 * std::f64::consts::PI * rng.next_f64();
+    let r = (1.0 - z * z).sqrt();
+    Vector3D::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+#[test]
+fn origin_is_pushed_to_the_side_the_ray_leaves_to() {
+    let point = Vector3D::new(1.0, 2.0, 3.0);
+    let mut rng = Rng::new(7);
+
+    for _ in 0..200 {
+        let normal = random_direction(&mut rng);
+        let direction = random_direction(&mut rng);
+        let mut flipped = normal;
+        flipped.v3d_mul_scalar(-1.0);
+
+        let origin = Ray::offset_ray_origin(point, normal, direction, EPS);
+        // Which way the normal faces doesn't matter
+        assert_eq!(origin, Ray::offset_ray_origin(point, flipped, direction, EPS));
+
+        let mut offset = origin;
+        offset.v3d_sub(point);
+        assert!((offset.v3d_length() - EPS).abs() < 1e-12);
+        assert_eq!(offset.v3d_dot_mul(normal) > 0.0, direction.v3d_dot_mul(normal) > 0.0);
+    }
+}
+
+#[test]
+fn secondary_rays_do_not_hit_their_own_surface() {
+    // Near hits are only kept away by the offset origins here
+    let settings = RenderSettings {
+        ray_epsilon: 0.0,
+        ..Default::default()
+    };
+    let mut globals = GlobalSettings::new(16, 16, settings, Camera::default());
+
+    // Far from the origin, where the hit points are least precise
+    add_sphere(&Vector3D::new(-1.5e4, 1e4, 5e4), 4e3, matte(), &mut globals).unwrap();
+    add_torus(Vector3D::new(0.0, 0.0, 6.0), Vector3D::new(0.3, 1.0, -0.2), 1.5, 0.4, matte(), &mut globals).unwrap();
+    add_quad(
+        Vector3D::new(-3.0, -1.0, 2.0),
+        Vector3D::new(6.0, 0.1, 0.0),
+        Vector3D::new(0.0, -0.2, 30.0),
+        matte(),
+        &mut globals,
+    )
+    .unwrap();
+
+    // Points the camera rays aim around, and how far around, per primitive
+    let aims = [
+        (Vector3D::new(-1.5e4, 1e4, 5e4), 4e3),
+        (Vector3D::new(0.0, 0.0, 6.0), 2.0),
+        (Vector3D::new(0.0, -1.5, 12.0), 3.0),
+    ];
+    let mut rng = Rng::new(3);
+    let mut tested = [0; 3];
+
+    for k in 0..3000 {
+        let (center, spread) = aims[k % aims.len()];
+        let mut direction = Vector3D::new(rng.next_f64() - 0.5, rng.next_f64() - 0.5, 0.0);
+        direction.v3d_mul_scalar(2.0 * spread);
+        direction.v3d_add(center);
+        direction.v3d_norm();
+        let primary = Ray {
+            origin: Vector3D::ZERO,
+            direction,
+            time: 0.0,
+        };
+        let Some((i, dist)) = closest_hit(&primary, RayKind::Camera, &globals) else {
+            continue;
+        };
+
+        let mut pi = primary.direction;
+        pi.v3d_mul_scalar(dist);
+        pi.v3d_add(primary.origin);
+        let n = globals.primitives()[i].normal(pi, 0.0);
+
+        for _ in 0..20 {
+            let direction = random_direction(&mut rng);
+            // Rays leaving right along the surface can hit it again a
+            // little further on, like a torus does
+            if direction.v3d_dot_mul(n).abs() < 1e-4 {
+                continue;
+            }
+
+            let ray = Ray {
+                origin: Ray::offset_ray_origin(pi, n, direction, EPS),
+                direction,
+                time: 0.0,
+            };
+            if let Some((j, d)) = closest_hit(&ray, RayKind::Shadow, &globals) {
+                assert!(j != i || d > 1e-5, "primitive {} hit itself {} away, leaving in {}", i, d, direction);
+            }
+            tested[i] += 1;
+        }
+    }
+
+    assert!(tested.iter().all(|&t| t > 100), "{:?}", tested);
+}
@@ -0,0 +1,43 @@
+// Render region test. Rendering only a corner of the image fills in just
+// those pixels, the same as a full render does, and leaves the rest empty
+// and transparent.
+
+use lux::*;
+
+const SIZE: u32 = 4;
+
+fn render(region: Option<Region>) -> GlobalSettings {
+    let settings = RenderSettings {
+        region,
+        ..Default::default()
+    };
+    let mut globals = GlobalSettings::new(SIZE, SIZE, settings, Camera::default());
+    demo::build_scene(&mut globals);
+    render_scene(&mut globals).unwrap();
+    globals
+}
+
+#[test]
+fn a_region_only_renders_its_own_pixels() {
+    let full = render(None);
+    let corner = render(Some(Region {
+        x: 0,
+        y: 0,
+        width: 2,
+        height: 2,
+    }));
+
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            let i = (y * SIZE + x) as usize;
+            if x < 2 && y < 2 {
+                assert_ne!(corner.framebuffer[i], Vector3D::ZERO, "({}, {})", x, y);
+                assert_eq!(corner.framebuffer[i], full.framebuffer[i], "({}, {})", x, y);
+                assert_eq!(corner.img.get_pixel(x, y), full.img.get_pixel(x, y));
+            } else {
+                assert_eq!(corner.framebuffer[i], Vector3D::ZERO, "({}, {})", x, y);
+                assert_eq!(corner.img.get_pixel(x, y).0, [0, 0, 0, 0], "({}, {})", x, y);
+            }
+        }
+    }
+}
@@ -0,0 +1,83 @@
+// Rendering to memory. `render_to_buffer` and `render_to_hdr_buffer` give
+// the caller the pixels at the size the config asks for, without any files.
+
+use lux::*;
+
+const WIDTH: u32 = 32;
+const HEIGHT: u32 = 24;
+
+// Red sphere in the middle of the view, on a scene set up at another size
+fn scene() -> GlobalSettings {
+    let mut globals = GlobalSettings::new(4, 4, RenderSettings::default(), Camera::default());
+    let red = Material {
+        color: Vector3D::new(1.0, 0.0, 0.0),
+        diffusive: 0.8,
+        ..Default::default()
+    };
+    add_sphere(&Vector3D::new(0.0, 0.0, 3.0), 1.0, red, &mut globals).unwrap();
+    add_light(Vector3D::ZERO, Vector3D::ONE, 1.0, &mut globals).unwrap();
+    globals
+}
+
+fn config() -> RenderConfig {
+    RenderConfig {
+        width: WIDTH,
+        height: HEIGHT,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn buffer_has_the_rendered_pixels() {
+    let mut globals = scene();
+    let pixels = render_to_buffer(&mut globals, &Camera::default(), &config()).unwrap();
+    assert_eq!(pixels.len(), (WIDTH * HEIGHT) as usize);
+
+    // Lit red in the middle, the background in the corner
+    let middle = pixels[(HEIGHT / 2 * WIDTH + WIDTH / 2) as usize];
+    assert!(middle[0] > 150 && middle[1] < 20 && middle[2] < 20, "{:?}", middle);
+    let expected = BACKGROUND.map(|c| (c * 255.0).floor());
+    assert_eq!(pixels[0], image::Rgba([expected.x as u8, expected.y as u8, expected.z as u8, 255]));
+
+    // Same as the image the render left in `globals`
+    assert_eq!(globals.img.dimensions(), (WIDTH, HEIGHT));
+    assert!(globals.img.pixels().eq(pixels.iter()));
+}
+
+#[test]
+fn hdr_buffer_matches_tracing_the_pixel() {
+    let mut globals = scene();
+    let camera = Camera::default();
+    let pixels = render_to_hdr_buffer(&mut globals, &camera, &config()).unwrap();
+
+    for (x, y) in [(WIDTH / 2, HEIGHT / 2), (3, 20), (0, 0)] {
+        let expected = trace_pixel(&globals, &camera, x, y, WIDTH, HEIGHT);
+        assert_eq!(pixels[(y * WIDTH + x) as usize], expected, "pixel ({}, {})", x, y);
+    }
+}
+
+#[test]
+fn config_settings_and_camera_are_used() {
+    let mut globals = scene();
+    let config = RenderConfig {
+        render: RenderSettings {
+            shading: Shading::Normals,
+            ..Default::default()
+        },
+        ..config()
+    };
+    // Moved aside, so the sphere is off to the left
+    let camera = Camera {
+        position: Vector3D::new(1.0, 0.0, 0.0),
+        ..Default::default()
+    };
+
+    let pixels = render_to_hdr_buffer(&mut globals, &camera, &config).unwrap();
+    assert!(globals.settings.shading == Shading::Normals);
+
+    // Normals come out as colors, so there's green where the red sphere is
+    let (x, y) = (WIDTH / 4, HEIGHT / 2);
+    let pixel = pixels[(y * WIDTH + x) as usize];
+    assert!(pixel.y > 0.2, "{}", pixel);
+    assert_eq!(pixel, trace_pixel(&globals, &camera, x, y, WIDTH, HEIGHT));
+}
@@ -0,0 +1,63 @@
+// Roughness test. A mirror shows a small lamp behind the camera as a sharp
+// highlight. The rougher the metal, the wider and dimmer the highlight
+// gets: the same light spread over more of the picture.
+
+use lux::*;
+
+const WIDTH: u32 = 48;
+const HEIGHT: u32 = 48;
+
+// Middle row of a metal wall with `roughness` reflecting the lamp
+fn highlight(roughness: f64) -> Vec<f64> {
+    let settings = RenderSettings {
+        mode: RenderMode::PathTrace,
+        samples: 256,
+        max_depth: 2,
+        seed: 3,
+        ..Default::default()
+    };
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, settings, Camera::default());
+    globals.ambient = Vector3D::ZERO;
+    globals.environment = Vector3D::ZERO;
+
+    let metal = Material {
+        reflective: 1.0,
+        roughness,
+        ..Default::default()
+    };
+    let lamp = Material {
+        color: Vector3D::ZERO,
+        emission: Vector3D::new(10.0, 10.0, 10.0),
+        ..Default::default()
+    };
+    add_quad(Vector3D::new(-20.0, -20.0, 5.0), Vector3D::new(0.0, 40.0, 0.0), Vector3D::new(40.0, 0.0, 0.0), metal, &mut globals).unwrap();
+    add_sphere(&Vector3D::new(0.0, 0.0, -15.0), 2.0, lamp, &mut globals).unwrap();
+    render_scene(&mut globals).unwrap();
+
+    let row = (HEIGHT / 2 * WIDTH) as usize;
+    globals.framebuffer[row..row + WIDTH as usize].iter().map(|c| c.luminance()).collect()
+}
+
+fn peak(row: &[f64]) -> f64 {
+    row.iter().copied().fold(0.0, f64::max)
+}
+
+// How far from its middle the light of `row` lies, on average, in pixels
+fn spread(row: &[f64]) -> f64 {
+    let total: f64 = row.iter().sum();
+    let mean = row.iter().enumerate().map(|(x, c)| x as f64 * c).sum::<f64>() / total;
+    (row.iter().enumerate().map(|(x, c)| (x as f64 - mean).powi(2) * c).sum::<f64>() / total).sqrt()
+}
+
+#[test]
+fn rougher_highlights_are_wider_and_dimmer() {
+    let rows: Vec<Vec<f64>> = [0.0, 0.1, 0.25].into_iter().map(highlight).collect();
+
+    // The mirror shows the lamp at its full brightness
+    assert!((peak(&rows[0]) - 10.0).abs() < 1e-6, "{}", peak(&rows[0]));
+    for pair in rows.windows(2) {
+        let (smoother, rougher) = (&pair[0], &pair[1]);
+        assert!(peak(rougher) < 0.8 * peak(smoother), "{} vs {}", peak(rougher), peak(smoother));
+        assert!(spread(rougher) > 1.25 * spread(smoother), "{} vs {}", spread(rougher), spread(smoother));
+    }
+}
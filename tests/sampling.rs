@@ -0,0 +1,73 @@
+// Direction sampling helpers: samples land on the right side of the normal
+// or inside the cone, and the bases they're built on are orthonormal.
+
+use lux::math::{cosine_weighted_hemisphere, orthonormal_basis, sample_cone, uniform_hemisphere};
+use lux::*;
+
+const SAMPLES: usize = 2000;
+
+// A spread of unit vectors, including the axes and the -z pole the basis
+// construction has to treat specially
+fn normals() -> Vec<Vector3D> {
+    let mut normals = vec![Vector3D::UNIT_X, Vector3D::UNIT_Y, Vector3D::UNIT_Z, Vector3D::new(0.0, 0.0, -1.0)];
+    let mut rng = Rng::new(7);
+    for _ in 0..20 {
+        normals.push(uniform_hemisphere(Vector3D::UNIT_Z, &mut rng));
+        normals.push(uniform_hemisphere(Vector3D::new(0.0, 0.0, -1.0), &mut rng));
+    }
+    normals
+}
+
+#[test]
+fn basis_is_orthonormal() {
+    for n in normals() {
+        let (t, b) = orthonormal_basis(n);
+        for v in [t, b] {
+            assert!((v.v3d_length() - 1.0).abs() < 1e-9);
+            assert!(v.v3d_dot_mul(n).abs() < 1e-9);
+        }
+        assert!(t.v3d_dot_mul(b).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn hemisphere_samples_face_the_normal() {
+    let mut rng = Rng::new(1);
+    for n in normals() {
+        let (mut cos_sum, mut uniform_sum) = (0.0, 0.0);
+        for _ in 0..SAMPLES {
+            let d = cosine_weighted_hemisphere(n, &mut rng);
+            assert!((d.v3d_length() - 1.0).abs() < 1e-9);
+            assert!(d.v3d_dot_mul(n) >= 0.0);
+            cos_sum += d.v3d_dot_mul(n);
+
+            let d = uniform_hemisphere(n, &mut rng);
+            assert!((d.v3d_length() - 1.0).abs() < 1e-9);
+            assert!(d.v3d_dot_mul(n) >= 0.0);
+            uniform_sum += d.v3d_dot_mul(n);
+        }
+
+        // Average cosine: 2/3 for the cosine weighted pdf, 1/2 for uniform
+        assert!((cos_sum / SAMPLES as f64 - 2.0 / 3.0).abs() < 0.03);
+        assert!((uniform_sum / SAMPLES as f64 - 0.5).abs() < 0.03);
+    }
+}
+
+#[test]
+fn cone_samples_stay_inside_the_angle() {
+    let mut rng = Rng::new(2);
+    for n in normals() {
+        for angle in [0.05_f64, 0.5, 1.2] {
+            let cos_max = angle.cos();
+            let mut widest: f64 = 1.0;
+            for _ in 0..SAMPLES / 4 {
+                let d = sample_cone(n, angle, &mut rng);
+                let cos = d.v3d_dot_mul(n);
+                assert!(cos >= cos_max - 1e-9, "{} outside a cone of {}", cos.acos(), angle);
+                widest = widest.min(cos);
+            }
+            // And they do spread out to the edge
+            assert!(widest < 1.0 - 0.9 * (1.0 - cos_max));
+        }
+    }
+}
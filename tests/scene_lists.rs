@@ -0,0 +1,37 @@
+// Scene contents test. `primitives()` and `lights()` hand back exactly what
+// was added, in order.
+
+use lux::*;
+
+#[test]
+fn lists_hold_what_was_added() {
+    let mut globals = GlobalSettings::new(4, 4, RenderSettings::default(), Camera::default());
+    globals.add_material(
+        "white",
+        Material {
+            diffusive: 1.0,
+            ..Default::default()
+        },
+    );
+    assert!(globals.primitives().is_empty());
+    assert!(globals.lights().is_empty());
+
+    let centers = [Vector3D::new(0.0, 0.0, 3.0), Vector3D::new(1.0, -1.0, 4.0), Vector3D::new(-2.0, 0.5, 5.0)];
+    for (i, c) in centers.iter().enumerate() {
+        add_sphere(c, 0.5 + i as f64 * 0.25, "white", &mut globals).unwrap();
+    }
+    add_light(Vector3D::new(0.0, 2.0, 0.0), Vector3D::ONE, 1.0, &mut globals).unwrap();
+    add_light(Vector3D::new(3.0, 0.0, 0.0), Vector3D::new(1.0, 0.5, 0.0), 2.0, &mut globals).unwrap();
+
+    assert_eq!(globals.primitives().len(), centers.len());
+    for (i, (p, c)) in globals.primitives().iter().zip(centers).enumerate() {
+        let (min, max) = p.bounds();
+        let radius = 0.5 + i as f64 * 0.25;
+        assert!(min.approx_eq(Vector3D::new(c.x - radius, c.y - radius, c.z - radius), 1e-12));
+        assert!(max.approx_eq(Vector3D::new(c.x + radius, c.y + radius, c.z + radius), 1e-12));
+    }
+
+    let positions: Vec<Vector3D> = globals.lights().iter().map(|l| l.position).collect();
+    assert_eq!(positions, [Vector3D::new(0.0, 2.0, 0.0), Vector3D::new(3.0, 0.0, 0.0)]);
+    assert_eq!(globals.lights()[1].intensity, 2.0);
+}
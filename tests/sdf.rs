@@ -0,0 +1,102 @@
+// Ray marched SDF shapes: the marched sphere lands where the exact one is
+// hit, and the built-in shapes load from a scene file and can be hit.
+
+use lux::sdf::SDF_EPSILON;
+use lux::*;
+
+fn ray(origin: Vector3D, direction: Vector3D) -> Ray {
+    let mut direction = direction;
+    direction.v3d_norm();
+    Ray { origin, direction, time: 0.0 }
+}
+
+#[test]
+fn sphere_sdf_matches_the_exact_sphere() {
+    let center = Vector3D::new(0.2, -0.1, 3.0);
+    let exact = PrimSphere {
+        position: center,
+        velocity: Vector3D::ZERO,
+        radius: 0.8,
+        m: MaterialRef::Named("white".to_string()),
+    };
+    let marched = PrimSdf::sphere(center, 0.8, "white");
+
+    let mut hits = 0;
+    for i in 0..15 {
+        for j in 0..15 {
+            let target = Vector3D::new(i as f64 * 0.15 - 1.0, j as f64 * 0.15 - 1.1, 3.0);
+            let r = ray(Vector3D::ZERO, target);
+
+            let (mut want, mut got) = (0.0, 0.0);
+            let expected = exact.intersect(r, 1e-4, &mut want);
+            assert_eq!(marched.intersect(r, 1e-4, &mut got), expected);
+            if expected == 0 {
+                continue;
+            }
+            hits += 1;
+
+            // The march stops within epsilon of the surface, which is
+            // epsilon / cos further along the ray
+            let n = exact.normal(Vector3D::new(r.direction.x * want, r.direction.y * want, r.direction.z * want), 0.0);
+            let cos = n.v3d_dot_mul(r.direction).abs();
+            assert!((got - want).abs() <= SDF_EPSILON / cos, "{} vs {}", got, want);
+        }
+    }
+    assert!(hits > 50);
+
+    // From inside it finds the way out
+    let r = ray(center, Vector3D::new(0.3, 1.0, 0.0));
+    let mut got = 0.0;
+    assert_eq!(marched.intersect(r, 1e-4, &mut got), -1);
+    assert!((got - 0.8).abs() <= SDF_EPSILON);
+}
+
+#[test]
+fn builtin_shapes_load_from_a_scene() {
+    let text = r#"
+[material.white]
+diffusive = 1.0
+
+[[sdf]]
+shape = "blend"
+center_a = [-0.4, 0.0, 3.0]
+radius_a = 0.5
+center_b = [0.4, 0.0, 3.0]
+radius_b = 0.4
+smoothness = 0.3
+material = "white"
+
+[[sdf]]
+shape = "rounded_box"
+center = [0.0, 2.0, 3.0]
+size = [1.0, 0.5, 1.0]
+radius = 0.1
+material = "white"
+"#;
+    let mut globals = GlobalSettings::new(4, 4, RenderSettings::default(), Camera::default());
+    scene::from_toml(text, &mut globals).unwrap();
+    let prims = globals.primitives();
+    assert_eq!(prims.iter().map(|p| p.kind()).collect::<Vec<_>>(), ["sdf", "sdf"]);
+
+    // The blend fills in the waist between the spheres. At x = 0 the bigger
+    // sphere alone reaches up to y = 0.3.
+    let mut dist = 0.0;
+    let down = ray(Vector3D::new(0.0, 1.0, 3.0), Vector3D::new(0.0, -1.0, 0.0));
+    assert_eq!(prims[0].intersect(down, 1e-4, &mut dist), 1);
+    assert!(dist < 0.7 - 0.02, "{}", dist);
+
+    // Flat top of the box, facing up
+    let mut dist = 0.0;
+    let r = ray(Vector3D::new(0.1, 3.0, 3.0), Vector3D::new(0.0, -1.0, 0.0));
+    assert_eq!(prims[1].intersect(r, 1e-4, &mut dist), 1);
+    assert!((dist - 0.75).abs() <= SDF_EPSILON, "{}", dist);
+    let n = prims[1].normal(Vector3D::new(0.1, 3.0 - dist, 3.0), 0.0);
+    assert!(n.approx_eq(Vector3D::UNIT_Y, 1e-3), "{}", n);
+}
+
+#[test]
+fn unknown_shape_is_a_parse_error() {
+    let text = "[material.white]\n\n[[sdf]]\nshape = \"cone\"\nmaterial = \"white\"\n";
+    let mut globals = GlobalSettings::new(4, 4, RenderSettings::default(), Camera::default());
+    assert!(matches!(scene::from_toml(text, &mut globals), Err(LuxError::Parse(_))));
+}
@@ -0,0 +1,64 @@
+// Shadow test on the built-in floor scene: the floor right under each ball
+// is in its shadow, so it has to come out darker than floor of the same
+// color that nothing stands over.
+
+use lux::*;
+
+const WIDTH: u32 = 160;
+const HEIGHT: u32 = 120;
+
+#[test]
+fn floor_under_the_balls_is_shadowed() {
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, RenderSettings::default(), Camera::default());
+    demo::floor(&mut globals);
+    assert!(globals.settings.shadows);
+    render_scene(&mut globals).unwrap();
+
+    let under: Vec<(f64, f64)> = demo::FLOOR_BALLS.iter().map(|&((x, _, z), _, _)| (x, z)).collect();
+
+    // Luminance sums and counts of shadowed and lit floor pixels, per tile
+    // material
+    let mut shadowed: Vec<(String, f64, u32)> = Vec::new();
+    let mut lit: Vec<(String, f64, u32)> = Vec::new();
+
+    let camera = globals.camera;
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            let hit = match globals.pick(&camera, x, y, WIDTH, HEIGHT) {
+                Some(hit) => hit,
+                None => continue,
+            };
+            let p = &globals.primitives()[hit.primitive_index];
+            let name = match (p.kind(), p.material_ref()) {
+                ("quad", MaterialRef::Named(name)) => name.clone(),
+                _ => continue,
+            };
+
+            let closest = under
+                .iter()
+                .map(|(bx, bz)| ((hit.point.x - bx).powi(2) + (hit.point.z - bz).powi(2)).sqrt())
+                .fold(f64::INFINITY, f64::min);
+            let list = match closest {
+                d if d < 0.2 => &mut shadowed,
+                d if d > 1.5 => &mut lit,
+                _ => continue,
+            };
+
+            let lum = globals.framebuffer[(y * WIDTH + x) as usize].luminance();
+            match list.iter_mut().find(|(n, _, _)| *n == name) {
+                Some(entry) => {
+                    entry.1 += lum;
+                    entry.2 += 1;
+                }
+                None => list.push((name, lum, 1)),
+            }
+        }
+    }
+
+    assert!(!shadowed.is_empty(), "no floor seen under the balls");
+    for (name, sum, count) in &shadowed {
+        let (_, lit_sum, lit_count) = lit.iter().find(|(n, _, _)| n == name).unwrap();
+        let (dark, bright) = (sum / *count as f64, lit_sum / *lit_count as f64);
+        assert!(dark < 0.5 * bright, "{}: shadowed {} vs lit {}", name, dark, bright);
+    }
+}
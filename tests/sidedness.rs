@@ -0,0 +1,124 @@
+// Per-material sidedness. Rays hit both sides of a two-sided surface but
+// only the front of a front only one, for camera rays, shadows and
+// reflections alike. There are no triangles, so quads stand in for them.
+
+use lux::*;
+
+fn matte(sidedness: Sidedness) -> Material {
+    Material {
+        diffusive: 0.8,
+        sidedness,
+        ..Default::default()
+    }
+}
+
+// Quad at z = 3 facing the camera at the origin (-z)
+fn facing_camera(sidedness: Sidedness) -> GlobalSettings {
+    let mut globals = GlobalSettings::new(16, 12, RenderSettings::default(), Camera::default());
+    add_quad(Vector3D::new(-1.0, -1.0, 3.0), Vector3D::new(0.0, 2.0, 0.0), Vector3D::new(2.0, 0.0, 0.0), matte(sidedness), &mut globals).unwrap();
+    assert!(globals.primitives()[0].normal(Vector3D::new(0.0, 0.0, 3.0), 0.0).z < 0.0);
+    globals
+}
+
+// Whether a ray from (0, 0, z) along `dz` hits anything
+fn hits(globals: &GlobalSettings, z: f64, dz: f64) -> bool {
+    let ray = Ray {
+        origin: Vector3D::new(0.0, 0.0, z),
+        direction: Vector3D::new(0.0, 0.0, dz),
+        time: 0.0,
+    };
+    closest_hit(&ray, RayKind::Camera, globals).is_some()
+}
+
+#[test]
+fn two_sided_is_hit_from_both_sides() {
+    let globals = facing_camera(Sidedness::TwoSided);
+    assert!(hits(&globals, 0.0, 1.0));
+    assert!(hits(&globals, 6.0, -1.0));
+}
+
+#[test]
+fn front_only_is_only_hit_from_the_front() {
+    let globals = facing_camera(Sidedness::FrontOnly);
+    assert!(hits(&globals, 0.0, 1.0));
+    assert!(!hits(&globals, 6.0, -1.0));
+}
+
+#[test]
+fn front_only_casts_no_shadow_from_behind() {
+    let shadow_on_floor = |sidedness| {
+        let settings = RenderSettings {
+            shadows: true,
+            ..Default::default()
+        };
+        let mut globals = GlobalSettings::new(16, 12, settings, Camera::default());
+        add_quad(Vector3D::new(-4.0, -1.0, -4.0), Vector3D::new(0.0, 0.0, 8.0), Vector3D::new(8.0, 0.0, 0.0), matte(Sidedness::TwoSided), &mut globals).unwrap();
+        // Facing up, so shadow rays from the floor below come at its back
+        add_quad(Vector3D::new(-1.0, 0.0, -1.0), Vector3D::new(0.0, 0.0, 2.0), Vector3D::new(2.0, 0.0, 0.0), matte(sidedness), &mut globals).unwrap();
+        assert!(globals.primitives()[1].normal(Vector3D::ZERO, 0.0).y > 0.0);
+        add_light(Vector3D::new(0.0, 5.0, 0.0), Vector3D::ONE, 1.0, &mut globals).unwrap();
+
+        let ray = Ray {
+            origin: Vector3D::new(0.0, -0.5, 0.0),
+            direction: Vector3D::new(0.0, -1.0, 0.0),
+            time: 0.0,
+        };
+        trace(&ray, 0, &globals).luminance()
+    };
+
+    assert!(shadow_on_floor(Sidedness::FrontOnly) > shadow_on_floor(Sidedness::TwoSided) + 0.5);
+}
+
+#[test]
+fn reflections_see_through_the_back() {
+    let mut globals = facing_camera(Sidedness::FrontOnly);
+    let mut mirror = matte(Sidedness::TwoSided);
+    mirror.diffusive = 0.0;
+    mirror.reflective = 1.0;
+    // Mirror behind the quad, facing back at its back
+    add_quad(Vector3D::new(-1.0, -1.0, 5.0), Vector3D::new(2.0, 0.0, 0.0), Vector3D::new(0.0, 2.0, 0.0), mirror, &mut globals).unwrap();
+    add_light(Vector3D::new(0.0, 0.0, 4.0), Vector3D::ONE, 1.0, &mut globals).unwrap();
+
+    // Reflected back along -z, the ray passes the back of the quad and
+    // leaves the scene
+    let ray = Ray {
+        origin: Vector3D::new(0.0, 0.0, 4.0),
+        direction: Vector3D::UNIT_Z,
+        time: 0.0,
+    };
+    assert_eq!(trace(&ray, 0, &globals), BACKGROUND);
+}
+
+#[test]
+fn camera_outside_the_cornell_box_looks_in() {
+    let mut globals = GlobalSettings::new(16, 12, RenderSettings::default(), Camera::default());
+    demo::cornell(&mut globals);
+
+    // Just behind the back wall, looking through it towards the opening,
+    // past the spheres
+    let ray = Ray {
+        origin: Vector3D::new(0.0, 0.3, 0.6),
+        direction: Vector3D::new(0.0, 0.0, -1.0),
+        time: 0.0,
+    };
+    assert_eq!(closest_hit(&ray, RayKind::Camera, &globals), None);
+
+    // From inside the box the same wall is there
+    let ray = Ray {
+        origin: Vector3D::new(0.0, 0.3, 0.0),
+        direction: Vector3D::UNIT_Z,
+        time: 0.0,
+    };
+    assert_eq!(closest_hit(&ray, RayKind::Camera, &globals).map(|(i, _)| i), Some(2));
+}
+
+#[test]
+fn sidedness_from_the_scene_file() {
+    let mut globals = GlobalSettings::new(4, 4, RenderSettings::default(), Camera::default());
+    scene::from_toml("[material.wall]\nsidedness = \"front_only\"\n[material.card]\n", &mut globals).unwrap();
+    assert_eq!(globals.materials["wall"].sidedness, Sidedness::FrontOnly);
+    assert_eq!(globals.materials["card"].sidedness, Sidedness::TwoSided);
+
+    let result = scene::from_toml("[material.wall]\nsidedness = \"both\"\n", &mut globals);
+    assert!(matches!(result, Err(LuxError::Parse(_))), "{:?}", result.err());
+}
@@ -0,0 +1,51 @@
+// Spot light test. A spot pointing down at a floor lights the points inside
+// its cone and leaves the ones outside it dark.
+
+use lux::*;
+
+const WIDTH: u32 = 48;
+const HEIGHT: u32 = 36;
+
+#[test]
+fn only_points_inside_the_cone_get_light() {
+    let mut camera = Camera {
+        position: Vector3D::new(0.0, 6.0, -6.0),
+        ..Default::default()
+    };
+    camera.look_at(Vector3D::ZERO, Vector3D::UNIT_Y).unwrap();
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, RenderSettings::default(), camera);
+    globals.ambient = Vector3D::ZERO;
+    globals.environment = Vector3D::ZERO;
+
+    let floor = Material {
+        diffusive: 1.0,
+        ..Default::default()
+    };
+    add_quad(Vector3D::new(-6.0, 0.0, -6.0), Vector3D::new(0.0, 0.0, 12.0), Vector3D::new(12.0, 0.0, 0.0), floor, &mut globals).unwrap();
+    let spot = Vector3D::new(0.0, 4.0, 0.0);
+    add_spot_light(spot, Vector3D::new(0.0, -1.0, 0.0), 15.0, 25.0, Vector3D::ONE, 1.0, &mut globals).unwrap();
+    render_scene(&mut globals).unwrap();
+
+    let (mut inside, mut outside) = (0, 0);
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            let Some(hit) = globals.pick(&camera, x, y, WIDTH, HEIGHT) else {
+                continue;
+            };
+            let mut to_point = hit.point;
+            to_point.v3d_sub(spot);
+            to_point.v3d_norm();
+            let angle = (-to_point.y).acos().to_degrees();
+
+            let color = globals.framebuffer[(y * WIDTH + x) as usize];
+            if angle < 14.0 {
+                assert!(color.luminance() > 0.1, "({}, {}) at {:.1}° is dark", x, y, angle);
+                inside += 1;
+            } else if angle > 26.0 {
+                assert_eq!(color, Vector3D::ZERO, "({}, {}) at {:.1}° is lit", x, y, angle);
+                outside += 1;
+            }
+        }
+    }
+    assert!(inside > 20 && outside > 20, "{} inside, {} outside", inside, outside);
+}
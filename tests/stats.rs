@@ -0,0 +1,60 @@
+// Render statistics test. Every sample of every pixel sends exactly one
+// camera ray, and the passes (one per thread) between them cover every
+// pixel once. `--stats-json` takes the file to save them to.
+
+use std::process::Command;
+
+use lux::*;
+
+const WIDTH: u32 = 12;
+const HEIGHT: u32 = 9;
+const SAMPLES: u32 = 3;
+
+#[test]
+fn primary_rays_are_pixels_times_samples() {
+    let settings = RenderSettings {
+        samples: SAMPLES,
+        stats: true,
+        threads: 3,
+        ..Default::default()
+    };
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, settings, Camera::default());
+    demo::build_scene(&mut globals);
+    render_scene(&mut globals).unwrap();
+
+    assert_eq!(globals.pass_stats.len(), 3);
+    let total = stats::total(&globals.pass_stats);
+    assert_eq!(total.counts.primary, (WIDTH * HEIGHT * SAMPLES) as u64);
+    // Every ray starts at the root of the BVH
+    assert!(total.counts.nodes >= total.counts.rays());
+}
+
+#[test]
+fn nothing_is_counted_without_stats() {
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, RenderSettings::default(), Camera::default());
+    demo::build_scene(&mut globals);
+    render_scene(&mut globals).unwrap();
+
+    assert!(globals.pass_stats.is_empty());
+    assert_eq!(globals.counters.take(), stats::RayCounts::default());
+}
+
+#[test]
+fn stats_json_saves_to_the_file_it_is_given() {
+    let dir = env!("CARGO_TARGET_TMPDIR");
+    let path = format!("{}/stats.json", dir);
+    let lux = || {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_lux"));
+        command.args(["--width", "8", "--height", "6", "--dump", "stats.ppm"]).current_dir(dir);
+        command
+    };
+
+    let _ = std::fs::remove_file(&path);
+    assert!(lux().args(["--stats-json", &path]).output().unwrap().status.success());
+    assert!(std::fs::read_to_string(&path).unwrap().contains("\"primary\""));
+
+    // Without a file it says so, rather than asking for a number
+    let output = lux().arg("--stats-json").output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--stats-json expects a file name"));
+}
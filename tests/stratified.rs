@@ -0,0 +1,60 @@
+// Stratified sampler tests. The samples of a pixel are spread evenly over
+// all of it, whether their number is a square or not: nothing is left out
+// or sampled twice as often, so the image can't shift towards one side.
+
+use lux::*;
+
+// Offsets of all `count` samples of each of `pixels` pixels
+fn offsets(count: u32, pixels: u64) -> Vec<Vec<(f64, f64)>> {
+    (0..pixels)
+        .map(|seed| {
+            let mut rng = Rng::new(seed);
+            (0..count).map(|i| Sampler::Stratified.offset(i, count, &mut rng)).collect()
+        })
+        .collect()
+}
+
+// How many of `samples` land in each quadrant of the pixel, top left,
+// top right, bottom left, bottom right
+fn quadrants(samples: &[(f64, f64)]) -> [usize; 4] {
+    let mut counts = [0; 4];
+    for &(x, y) in samples {
+        assert!((0.0..1.0).contains(&x) && (0.0..1.0).contains(&y), "({}, {})", x, y);
+        counts[(x >= 0.5) as usize + 2 * (y >= 0.5) as usize] += 1;
+    }
+    counts
+}
+
+#[test]
+fn square_counts_fill_every_quadrant_alike() {
+    for count in [4, 16, 64] {
+        for pixel in offsets(count, 50) {
+            assert_eq!(quadrants(&pixel), [count as usize / 4; 4], "{} samples", count);
+        }
+    }
+}
+
+#[test]
+fn other_counts_cover_the_whole_pixel() {
+    for count in [2, 3, 5, 6, 7, 8, 10, 12, 24] {
+        let all: Vec<(f64, f64)> = offsets(count, 4000).into_iter().flatten().collect();
+        let total = all.len() as f64;
+        for (quadrant, n) in quadrants(&all).into_iter().enumerate() {
+            let share = n as f64 / total;
+            assert!((share - 0.25).abs() < 0.015, "{} samples: {} in quadrant {}", count, share, quadrant);
+        }
+
+        // Centered on the pixel on average
+        let mean = |of: fn(&(f64, f64)) -> f64| all.iter().map(of).sum::<f64>() / total;
+        assert!((mean(|s| s.0) - 0.5).abs() < 0.01 && (mean(|s| s.1) - 0.5).abs() < 0.01, "{} samples", count);
+    }
+
+    // Each sample of a pixel gets a strip of its own: with 3 samples, one
+    // is in the top two thirds' left half and right half each, the last at
+    // the bottom
+    for pixel in offsets(3, 50) {
+        assert!(pixel[0].0 < 0.5 && pixel[0].1 < 2.0 / 3.0);
+        assert!(pixel[1].0 >= 0.5 && pixel[1].1 < 2.0 / 3.0);
+        assert!(pixel[2].1 >= 2.0 / 3.0);
+    }
+}
@@ -0,0 +1,79 @@
+// Multithreaded rendering tests. The threads take tiles of rows from a
+// queue; however many there are, every pixel gets rendered exactly once and
+// the image comes out the same, with or without budgets and regions.
+
+use lux::*;
+
+const WIDTH: u32 = 40;
+const HEIGHT: u32 = 29;
+
+fn render(threads: u32, settings: RenderSettings) -> GlobalSettings {
+    let settings = RenderSettings { threads, ..settings };
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, settings, Camera::default());
+    demo::build_scene(&mut globals);
+    render_scene(&mut globals).unwrap();
+    globals
+}
+
+fn assert_same(settings: RenderSettings) {
+    let one = render(1, settings);
+    // More threads than rows too, and counts that don't divide the height
+    for threads in [2, 3, 7, 64] {
+        let many = render(threads, settings);
+        assert_eq!(many.framebuffer, one.framebuffer, "{} threads", threads);
+        assert_eq!(many.depth_buffer, one.depth_buffer, "{} threads", threads);
+        assert_eq!(many.img, one.img, "{} threads", threads);
+    }
+}
+
+#[test]
+fn whitted_is_the_same_on_any_number_of_threads() {
+    assert_same(RenderSettings::default());
+}
+
+#[test]
+fn path_tracing_is_the_same_on_any_number_of_threads() {
+    assert_same(RenderSettings {
+        mode: RenderMode::PathTrace,
+        samples: 6,
+        ..Default::default()
+    });
+}
+
+#[test]
+fn budgeted_regions_are_the_same_on_any_number_of_threads() {
+    assert_same(RenderSettings {
+        samples: 8,
+        target_noise: Some(0.0),
+        region: Some(Region {
+            x: 5,
+            y: 3,
+            width: 20,
+            height: 17,
+        }),
+        ..Default::default()
+    });
+}
+
+#[test]
+fn every_pixel_is_rendered_exactly_once() {
+    const SAMPLES: u32 = 3;
+
+    for threads in [1, 2, 3, 5, 8, 64] {
+        let globals = render(
+            threads,
+            RenderSettings {
+                samples: SAMPLES,
+                stats: true,
+                ..Default::default()
+            },
+        );
+
+        // No gaps: every pixel got all its samples. No pixel twice: the
+        // camera rays add up to exactly one go at every pixel.
+        assert!(globals.sample_counts.iter().all(|&n| n == SAMPLES), "{} threads", threads);
+        assert_eq!(globals.pass_stats.len(), threads as usize);
+        let total = stats::total(&globals.pass_stats);
+        assert_eq!(total.counts.primary, (WIDTH * HEIGHT * SAMPLES) as u64, "{} threads", threads);
+    }
+}
@@ -0,0 +1,41 @@
+// Cel shading test. A single matte sphere lit from the side shades smoothly
+// by default; with two toon bands it should come out in exactly two flat
+// levels, unlit and fully lit.
+
+use std::collections::HashSet;
+
+use lux::*;
+
+const WIDTH: u32 = 80;
+const HEIGHT: u32 = 60;
+
+// Distinct colors in a render of one white sphere
+fn sphere_colors(toon_bands: Option<u32>) -> usize {
+    let settings = RenderSettings {
+        toon_bands,
+        ..Default::default()
+    };
+
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, settings, Camera::default());
+    let white = Material {
+        diffusive: 1.0,
+        ..Default::default()
+    };
+    globals.add_material("white", white);
+    add_sphere(&Vector3D::new(0.0, 0.0, 3.0), 1.5, "white", &mut globals).unwrap();
+    add_light(Vector3D::new(-5.0, 5.0, -2.0), Vector3D::ONE, 1.0, &mut globals).unwrap();
+    render_scene(&mut globals).unwrap();
+
+    globals.img.pixels().map(|p| p.0).collect::<HashSet<_>>().len()
+}
+
+#[test]
+fn smooth_shading_has_a_gradient() {
+    assert!(sphere_colors(None) > 10);
+}
+
+#[test]
+fn two_toon_bands_give_two_levels() {
+    // The two levels and the background around the sphere
+    assert_eq!(sphere_colors(Some(2)), 3);
+}
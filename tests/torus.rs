@@ -0,0 +1,95 @@
+// Quartic solver and torus tests. The solver finds the real roots of
+// quartics with four, two and no real roots, in order. A torus is hit at
+// the nearest of the up to four places a ray crosses its surface: not at
+// all through the hole in the middle, at the near side of the tube through
+// it, and barely, but exactly on the surface, by a ray that just grazes it.
+
+use lux::math::solve_quartic;
+use lux::*;
+
+const MAJOR: f64 = 2.0;
+const MINOR: f64 = 0.5;
+
+fn assert_roots(roots: Vec<f64>, expected: &[f64]) {
+    assert_eq!(roots.len(), expected.len(), "{:?} instead of {:?}", roots, expected);
+    for (root, want) in roots.iter().zip(expected) {
+        assert!((root - want).abs() < 1e-9, "{:?} instead of {:?}", roots, expected);
+    }
+}
+
+#[test]
+fn quartic_roots() {
+    // (x - 1)(x - 2)(x - 3)(x - 4)
+    assert_roots(solve_quartic(1.0, -10.0, 35.0, -50.0, 24.0), &[1.0, 2.0, 3.0, 4.0]);
+    // Scaled and out of order: 2 (x + 3)(x - 0.5)(x - 1.5)(x + 0.25)
+    assert_roots(solve_quartic(2.0, 2.5, -10.0, 1.875, 1.125), &[-3.0, -0.25, 0.5, 1.5]);
+    // (x - 1)(x + 2)(x^2 + 1)
+    assert_roots(solve_quartic(1.0, 1.0, -1.0, 1.0, -2.0), &[-2.0, 1.0]);
+    // (x^2 + 1)(x^2 + 4) and x^4 + 1
+    assert_roots(solve_quartic(1.0, 0.0, 5.0, 0.0, 4.0), &[]);
+    assert_roots(solve_quartic(1.0, 0.0, 0.0, 0.0, 1.0), &[]);
+    // No x^4, so a cubic: (x - 1)(x - 2)(x - 3)
+    assert_roots(solve_quartic(0.0, 1.0, -6.0, 11.0, -6.0), &[1.0, 2.0, 3.0]);
+}
+
+// Ring around the z axis through the origin
+fn torus() -> PrimTorus {
+    PrimTorus {
+        center: Vector3D::ZERO,
+        axis: Vector3D::UNIT_Z,
+        major_radius: MAJOR,
+        minor_radius: MINOR,
+        m: MaterialRef::Named("white".to_string()),
+    }
+}
+
+// Result of intersecting the torus and the distance to the hit
+fn hit(origin: Vector3D, direction: Vector3D) -> (i32, f64) {
+    let mut dist = 0.0;
+    let res = torus().intersect(Ray { origin, direction, time: 0.0 }, 1e-4, &mut dist);
+    (res, dist)
+}
+
+#[test]
+fn rays_through_the_hole_miss() {
+    assert_eq!(hit(Vector3D::new(0.0, 0.0, -10.0), Vector3D::UNIT_Z).0, 0);
+    // Off the axis, but still clear of the tube
+    assert_eq!(hit(Vector3D::new(1.0, 0.5, -10.0), Vector3D::UNIT_Z).0, 0);
+}
+
+#[test]
+fn rays_through_the_tube_hit_its_near_side() {
+    // Down through the tube, which it crosses from 9.5 to 10.5
+    let (res, dist) = hit(Vector3D::new(MAJOR, 0.0, -10.0), Vector3D::UNIT_Z);
+    assert_eq!(res, 1);
+    assert!((dist - 9.5).abs() < 1e-9, "{}", dist);
+
+    // Across the ring plane it crosses the tube twice, at 7.5 and 8.5 and
+    // at 11.5 and 12.5, and hits the first one
+    let (res, dist) = hit(Vector3D::new(-10.0, 0.0, 0.0), Vector3D::UNIT_X);
+    assert_eq!(res, 1);
+    assert!((dist - 7.5).abs() < 1e-9, "{}", dist);
+
+    // From inside the tube, it hits its wall on the way out
+    let (res, dist) = hit(Vector3D::new(-MAJOR, 0.0, 0.0), Vector3D::UNIT_X);
+    assert_eq!(res, -1);
+    assert!((dist - MINOR).abs() < 1e-9, "{}", dist);
+}
+
+#[test]
+fn grazing_rays() {
+    // Just inside the outer edge of the tube the ray cuts a short chord
+    // near z = 0, just outside it there's nothing
+    let edge = MAJOR + MINOR;
+    let origin = Vector3D::new(edge - 1e-4, 0.0, -10.0);
+    let (res, dist) = hit(origin, Vector3D::UNIT_Z);
+    assert_eq!(res, 1);
+    assert!((dist - 10.0).abs() < 0.02, "{}", dist);
+
+    // Right on the surface, however little the ray meets it
+    let point = Vector3D::new(origin.x, 0.0, origin.z + dist);
+    let from_ring = Vector3D::new(point.x - MAJOR, 0.0, point.z);
+    assert!((from_ring.v3d_length() - MINOR).abs() < 1e-9, "{}", from_ring.v3d_length());
+
+    assert_eq!(hit(Vector3D::new(edge + 1e-4, 0.0, -10.0), Vector3D::UNIT_Z).0, 0);
+}
@@ -0,0 +1,51 @@
+// Single pixel tests. A sphere in the middle of the view is lit where the
+// middle pixel looks at it, and a corner pixel sees only the background.
+// Threads tracing the same scene side by side see what one thread does.
+
+use lux::*;
+
+const WIDTH: u32 = 64;
+const HEIGHT: u32 = 48;
+
+fn white() -> Material {
+    Material {
+        diffusive: 0.8,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn centered_sphere_is_lit_and_corner_is_background() {
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, RenderSettings::default(), Camera::default());
+    add_sphere(&Vector3D::new(0.0, 0.0, 3.0), 1.0, white(), &mut globals).unwrap();
+    add_light(Vector3D::new(0.0, 0.0, -2.0), Vector3D::ONE, 1.0, &mut globals).unwrap();
+
+    let camera = globals.camera;
+    let center = trace_pixel(&globals, &camera, WIDTH / 2, HEIGHT / 2, WIDTH, HEIGHT);
+    let corner = trace_pixel(&globals, &camera, 0, 0, WIDTH, HEIGHT);
+
+    assert!(center.luminance() > BACKGROUND.luminance() + 0.5, "center: {}", center.luminance());
+    for c in 0..3 {
+        assert_eq!(corner[c], BACKGROUND[c]);
+    }
+}
+
+#[test]
+fn threads_share_one_scene() {
+    let mut scene = Scene::new(RenderSettings::default(), Camera::default());
+    add_sphere(&Vector3D::new(0.0, 0.0, 3.0), 1.0, white(), &mut scene).unwrap();
+    add_light(Vector3D::new(0.0, 0.0, -2.0), Vector3D::ONE, 1.0, &mut scene).unwrap();
+
+    // Every thread traces its own rows of the same scene, and gets what one
+    // thread tracing them all does
+    let scene = &scene;
+    let row = |y: u32| (0..WIDTH).map(|x| trace_pixel(scene, &scene.camera, x, y, WIDTH, HEIGHT)).collect::<Vec<_>>();
+    let alone: Vec<Vec<Vector3D>> = (0..HEIGHT).map(row).collect();
+    let shared: Vec<Vec<Vector3D>> = std::thread::scope(|s| {
+        let threads: Vec<_> = (0..4).map(|t| s.spawn(move || (t..HEIGHT).step_by(4).map(|y| (y, row(y))).collect::<Vec<_>>())).collect();
+        let mut rows: Vec<_> = threads.into_iter().flat_map(|t| t.join().unwrap()).collect();
+        rows.sort_by_key(|&(y, _)| y);
+        rows.into_iter().map(|(_, r)| r).collect()
+    });
+    assert!(alone == shared);
+}
@@ -0,0 +1,107 @@
+// Translucency tests. A thin surface lit from behind glows where the light
+// is closest, things behind it block that light like any shadow, and
+// materials that aren't translucent look the same as before.
+
+use lux::*;
+
+const WIDTH: u32 = 32;
+const HEIGHT: u32 = 24;
+
+fn paper(translucency: f64) -> Material {
+    Material {
+        color: Vector3D::new(1.0, 0.9, 0.7),
+        diffusive: 1.0,
+        translucency,
+        ..Default::default()
+    }
+}
+
+// A sheet facing the camera with a light at `light`, rendered
+fn sheet(translucency: f64, light: Vector3D, shadows: bool) -> GlobalSettings {
+    let settings = RenderSettings {
+        shadows,
+        ..Default::default()
+    };
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, settings, Camera::default());
+    globals.ambient = Vector3D::ZERO;
+    add_quad(Vector3D::new(-4.0, -4.0, 0.0), Vector3D::new(0.0, 8.0, 0.0), Vector3D::new(8.0, 0.0, 0.0), paper(translucency), &mut globals).unwrap();
+    add_light(light, Vector3D::ONE, 1.0, &mut globals).unwrap();
+    render_scene(&mut globals).unwrap();
+    globals
+}
+
+fn brightness(c: Vector3D) -> f64 {
+    c.x + c.y + c.z
+}
+
+#[test]
+fn sheet_lit_from_behind_glows_where_the_light_is_closest() {
+    let light = Vector3D::new(1.0, 0.5, 0.6);
+    let globals = sheet(0.8, light, false);
+
+    let (brightest, _) = globals
+        .framebuffer
+        .iter()
+        .enumerate()
+        .max_by(|a, b| brightness(*a.1).total_cmp(&brightness(*b.1)))
+        .unwrap();
+    let (x, y) = ((brightest as u32 % WIDTH) as f64 + 0.5, (brightest as u32 / WIDTH) as f64 + 0.5);
+
+    // Right in front of the light
+    let (fx, fy) = globals.camera.project(Vector3D::new(light.x, light.y, 0.0), WIDTH, HEIGHT).unwrap();
+    assert!((x - fx).abs() <= 1.0 && (y - fy).abs() <= 1.0, "brightest at ({}, {}), light at ({}, {})", x, y, fx, fy);
+
+    // And fading out away from it
+    assert!(brightness(globals.framebuffer[0]) < 0.5 * brightness(globals.framebuffer[brightest]));
+    assert!(brightness(globals.framebuffer[0]) > 0.0);
+}
+
+#[test]
+fn opaque_sheet_stays_dark_from_behind() {
+    let globals = sheet(0.0, Vector3D::new(1.0, 0.5, 0.6), true);
+    assert!(globals.framebuffer.iter().all(|&c| c == Vector3D::ZERO));
+}
+
+#[test]
+fn translucency_leaves_the_lit_side_alone() {
+    let front = Vector3D::new(1.0, 0.5, -2.0);
+    let opaque = sheet(0.0, front, true);
+    let translucent = sheet(0.8, front, true);
+    assert!(opaque.framebuffer.iter().any(|&c| c != Vector3D::ZERO));
+    assert_eq!(opaque.framebuffer, translucent.framebuffer);
+}
+
+#[test]
+fn things_behind_the_sheet_cast_shadows_on_it() {
+    let light = Vector3D::new(0.0, 0.0, 2.0);
+    let settings = RenderSettings {
+        shadows: true,
+        ..Default::default()
+    };
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, settings, Camera::default());
+    globals.ambient = Vector3D::ZERO;
+    add_quad(Vector3D::new(-4.0, -4.0, 0.0), Vector3D::new(0.0, 8.0, 0.0), Vector3D::new(8.0, 0.0, 0.0), paper(0.8), &mut globals).unwrap();
+    add_sphere(&Vector3D::new(0.0, 0.0, 1.0), 0.3, paper(0.0), &mut globals).unwrap();
+    add_light(light, Vector3D::ONE, 1.0, &mut globals).unwrap();
+    render_scene(&mut globals).unwrap();
+
+    let unblocked = sheet(0.8, light, true);
+    let middle = (HEIGHT / 2 * WIDTH + WIDTH / 2) as usize;
+    assert_eq!(globals.framebuffer[middle], Vector3D::ZERO);
+    assert!(brightness(unblocked.framebuffer[middle]) > 0.0);
+    // Away from the ball's shadow the sheet glows as before
+    assert_eq!(globals.framebuffer[0], unblocked.framebuffer[0]);
+}
+
+#[test]
+fn negative_translucency_is_a_parse_error() {
+    let path = format!("{}/translucency.toml", env!("CARGO_TARGET_TMPDIR"));
+    std::fs::write(&path, "[material.paper]\ntranslucency = -0.5\n").unwrap();
+
+    let mut globals = GlobalSettings::new(4, 4, RenderSettings::default(), Camera::default());
+    assert!(matches!(scene::load(&path, &mut globals), Err(LuxError::Parse(_))));
+
+    std::fs::write(&path, "[material.paper]\ntranslucency = 0.5\n").unwrap();
+    scene::load(&path, &mut globals).unwrap();
+    assert_eq!(globals.materials["paper"].translucency, 0.5);
+}
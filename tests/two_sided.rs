@@ -0,0 +1,42 @@
+// Two-sided shading test. A quad turned away from the camera is shaded on
+// the side the camera sees, unless back faces are culled, which makes it
+// disappear.
+
+use lux::*;
+
+const WIDTH: u32 = 16;
+const HEIGHT: u32 = 12;
+
+// Color of the middle pixel, looking at the back of a matte quad lit from
+// the camera's side
+fn back_of_quad(cull_back_faces: bool) -> Vector3D {
+    let settings = RenderSettings {
+        cull_back_faces,
+        ..Default::default()
+    };
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, settings, Camera::default());
+    let white = Material {
+        diffusive: 0.8,
+        ..Default::default()
+    };
+
+    // Facing +z, away from the camera behind it
+    add_quad(Vector3D::new(-2.0, -2.0, 3.0), Vector3D::new(4.0, 0.0, 0.0), Vector3D::new(0.0, 4.0, 0.0), white, &mut globals).unwrap();
+    assert!(globals.primitives()[0].normal(Vector3D::new(0.0, 0.0, 3.0), 0.0).z > 0.0);
+    add_light(Vector3D::new(0.0, 0.0, -1.0), Vector3D::ONE, 1.0, &mut globals).unwrap();
+    render_scene(&mut globals).unwrap();
+
+    globals.framebuffer[(HEIGHT / 2 * WIDTH + WIDTH / 2) as usize]
+}
+
+#[test]
+fn back_face_is_shaded() {
+    let mut lit = back_of_quad(false);
+    lit.v3d_sub(BACKGROUND);
+    assert!(lit.min_component() > 0.5, "{}", lit);
+}
+
+#[test]
+fn culled_back_face_shows_the_background() {
+    assert_eq!(back_of_quad(true), BACKGROUND);
+}
@@ -0,0 +1,156 @@
+// Tests for the `Vector3D` math type: construction, conversions, comparing,
+// printing and the arithmetic the tracer builds on.
+
+use lux::Vector3D;
+
+const EPS: f64 = 1e-12;
+
+#[test]
+fn constructors_agree() {
+    let v = Vector3D::new(1.0, -2.0, 3.5);
+
+    assert_eq!(Vector3D::from((1.0, -2.0, 3.5)), v);
+    assert_eq!(Vector3D::from([1.0, -2.0, 3.5]), v);
+    assert_eq!(Vector3D::v3d_new((1.0, -2.0, 3.5)), v);
+
+    let into: Vector3D = [1.0, -2.0, 3.5].into();
+    assert_eq!(into, v);
+}
+
+#[test]
+fn constants() {
+    assert_eq!(Vector3D::default(), Vector3D::ZERO);
+    assert_eq!(Vector3D::ZERO, Vector3D::new(0.0, 0.0, 0.0));
+    assert_eq!(Vector3D::ONE, Vector3D::new(1.0, 1.0, 1.0));
+
+    let units = [Vector3D::UNIT_X, Vector3D::UNIT_Y, Vector3D::UNIT_Z];
+    for (axis, unit) in units.iter().enumerate() {
+        assert_eq!(unit.v3d_length(), 1.0);
+        assert_eq!(unit[axis], 1.0);
+    }
+    assert_eq!(Vector3D::cross(Vector3D::UNIT_X, Vector3D::UNIT_Y), Vector3D::UNIT_Z);
+}
+
+#[test]
+fn index_reads_and_writes_components() {
+    let mut v = Vector3D::new(4.0, 5.0, 6.0);
+    assert_eq!((v[0], v[1], v[2]), (4.0, 5.0, 6.0));
+
+    v[1] = -1.0;
+    assert_eq!(v, Vector3D::new(4.0, -1.0, 6.0));
+}
+
+#[test]
+#[should_panic(expected = "out of range")]
+fn index_past_z_panics() {
+    let _ = Vector3D::ONE[3];
+}
+
+#[test]
+fn approx_eq_allows_eps_per_component() {
+    let v = Vector3D::new(1.0, 2.0, 3.0);
+
+    assert!(v.approx_eq(Vector3D::new(1.0 + 1e-10, 2.0 - 1e-10, 3.0), 1e-9));
+    assert!(!v.approx_eq(Vector3D::new(1.0, 2.0, 3.0 + 1e-8), 1e-9));
+    assert!(v.approx_eq(v, 0.0));
+    assert!(!Vector3D::new(f64::NAN, 0.0, 0.0).approx_eq(Vector3D::new(f64::NAN, 0.0, 0.0), 1.0));
+}
+
+#[test]
+fn display() {
+    assert_eq!(Vector3D::new(1.0, -2.5, 0.0).to_string(), "(1, -2.5, 0)");
+    assert_eq!(format!("{:.2}", Vector3D::new(1.0, 2.0, 1.0 / 3.0)), "(1.00, 2.00, 0.33)");
+}
+
+#[test]
+fn arithmetic() {
+    let mut v = Vector3D::new(1.0, 2.0, 3.0);
+    v.v3d_add(Vector3D::new(1.0, 1.0, 1.0));
+    assert_eq!(v, Vector3D::new(2.0, 3.0, 4.0));
+
+    v.v3d_sub(Vector3D::ONE);
+    assert_eq!(v, Vector3D::new(1.0, 2.0, 3.0));
+
+    v.v3d_mul_scalar(2.0);
+    assert_eq!(v, Vector3D::new(2.0, 4.0, 6.0));
+
+    v.v3d_mul_v3d(Vector3D::new(0.5, 0.25, -1.0));
+    assert_eq!(v, Vector3D::new(1.0, 1.0, -6.0));
+
+    assert_eq!(Vector3D::new(1.0, 2.0, 3.0).v3d_dot_mul(Vector3D::new(4.0, -5.0, 6.0)), 12.0);
+    assert_eq!(Vector3D::new(3.0, 4.0, 12.0).v3d_length(), 13.0);
+}
+
+#[test]
+fn cross_is_perpendicular_and_anticommutative() {
+    let a = Vector3D::new(1.0, 2.0, 3.0);
+    let b = Vector3D::new(-2.0, 0.5, 4.0);
+    let c = Vector3D::cross(a, b);
+
+    assert!(c.v3d_dot_mul(a).abs() < EPS);
+    assert!(c.v3d_dot_mul(b).abs() < EPS);
+
+    let mut back = Vector3D::cross(b, a);
+    back.v3d_mul_scalar(-1.0);
+    assert!(back.approx_eq(c, EPS));
+}
+
+#[test]
+fn norm_gives_unit_length() {
+    let mut v = Vector3D::new(3.0, -4.0, 12.0);
+    v.v3d_norm();
+
+    assert!((v.v3d_length() - 1.0).abs() < EPS);
+    assert!(v.approx_eq(Vector3D::new(3.0 / 13.0, -4.0 / 13.0, 12.0 / 13.0), EPS));
+}
+
+#[test]
+fn reflect_flips_the_normal_part() {
+    let mut incident = Vector3D::new(1.0, -1.0, 0.0);
+    incident.v3d_norm();
+
+    let r = Vector3D::reflect(incident, Vector3D::UNIT_Y);
+    assert!(r.approx_eq(Vector3D::new(incident.x, -incident.y, 0.0), EPS));
+}
+
+#[test]
+fn refract_follows_snell() {
+    let eta: f64 = 1.0 / 1.5;
+    let angle: f64 = 0.6;
+    let incident = Vector3D::new(angle.sin(), -angle.cos(), 0.0);
+
+    let t = Vector3D::refract(incident, Vector3D::UNIT_Y, eta).unwrap();
+    assert!((t.v3d_length() - 1.0).abs() < EPS);
+    // sin of the angle to the normal shrinks by eta
+    assert!((t.x - eta * angle.sin()).abs() < EPS);
+    assert!(t.y < 0.0);
+
+    // Head on it goes straight through
+    let straight = Vector3D::refract(Vector3D::new(0.0, -1.0, 0.0), Vector3D::UNIT_Y, eta).unwrap();
+    assert!(straight.approx_eq(Vector3D::new(0.0, -1.0, 0.0), EPS));
+}
+
+#[test]
+fn refract_reports_total_internal_reflection() {
+    // From glass into air past the critical angle (about 41.8 degrees)
+    let angle: f64 = 1.0;
+    let incident = Vector3D::new(angle.sin(), -angle.cos(), 0.0);
+
+    assert!(Vector3D::refract(incident, Vector3D::UNIT_Y, 1.5).is_none());
+}
+
+#[test]
+fn component_helpers() {
+    let a = Vector3D::new(1.0, 5.0, -2.0);
+    let b = Vector3D::new(3.0, 0.0, -1.0);
+
+    assert_eq!(a.min(b), Vector3D::new(1.0, 0.0, -2.0));
+    assert_eq!(a.max(b), Vector3D::new(3.0, 5.0, -1.0));
+    assert_eq!(a.clamp(Vector3D::ZERO, Vector3D::ONE), Vector3D::new(1.0, 1.0, 0.0));
+    assert_eq!(a.map(|c| c * 2.0), Vector3D::new(2.0, 10.0, -4.0));
+    assert_eq!(a.zip(b, |x, y| x - y), Vector3D::new(-2.0, 5.0, -1.0));
+    assert_eq!(a.components().collect::<Vec<_>>(), [1.0, 5.0, -2.0]);
+    assert_eq!((a.max_component(), a.min_component()), (5.0, -2.0));
+
+    assert!((Vector3D::ONE.luminance() - 1.0).abs() < EPS);
+}
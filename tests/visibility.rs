@@ -0,0 +1,120 @@
+// Per-primitive visibility test. A sphere hidden from the camera only shows
+// up as its shadow, and a sphere that casts no shadow is still there but
+// doesn't darken the floor.
+
+use lux::*;
+
+const WIDTH: u32 = 32;
+const HEIGHT: u32 = 24;
+
+// Floor lit from above with a sphere hovering over it, flags for the
+// sphere, with shadows
+fn render(sphere: Option<VisibilityFlags>) -> GlobalSettings {
+    let settings = RenderSettings {
+        shadows: true,
+        ..Default::default()
+    };
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, settings, Camera::default());
+    let matte = Material {
+        color: Vector3D::new(0.8, 0.8, 0.8),
+        diffusive: 1.0,
+        ..Default::default()
+    };
+    add_quad(Vector3D::new(-5.0, -1.0, -5.0), Vector3D::new(0.0, 0.0, 10.0), Vector3D::new(10.0, 0.0, 0.0), matte, &mut globals).unwrap();
+    add_light(Vector3D::new(0.0, 4.0, 0.0), Vector3D::ONE, 1.0, &mut globals).unwrap();
+    if let Some(flags) = sphere {
+        add_sphere(&Vector3D::new(0.0, 0.5, 0.0), 0.5, matte, &mut globals).unwrap();
+        globals.set_visibility(1, flags);
+    }
+    render_scene(&mut globals).unwrap();
+    globals
+}
+
+// Pixels that differ between two renders
+fn changed(a: &GlobalSettings, b: &GlobalSettings) -> Vec<usize> {
+    (0..a.framebuffer.len()).filter(|&i| a.framebuffer[i] != b.framebuffer[i]).collect()
+}
+
+// Whether pixel `i` sees the floor in the render without the sphere
+fn on_floor(empty: &GlobalSettings, i: usize) -> bool {
+    empty.depth_buffer[i].is_finite()
+}
+
+#[test]
+fn camera_invisible_sphere_only_leaves_its_shadow() {
+    let empty = render(None);
+    let hidden = render(Some(VisibilityFlags {
+        camera: false,
+        ..Default::default()
+    }));
+
+    let shadow = changed(&empty, &hidden);
+    assert!(!shadow.is_empty());
+    for i in shadow {
+        // Darker floor, nothing else: camera rays go right through it
+        assert!(on_floor(&empty, i));
+        assert_eq!(hidden.depth_buffer[i], empty.depth_buffer[i]);
+        assert!(hidden.framebuffer[i].luminance() < empty.framebuffer[i].luminance());
+    }
+}
+
+#[test]
+fn shadow_invisible_sphere_floats_unshadowed() {
+    let empty = render(None);
+    let visible = render(Some(VisibilityFlags::default()));
+    let unshadowing = render(Some(VisibilityFlags {
+        shadow: false,
+        ..Default::default()
+    }));
+
+    // The sphere is there...
+    let sphere = changed(&empty, &unshadowing);
+    assert!(!sphere.is_empty());
+    assert!(sphere.iter().all(|&i| unshadowing.depth_buffer[i] < empty.depth_buffer[i]));
+
+    // ...but the floor where its shadow would be is as bright as without it
+    let shadow: Vec<usize> = changed(&empty, &visible).into_iter().filter(|&i| visible.depth_buffer[i] == empty.depth_buffer[i]).collect();
+    assert!(!shadow.is_empty());
+    for i in shadow {
+        assert_eq!(unshadowing.framebuffer[i], empty.framebuffer[i]);
+    }
+}
+
+#[test]
+fn reflection_invisible_sphere_is_missing_from_mirrors() {
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, RenderSettings::default(), Camera::default());
+    globals.camera.position = Vector3D::new(0.0, 0.0, -5.0);
+    let red = Material {
+        color: Vector3D::new(1.0, 0.0, 0.0),
+        diffusive: 1.0,
+        ..Default::default()
+    };
+    let mirror = Material {
+        color: Vector3D::ONE,
+        diffusive: 0.0,
+        reflective: 1.0,
+        ..red
+    };
+    // Mirror ahead, the sphere behind the camera only shows in it
+    add_quad(Vector3D::new(-10.0, -10.0, 0.0), Vector3D::new(20.0, 0.0, 0.0), Vector3D::new(0.0, 20.0, 0.0), mirror, &mut globals).unwrap();
+    add_sphere(&Vector3D::new(0.0, 0.0, -8.0), 1.0, red, &mut globals).unwrap();
+    add_light(Vector3D::new(0.0, 3.0, -5.0), Vector3D::ONE, 1.0, &mut globals).unwrap();
+    let middle = (HEIGHT / 2 * WIDTH + WIDTH / 2) as usize;
+
+    render_scene(&mut globals).unwrap();
+    assert!(globals.framebuffer[middle].x > 0.1);
+
+    globals.set_visibility(1, VisibilityFlags { reflection: false, ..Default::default() });
+    render_scene(&mut globals).unwrap();
+    assert_eq!(globals.framebuffer[middle], globals.environment);
+}
+
+#[test]
+fn scene_file_sets_visibility() {
+    let text = "[material.grey]\n\n[[sphere]]\nposition = [0.0, 0.0, 2.0]\nradius = 0.5\nmaterial = \"grey\"\ncamera = false\nshadow = false\n";
+    let mut globals = GlobalSettings::new(4, 4, RenderSettings::default(), Camera::default());
+    scene::from_toml(text, &mut globals).unwrap();
+
+    let flags = globals.visibility(0);
+    assert_eq!(flags, VisibilityFlags { camera: false, shadow: false, reflection: true });
+}
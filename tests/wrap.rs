@@ -0,0 +1,50 @@
+// Wrap lighting test. A diffuse surface turned slightly past 90 degrees
+// away from the light is dark, unless its material lets the light wrap
+// around the terminator.
+
+use lux::*;
+
+const WIDTH: u32 = 16;
+const HEIGHT: u32 = 12;
+
+// Middle pixel of a quad facing the camera, lit from 95 degrees off its
+// normal
+fn past_the_terminator(wrap: f64) -> Vector3D {
+    let mut globals = GlobalSettings::new(WIDTH, HEIGHT, RenderSettings::default(), Camera::default());
+    globals.ambient = Vector3D::ZERO;
+    let wax = Material {
+        color: Vector3D::new(1.0, 0.9, 0.8),
+        diffusive: 1.0,
+        wrap,
+        ..Default::default()
+    };
+    add_quad(Vector3D::new(-3.0, -3.0, 0.0), Vector3D::new(0.0, 6.0, 0.0), Vector3D::new(6.0, 0.0, 0.0), wax, &mut globals).unwrap();
+    let behind = 10.0 * 5.0_f64.to_radians().tan();
+    add_light(Vector3D::new(10.0, 0.0, behind), Vector3D::ONE, 1.0, &mut globals).unwrap();
+    render_scene(&mut globals).unwrap();
+
+    globals.framebuffer[(HEIGHT / 2 * WIDTH + WIDTH / 2) as usize]
+}
+
+#[test]
+fn wrapped_light_reaches_past_the_terminator() {
+    assert_eq!(past_the_terminator(0.0), Vector3D::ZERO);
+
+    let wrapped = past_the_terminator(0.5);
+    assert!(wrapped.x > 0.0 && wrapped.y > 0.0 && wrapped.z > 0.0, "{}", wrapped);
+    // More wrap, more light
+    assert!(past_the_terminator(1.0).x > wrapped.x);
+}
+
+#[test]
+fn negative_wrap_is_a_parse_error() {
+    let path = format!("{}/wrap.toml", env!("CARGO_TARGET_TMPDIR"));
+    std::fs::write(&path, "[material.wax]\nwrap = -0.5\n").unwrap();
+
+    let mut globals = GlobalSettings::new(4, 4, RenderSettings::default(), Camera::default());
+    assert!(matches!(scene::load(&path, &mut globals), Err(LuxError::Parse(_))));
+
+    std::fs::write(&path, "[material.wax]\nwrap = 0.5\n").unwrap();
+    scene::load(&path, &mut globals).unwrap();
+    assert_eq!(globals.materials["wax"].wrap, 0.5);
+}